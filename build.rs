@@ -0,0 +1,41 @@
+//! Build script for ChatDelta CLI
+//!
+//! Records the chatdelta version Cargo resolved, for the `--test` environment report.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    // The lockfile sits beside the manifest, or at a workspace root above it
+    let lock = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.exists());
+    if let Some(path) = &lock {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    let version = lock
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| locked_version(&text, "chatdelta"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CHATDELTA_VERSION={}", version);
+}
+
+/// The version a Cargo.lock records for package `name`
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    lock.split("[[package]]").find_map(|package| {
+        let field = |key: &str| {
+            package
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(" = "))
+                .map(|value| value.trim_matches('"'))
+        };
+        match field("name") == Some(name) {
+            true => field("version").map(str::to_string),
+            false => None,
+        }
+    })
+}
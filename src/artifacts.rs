@@ -0,0 +1,119 @@
+//! Per-run artifact directory for ChatDelta CLI (`--output-dir`)
+//!
+//! Each invocation writes a timestamped subdirectory holding the prompt, every model
+//! response, the summary, timing metrics and the effective configuration.
+
+use crate::cli::Args;
+use crate::output::ResponseMeta;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Everything a run produced, gathered for writing to disk
+pub struct RunArtifacts<'a> {
+    pub prompt: &'a str,
+    pub responses: &'a [(String, String)],
+    pub digest: Option<&'a str>,
+    pub meta: &'a HashMap<String, ResponseMeta>,
+    pub query_duration: Duration,
+    pub summary_duration: Option<Duration>,
+}
+
+/// Subdirectory name: UTC timestamp plus the session id (or a fresh run id)
+pub fn run_dir_name(args: &Args) -> String {
+    let id = args
+        .session_id
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    format!("{}-{}", Utc::now().format("%Y%m%d-%H%M%S"), id)
+}
+
+/// This run's directory under `base`
+pub fn run_dir(base: &Path, args: &Args) -> PathBuf {
+    base.join(run_dir_name(args))
+}
+
+/// Write all artifacts into the run directory `dir`
+pub fn write_run_artifacts(
+    dir: &Path,
+    args: &Args,
+    run: &RunArtifacts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+
+    fs::write(dir.join("prompt.txt"), run.prompt)?;
+
+    let responses_dir = dir.join("responses");
+    for (name, response) in run.responses {
+        crate::save_individual_response(&responses_dir, name, response, None, None)?;
+    }
+
+    if let Some(summary) = run.digest {
+        fs::write(dir.join("summary.txt"), summary)?;
+    }
+
+    fs::write(
+        dir.join("metrics.json"),
+        serde_json::to_string_pretty(&metrics_json(run))?,
+    )?;
+    fs::write(
+        dir.join("config.json"),
+        serde_json::to_string_pretty(&config_json(args))?,
+    )?;
+
+    Ok(())
+}
+
+/// Timings and per-model details
+fn metrics_json(run: &RunArtifacts) -> serde_json::Value {
+    let models: serde_json::Map<String, serde_json::Value> = run
+        .responses
+        .iter()
+        .map(|(name, response)| {
+            let meta = run.meta.get(name).cloned().unwrap_or_default();
+            let value = serde_json::json!({
+                "chars": response.len(),
+                "latency_ms": meta.latency_ms,
+                "total_tokens": meta.total_tokens,
+                "finish_reason": meta.finish_reason,
+                "response_hash": meta.response_hash,
+            });
+            (name.clone(), value)
+        })
+        .collect();
+
+    serde_json::json!({
+        "query_time_ms": run.query_duration.as_millis() as u64,
+        "summary_time_ms": run.summary_duration.map(|d| d.as_millis() as u64),
+        "successful_responses": run.responses.len(),
+        "models": models,
+    })
+}
+
+/// Effective settings for the run; API keys are never included
+fn config_json(args: &Args) -> serde_json::Value {
+    let selected: Vec<&str> = ["gpt", "gemini", "claude"]
+        .into_iter()
+        .filter(|ai| args.should_use_ai(ai))
+        .collect();
+
+    serde_json::json!({
+        "models": {
+            "gpt": args.gpt_model,
+            "gemini": args.gemini_model,
+            "claude": args.claude_model,
+        },
+        "selected": selected,
+        "system_prompt": args.system_prompt,
+        "temperature": args.temperature,
+        "max_tokens": args.max_tokens,
+        "timeout": args.timeout,
+        "retries": args.retries,
+        "retry_strategy": args.retry_strategy,
+        "format": args.format,
+        "summary": !args.no_summary,
+    })
+}
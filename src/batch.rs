@@ -0,0 +1,159 @@
+//! Batch runs for ChatDelta CLI (`--batch`)
+//!
+//! A batch file holds one prompt per line; blank lines and `#` comments are skipped. Before
+//! anything is sent, a pre-flight summary shows the prompt and request counts and an
+//! estimated cost, and the run must be confirmed on a terminal or with `--yes`.
+
+use crate::cli::{Args, AI_NAMES};
+use crate::cost::{self, CostDecision, Pricing};
+
+/// Prompts in a batch file, one per non-blank, non-comment line
+pub fn read_prompts(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// What a batch will send, for the pre-flight summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchEstimate {
+    pub prompts: usize,
+    /// Display name and model of each selected AI or model alias
+    pub models: Vec<(String, String)>,
+    /// Model requests: one per prompt per model
+    pub requests: usize,
+    /// Summary requests, one per prompt when a summary is generated
+    pub summary_requests: usize,
+    /// Estimated USD for the model requests (summaries aren't priced)
+    pub cost: f64,
+    /// Models missing from the pricing table
+    pub unpriced: Vec<String>,
+}
+
+/// Estimate a batch of `prompts` against the AIs and aliases `args` selects
+pub fn estimate(args: &Args, prompts: &[String], table: &[(&str, Pricing)]) -> BatchEstimate {
+    let mut models: Vec<(String, String)> = AI_NAMES
+        .iter()
+        .filter(|ai| args.should_use_ai(ai))
+        .map(|ai| {
+            (
+                Args::display_name(ai).to_string(),
+                args.model_for(ai).to_string(),
+            )
+        })
+        .collect();
+    models.extend(
+        args.selected_aliases()
+            .into_iter()
+            .map(|(name, alias)| (name.to_string(), alias.model.clone())),
+    );
+
+    let model_names: Vec<&str> = models.iter().map(|(_, model)| model.as_str()).collect();
+    let system = args.system_prompt.as_deref().unwrap_or_default();
+    let mut total = 0.0;
+    let mut unpriced = Vec::new();
+    for prompt in prompts {
+        let tokens = cost::estimate_tokens(&format!("{}{}", system, prompt));
+        let (cost, missing) = cost::estimate_cost(table, &model_names, tokens, args.max_tokens);
+        total += cost;
+        unpriced = missing;
+    }
+
+    let summarized = !args.no_summary && models.len() >= 2;
+    BatchEstimate {
+        prompts: prompts.len(),
+        requests: prompts.len() * models.len(),
+        summary_requests: if summarized { prompts.len() } else { 0 },
+        models,
+        cost: total,
+        unpriced,
+    }
+}
+
+/// The pre-flight summary, one fact per line
+pub fn render(estimate: &BatchEstimate) -> String {
+    let models: Vec<String> = estimate
+        .models
+        .iter()
+        .map(|(name, model)| format!("{} ({})", name, model))
+        .collect();
+    let mut out = format!(
+        "Batch: {} prompt{}\n  Models: {}\n  Requests: {}",
+        estimate.prompts,
+        if estimate.prompts == 1 { "" } else { "s" },
+        models.join(", "),
+        estimate.requests
+    );
+    if estimate.summary_requests > 0 {
+        out.push_str(&format!(" (+{} summary)", estimate.summary_requests));
+    }
+    out.push_str(&format!("\n  Estimated cost: ${:.4}", estimate.cost));
+    if !estimate.unpriced.is_empty() {
+        out.push_str(&format!(" (excluding {})", estimate.unpriced.join(", ")));
+    }
+    out.push('\n');
+    out
+}
+
+/// Whether to start the batch: --yes proceeds, a terminal asks, anything else aborts
+pub fn decide(interactive: bool, assume_yes: bool) -> CostDecision {
+    // Every batch needs confirming, so the estimate is always "over the limit"
+    cost::decide(1.0, 0.0, interactive, assume_yes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    const TEST_PRICING: &[(&str, Pricing)] = &[(
+        "gpt-4o",
+        Pricing {
+            input_per_mtok: 1_000_000.0,
+            output_per_mtok: 0.0,
+        },
+    )];
+
+    #[test]
+    fn prompts_skip_blank_and_comment_lines() {
+        let prompts = read_prompts("# questions\nWhat is Rust?\n\n  Why borrow?  \n");
+        assert_eq!(prompts, vec!["What is Rust?", "Why borrow?"]);
+    }
+
+    #[test]
+    fn estimate_counts_requests_and_cost() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--only",
+            "gpt,claude",
+            "--batch",
+            "prompts.txt",
+        ])
+        .unwrap();
+        // 8 and 4 characters: 2 and 1 tokens at $1 per token for the priced model
+        let prompts = vec!["12345678".to_string(), "1234".to_string()];
+
+        let estimate = estimate(&args, &prompts, TEST_PRICING);
+        assert_eq!(estimate.prompts, 2);
+        assert_eq!(estimate.requests, 4);
+        assert_eq!(estimate.summary_requests, 2);
+        assert!((estimate.cost - 3.0).abs() < 1e-9);
+        assert_eq!(estimate.unpriced, vec!["claude-sonnet-4-6"]);
+
+        let summary = render(&estimate);
+        assert!(summary.starts_with("Batch: 2 prompts\n"));
+        assert!(summary.contains("Models: ChatGPT (gpt-4o), Claude (claude-sonnet-4-6)"));
+        assert!(summary.contains("Requests: 4 (+2 summary)"));
+        assert!(summary.contains("Estimated cost: $3.0000 (excluding claude-sonnet-4-6)"));
+    }
+
+    #[test]
+    fn yes_skips_confirmation_and_pipes_abort() {
+        assert_eq!(decide(false, true), CostDecision::Proceed);
+        assert_eq!(decide(true, true), CostDecision::Proceed);
+        assert_eq!(decide(true, false), CostDecision::Confirm);
+        assert_eq!(decide(false, false), CostDecision::Abort);
+    }
+}
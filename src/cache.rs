@@ -0,0 +1,569 @@
+//! Response cache for ChatDelta CLI (`--cache`)
+//!
+//! Successful responses are stored as JSON files in `~/.chatdelta/cache` (or `--cache-dir`),
+//! one per request, named by a SHA-256 of everything that shapes the reply. The layout is
+//! content-addressed and machine-independent, so a cache can be shared: `--cache-export`
+//! packs it into a tarball with its format version and `--cache-import` merges one in.
+//! Each entry also records its own key, so a file stored under the wrong name is never
+//! served. Entries older than `--cache-ttl` are ignored on lookup; `--cache-stats`,
+//! `--cache-prune` and `--cache-clear` manage the directory.
+
+use crate::cli::Args;
+use crate::logging;
+use chatdelta::{AiResponse, ResponseMetadata};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Version of the on-disk cache format, stored in `VERSION` and in exported tarballs
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Name of the format version file, in the cache directory and in tarballs
+const VERSION_FILE: &str = "VERSION";
+
+/// Cache directory: --cache-dir, or ~/.chatdelta/cache
+pub fn cache_dir(custom: Option<&Path>) -> PathBuf {
+    if let Some(dir) = custom {
+        return dir.to_path_buf();
+    }
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".chatdelta")
+        .join("cache")
+}
+
+/// A cached response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub created_at: DateTime<Utc>,
+    pub model: String,
+    pub content: String,
+    #[serde(default)]
+    pub total_tokens: Option<u32>,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// The key the entry was stored under; absent in entries from before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl CacheEntry {
+    pub fn new(model: &str, response: &AiResponse) -> Self {
+        CacheEntry {
+            created_at: Utc::now(),
+            model: model.to_string(),
+            content: response.content.clone(),
+            total_tokens: response.metadata.total_tokens,
+            finish_reason: response.metadata.finish_reason.clone(),
+            key: None,
+        }
+    }
+
+    /// The entry as a response; latency is left unset since nothing was requested
+    pub fn into_response(self) -> AiResponse {
+        AiResponse {
+            content: self.content,
+            metadata: ResponseMetadata {
+                model_used: Some(self.model),
+                total_tokens: self.total_tokens,
+                finish_reason: self.finish_reason,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn is_expired(&self, ttl_secs: u64, now: DateTime<Utc>) -> bool {
+        (now - self.created_at).num_seconds() > ttl_secs as i64
+    }
+
+    /// Whether the entry may be served for `key`
+    fn belongs_to(&self, key: &str) -> bool {
+        self.key.as_deref().is_none_or(|own| own == key)
+    }
+
+    /// Same answer, ignoring when it was cached
+    fn same_response(&self, other: &CacheEntry) -> bool {
+        (
+            &self.model,
+            &self.content,
+            self.total_tokens,
+            &self.finish_reason,
+        ) == (
+            &other.model,
+            &other.content,
+            other.total_tokens,
+            &other.finish_reason,
+        )
+    }
+}
+
+/// Whether `name` is a cache key (64 lower-case hex digits)
+fn is_key(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Cache key for a request: hex SHA-256 of the AI, model, prompt, and the settings that
+/// change the reply
+pub fn cache_key(args: &Args, name: &str, model: &str, prompt: &str) -> String {
+    let base_url = args.ai_for_client(name).and_then(|ai| args.base_url(ai));
+    let request = serde_json::json!([
+        name,
+        model,
+        base_url,
+        args.system_prompt,
+        args.temperature,
+        args.max_tokens,
+        prompt,
+    ]);
+    let digest = Sha256::digest(request.to_string().as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Cached responses in one directory
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl_secs: u64,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl_secs: u64) -> Self {
+        ResponseCache { dir, ttl_secs }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The entry for `key`, unless it is missing, unreadable, stored for another key, older
+    /// than the TTL, or the cache is in another format version
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        if !format_matches(&self.dir) {
+            return None;
+        }
+        let entry = read_entry(&self.path(key))?;
+        (entry.belongs_to(key) && !entry.is_expired(self.ttl_secs, Utc::now())).then_some(entry)
+    }
+
+    pub fn put(&self, key: &str, entry: &CacheEntry) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            format!(
+                "Failed to create cache directory {}: {}",
+                self.dir.display(),
+                e
+            )
+        })?;
+        let version = self.dir.join(VERSION_FILE);
+        if !version.exists() {
+            fs::write(&version, format!("{}\n", CACHE_FORMAT_VERSION))
+                .map_err(|e| format!("Failed to write {}: {}", version.display(), e))?;
+        }
+        let entry = CacheEntry {
+            key: Some(key.to_string()),
+            ..entry.clone()
+        };
+        let json = serde_json::to_string_pretty(&entry).map_err(|e| e.to_string())?;
+        fs::write(self.path(key), json).map_err(|e| format!("Failed to write cache entry: {}", e))
+    }
+}
+
+/// Whether the cache in `dir` is in the current format; a cache without a version file
+/// predates versioning and is the same format
+fn format_matches(dir: &Path) -> bool {
+    fs::read_to_string(dir.join(VERSION_FILE)).map_or(true, |version| {
+        version.trim() == CACHE_FORMAT_VERSION.to_string()
+    })
+}
+
+fn read_entry(path: &Path) -> Option<CacheEntry> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Cache entry files in `dir`
+fn entry_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read cache directory {}: {}", dir.display(), e))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect())
+}
+
+/// Totals for --cache-stats
+#[derive(Debug, Default, Serialize)]
+pub struct CacheStats {
+    pub entries: u32,
+    pub total_size_bytes: u64,
+    pub oldest_entry: Option<DateTime<Utc>>,
+}
+
+impl CacheStats {
+    pub fn size_human_readable(&self) -> String {
+        logging::format_size(self.total_size_bytes)
+    }
+}
+
+/// Entry count, size and oldest entry; None when the cache directory doesn't exist
+pub fn stats(dir: &Path) -> Result<Option<CacheStats>, String> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut stats = CacheStats::default();
+    for path in entry_files(dir)? {
+        stats.entries += 1;
+        stats.total_size_bytes += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if let Some(entry) = read_entry(&path) {
+            stats.oldest_entry = Some(
+                stats
+                    .oldest_entry
+                    .map_or(entry.created_at, |t| t.min(entry.created_at)),
+            );
+        }
+    }
+    Ok(Some(stats))
+}
+
+/// Remove entries older than `ttl_secs` as of `now`, and unreadable ones. Returns how
+/// many were removed; None when the cache directory doesn't exist.
+pub fn prune(dir: &Path, ttl_secs: u64, now: DateTime<Utc>) -> Result<Option<usize>, String> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut removed = 0;
+    for path in entry_files(dir)? {
+        if read_entry(&path).is_none_or(|entry| entry.is_expired(ttl_secs, now)) {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            removed += 1;
+        }
+    }
+    Ok(Some(removed))
+}
+
+/// Pack the cache's entries and format version into a tar archive at `tarball`. Returns the
+/// number of entries written; unreadable entries are left out.
+pub fn export(dir: &Path, tarball: &Path) -> Result<usize, String> {
+    let files = if dir.is_dir() {
+        entry_files(dir)?
+    } else {
+        Vec::new()
+    };
+    let file = File::create(tarball)
+        .map_err(|e| format!("Failed to create {}: {}", tarball.display(), e))?;
+    let mut archive = tar::Builder::new(file);
+    let failed = |e: std::io::Error| format!("Failed to write {}: {}", tarball.display(), e);
+
+    let version = format!("{}\n", CACHE_FORMAT_VERSION);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(version.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, VERSION_FILE, version.as_bytes())
+        .map_err(failed)?;
+
+    let mut exported = 0;
+    for path in files {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let valid = name.strip_suffix(".json").is_some_and(is_key) && read_entry(&path).is_some();
+        if valid {
+            archive.append_path_with_name(&path, name).map_err(failed)?;
+            exported += 1;
+        }
+    }
+    archive.finish().map_err(failed)?;
+    Ok(exported)
+}
+
+/// What --cache-import did with each entry in the archive
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Already cached with the same response
+    pub unchanged: usize,
+    /// Cached locally with a different response; the local entry is kept
+    pub conflicts: usize,
+    /// Not a cache entry, unreadable, or stored under a name that isn't its key
+    pub rejected: usize,
+}
+
+impl ImportReport {
+    pub fn render(&self) -> String {
+        format!(
+            "Imported {} cached response{} ({} already present, {} conflicting kept as cached here, {} rejected)",
+            self.imported,
+            if self.imported == 1 { "" } else { "s" },
+            self.unchanged,
+            self.conflicts,
+            self.rejected
+        )
+    }
+}
+
+/// Merge the entries of an archive written by `export` into the cache in `dir`. The
+/// archive must carry the current format version.
+pub fn import(dir: &Path, tarball: &Path) -> Result<ImportReport, String> {
+    let file =
+        File::open(tarball).map_err(|e| format!("Failed to open {}: {}", tarball.display(), e))?;
+    let invalid =
+        |e: std::io::Error| format!("Failed to read cache archive {}: {}", tarball.display(), e);
+    let mut archive = tar::Archive::new(file);
+    let mut version = None;
+    let mut entries = Vec::new();
+    for item in archive.entries().map_err(invalid)? {
+        let mut item = item.map_err(invalid)?;
+        let name = item.path().map_err(invalid)?.to_string_lossy().into_owned();
+        let mut content = String::new();
+        if item.read_to_string(&mut content).is_err() {
+            entries.push((name, None));
+            continue;
+        }
+        match name.as_str() {
+            VERSION_FILE => version = Some(content.trim().to_string()),
+            _ => entries.push((name, Some(content))),
+        }
+    }
+    match version {
+        Some(v) if v == CACHE_FORMAT_VERSION.to_string() => {}
+        Some(v) => {
+            return Err(format!(
+                "Cache archive {} is format version {}; this version of chatdelta reads version {}",
+                tarball.display(),
+                v,
+                CACHE_FORMAT_VERSION
+            ))
+        }
+        None => {
+            return Err(format!(
+                "{} is not a chatdelta cache archive (no {})",
+                tarball.display(),
+                VERSION_FILE
+            ))
+        }
+    }
+
+    let cache = ResponseCache::new(dir.to_path_buf(), u64::MAX);
+    let mut report = ImportReport::default();
+    for (name, content) in entries {
+        let parsed = name
+            .strip_suffix(".json")
+            .filter(|key| is_key(key))
+            .and_then(|key| {
+                let entry: CacheEntry = serde_json::from_str(content.as_deref()?).ok()?;
+                entry.belongs_to(key).then(|| (key.to_string(), entry))
+            });
+        let Some((key, entry)) = parsed else {
+            report.rejected += 1;
+            continue;
+        };
+        match read_entry(&cache.path(&key)) {
+            Some(local) if local.same_response(&entry) => report.unchanged += 1,
+            Some(_) => report.conflicts += 1,
+            None => {
+                cache.put(&key, &entry)?;
+                report.imported += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Delete the cache directory. Returns the number of entries it held; None when it
+/// doesn't exist.
+pub fn clear(dir: &Path) -> Result<Option<usize>, String> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let count = entry_files(dir)?.len();
+    fs::remove_dir_all(dir).map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))?;
+    Ok(Some(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn temp_cache() -> PathBuf {
+        std::env::temp_dir().join(format!("chatdelta-cache-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn entry(content: &str, age_secs: i64) -> CacheEntry {
+        CacheEntry {
+            created_at: Utc::now() - chrono::Duration::seconds(age_secs),
+            model: "gpt-4o".to_string(),
+            content: content.to_string(),
+            total_tokens: Some(12),
+            finish_reason: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn keys_depend_on_everything_that_shapes_the_reply() {
+        let args = Args::try_parse_from(["chatdelta", "Hi"]).unwrap();
+        let key = cache_key(&args, "ChatGPT", "gpt-4o", "Hi");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key(&args, "ChatGPT", "gpt-4o", "Hi"));
+        assert_ne!(key, cache_key(&args, "ChatGPT", "gpt-4o-mini", "Hi"));
+        assert_ne!(key, cache_key(&args, "ChatGPT", "gpt-4o", "Hi!"));
+
+        let warm = Args::try_parse_from(["chatdelta", "--temperature", "0.9", "Hi"]).unwrap();
+        assert_ne!(key, cache_key(&warm, "ChatGPT", "gpt-4o", "Hi"));
+
+        // Another endpoint serving the same model gets its own entries
+        let gateway =
+            Args::try_parse_from(["chatdelta", "--gpt-base-url", "http://localhost:4000", "Hi"])
+                .unwrap();
+        assert_ne!(key, cache_key(&gateway, "ChatGPT", "gpt-4o", "Hi"));
+        assert_eq!(
+            cache_key(&args, "Claude", "claude-sonnet-4-6", "Hi"),
+            cache_key(&gateway, "Claude", "claude-sonnet-4-6", "Hi")
+        );
+    }
+
+    #[test]
+    fn stored_responses_are_served_until_they_expire() {
+        let dir = temp_cache();
+        let cache = ResponseCache::new(dir.clone(), 60);
+        cache.put("fresh", &entry("cached answer", 0)).unwrap();
+        cache.put("stale", &entry("old answer", 120)).unwrap();
+
+        let response = cache.get("fresh").unwrap().into_response();
+        assert_eq!(response.content, "cached answer");
+        assert_eq!(response.metadata.total_tokens, Some(12));
+        assert!(cache.get("stale").is_none());
+        assert!(cache.get("missing").is_none());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn stats_count_entries_and_prune_removes_expired() {
+        let dir = temp_cache();
+        assert!(
+            stats(&dir).unwrap().is_none(),
+            "missing directory is not an error"
+        );
+        assert!(prune(&dir, 60, Utc::now()).unwrap().is_none());
+
+        let cache = ResponseCache::new(dir.clone(), 3600);
+        cache.put("a", &entry("one", 10)).unwrap();
+        cache.put("b", &entry("two", 7200)).unwrap();
+        cache.put("c", &entry("three", 9000)).unwrap();
+        fs::write(dir.join("corrupt.json"), "not json").unwrap();
+
+        let before = stats(&dir).unwrap().unwrap();
+        assert_eq!(before.entries, 4);
+        assert!(before.total_size_bytes > 0);
+        let oldest = before.oldest_entry.unwrap();
+        assert!((Utc::now() - oldest).num_seconds() >= 9000);
+
+        // The two expired entries and the unreadable one go
+        assert_eq!(prune(&dir, 3600, Utc::now()).unwrap(), Some(3));
+        let after = stats(&dir).unwrap().unwrap();
+        assert_eq!(after.entries, 1);
+        assert!(cache.get("a").is_some());
+
+        assert_eq!(clear(&dir).unwrap(), Some(1));
+        assert!(!dir.exists());
+        assert!(clear(&dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn entries_are_only_served_for_their_own_key() {
+        let dir = temp_cache();
+        let cache = ResponseCache::new(dir.clone(), 3600);
+        cache.put("k1", &entry("first", 0)).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("VERSION")).unwrap().trim(), "1");
+        // A file copied under another key's name is not served for it
+        fs::copy(dir.join("k1.json"), dir.join("k2.json")).unwrap();
+        assert!(cache.get("k1").is_some());
+        assert!(cache.get("k2").is_none());
+
+        // A cache written by another format version is ignored
+        fs::write(dir.join("VERSION"), "99\n").unwrap();
+        assert!(cache.get("k1").is_none());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn export_and_import_round_trip_a_cache() {
+        let args = Args::try_parse_from(["chatdelta", "Hi"]).unwrap();
+        let keys: Vec<String> = ["one", "two", "three"]
+            .iter()
+            .map(|p| cache_key(&args, "ChatGPT", "gpt-4o", p))
+            .collect();
+        let source = temp_cache();
+        let cache = ResponseCache::new(source.clone(), 3600);
+        for (key, content) in keys
+            .iter()
+            .zip(["answer one", "answer two", "answer three"])
+        {
+            cache.put(key, &entry(content, 10)).unwrap();
+        }
+        fs::write(source.join("corrupt.json"), "not json").unwrap();
+
+        let tarball = temp_cache().with_extension("tar");
+        assert_eq!(export(&source, &tarball).unwrap(), 3);
+
+        let target = temp_cache();
+        let report = import(&target, &tarball).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                imported: 3,
+                ..Default::default()
+            }
+        );
+        let imported = ResponseCache::new(target.clone(), 3600);
+        for key in &keys {
+            let (original, copy) = (cache.get(key).unwrap(), imported.get(key).unwrap());
+            assert_eq!(
+                (copy.content, copy.created_at),
+                (original.content, original.created_at)
+            );
+        }
+
+        // Importing again changes nothing; a differing local entry is kept
+        imported.put(&keys[0], &entry("local answer", 0)).unwrap();
+        let again = import(&target, &tarball).unwrap();
+        assert_eq!(
+            (again.imported, again.unchanged, again.conflicts),
+            (0, 2, 1)
+        );
+        assert_eq!(imported.get(&keys[0]).unwrap().content, "local answer");
+
+        // Archives without the current version are refused
+        let other = temp_cache().with_extension("tar");
+        let mut builder = tar::Builder::new(File::create(&other).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "VERSION", &b"99\n"[..])
+            .unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+        let err = import(&target, &other).unwrap_err();
+        assert!(err.contains("format version 99"), "{}", err);
+
+        for path in [&source, &target] {
+            fs::remove_dir_all(path).ok();
+        }
+        fs::remove_file(&tarball).ok();
+        fs::remove_file(&other).ok();
+    }
+
+    #[test]
+    fn sizes_are_human_readable() {
+        let stats = CacheStats {
+            total_size_bytes: 1536,
+            ..Default::default()
+        };
+        assert_eq!(stats.size_human_readable(), "1.50 KB");
+    }
+}
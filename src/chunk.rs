@@ -0,0 +1,331 @@
+//! Prompt chunking for ChatDelta CLI (`--chunk-prompt`)
+//!
+//! A prompt too large for a model's context window is split into overlapping windows on
+//! paragraph and sentence boundaries, and each window is sent as its own request.
+
+use crate::cost::estimate_tokens;
+use crate::models;
+use std::collections::{BTreeSet, HashMap};
+
+/// Context window sizes in tokens for models missing from the model table, keyed by
+/// model-name prefix (the longest matching prefix wins)
+pub const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("o3-", 200_000),
+    ("gemini-", 1_048_576),
+    ("claude-", 200_000),
+];
+
+/// Assumed window for models missing from the table
+pub const DEFAULT_CONTEXT_WINDOW: u32 = 128_000;
+
+/// Tokens kept free for the "part i of n" framing around each chunk
+const FRAMING_TOKENS: u32 = 100;
+
+/// Context window for a model: from the model table when listed, otherwise by longest
+/// matching prefix
+pub fn context_window(model: &str) -> u32 {
+    if let Some(info) = models::lookup(model) {
+        return info.context_window;
+    }
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(DEFAULT_CONTEXT_WINDOW, |(_, tokens)| *tokens)
+}
+
+/// How prompts are chunked for models whose context window they exceed
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkConfig {
+    /// Tokens repeated from the end of one chunk at the start of the next (--chunk-overlap)
+    pub overlap_tokens: u32,
+    /// Tokens reserved for each reply (--max-tokens)
+    pub reply_tokens: u32,
+}
+
+impl ChunkConfig {
+    /// The chunks to send `model`, or None when the prompt fits in one request. Each
+    /// request keeps room for `instructions`, which go out with every chunk.
+    pub fn split(
+        &self,
+        prompt: &str,
+        model: &str,
+        instructions: Option<&str>,
+    ) -> Option<Vec<String>> {
+        let reserved = self
+            .reply_tokens
+            .saturating_add(FRAMING_TOKENS)
+            .saturating_add(instructions.map_or(0, estimate_tokens));
+        let window = context_window(model).saturating_sub(reserved);
+        if estimate_tokens(prompt) <= window {
+            return None;
+        }
+        Some(split_text(
+            prompt,
+            window as usize * 4,
+            self.overlap_tokens as usize * 4,
+        ))
+    }
+}
+
+/// The part prompts for each model whose context window the prompt overflows, split once
+/// before any request goes out
+#[derive(Debug, Default)]
+pub struct ChunkPlans {
+    parts: HashMap<String, Vec<String>>,
+}
+
+impl ChunkPlans {
+    /// Split `prompt` for each distinct model. `instructions` (the --tool-schema directions)
+    /// close every part, so each request carries them rather than only the last.
+    pub fn new<'a>(
+        config: ChunkConfig,
+        prompt: &str,
+        instructions: Option<&str>,
+        models: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut parts = HashMap::new();
+        for model in models.into_iter().collect::<BTreeSet<_>>() {
+            let Some(chunks) = config.split(prompt, model, instructions) else {
+                continue;
+            };
+            let framed = chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    let part = part_prompt(index, chunks.len(), chunk);
+                    match instructions {
+                        Some(instructions) => format!("{}\n\n{}", part, instructions),
+                        None => part,
+                    }
+                })
+                .collect();
+            parts.insert(model.to_string(), framed);
+        }
+        ChunkPlans { parts }
+    }
+
+    /// The part prompts for `model`, or None when the whole prompt fits
+    pub fn parts(&self, model: &str) -> Option<&[String]> {
+        self.parts.get(model).map(Vec::as_slice)
+    }
+}
+
+/// Prompt for one chunk, telling the model where it sits in the whole
+pub fn part_prompt(index: usize, total: usize, chunk: &str) -> String {
+    format!(
+        "This is part {} of {} of a longer input that was split to fit your context window. \
+         Respond to this part.\n\n{}",
+        index + 1,
+        total,
+        chunk
+    )
+}
+
+/// Split `text` into windows of at most `max_chars` characters, breaking at paragraphs,
+/// then sentences, then whitespace. Each window after the first starts with up to
+/// `overlap_chars` characters of whole pieces from the end of the previous one.
+pub fn split_text(text: &str, max_chars: usize, overlap_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut windows = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut len = 0;
+
+    for piece in pieces(text, max_chars) {
+        let piece_len = piece.chars().count();
+        if len + piece_len > max_chars && !current.is_empty() {
+            windows.push(current.concat().trim().to_string());
+
+            // Carry whole pieces from the end of this window into the next
+            let mut carried = 0;
+            let keep = current
+                .iter()
+                .rev()
+                .take_while(|p| {
+                    carried += p.chars().count();
+                    carried <= overlap_chars
+                })
+                .count();
+            current.drain(..current.len() - keep);
+            len = current.iter().map(|p| p.chars().count()).sum();
+            if len + piece_len > max_chars {
+                current.clear();
+                len = 0;
+            }
+        }
+        current.push(piece);
+        len += piece_len;
+    }
+    if !current.is_empty() {
+        windows.push(current.concat().trim().to_string());
+    }
+    windows.retain(|w| !w.is_empty());
+    windows
+}
+
+/// Break text into pieces no longer than `max_chars`: paragraphs where they fit, else
+/// sentences, else whitespace-delimited runs. Pieces keep their trailing separators so
+/// concatenating them restores the text.
+fn pieces(text: &str, max_chars: usize) -> Vec<&str> {
+    let fits = |s: &str| s.chars().count() <= max_chars;
+    let mut out = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        if fits(paragraph) {
+            out.push(paragraph);
+            continue;
+        }
+        for sentence in paragraph.split_inclusive(['.', '!', '?']) {
+            if fits(sentence) {
+                out.push(sentence);
+            } else {
+                out.extend(hard_split(sentence, max_chars));
+            }
+        }
+    }
+    out
+}
+
+/// Split at the last whitespace before each `max_chars` boundary (or exactly at it when
+/// there is none)
+fn hard_split(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > max_chars {
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(i, _)| i);
+        let cut = rest[..limit]
+            .rfind(char::is_whitespace)
+            .map(|i| i + rest[i..].chars().next().map_or(1, char::len_utf8))
+            .filter(|&i| i > 0)
+            .unwrap_or(limit);
+        out.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        out.push(rest);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_input() -> String {
+        (1..=6)
+            .map(|p| {
+                (1..=4)
+                    .map(|s| format!("Paragraph {} sentence {} has some words.", p, s))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    #[test]
+    fn chunks_break_on_paragraphs_and_overlap() {
+        let text = long_input();
+        let paragraph_len = text.split("\n\n").next().unwrap().len() + 2;
+        let chunks = split_text(&text, paragraph_len * 2, paragraph_len);
+
+        assert!(chunks.len() >= 5, "{} chunks", chunks.len());
+        assert!(chunks
+            .iter()
+            .all(|c| c.chars().count() <= paragraph_len * 2));
+        for chunk in &chunks {
+            assert!(
+                chunk.starts_with("Paragraph "),
+                "chunk starts mid-paragraph: {:?}",
+                chunk
+            );
+            assert!(
+                chunk.ends_with("has some words."),
+                "chunk ends mid-sentence: {:?}",
+                chunk
+            );
+        }
+        // Each chunk after the first repeats the last paragraph of the one before
+        for pair in chunks.windows(2) {
+            let last_paragraph = pair[0].rsplit("\n\n").next().unwrap();
+            assert!(pair[1].starts_with(last_paragraph));
+        }
+        assert!(chunks.last().unwrap().contains("Paragraph 6 sentence 4"));
+    }
+
+    #[test]
+    fn oversized_paragraphs_fall_back_to_sentences_then_words() {
+        let text = long_input().replace("\n\n", " ");
+        let chunks = split_text(&text, 120, 0);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 120));
+        assert!(chunks.iter().all(|c| c.ends_with('.')));
+        assert_eq!(chunks.concat().matches("sentence").count(), 24);
+
+        let words = "lorem ipsum dolor ".repeat(20);
+        let chunks = split_text(&words, 25, 0);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 25));
+        // Words are never cut in half
+        assert!(chunks
+            .iter()
+            .flat_map(|c| c.split_whitespace())
+            .all(|w| matches!(w, "lorem" | "ipsum" | "dolor")));
+    }
+
+    #[test]
+    fn every_part_carries_the_instructions() {
+        let config = ChunkConfig {
+            overlap_tokens: 0,
+            reply_tokens: 1024,
+        };
+        let huge = long_input().repeat(2_000);
+        let instructions = "Respond only with JSON.";
+        let plans = ChunkPlans::new(
+            config,
+            &huge,
+            Some(instructions),
+            ["gpt-4o", "gpt-4o", "gemini-2.5-pro"],
+        );
+
+        let parts = plans.parts("gpt-4o").expect("over the gpt-4o window");
+        assert!(parts.len() > 1);
+        assert!(parts
+            .iter()
+            .all(|part| part.ends_with("\n\nRespond only with JSON.")));
+        assert!(parts
+            .iter()
+            .all(|part| estimate_tokens(part) <= 128_000 - 1024));
+        assert!(parts[0].starts_with(&format!("This is part 1 of {}", parts.len())));
+        assert!(plans.parts("gemini-2.5-pro").is_none());
+    }
+
+    #[test]
+    fn only_oversized_prompts_are_split() {
+        let config = ChunkConfig {
+            overlap_tokens: 50,
+            reply_tokens: 1024,
+        };
+        assert!(config.split("Short prompt", "gpt-4o", None).is_none());
+
+        let huge = long_input().repeat(2_000);
+        let chunks = config
+            .split(&huge, "gpt-4o", None)
+            .expect("over the gpt-4o window");
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| estimate_tokens(c) <= 128_000 - 1124));
+        assert!(config.split(&huge, "gemini-2.5-pro", None).is_none());
+
+        let huge_reply = ChunkConfig {
+            overlap_tokens: 0,
+            reply_tokens: u32::MAX,
+        };
+        assert!(huge_reply.split("Short prompt", "gpt-4o", None).is_some());
+        assert_eq!(context_window("some-local-model"), DEFAULT_CONTEXT_WINDOW);
+        assert_eq!(context_window("o3-mini"), 200_000);
+        assert_eq!(context_window("gpt-4.1-nano"), 1_047_576);
+        assert!(part_prompt(0, 3, "body").starts_with("This is part 1 of 3"));
+    }
+}
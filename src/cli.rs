@@ -1,11 +1,31 @@
 //! Command-line interface for ChatDelta
 
+use crate::config::{ModelAlias, ModelMap};
+use crate::filter::ResponseFilter;
+use crate::providers::Provider;
+use crate::refusal::RefusalDetector;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// Every AI the CLI can query, as named in --only/--exclude
+pub const AI_NAMES: [&str; 3] = ["gpt", "gemini", "claude"];
+
+/// Named AI groups for --only/--exclude: an AI belongs to a group when its configured
+/// model name contains one of the group's markers
+const AI_GROUPS: &[(&str, &[&str])] = &[
+    ("fast", &["mini", "haiku", "flash"]),
+    ("flagship", &["opus", "pro", "gpt-5", "o3"]),
+];
 
 /// Command line arguments for chatdelta
-#[derive(Parser, Debug)]
-#[command(version, about = "Query multiple AIs and connect their responses", long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[command(
+    version,
+    about = "Query multiple AIs and connect their responses",
+    long_about = None,
+    after_help = crate::error::EXIT_CODES_HELP
+)]
 pub struct Args {
     /// Prompt to send to the AIs (use '-' to read from stdin)
     pub prompt: Option<String>,
@@ -14,6 +34,121 @@ pub struct Args {
     #[arg(long, short = 'F', conflicts_with = "prompt")]
     pub prompt_file: Option<PathBuf>,
 
+    /// Directory of named prompt templates (<name>.txt or <name>.md)
+    #[arg(long, value_name = "DIR")]
+    pub prompt_template_dir: Option<PathBuf>,
+
+    /// Use the named template from --prompt-template-dir as the prompt (fill placeholders with --var)
+    #[arg(long, value_name = "NAME", requires = "prompt_template_dir", conflicts_with_all = ["prompt", "prompt_file"])]
+    pub template_name: Option<String>,
+
+    /// List the templates in --prompt-template-dir and exit
+    #[arg(long, requires = "prompt_template_dir")]
+    pub list_templates: bool,
+
+    /// Run every prompt in a file (one per line) after a confirmed pre-flight estimate (not with per-run output files)
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "prompt",
+            "prompt_file",
+            "template_name",
+            "prompt_from_git_diff",
+            "prompt_base64",
+            "conversation",
+            "output",
+            "log",
+            "save_responses",
+            "metrics_file",
+            "save_prompt",
+            "status_file",
+        ]
+    )]
+    pub batch: Option<PathBuf>,
+
+    /// Use the output of `git diff [REF]` as the prompt, for code review
+    #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "", conflicts_with_all = ["prompt", "prompt_file"])]
+    pub prompt_from_git_diff: Option<String>,
+
+    /// Instructions placed before the prompt (replaces the default review instruction with --prompt-from-git-diff)
+    #[arg(long, value_name = "TEXT")]
+    pub prompt_prefix: Option<String>,
+
+    /// Role preset supplying a system prompt and prompt prefix: reviewer, summarizer, translator, or one from --role-file
+    #[arg(long, value_name = "NAME")]
+    pub role: Option<String>,
+
+    /// TOML file of extra roles (name = { system = "...", prefix = "..." })
+    #[arg(long, value_name = "PATH")]
+    pub role_file: Option<PathBuf>,
+
+    /// List the role presets (built-in and from --role-file) and exit
+    #[arg(long)]
+    pub list_roles: bool,
+
+    /// Format of --prompt-file: text, yaml, json (yaml/json files provide system, user, vars)
+    #[arg(long, default_value = "text")]
+    pub input_format: String,
+
+    /// The prompt (or stdin with `-`) is base64-encoded; decode it before use
+    #[arg(long, alias = "prompt-encoding-base64", requires = "prompt")]
+    pub prompt_base64: bool,
+
+    /// Keep leading/trailing whitespace of stdin and --prompt-file prompts instead of trimming
+    #[arg(long)]
+    pub no_trim: bool,
+
+    /// Remove comment lines from the prompt before sending (escape a literal line with a backslash)
+    #[arg(long)]
+    pub strip_comments: bool,
+
+    /// Line prefix that marks a comment for --strip-comments
+    #[arg(long, default_value = "#", value_name = "PREFIX")]
+    pub comment_prefix: String,
+
+    /// Warn before sending a prompt that looks like it contains PII (emails, phone, card or SSN numbers)
+    #[arg(long)]
+    pub pii_scan: bool,
+
+    /// Refuse to send a prompt that looks like it contains PII (implies --pii-scan)
+    #[arg(long)]
+    pub pii_block: bool,
+
+    /// Send the prompt even when --pii-block finds PII; the findings are still reported
+    #[arg(long)]
+    pub allow_pii: bool,
+
+    /// Template variable for {{NAME}} placeholders in the prompt (repeatable)
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    pub vars: Vec<String>,
+
+    /// Fill this {{NAME}} placeholder with piped stdin instead of using stdin as the whole prompt (a {{stdin}} placeholder is filled automatically)
+    #[arg(long, value_name = "NAME")]
+    pub stdin_into: Option<String>,
+
+    /// Combine --prompt-file with piped stdin instead of ignoring the pipe
+    #[arg(long, requires = "prompt_file", conflicts_with = "stdin_into")]
+    pub merge_stdin_with_file: bool,
+
+    /// Where --merge-stdin-with-file puts the piped text: append or prepend
+    #[arg(
+        long,
+        default_value = "append",
+        value_name = "POSITION",
+        requires = "merge_stdin_with_file"
+    )]
+    pub stdin_position: String,
+
+    /// Text between the prompt file and piped stdin (\n and \t are expanded)
+    #[arg(
+        long,
+        default_value = "\\n\\n",
+        value_name = "SEP",
+        requires = "merge_stdin_with_file"
+    )]
+    pub stdin_separator: String,
+
     /// Optional path to log the full interaction
     #[arg(long, short)]
     pub log: Option<PathBuf>,
@@ -22,6 +157,34 @@ pub struct Args {
     #[arg(long)]
     pub log_dir: Option<PathBuf>,
 
+    /// Re-send a prompt a model refused to an AI left out of the run (see --only/--exclude); the refusal is logged
+    #[arg(long, alias = "auto-retry-other-model-on-refusal")]
+    pub retry_on_refusal: bool,
+
+    /// Regex marking a reply as a refusal, in addition to the built-in phrases (repeatable)
+    #[arg(
+        long = "refusal-pattern",
+        value_name = "REGEX",
+        requires = "retry_on_refusal"
+    )]
+    pub refusal_patterns: Vec<String>,
+
+    /// Regex removed from every response before display, logging and summary: matching spans are cut and emptied lines dropped (repeatable; not with --stream)
+    #[arg(
+        long = "response-filter",
+        value_name = "REGEX",
+        conflicts_with = "stream"
+    )]
+    pub response_filters: Vec<String>,
+
+    /// Leave refused replies out of the output once --retry-on-refusal has re-sent them
+    #[arg(long, requires = "retry_on_refusal")]
+    pub hide_refusals: bool,
+
+    /// Trace the CLI's own behavior (client creation, requests, retries, summary) to stderr: error, warn, info, debug, trace
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
     /// Log format: simple, json, structured
     #[arg(long, default_value = "simple")]
     pub log_format: String,
@@ -30,14 +193,50 @@ pub struct Args {
     #[arg(long)]
     pub log_metrics: bool,
 
+    /// Fail the run (exit code 4) when fewer than N models answer; the answers are still shown
+    #[arg(long, value_name = "N")]
+    pub min_success: Option<usize>,
+
+    /// Keep only the first model to answer and cancel the others; no summary is generated
+    #[arg(
+        long,
+        alias = "first-response-wins",
+        conflicts_with_all = ["stream", "only_summary", "summary_models", "eager_summary", "retry_on_refusal", "min_success", "cache"]
+    )]
+    pub race: bool,
+
+    /// Print the total wall-clock time of the run to stderr when it ends
+    #[arg(long)]
+    pub elapsed: bool,
+
+    /// Write per-provider request, latency and token metrics to this file
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Format of --metrics-file: json or prometheus (default: prometheus for .prom files, else json)
+    #[arg(long, value_name = "FORMAT", requires = "metrics_file")]
+    pub metrics_format: Option<String>,
+
     /// Enable detailed error logging
     #[arg(long)]
     pub log_errors: bool,
 
+    /// Write identical errors from several models as one entry listing the models
+    #[arg(long, requires = "log_errors")]
+    pub coalesce_errors: bool,
+
+    /// Fail the run when the structured log can't be written, instead of warning and continuing without it
+    #[arg(long)]
+    pub strict_logging: bool,
+
     /// Log session ID for tracking related interactions
     #[arg(long)]
     pub session_id: Option<String>,
 
+    /// Log interaction ID, e.g. an external trace ID, instead of a random UUID (--batch prompts get <ID>-1, <ID>-2, ...)
+    #[arg(long, value_name = "ID")]
+    pub interaction_id: Option<String>,
+
     /// Verbose output - show detailed progress and API responses
     #[arg(long, short)]
     pub verbose: bool,
@@ -46,30 +245,211 @@ pub struct Args {
     #[arg(long, short)]
     pub quiet: bool,
 
-    /// Output format: text, json, markdown
+    /// Report failed models in one line, e.g. "2 models failed: ChatGPT (429), Claude (401)"; full errors stay in the logs
+    #[arg(long)]
+    pub compact_errors: bool,
+
+    /// Suppress only the "Generating summary..." status lines, keeping query progress
+    #[arg(long)]
+    pub quiet_summary: bool,
+
+    /// Output format: text, json, ndjson, markdown
     #[arg(long, short = 'f', default_value = "text")]
     pub format: String,
 
+    /// Wrap text output to a column width: a number, auto (terminal width), or none
+    #[arg(long, default_value = "none", value_name = "N|auto|none")]
+    pub wrap: String,
+
+    /// Render delimited (CSV, TSV, semicolon) data in responses as tables in text and markdown output
+    #[arg(long)]
+    pub render_tables: bool,
+
+    /// Show a word-level diff of each response against the first
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Color diff output: auto (terminal without NO_COLOR), always, or never
+    #[arg(long, default_value = "auto", value_name = "auto|always|never")]
+    pub color: String,
+
+    /// Where the summary goes in text and markdown output: top or bottom
+    #[arg(long, default_value = "bottom", value_name = "top|bottom")]
+    pub summary_position: String,
+
     /// Skip summary generation - just show individual responses
     #[arg(long)]
     pub no_summary: bool,
 
-    /// Only query specific AIs (comma-separated: gpt,gemini,claude)
+    /// Split a prompt that exceeds a model's context window into chunks and query each in turn
+    #[arg(long)]
+    pub chunk_prompt: bool,
+
+    /// Tokens of overlap between consecutive --chunk-prompt chunks
+    #[arg(
+        long,
+        default_value = "200",
+        value_name = "TOKENS",
+        requires = "chunk_prompt"
+    )]
+    pub chunk_overlap: u32,
+
+    /// Summarize even a single response, e.g. the only survivor of a multi-model query
+    #[arg(long, conflicts_with = "no_summary")]
+    pub summarize_single: bool,
+
+    /// Query only the model the summary would use (within --only/--exclude) and condense its answer with the summary prompt
+    #[arg(long, conflicts_with_all = ["no_summary", "summary_models", "race"])]
+    pub force_summary_model_only: bool,
+
+    /// Start summarizing once two responses are in, then refine with the rest (not with --summary-models, --tool-schema or --json-path-extract)
+    #[arg(long, conflicts_with_all = ["no_summary", "summary_models", "tool_schema", "json_path_extract"])]
+    pub eager_summary: bool,
+
+    /// Structure to ask the summary for: text, markdown, or json (default: matches --format)
+    #[arg(long, value_name = "text|markdown|json")]
+    pub summary_format: Option<String>,
+
+    /// Summarize in groups of this many responses, then summarize the group summaries
+    #[arg(long, default_value = "8", value_name = "N")]
+    pub summary_chunk_size: usize,
+
+    /// Show the summarizer the original prompt along with the responses
+    #[arg(long, conflicts_with = "no_summary")]
+    pub summary_include_prompt: bool,
+
+    /// Summarize with this AI (gpt, gemini, claude) when it has a key, before trying the others
+    #[arg(long, value_name = "AI", conflicts_with_all = ["no_summary", "summary_models", "force_summary_model_only"])]
+    pub summary_model: Option<String>,
+
+    /// With --summary-model: report a summary error instead of falling back to another AI
+    #[arg(long, requires = "summary_model")]
+    pub no_fallback_summary: bool,
+
+    /// Summarize with each of these AIs in parallel and show every summary (comma-separated: gpt,gemini,claude)
+    #[arg(long, value_delimiter = ',', conflicts_with = "no_summary")]
+    pub summary_models: Vec<String>,
+
+    /// Output only the summary, suppressing individual responses (fails if no summary is produced)
+    #[arg(long, conflicts_with = "no_summary")]
+    pub only_summary: bool,
+
+    /// Keep individual responses off the terminal and --output while still logging them and summarizing them
+    #[arg(long, conflicts_with = "no_summary")]
+    pub hide_responses: bool,
+
+    /// Prepend the prompt to text output (markdown and JSON always include it)
+    #[arg(long)]
+    pub include_prompt: bool,
+
+    /// Parse each response as JSON and show only the value at this JSONPath (e.g. $.answer)
+    #[arg(long, value_name = "PATH")]
+    pub json_path_extract: Option<String>,
+
+    /// Treat a response as failed when --json-path-extract can't extract from it
+    #[arg(long, requires = "json_path_extract")]
+    pub strict_extract: bool,
+
+    /// JSON function schema; ask each model for arguments to it and show the parsed call
+    #[arg(long, value_name = "FILE")]
+    pub tool_schema: Option<PathBuf>,
+
+    /// Add timestamp, models, settings, and per-response latency/tokens to JSON output
+    #[arg(long)]
+    pub attach_metadata: bool,
+
+    /// Show tokens per second for each model (verbose text output and JSON)
+    #[arg(long)]
+    pub show_throughput: bool,
+
+    /// Find sentences most responses share (fuzzily) and mark them as consensus: highlighted in text and markdown, listed under "consensus" in JSON
+    #[arg(long)]
+    pub highlight_agreement: bool,
+
+    /// Label each response's tone (cautious/neutral/confident) and sentiment with a local word-list heuristic (verbose text output and JSON)
+    #[arg(long)]
+    pub annotate_tone: bool,
+
+    /// Include a SHA-256 hash of each response in JSON output and logs
+    #[arg(long)]
+    pub with_hash: bool,
+
+    /// In JSON output, list responses as an array of {"model", "response"} objects in query order
+    #[arg(long)]
+    pub json_ordered_array: bool,
+
+    /// Only query specific AIs or groups (comma-separated: gpt,gemini,claude,all,fast,flagship)
     #[arg(long, value_delimiter = ',')]
     pub only: Vec<String>,
 
-    /// Exclude specific AIs (comma-separated: gpt,gemini,claude)
+    /// Fail instead of skipping when a provider named in --only has no key or can't be created
+    #[arg(long)]
+    pub strict_models: bool,
+
+    /// TOML file of model aliases (name = { provider = "claude", model = "..." }) selectable with --only
+    #[arg(long, value_name = "PATH")]
+    pub model_map_file: Option<PathBuf>,
+
+    /// Aliases loaded from --model-map-file
+    #[arg(skip)]
+    pub model_map: ModelMap,
+
+    /// Where API key rotation starts, so --batch prompts don't all begin on the first key
+    #[arg(skip)]
+    pub key_offset: usize,
+
+    /// Exclude specific AIs or groups (comma-separated: gpt,gemini,claude,all,fast,flagship)
     #[arg(long, value_delimiter = ',')]
     pub exclude: Vec<String>,
 
-    /// Timeout for API requests in seconds
+    /// Per-request timeout in seconds (--read-timeout overrides it; chatdelta has no separate connect timeout)
     #[arg(long, default_value = "30")]
     pub timeout: u64,
 
+    /// Seconds allowed for connecting to a provider (defaults to --timeout)
+    #[arg(long, value_name = "SECS")]
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds allowed for a provider to generate its response (defaults to --timeout)
+    #[arg(long, value_name = "SECS")]
+    pub read_timeout: Option<u64>,
+
+    /// Print each warning as it happens instead of one block at the end
+    #[arg(long)]
+    pub warnings_inline: bool,
+
+    /// Skip the summary when it likely wouldn't finish before --max-runtime
+    #[arg(long, requires = "max_runtime")]
+    pub deadline_aware_summary: bool,
+
+    /// Abort (or ask on a terminal) if the estimated cost in USD exceeds this limit
+    #[arg(long, value_name = "USD")]
+    pub cost_limit: Option<f64>,
+
+    /// Proceed without confirmation when a --cost-limit is exceeded
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+
+    /// Overall deadline in seconds for the queries and summary (conversation mode is exempt)
+    #[arg(long, value_name = "SECONDS")]
+    pub max_runtime: Option<u64>,
+
     /// Number of retry attempts for failed requests
     #[arg(long, default_value = "0")]
     pub retries: u32,
 
+    /// Cap on retries shared by all models in a run; once used up, failures are not retried
+    #[arg(long, value_name = "N")]
+    pub retry_budget: Option<u32>,
+
+    /// Double the request timeout on each retry (e.g. 30s, 60s, 120s), up to --max-timeout
+    #[arg(long, conflicts_with_all = ["stream", "benchmark", "conversation", "test"])]
+    pub timeout_escalation: bool,
+
+    /// Longest timeout an escalated retry may use, in seconds (defaults to 4x the request timeout)
+    #[arg(long, value_name = "SECS", requires = "timeout_escalation")]
+    pub max_timeout: Option<u64>,
+
     /// OpenAI model to use
     #[arg(long, default_value = "gpt-4o")]
     pub gpt_model: String,
@@ -82,6 +462,38 @@ pub struct Args {
     #[arg(long, default_value = "claude-sonnet-4-6")]
     pub claude_model: String,
 
+    /// OpenAI model to retry with once when --gpt-model times out or hits a server error
+    #[arg(long, value_name = "MODEL")]
+    pub gpt_model_fallback: Option<String>,
+
+    /// Gemini model to retry with once when --gemini-model times out or hits a server error
+    #[arg(long, value_name = "MODEL")]
+    pub gemini_model_fallback: Option<String>,
+
+    /// Claude model to retry with once when --claude-model times out or hits a server error
+    #[arg(long, value_name = "MODEL")]
+    pub claude_model_fallback: Option<String>,
+
+    /// Base URL override for OpenAI requests, e.g. a gateway (env: OPENAI_BASE_URL)
+    #[arg(long, value_name = "URL")]
+    pub gpt_base_url: Option<String>,
+
+    /// Base URL override for Gemini requests (env: GEMINI_BASE_URL)
+    #[arg(long, value_name = "URL")]
+    pub gemini_base_url: Option<String>,
+
+    /// Base URL override for Claude requests (env: ANTHROPIC_BASE_URL)
+    #[arg(long, value_name = "URL")]
+    pub claude_base_url: Option<String>,
+
+    /// OpenAI API keys to rotate between (comma-separated; overrides OPENAI_API_KEY)
+    #[arg(long, value_delimiter = ',', value_name = "KEYS")]
+    pub openai_keys: Vec<String>,
+
+    /// Anthropic API keys to rotate between (comma-separated; overrides ANTHROPIC_API_KEY)
+    #[arg(long, value_delimiter = ',', value_name = "KEYS")]
+    pub anthropic_keys: Vec<String>,
+
     /// Maximum tokens for Claude responses
     #[arg(long, default_value = "1024")]
     pub max_tokens: u32,
@@ -94,18 +506,153 @@ pub struct Args {
     #[arg(long)]
     pub list_models: bool,
 
+    /// With --list-models: show context window, vision, tools and cost tier per model (as a table, or --format json)
+    #[arg(long, requires = "list_models")]
+    pub detailed: bool,
+
     /// Test API connections and exit
     #[arg(long)]
     pub test: bool,
 
+    /// Retries for each --test connection probe, to ride out transient errors
+    #[arg(long, default_value = "0", value_name = "N")]
+    pub test_retries: u32,
+
+    /// Prompt sent by --test to probe each connection
+    #[arg(
+        long,
+        default_value = "Hello, please respond with just 'OK' to confirm you're working.",
+        value_name = "TEXT"
+    )]
+    pub test_prompt: String,
+
+    /// Answer with offline mock clients instead of the real APIs
+    #[cfg(any(test, feature = "mock"))]
+    #[arg(long)]
+    pub mock: bool,
+
+    /// Delay before each mock response, in milliseconds
+    #[cfg(any(test, feature = "mock"))]
+    #[arg(long, default_value = "0", value_name = "MS", requires = "mock")]
+    pub mock_delay: u64,
+
+    /// Make the listed mock providers fail (comma-separated: gpt,gemini,claude)
+    #[cfg(any(test, feature = "mock"))]
+    #[arg(long, value_delimiter = ',', requires = "mock")]
+    pub mock_fail: Vec<String>,
+
+    /// With --test, check keys, models, prompt file and log directory locally without any HTTP calls
+    #[arg(long, requires = "test")]
+    pub no_network: bool,
+
     /// Check API key configuration and provide setup guidance
     #[arg(long)]
     pub doctor: bool,
 
+    /// Send the prompt N times to each model and report latency percentiles and success rate
+    #[arg(long, value_name = "N", conflicts_with = "conversation")]
+    pub benchmark: Option<u32>,
+
+    /// Send each model a tiny throwaway request first so measured latency reflects warm connections
+    #[arg(long)]
+    pub warmup: bool,
+
+    /// Most --benchmark requests in flight at once (default: one per model)
+    #[arg(long, value_name = "N")]
+    pub max_concurrency: Option<usize>,
+
+    /// Print aggregate stats across the JSON logs in the log directory and exit
+    #[arg(long)]
+    pub report: bool,
+
+    /// List the sessions found in the log directory (IDs, counts, first/last use, models) and exit
+    #[arg(long)]
+    pub list_sessions: bool,
+
+    /// Reuse cached responses for identical requests (same model, prompt and settings) and cache new ones
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Seconds a cached response stays valid, for --cache and --cache-prune
+    #[arg(long, default_value = "86400", value_name = "SECS")]
+    pub cache_ttl: u64,
+
+    /// Show the response cache's entry count, size and oldest entry, and exit
+    #[arg(long)]
+    pub cache_stats: bool,
+
+    /// Remove cached responses older than --cache-ttl and exit
+    #[arg(long)]
+    pub cache_prune: bool,
+
+    /// Delete the response cache and exit
+    #[arg(long)]
+    pub cache_clear: bool,
+
+    /// Use this directory for the response cache instead of ~/.chatdelta/cache
+    #[arg(long, value_name = "PATH")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Pack the response cache into a tar archive, e.g. to share it or commit it for offline demos, and exit
+    #[arg(long, value_name = "TARBALL")]
+    pub cache_export: Option<PathBuf>,
+
+    /// Merge a --cache-export archive into the response cache (cached entries win on conflict) and exit
+    #[arg(long, value_name = "TARBALL")]
+    pub cache_import: Option<PathBuf>,
+
+    /// With --report, only include interactions from this date (YYYY-MM-DD or RFC 3339) on
+    #[arg(long, value_name = "DATE", requires = "report")]
+    pub since: Option<String>,
+
+    /// Write the result to this file instead of stdout (with --stream, chunk by chunk)
+    #[arg(long, short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// On a terminal, show the result through $PAGER (default `less -FRX`); off when piped, with --output or --quiet
+    #[arg(long)]
+    pub paginate: bool,
+
     /// Save individual model responses to separate files
     #[arg(long)]
     pub save_responses: Option<PathBuf>,
 
+    /// Start each --save-responses file with the prompt under a `=== Prompt ===` header
+    #[arg(long, requires = "save_responses")]
+    pub echo_prompt: bool,
+
+    /// Add a per-run timestamp to --save-responses and --log filenames so later runs don't overwrite them
+    #[arg(long)]
+    pub timestamp_filenames: bool,
+
+    /// Append an anonymized record of each run (models, outcomes, latency, estimated cost; never prompts or responses) to this JSONL file
+    #[arg(long, value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
+
+    /// Write the prompt as sent, after templates, variables and other processing, to this file
+    #[arg(long, value_name = "PATH")]
+    pub save_prompt: Option<PathBuf>,
+
+    /// Write prompt, responses, summary, metrics and config to a timestamped subdirectory per run
+    #[arg(long, value_name = "PATH")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Print the JSON Schema for --format json output and exit
+    #[arg(long)]
+    pub print_output_schema: bool,
+
+    /// Write each model's raw request (API keys redacted) and response to the run's --output-dir, or stderr
+    #[arg(long)]
+    pub debug_io: bool,
+
+    /// Also write a JSON status summary (exit reason, counts) to --status-file
+    #[arg(long, requires = "status_file")]
+    pub split_output: bool,
+
+    /// Where --split-output writes the run status
+    #[arg(long, value_name = "PATH", requires = "split_output")]
+    pub status_file: Option<PathBuf>,
+
     /// Show progress spinner for long operations
     #[arg(long, default_value = "true")]
     pub progress: bool,
@@ -122,6 +669,18 @@ pub struct Args {
     #[arg(long, default_value = "exponential")]
     pub retry_strategy: String,
 
+    /// Retry strategy for OpenAI, overriding --retry-strategy
+    #[arg(long, value_name = "STRATEGY")]
+    pub gpt_retry_strategy: Option<String>,
+
+    /// Retry strategy for Gemini, overriding --retry-strategy
+    #[arg(long, value_name = "STRATEGY")]
+    pub gemini_retry_strategy: Option<String>,
+
+    /// Retry strategy for Claude, overriding --retry-strategy
+    #[arg(long, value_name = "STRATEGY")]
+    pub claude_retry_strategy: Option<String>,
+
     /// Enable conversation mode (interactive chat)
     #[arg(long, short = 'c')]
     pub conversation: bool,
@@ -134,19 +693,39 @@ pub struct Args {
     #[arg(long)]
     pub stream: bool,
 
+    /// Compare responses against a baseline saved with --format json; exit non-zero on divergence
+    #[arg(long)]
+    pub compare_to_file: Option<PathBuf>,
+
+    /// Minimum similarity (0.0-1.0) for a response to match its baseline
+    #[arg(long, default_value = "0.9")]
+    pub compare_tolerance: f64,
+
+    /// Exit non-zero unless every pair of responses agrees (see --agreement-threshold)
+    #[arg(long)]
+    pub fail_if_disagree: bool,
+
+    /// Minimum pairwise similarity (0.0-1.0) for responses to count as agreeing
+    #[arg(long, default_value = "0.8")]
+    pub agreement_threshold: f64,
+
     /// Load conversation history from file
     #[arg(long)]
     pub load_conversation: Option<PathBuf>,
 
-    /// Save conversation history to file
+    /// Save conversation history to file (a .md path writes a Markdown transcript)
     #[arg(long)]
     pub save_conversation: Option<PathBuf>,
 
+    /// Write a Markdown transcript of the conversation on 'save' and at exit
+    #[arg(long, value_name = "PATH")]
+    pub export_transcript: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// Run a structured multi-model debate between two AI models
     Debate(DebateArgs),
@@ -213,7 +792,10 @@ pub struct DebateArgs {
 impl DebateArgs {
     pub fn validate(&self) -> Result<(), String> {
         if self.rounds > 10 {
-            return Err("--rounds cannot exceed 10 (use a lower value for reasonable debate length)".to_string());
+            return Err(
+                "--rounds cannot exceed 10 (use a lower value for reasonable debate length)"
+                    .to_string(),
+            );
         }
         if self.max_turn_chars < 100 {
             return Err("--max-turn-chars must be at least 100".to_string());
@@ -239,15 +821,9 @@ impl Args {
         }
 
         // Prompt is required unless using special commands, prompt file, or conversation mode
-        if self.prompt.is_none()
-            && self.prompt_file.is_none()
-            && !self.list_models
-            && !self.test
-            && !self.doctor
-            && !self.conversation
-        {
+        if self.prompt.is_none() && !self.runs_without_prompt() {
             return Err(
-                "Prompt is required unless using --prompt-file, --list-models, --test, --doctor, or --conversation"
+                "Prompt is required unless using --prompt-file, --batch, --template-name, --list-templates, --list-roles, --prompt-from-git-diff, --list-models, --print-output-schema, --test, --doctor, --report, --list-sessions, --cache-stats/--cache-prune/--cache-clear/--cache-export/--cache-import, --benchmark, or --conversation"
                     .to_string(),
             );
         }
@@ -266,12 +842,43 @@ impl Args {
             }
         }
 
+        RefusalDetector::new(&self.refusal_patterns)?;
+        ResponseFilter::new(&self.response_filters)?;
+
+        if let Some(required) = self.min_success {
+            let selected = AI_NAMES.iter().filter(|ai| self.should_use_ai(ai)).count()
+                + self.selected_aliases().len();
+            if required == 0 || required > selected {
+                return Err(format!(
+                    "--min-success must be between 1 and the number of selected models ({})",
+                    selected
+                ));
+            }
+        }
+
+        if !matches!(self.resolved_metrics_format(), "json" | "prometheus") {
+            return Err("Metrics format must be json or prometheus".to_string());
+        }
+
+        if !matches!(self.stdin_position.as_str(), "append" | "prepend") {
+            return Err("Stdin position must be append or prepend".to_string());
+        }
+
         if self.verbose && self.quiet {
             return Err("Cannot use both --verbose and --quiet flags".to_string());
         }
 
-        if !matches!(self.format.as_str(), "text" | "json" | "markdown") {
-            return Err("Output format must be one of: text, json, markdown".to_string());
+        if !matches!(
+            self.format.as_str(),
+            "text" | "json" | "ndjson" | "markdown"
+        ) {
+            return Err("Output format must be one of: text, json, ndjson, markdown".to_string());
+        }
+
+        if !matches!(self.wrap.as_str(), "none" | "auto")
+            && !self.wrap.parse::<usize>().is_ok_and(|n| n > 0)
+        {
+            return Err("Wrap must be a positive column width, auto, or none".to_string());
         }
 
         if !matches!(
@@ -281,6 +888,34 @@ impl Args {
             return Err("Retry strategy must be one of: exponential, linear, fixed".to_string());
         }
 
+        if let Some(ai) = AI_NAMES.iter().find(|ai| {
+            !matches!(
+                self.retry_strategy_for(ai),
+                "exponential" | "linear" | "fixed"
+            )
+        }) {
+            return Err(format!(
+                "--{}-retry-strategy must be one of: exponential, linear, fixed",
+                ai
+            ));
+        }
+
+        if !matches!(self.summary_position.as_str(), "top" | "bottom") {
+            return Err("Summary position must be one of: top, bottom".to_string());
+        }
+
+        if !matches!(self.color.as_str(), "auto" | "always" | "never") {
+            return Err("--color must be one of: auto, always, never".to_string());
+        }
+
+        if !matches!(self.input_format.as_str(), "text" | "yaml" | "json") {
+            return Err("Input format must be one of: text, yaml, json".to_string());
+        }
+
+        if self.input_format != "text" && self.prompt_file.is_none() {
+            return Err("--input-format requires --prompt-file".to_string());
+        }
+
         if !matches!(self.log_format.as_str(), "simple" | "json" | "structured") {
             return Err("Log format must be one of: simple, json, structured".to_string());
         }
@@ -289,44 +924,398 @@ impl Args {
             return Err("Cannot use both --only and --exclude flags".to_string());
         }
 
-        for ai in &self.only {
-            if !matches!(ai.as_str(), "gpt" | "gemini" | "claude") {
+        if !self.only.is_empty()
+            && self.expand_ai_names(&self.only)?.is_empty()
+            && self.selected_aliases().is_empty()
+        {
+            return Err(format!(
+                "--only {} matches no AI with the configured models",
+                self.only.join(",")
+            ));
+        }
+        // Aliases only ever run when --only names them, so excluding one would do nothing
+        if let Some(alias) = self
+            .exclude
+            .iter()
+            .find(|name| self.model_map.contains_key(*name))
+        {
+            return Err(format!(
+                "--exclude {}: model aliases are queried only when named in --only",
+                alias
+            ));
+        }
+        self.expand_ai_names(&self.exclude)?;
+
+        if let Some(temp) = self.temperature {
+            if !(0.0..=2.0).contains(&temp) {
+                return Err("Temperature must be between 0.0 and 2.0".to_string());
+            }
+        }
+
+        if self.timeout == 0 {
+            return Err("Timeout must be greater than 0".to_string());
+        }
+        if self.connect_timeout == Some(0) || self.read_timeout == Some(0) {
+            return Err("--connect-timeout and --read-timeout must be greater than 0".to_string());
+        }
+        if self.timeout_escalation {
+            if self.retries == 0 {
+                return Err("--timeout-escalation needs --retries greater than 0".to_string());
+            }
+            if self.timeout_cap() < self.request_timeout() {
                 return Err(format!(
-                    "Unknown AI '{}'. Valid options: gpt, gemini, claude",
-                    ai
+                    "--max-timeout must be at least the request timeout ({}s)",
+                    self.request_timeout().as_secs()
                 ));
             }
         }
 
-        for ai in &self.exclude {
+        #[cfg(any(test, feature = "mock"))]
+        for ai in &self.mock_fail {
             if !matches!(ai.as_str(), "gpt" | "gemini" | "claude") {
                 return Err(format!(
-                    "Unknown AI '{}'. Valid options: gpt, gemini, claude",
+                    "Invalid --mock-fail provider '{}': use gpt, gemini, or claude",
                     ai
                 ));
             }
         }
 
-        if let Some(temp) = self.temperature {
-            if !(0.0..=2.0).contains(&temp) {
-                return Err("Temperature must be between 0.0 and 2.0".to_string());
+        if self.comment_prefix.trim().is_empty() {
+            return Err("Comment prefix cannot be empty".to_string());
+        }
+
+        if let Some(path) = &self.json_path_extract {
+            crate::extract::validate_path(path)?;
+        }
+
+        if self.benchmark == Some(0) {
+            return Err("--benchmark needs at least one run".to_string());
+        }
+
+        if self.max_concurrency == Some(0) {
+            return Err("--max-concurrency must be at least 1".to_string());
+        }
+
+        if let Some(since) = &self.since {
+            crate::logging::parse_since(since)?;
+        }
+
+        if let Some(ai) = self
+            .summary_model
+            .as_deref()
+            .filter(|ai| !AI_NAMES.contains(ai))
+        {
+            return Err(format!(
+                "Invalid --summary-model '{}': use gpt, gemini, or claude",
+                ai
+            ));
+        }
+
+        if let Some(ai) = self
+            .summary_models
+            .iter()
+            .find(|ai| !AI_NAMES.contains(&ai.as_str()))
+        {
+            return Err(format!(
+                "Invalid --summary-models entry '{}': use gpt, gemini, or claude",
+                ai
+            ));
+        }
+
+        if let Some(format) = &self.summary_format {
+            if !matches!(format.as_str(), "text" | "markdown" | "json") {
+                return Err("--summary-format must be one of: text, markdown, json".to_string());
             }
         }
 
-        if self.timeout == 0 {
-            return Err("Timeout must be greater than 0".to_string());
+        if self.summary_chunk_size < 2 {
+            return Err("Summary chunk size must be at least 2".to_string());
+        }
+
+        if self
+            .cost_limit
+            .is_some_and(|limit| limit.is_nan() || limit < 0.0)
+        {
+            return Err("Cost limit must be a non-negative amount".to_string());
+        }
+
+        if self.max_runtime == Some(0) {
+            return Err("Max runtime must be greater than 0".to_string());
+        }
+
+        for ai in ["gpt", "gemini", "claude"] {
+            if let Some(url) = self.base_url(ai) {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(format!(
+                        "Invalid base URL '{}' for {}: must start with http:// or https://",
+                        url, ai
+                    ));
+                }
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.compare_tolerance) {
+            return Err("Compare tolerance must be between 0.0 and 1.0".to_string());
+        }
+
+        if !(0.0..=1.0).contains(&self.agreement_threshold) {
+            return Err("Agreement threshold must be between 0.0 and 1.0".to_string());
         }
 
         Ok(())
     }
 
+    /// Base URL override for an AI, from its --*-base-url flag or environment variable
+    pub fn base_url(&self, ai_name: &str) -> Option<String> {
+        let (flag, env_var) = match ai_name {
+            "gpt" => (&self.gpt_base_url, "OPENAI_BASE_URL"),
+            "gemini" => (&self.gemini_base_url, "GEMINI_BASE_URL"),
+            "claude" => (&self.claude_base_url, "ANTHROPIC_BASE_URL"),
+            _ => return None,
+        };
+        flag.clone()
+            .or_else(|| std::env::var(env_var).ok())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// Whether individual responses are left out of the result output (--only-summary or
+    /// --hide-responses); logs and summaries still see them
+    pub fn hides_responses(&self) -> bool {
+        self.only_summary || self.hide_responses
+    }
+
+    /// Whether the run gets its prompt elsewhere (a file or git diff) or is a command or
+    /// mode that needs none
+    pub fn runs_without_prompt(&self) -> bool {
+        self.prompt_file.is_some()
+            || self.batch.is_some()
+            || self.template_name.is_some()
+            || self.list_templates
+            || self.list_roles
+            || self.prompt_from_git_diff.is_some()
+            || self.list_models
+            || self.print_output_schema
+            || self.test
+            || self.doctor
+            || self.report
+            || self.list_sessions
+            || self.cache_stats
+            || self.cache_prune
+            || self.cache_clear
+            || self.cache_export.is_some()
+            || self.cache_import.is_some()
+            || self.benchmark.is_some()
+            || self.conversation
+    }
+
+    /// The prompt to show the summarizer, with --summary-include-prompt
+    pub fn summary_prompt_context(&self) -> Option<&str> {
+        self.prompt
+            .as_deref()
+            .filter(|_| self.summary_include_prompt)
+    }
+
+    /// Summary structure: --summary-format, or the one matching --format (ndjson asks for json)
+    pub fn resolved_summary_format(&self) -> &'static str {
+        match self.summary_format.as_deref().unwrap_or(&self.format) {
+            "markdown" => "markdown",
+            "json" | "ndjson" => "json",
+            _ => "text",
+        }
+    }
+
+    /// Format for --metrics-file: --metrics-format, or by the file's extension
+    pub fn resolved_metrics_format(&self) -> &str {
+        match (&self.metrics_format, &self.metrics_file) {
+            (Some(format), _) => format,
+            (None, Some(path)) if path.extension().is_some_and(|ext| ext == "prom") => "prometheus",
+            _ => "json",
+        }
+    }
+
+    /// Whether to print summary status lines (off with --quiet or --quiet-summary)
+    pub fn show_summary_status(&self) -> bool {
+        !self.quiet && !self.quiet_summary
+    }
+
+    /// Whether --mock was requested (always false without the mock feature)
+    pub fn use_mock(&self) -> bool {
+        #[cfg(any(test, feature = "mock"))]
+        if self.mock {
+            return true;
+        }
+        false
+    }
+
+    /// Model configured for an AI (gpt, gemini, claude)
+    pub fn model_for(&self, ai_name: &str) -> &str {
+        match ai_name {
+            "gpt" => &self.gpt_model,
+            "gemini" => &self.gemini_model,
+            _ => &self.claude_model,
+        }
+    }
+
+    /// Fallback model configured for an AI (gpt, gemini, claude)
+    pub fn fallback_model_for(&self, ai_name: &str) -> Option<&str> {
+        match ai_name {
+            "gpt" => self.gpt_model_fallback.as_deref(),
+            "gemini" => self.gemini_model_fallback.as_deref(),
+            _ => self.claude_model_fallback.as_deref(),
+        }
+    }
+
+    /// Retry strategy for an AI: its --<ai>-retry-strategy, or the global --retry-strategy
+    pub fn retry_strategy_for(&self, ai_name: &str) -> &str {
+        let strategy = match ai_name {
+            "gpt" => &self.gpt_retry_strategy,
+            "gemini" => &self.gemini_retry_strategy,
+            _ => &self.claude_retry_strategy,
+        };
+        strategy.as_deref().unwrap_or(&self.retry_strategy)
+    }
+
+    /// Display name the clients report for an AI (gpt, gemini, claude)
+    pub fn display_name(ai_name: &str) -> &'static str {
+        Provider::for_ai(ai_name).display_name()
+    }
+
+    /// The AI (gpt, gemini, claude) behind a client's name: a display name or a model alias
+    pub fn ai_for_client(&self, name: &str) -> Option<&'static str> {
+        AI_NAMES
+            .iter()
+            .copied()
+            .find(|ai| Self::display_name(ai) == name)
+            .or_else(|| self.model_map.get(name).map(ModelAlias::ai))
+    }
+
+    /// Expand AI names and groups from --only/--exclude into concrete AIs.
+    /// `all` is every AI; other groups match the configured model names.
+    pub fn expand_ai_names(&self, names: &[String]) -> Result<Vec<&'static str>, String> {
+        let mut expanded = Vec::new();
+        for name in names {
+            let members: Vec<&'static str> = if let Some(ai) =
+                AI_NAMES.iter().find(|ai| *ai == name)
+            {
+                vec![*ai]
+            } else if name == "all" {
+                AI_NAMES.to_vec()
+            } else if self.model_map.contains_key(name) {
+                // A model alias gets its own client rather than selecting a built-in AI
+                Vec::new()
+            } else if let Some((_, markers)) = AI_GROUPS.iter().find(|(group, _)| group == name) {
+                AI_NAMES
+                    .into_iter()
+                    .filter(|ai| markers.iter().any(|m| self.model_for(ai).contains(m)))
+                    .collect()
+            } else {
+                return Err(format!(
+                    "Unknown AI or group '{}'. Valid options: gpt, gemini, claude, all, {}",
+                    name,
+                    AI_GROUPS
+                        .iter()
+                        .map(|(group, _)| *group)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            };
+            for ai in members {
+                if !expanded.contains(&ai) {
+                    expanded.push(ai);
+                }
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// The per-request timeout given to the clients. chatdelta's `ClientConfig` has a single
+    /// timeout covering the whole request, so it is the read timeout, which bounds the
+    /// slow part of a request.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout.unwrap_or(self.timeout))
+    }
+
+    /// The longest timeout --timeout-escalation may reach: --max-timeout, or four times
+    /// the request timeout
+    pub fn timeout_cap(&self) -> Duration {
+        self.max_timeout
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| self.request_timeout() * 4)
+    }
+
+    /// Warning that --connect-timeout can't be applied on its own
+    pub fn timeout_warning(&self) -> Option<String> {
+        let connect = self.connect_timeout?;
+        Some(format!(
+            "The chatdelta library supports a single request timeout; --connect-timeout {}s is not applied separately and each request may take up to {}s",
+            connect,
+            self.request_timeout().as_secs()
+        ))
+    }
+
+    /// Lower-case the --only and --exclude names so `GPT` selects gpt (model aliases keep
+    /// their case) and drop repeated names. Returns a warning for each name given twice.
+    pub fn normalize_selection(&mut self) -> Vec<String> {
+        let mut repeated = Vec::new();
+        for (flag, names) in [("--only", &mut self.only), ("--exclude", &mut self.exclude)] {
+            let mut kept: Vec<String> = Vec::with_capacity(names.len());
+            for name in names.drain(..) {
+                let name = match self.model_map.contains_key(name.trim()) {
+                    true => name.trim().to_string(),
+                    false => name.trim().to_lowercase(),
+                };
+                if !kept.contains(&name) {
+                    kept.push(name);
+                    continue;
+                }
+                let warning = format!("{} lists '{}' more than once; using it once", flag, name);
+                if !repeated.contains(&warning) {
+                    repeated.push(warning);
+                }
+            }
+            *names = kept;
+        }
+        repeated
+    }
+
+    /// `all` and the group names, which a model alias may not reuse
+    pub fn group_names() -> Vec<&'static str> {
+        std::iter::once("all")
+            .chain(AI_GROUPS.iter().map(|(group, _)| *group))
+            .collect()
+    }
+
+    /// Model aliases selected with --only, in the order given
+    pub fn selected_aliases(&self) -> Vec<(&str, &ModelAlias)> {
+        let mut selected: Vec<(&str, &ModelAlias)> = Vec::new();
+        for name in &self.only {
+            if let Some((name, alias)) = self.model_map.get_key_value(name) {
+                if !selected.iter().any(|(chosen, _)| chosen == name) {
+                    selected.push((name, alias));
+                }
+            }
+        }
+        selected
+    }
+
+    /// Whether an AI was named directly in --only (not just through a group)
+    pub fn explicitly_selected(&self, ai_name: &str) -> bool {
+        self.only.iter().any(|name| name == ai_name)
+    }
+
     /// Check if a specific AI should be used based on --only and --exclude flags
     pub fn should_use_ai(&self, ai_name: &str) -> bool {
         if !self.only.is_empty() {
-            return self.only.contains(&ai_name.to_string());
+            return self
+                .expand_ai_names(&self.only)
+                .unwrap_or_default()
+                .contains(&ai_name);
         }
         if !self.exclude.is_empty() {
-            return !self.exclude.contains(&ai_name.to_string());
+            return !self
+                .expand_ai_names(&self.exclude)
+                .unwrap_or_default()
+                .contains(&ai_name);
         }
         true
     }
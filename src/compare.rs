@@ -0,0 +1,429 @@
+//! Response comparison for ChatDelta CLI
+//!
+//! Word-level similarity scoring, consensus checks between models, sentences most models
+//! agree on, and regression checks against a saved baseline.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Similarity ratio between two texts in the range 0.0-1.0.
+///
+/// Computed as `2 * LCS / (len_a + len_b)` over lowercase words, so word order matters
+/// but whitespace and letter case do not.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let words_a: Vec<String> = a.split_whitespace().map(str::to_lowercase).collect();
+    let words_b: Vec<String> = b.split_whitespace().map(str::to_lowercase).collect();
+
+    let total = words_a.len() + words_b.len();
+    if total == 0 {
+        return 1.0;
+    }
+
+    // Two-row LCS table keeps memory linear in the length of `b`
+    let mut prev = vec![0usize; words_b.len() + 1];
+    let mut curr = vec![0usize; words_b.len() + 1];
+    for wa in &words_a {
+        for (j, wb) in words_b.iter().enumerate() {
+            curr[j + 1] = if wa == wb {
+                prev[j] + 1
+            } else {
+                curr[j].max(prev[j + 1])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    (2 * prev[words_b.len()]) as f64 / total as f64
+}
+
+/// Pairs of models whose responses are less similar than `threshold`, with their score.
+/// Pairs are listed in response order.
+pub fn disagreements(responses: &[(String, String)], threshold: f64) -> Vec<(String, String, f64)> {
+    let mut diverged = Vec::new();
+    for (i, (name_a, a)) in responses.iter().enumerate() {
+        for (name_b, b) in &responses[i + 1..] {
+            let score = similarity(a, b);
+            if score < threshold {
+                diverged.push((name_a.clone(), name_b.clone(), score));
+            }
+        }
+    }
+    diverged
+}
+
+/// Sentences at least this similar (after normalizing case and punctuation) say the same thing
+pub const CONSENSUS_SIMILARITY: f64 = 0.7;
+
+/// Sentences shorter than this many words are too generic to count as agreement
+const MIN_CONSENSUS_WORDS: usize = 3;
+
+/// A sentence a majority of the responses share, for --highlight-agreement
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConsensusPhrase {
+    /// The wording of the first response that has it
+    pub text: String,
+    /// Models whose responses contain it, in response order
+    pub models: Vec<String>,
+    /// Each of those models' own wording, for marking it in their responses
+    #[serde(skip)]
+    pub wordings: Vec<String>,
+}
+
+/// The sentences of a response: split at sentence ends and line breaks, with list markers
+/// and surrounding whitespace removed
+pub fn sentences(text: &str) -> Vec<&str> {
+    text.lines()
+        .flat_map(|line| line.split_inclusive(['.', '!', '?']))
+        .map(|sentence| {
+            sentence
+                .trim()
+                .trim_start_matches(['-', '*', '\u{2022}', '>', '#'])
+                .trim()
+        })
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Lowercase words without punctuation, for fuzzy sentence matching
+fn normalize(sentence: &str) -> String {
+    sentence
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Sentences that appear, fuzzily, in a majority of the responses. Each sentence is
+/// counted once, in the order the responses first state it.
+pub fn consensus(responses: &[(String, String)]) -> Vec<ConsensusPhrase> {
+    if responses.len() < 2 {
+        return Vec::new();
+    }
+    let majority = responses.len() / 2 + 1;
+    let split: Vec<Vec<(&str, String)>> = responses
+        .iter()
+        .map(|(_, text)| {
+            sentences(text)
+                .into_iter()
+                .map(|sentence| (sentence, normalize(sentence)))
+                .filter(|(_, normal)| normal.split(' ').count() >= MIN_CONSENSUS_WORDS)
+                .collect()
+        })
+        .collect();
+
+    let mut used: Vec<Vec<bool>> = split.iter().map(|s| vec![false; s.len()]).collect();
+    let mut phrases = Vec::new();
+    for (i, sentences) in split.iter().enumerate() {
+        for (k, (sentence, normal)) in sentences.iter().enumerate() {
+            if used[i][k] {
+                continue;
+            }
+            // The closest unused sentence in each later response
+            let matches: Vec<(usize, usize)> = (i + 1..split.len())
+                .filter_map(|j| {
+                    split[j]
+                        .iter()
+                        .enumerate()
+                        .filter(|(m, _)| !used[j][*m])
+                        .map(|(m, (_, other))| (m, similarity(normal, other)))
+                        .filter(|(_, score)| *score >= CONSENSUS_SIMILARITY)
+                        .max_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(m, _)| (j, m))
+                })
+                .collect();
+            if matches.len() + 1 < majority {
+                continue;
+            }
+            used[i][k] = true;
+            let mut phrase = ConsensusPhrase {
+                text: sentence.to_string(),
+                models: vec![responses[i].0.clone()],
+                wordings: vec![sentence.to_string()],
+            };
+            for (j, m) in matches {
+                used[j][m] = true;
+                phrase.models.push(responses[j].0.clone());
+                phrase.wordings.push(split[j][m].0.to_string());
+            }
+            phrases.push(phrase);
+        }
+    }
+    phrases
+}
+
+/// `response` with each consensus sentence of `model` passed through `mark`
+pub fn mark_consensus(
+    model: &str,
+    response: &str,
+    phrases: &[ConsensusPhrase],
+    mark: impl Fn(&str) -> String,
+) -> String {
+    let mut marked = response.to_string();
+    for phrase in phrases {
+        let wording = phrase
+            .models
+            .iter()
+            .zip(&phrase.wordings)
+            .find(|(name, _)| *name == model);
+        if let Some((_, wording)) = wording {
+            marked = marked.replacen(wording.as_str(), &mark(wording), 1);
+        }
+    }
+    marked
+}
+
+/// Outcome of comparing one model's response with its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub enum BaselineStatus {
+    /// Similarity at or above the tolerance
+    Match(f64),
+    /// Similarity below the tolerance
+    Differs(f64),
+    /// The model responded but has no baseline entry
+    MissingBaseline,
+    /// The baseline has an entry but the model produced no response this run
+    MissingResponse,
+}
+
+impl BaselineStatus {
+    pub fn passed(&self) -> bool {
+        matches!(
+            self,
+            BaselineStatus::Match(_) | BaselineStatus::MissingBaseline
+        )
+    }
+}
+
+/// Load baseline responses from a JSON result file written with `--format json`
+pub fn load_baseline(path: &Path) -> Result<HashMap<String, String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read baseline file '{}': {}", path.display(), e))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse baseline file '{}': {}", path.display(), e))?;
+
+    // Either a {model: response} object or, with --json-ordered-array, a list of records
+    match value.get("responses") {
+        Some(serde_json::Value::Object(responses)) => Ok(responses
+            .iter()
+            .filter_map(|(model, text)| text.as_str().map(|t| (model.clone(), t.to_string())))
+            .collect()),
+        Some(serde_json::Value::Array(records)) => Ok(records
+            .iter()
+            .filter_map(|record| {
+                let model = record.get("model")?.as_str()?;
+                let text = record.get("response")?.as_str()?;
+                Some((model.to_string(), text.to_string()))
+            })
+            .collect()),
+        _ => Err(format!(
+            "Baseline file '{}' has no 'responses' object (save one with --format json)",
+            path.display()
+        )),
+    }
+}
+
+/// Compare current responses with the baseline, returning a status per model.
+/// Models are reported in response order, followed by baseline-only models sorted by name.
+pub fn compare_to_baseline(
+    responses: &[(String, String)],
+    baseline: &HashMap<String, String>,
+    tolerance: f64,
+) -> Vec<(String, BaselineStatus)> {
+    let mut results = Vec::new();
+
+    for (model, response) in responses {
+        let status = match baseline.get(model) {
+            Some(expected) => {
+                let score = similarity(expected, response);
+                if score >= tolerance {
+                    BaselineStatus::Match(score)
+                } else {
+                    BaselineStatus::Differs(score)
+                }
+            }
+            None => BaselineStatus::MissingBaseline,
+        };
+        results.push((model.clone(), status));
+    }
+
+    let mut missing: Vec<&String> = baseline
+        .keys()
+        .filter(|model| !responses.iter().any(|(name, _)| name == *model))
+        .collect();
+    missing.sort();
+    for model in missing {
+        results.push((model.clone(), BaselineStatus::MissingResponse));
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(responses: &[(&str, &str)]) -> Vec<(String, String)> {
+        responses
+            .iter()
+            .map(|(n, r)| (n.to_string(), r.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn consensus_finds_sentences_most_responses_share() {
+        let responses = named(&[
+            ("ChatGPT", "Rust guarantees memory safety without a garbage collector. It is fast."),
+            ("Gemini", "Python is easier to learn.\n- Rust guarantees memory safety without garbage collection!"),
+            ("Claude", "Go has a garbage collector. Compile times are short."),
+        ]);
+        let phrases = consensus(&responses);
+        assert_eq!(phrases.len(), 1, "{:?}", phrases);
+        assert_eq!(
+            phrases[0].text,
+            "Rust guarantees memory safety without a garbage collector."
+        );
+        assert_eq!(phrases[0].models, vec!["ChatGPT", "Gemini"]);
+        assert_eq!(
+            phrases[0].wordings[1],
+            "Rust guarantees memory safety without garbage collection!"
+        );
+
+        let marked = mark_consensus("Gemini", &responses[1].1, &phrases, |s| {
+            format!("**{}**", s)
+        });
+        assert_eq!(
+            marked,
+            "Python is easier to learn.\n- **Rust guarantees memory safety without garbage collection!**"
+        );
+        assert_eq!(
+            mark_consensus("Claude", &responses[2].1, &phrases, |s| format!(
+                "**{}**",
+                s
+            )),
+            responses[2].1
+        );
+
+        // A single response has nothing to agree with; short sentences never count
+        assert!(consensus(&responses[..1]).is_empty());
+        assert!(consensus(&named(&[("A", "Yes."), ("B", "Yes.")])).is_empty());
+    }
+
+    fn baseline(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(m, r)| (m.to_string(), r.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn similarity_bounds() {
+        assert_eq!(
+            similarity("the quick brown fox", "The  quick brown FOX"),
+            1.0
+        );
+        assert_eq!(similarity("alpha beta", "gamma delta"), 0.0);
+        assert_eq!(similarity("", ""), 1.0);
+        let partial = similarity("one two three four", "one two five six");
+        assert!((partial - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn identical_baseline_passes() {
+        let responses = vec![
+            (
+                "ChatGPT".to_string(),
+                "Paris is the capital of France.".to_string(),
+            ),
+            (
+                "Claude".to_string(),
+                "The capital of France is Paris.".to_string(),
+            ),
+        ];
+        let base = baseline(&[
+            ("ChatGPT", "Paris is the capital of France."),
+            ("Claude", "The capital of France is Paris."),
+        ]);
+
+        let results = compare_to_baseline(&responses, &base, 0.9);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, status)| status.passed()));
+        assert_eq!(results[0].1, BaselineStatus::Match(1.0));
+    }
+
+    #[test]
+    fn divergent_baseline_fails() {
+        let responses = vec![(
+            "ChatGPT".to_string(),
+            "Rust uses ownership to manage memory.".to_string(),
+        )];
+        let base = baseline(&[
+            ("ChatGPT", "Python relies on a garbage collector."),
+            ("Gemini", "Some earlier answer."),
+        ]);
+
+        let results = compare_to_baseline(&responses, &base, 0.9);
+        assert!(matches!(results[0].1, BaselineStatus::Differs(_)));
+        assert_eq!(
+            results[1],
+            ("Gemini".to_string(), BaselineStatus::MissingResponse)
+        );
+        assert!(!results.iter().any(|(_, status)| status.passed()));
+    }
+
+    #[test]
+    fn disagreements_lists_divergent_pairs() {
+        let responses = vec![
+            (
+                "ChatGPT".to_string(),
+                "Paris is the capital of France".to_string(),
+            ),
+            (
+                "Claude".to_string(),
+                "paris is the capital of france".to_string(),
+            ),
+            ("Gemini".to_string(), "I would rather not say".to_string()),
+        ];
+
+        assert!(disagreements(&responses[..2], 0.8).is_empty());
+
+        let diverged = disagreements(&responses, 0.8);
+        let pairs: Vec<(&str, &str)> = diverged
+            .iter()
+            .map(|(a, b, _)| (a.as_str(), b.as_str()))
+            .collect();
+        assert_eq!(pairs, vec![("ChatGPT", "Gemini"), ("Claude", "Gemini")]);
+        assert!(diverged.iter().all(|(_, _, score)| *score < 0.8));
+    }
+
+    #[test]
+    fn load_baseline_reads_json_output() {
+        let path = std::env::temp_dir().join("chatdelta_test_baseline.json");
+        fs::write(
+            &path,
+            r#"{"prompt": "Hi", "responses": {"Claude": "Hello!"}, "summary": "Greeting"}"#,
+        )
+        .unwrap();
+
+        let base = load_baseline(&path).unwrap();
+        assert_eq!(base.get("Claude").map(String::as_str), Some("Hello!"));
+
+        fs::write(
+            &path,
+            r#"{"responses": [{"model": "Gemini", "response": "Hey"}]}"#,
+        )
+        .unwrap();
+        let ordered = load_baseline(&path).unwrap();
+        assert_eq!(ordered.get("Gemini").map(String::as_str), Some("Hey"));
+
+        fs::write(&path, r#"{"prompt": "Hi"}"#).unwrap();
+        assert!(load_baseline(&path).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+}
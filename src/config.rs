@@ -0,0 +1,120 @@
+//! Model map for ChatDelta CLI (`--model-map-file`)
+//!
+//! A TOML file gives task-oriented names to concrete models so a team can share them:
+//!
+//! ```toml
+//! reviewer = { provider = "claude", model = "claude-3-5-sonnet-20241022" }
+//! drafter = { provider = "claude", model = "claude-3-5-haiku-20241022" }
+//! ```
+//!
+//! Each name is selectable with `--only` and gets its own client for that provider and
+//! model, so several names may share a provider.
+
+use crate::cli::AI_NAMES;
+use crate::models;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A friendly name's provider and model
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelAlias {
+    /// gpt, gemini or claude (openai, chatgpt, google and anthropic are accepted too)
+    pub provider: String,
+    pub model: String,
+}
+
+impl ModelAlias {
+    /// The AI (gpt, gemini, claude) serving this alias
+    pub fn ai(&self) -> &'static str {
+        provider_ai(&self.provider).unwrap_or("claude")
+    }
+}
+
+/// Aliases by name
+pub type ModelMap = BTreeMap<String, ModelAlias>;
+
+/// The AI a provider name refers to
+fn provider_ai(provider: &str) -> Option<&'static str> {
+    match provider.to_lowercase().as_str() {
+        "gpt" | "openai" | "chatgpt" => Some("gpt"),
+        "gemini" | "google" => Some("gemini"),
+        "claude" | "anthropic" => Some("claude"),
+        _ => None,
+    }
+}
+
+/// Parse a model map, rejecting unknown providers, known models paired with the wrong
+/// provider, and names that shadow a built-in AI or group (`reserved`)
+pub fn parse_model_map(text: &str, reserved: &[&str]) -> Result<ModelMap, String> {
+    let map: ModelMap = toml::from_str(text).map_err(|e| format!("Invalid model map: {}", e))?;
+    for (name, alias) in &map {
+        if AI_NAMES.contains(&name.as_str()) || reserved.contains(&name.as_str()) {
+            return Err(format!(
+                "Model alias '{}' shadows a built-in AI or group",
+                name
+            ));
+        }
+        if provider_ai(&alias.provider).is_none() {
+            return Err(format!(
+                "Model alias '{}' has unknown provider '{}' (expected gpt, gemini or claude)",
+                name, alias.provider
+            ));
+        }
+        if let Some(info) = models::lookup(&alias.model).filter(|info| info.provider != alias.ai())
+        {
+            return Err(format!(
+                "Model alias '{}' pairs {} with provider '{}', but it is a {} model",
+                name, info.id, alias.provider, info.provider
+            ));
+        }
+    }
+    Ok(map)
+}
+
+/// Read and parse a model map file
+pub fn load_model_map(path: &Path, reserved: &[&str]) -> Result<ModelMap, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read model map {}: {}", path.display(), e))?;
+    parse_model_map(&text, reserved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"
+reviewer = { provider = "claude", model = "claude-3-5-sonnet-20241022" }
+drafter = { provider = "anthropic", model = "claude-3-5-haiku-20241022" }
+researcher = { provider = "openai", model = "o3" }
+"#;
+
+    #[test]
+    fn aliases_resolve_to_provider_and_model() {
+        let map = parse_model_map(MAP, &["all"]).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["reviewer"].ai(), "claude");
+        assert_eq!(map["reviewer"].model, "claude-3-5-sonnet-20241022");
+        // Two names for the same provider stay distinct
+        assert_eq!(map["drafter"].ai(), "claude");
+        assert_eq!(map["drafter"].model, "claude-3-5-haiku-20241022");
+        assert_eq!(map["researcher"].ai(), "gpt");
+    }
+
+    #[test]
+    fn bad_entries_are_rejected() {
+        let unknown =
+            parse_model_map(r#"x = { provider = "mistral", model = "m" }"#, &[]).unwrap_err();
+        assert!(unknown.contains("unknown provider 'mistral'"));
+        let shadow =
+            parse_model_map(r#"claude = { provider = "gpt", model = "gpt-4o" }"#, &[]).unwrap_err();
+        assert!(shadow.contains("shadows"));
+        assert!(parse_model_map(r#"fast = { provider = "gpt", model = "m" }"#, &["fast"]).is_err());
+        assert!(parse_model_map(r#"x = { provider = "gpt" }"#, &[]).is_err());
+        let mismatched =
+            parse_model_map(r#"x = { provider = "claude", model = "gpt-4o" }"#, &[]).unwrap_err();
+        assert!(mismatched.contains("is a gpt model"), "{}", mismatched);
+    }
+}
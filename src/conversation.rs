@@ -0,0 +1,246 @@
+//! Conversation transcripts for ChatDelta CLI
+//!
+//! Conversation mode keeps its own list of turns with timestamps alongside the
+//! `ChatSession` history so the exchange can be exported as a readable Markdown file.
+//! Saved JSON history is versioned and records the provider and model it was held with.
+
+use chatdelta::Message;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Current version of the saved conversation format
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A saved conversation (--save-conversation / --load-conversation)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationFile {
+    pub version: u32,
+    /// AI the conversation was held with (ChatGPT, Gemini, Claude)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub messages: Vec<Message>,
+}
+
+impl ConversationFile {
+    pub fn new(provider: &str, model: &str, messages: &[Message]) -> Self {
+        ConversationFile {
+            version: FORMAT_VERSION,
+            provider: Some(provider.to_string()),
+            model: Some(model.to_string()),
+            messages: messages.to_vec(),
+        }
+    }
+
+    /// Parse a saved conversation, migrating older formats.
+    /// Files written before versioning are a bare array of messages.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if value.is_array() {
+            let messages = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            return Ok(ConversationFile {
+                version: FORMAT_VERSION,
+                provider: None,
+                model: None,
+                messages,
+            });
+        }
+
+        let version = value
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .ok_or("missing format version")?;
+        if version > u64::from(FORMAT_VERSION) {
+            return Err(format!(
+                "format version {} is newer than this chatdelta supports (up to {}); upgrade chatdelta to load it",
+                version, FORMAT_VERSION
+            ));
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    /// Read and parse a saved conversation file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Failed to read conversation file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::parse(&json).map_err(|e| {
+            format!(
+                "Failed to load conversation file '{}': {}",
+                path.display(),
+                e
+            )
+        })
+    }
+}
+
+/// One message in the conversation, with the time it was sent or received
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub role: String,
+    pub content: String,
+    /// None for messages loaded from a saved history
+    pub timestamp: Option<DateTime<Local>>,
+}
+
+impl Turn {
+    pub fn now(role: &str, content: &str) -> Self {
+        Turn {
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: Some(Local::now()),
+        }
+    }
+}
+
+/// Turns for messages loaded with --load-conversation (no timestamps are stored)
+pub fn turns_from_history(messages: &[Message]) -> Vec<Turn> {
+    messages
+        .iter()
+        .map(|m| Turn {
+            role: m.role.clone(),
+            content: m.content.clone(),
+            timestamp: None,
+        })
+        .collect()
+}
+
+/// Render the conversation as Markdown with `**You:**` / `**Assistant:**` turns.
+/// Message bodies are kept verbatim on their own lines so multi-line text and code
+/// fences survive intact.
+pub fn render_markdown(turns: &[Turn]) -> String {
+    let mut md = String::from("# ChatDelta Conversation\n");
+    for turn in turns {
+        let speaker = match turn.role.as_str() {
+            "user" => "You",
+            "assistant" => "Assistant",
+            "system" => "System",
+            other => other,
+        };
+        md.push('\n');
+        match turn.timestamp {
+            Some(ts) => md.push_str(&format!(
+                "**{}:** _{}_\n\n",
+                speaker,
+                ts.format("%Y-%m-%d %H:%M:%S")
+            )),
+            None => md.push_str(&format!("**{}:**\n\n", speaker)),
+        }
+        md.push_str(turn.content.trim_end());
+        md.push('\n');
+    }
+    md
+}
+
+/// Whether a path asks for a Markdown transcript rather than JSON history
+pub fn is_markdown_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+}
+
+/// Save the conversation, choosing Markdown or JSON from the file extension
+pub fn save(
+    path: &Path,
+    file: &ConversationFile,
+    turns: &[Turn],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_markdown_path(path) {
+        export_transcript(path, turns)
+    } else {
+        fs::write(path, serde_json::to_string_pretty(file)?)?;
+        Ok(())
+    }
+}
+
+/// Write the Markdown transcript for --export-transcript
+pub fn export_transcript(path: &Path, turns: &[Turn]) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, render_markdown(turns))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn two_turn_conversation_markdown() {
+        let at = |s| Some(Local.with_ymd_and_hms(2025, 3, 1, 9, 30, s).unwrap());
+        let turns = vec![
+            Turn {
+                role: "user".to_string(),
+                content: "Show me hello world in Rust".to_string(),
+                timestamp: at(0),
+            },
+            Turn {
+                role: "assistant".to_string(),
+                content: "Here you go:\n\n```rust\nfn main() {\n    println!(\"Hello\");\n}\n```\n"
+                    .to_string(),
+                timestamp: at(4),
+            },
+        ];
+
+        assert_eq!(
+            render_markdown(&turns),
+            "# ChatDelta Conversation\n\
+             \n**You:** _2025-03-01 09:30:00_\n\nShow me hello world in Rust\n\
+             \n**Assistant:** _2025-03-01 09:30:04_\n\nHere you go:\n\n```rust\nfn main() {\n    println!(\"Hello\");\n}\n```\n"
+        );
+    }
+
+    #[test]
+    fn loaded_history_has_no_timestamps() {
+        let turns = turns_from_history(&[Message::user("Hi"), Message::assistant("Hello!")]);
+        let md = render_markdown(&turns);
+        assert!(md.contains("**You:**\n\nHi\n"));
+        assert!(md.contains("**Assistant:**\n\nHello!\n"));
+    }
+
+    #[test]
+    fn loads_v1_file() {
+        let json = r#"{
+            "version": 1,
+            "provider": "Claude",
+            "model": "claude-sonnet-4-6",
+            "messages": [{"role": "user", "content": "Hi"}, {"role": "assistant", "content": "Hello!"}]
+        }"#;
+        let file = ConversationFile::parse(json).unwrap();
+        assert_eq!(file.version, 1);
+        assert_eq!(file.model.as_deref(), Some("claude-sonnet-4-6"));
+        assert_eq!(file.messages.len(), 2);
+
+        let saved = ConversationFile::new("Claude", "claude-sonnet-4-6", &file.messages);
+        let reloaded = ConversationFile::parse(&serde_json::to_string(&saved).unwrap()).unwrap();
+        assert_eq!(reloaded.provider.as_deref(), Some("Claude"));
+    }
+
+    #[test]
+    fn rejects_future_version() {
+        let err = ConversationFile::parse(r#"{"version": 999, "messages": []}"#).unwrap_err();
+        assert!(err.contains("999"));
+        assert!(err.contains("upgrade"));
+    }
+
+    #[test]
+    fn migrates_unversioned_history() {
+        let file = ConversationFile::parse(r#"[{"role": "user", "content": "Hi"}]"#).unwrap();
+        assert_eq!(file.version, FORMAT_VERSION);
+        assert!(file.model.is_none());
+        assert_eq!(file.messages[0].content, "Hi");
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert!(is_markdown_path(Path::new("chat.md")));
+        assert!(is_markdown_path(Path::new("notes/CHAT.Markdown")));
+        assert!(!is_markdown_path(Path::new("chat.json")));
+    }
+}
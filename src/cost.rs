@@ -0,0 +1,145 @@
+//! Token and cost estimation for ChatDelta CLI
+//!
+//! Estimates are deliberately rough: tokens are approximated from character counts and
+//! output is priced at the full `--max-tokens` budget, so they err on the expensive side.
+
+/// Price per million tokens in USD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+const fn price(input_per_mtok: f64, output_per_mtok: f64) -> Pricing {
+    Pricing {
+        input_per_mtok,
+        output_per_mtok,
+    }
+}
+
+/// Published list prices keyed by model-name prefix (the longest matching prefix wins)
+pub const PRICING: &[(&str, Pricing)] = &[
+    ("gpt-5", price(1.25, 10.00)),
+    ("o3", price(2.00, 8.00)),
+    ("gpt-4o-mini", price(0.15, 0.60)),
+    ("gpt-4o", price(2.50, 10.00)),
+    ("gemini-3.1-pro", price(2.00, 12.00)),
+    ("gemini-2.5-pro", price(1.25, 10.00)),
+    ("gemini-2.5-flash-lite", price(0.10, 0.40)),
+    ("gemini-2.5-flash", price(0.30, 2.50)),
+    ("claude-opus", price(5.00, 25.00)),
+    ("claude-sonnet", price(3.00, 15.00)),
+    ("claude-haiku", price(1.00, 5.00)),
+];
+
+/// Approximate token count (about four characters per token for English text)
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/// Pricing for a model from `table`, by longest matching prefix
+pub fn price_for(table: &[(&str, Pricing)], model: &str) -> Option<Pricing> {
+    table
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Estimated cost of sending `prompt_tokens` to each model and receiving up to
+/// `output_tokens` back. Models missing from the table are returned separately.
+pub fn estimate_cost(
+    table: &[(&str, Pricing)],
+    models: &[&str],
+    prompt_tokens: u32,
+    output_tokens: u32,
+) -> (f64, Vec<String>) {
+    let mut total = 0.0;
+    let mut unpriced = Vec::new();
+    for model in models {
+        match price_for(table, model) {
+            Some(p) => {
+                total += (prompt_tokens as f64 * p.input_per_mtok
+                    + output_tokens as f64 * p.output_per_mtok)
+                    / 1_000_000.0;
+            }
+            None => unpriced.push(model.to_string()),
+        }
+    }
+    (total, unpriced)
+}
+
+/// What to do about an estimate relative to --cost-limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostDecision {
+    /// Within the limit, or over it with --yes
+    Proceed,
+    /// Over the limit on a terminal: ask the user
+    Confirm,
+    /// Over the limit with no way to ask
+    Abort,
+}
+
+pub fn decide(estimate: f64, limit: f64, interactive: bool, assume_yes: bool) -> CostDecision {
+    if estimate <= limit || assume_yes {
+        CostDecision::Proceed
+    } else if interactive {
+        CostDecision::Confirm
+    } else {
+        CostDecision::Abort
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PRICING: &[(&str, Pricing)] = &[
+        ("cheap", price(1.0, 2.0)),
+        ("pricey", price(10.0, 20.0)),
+        ("pricey-mini", price(0.5, 1.0)),
+    ];
+
+    #[test]
+    fn longest_prefix_wins() {
+        assert_eq!(
+            price_for(TEST_PRICING, "pricey-mini-2"),
+            Some(price(0.5, 1.0))
+        );
+        assert_eq!(
+            price_for(TEST_PRICING, "pricey-max"),
+            Some(price(10.0, 20.0))
+        );
+        assert_eq!(price_for(TEST_PRICING, "unknown"), None);
+        assert_eq!(
+            price_for(PRICING, "gpt-4o-mini").unwrap().input_per_mtok,
+            0.15
+        );
+        assert!(price_for(PRICING, "claude-haiku-4-5-20251001").is_some());
+    }
+
+    #[test]
+    fn estimate_against_limit() {
+        // 1M prompt tokens + 1M output tokens per model
+        let (cost, unpriced) = estimate_cost(
+            TEST_PRICING,
+            &["cheap", "pricey", "mystery"],
+            1_000_000,
+            1_000_000,
+        );
+        assert!((cost - 33.0).abs() < 1e-9);
+        assert_eq!(unpriced, vec!["mystery".to_string()]);
+
+        assert_eq!(decide(cost, 50.0, false, false), CostDecision::Proceed);
+        assert_eq!(decide(cost, 10.0, true, false), CostDecision::Confirm);
+        assert_eq!(decide(cost, 10.0, false, false), CostDecision::Abort);
+        assert_eq!(decide(cost, 10.0, false, true), CostDecision::Proceed);
+    }
+
+    #[test]
+    fn token_estimate_rounds_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+}
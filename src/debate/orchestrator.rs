@@ -3,13 +3,16 @@
 use std::collections::{HashMap, HashSet};
 use std::env;
 
-use chatdelta::{create_client, AiClient, ClientConfig};
+use crate::providers::create_client;
+use chatdelta::{AiClient, ClientConfig};
+
+use crate::keys::KeyRotation;
 
 use super::prompts;
 use super::protocol::{turn_sequence, TurnSpec};
 use super::renderer::DebateRenderer;
 use super::types::{
-    ConfidenceLevel, DebateConfig, DebateTurn, DebateTranscript, ModelSpec, ModeratorReport,
+    ConfidenceLevel, DebateConfig, DebateTranscript, DebateTurn, ModelSpec, ModeratorReport,
     ParticipantRole, TurnStage,
 };
 
@@ -49,7 +52,8 @@ impl Orchestrator {
             if turn_spec.role == ParticipantRole::Moderator {
                 self.run_moderator_turn(&mut transcript).await?;
             } else {
-                self.run_participant_turn(turn_spec, &mut transcript).await?;
+                self.run_participant_turn(turn_spec, &mut transcript)
+                    .await?;
             }
         }
 
@@ -88,12 +92,7 @@ impl Orchestrator {
         .map_err(|e| format!("{} ({}) failed: {}", spec.role, role_label, e))?;
 
         // Simple repetition heuristic: compare with this model's last turn
-        if let Some(prior) = transcript
-            .turns
-            .iter()
-            .rev()
-            .find(|t| t.role == spec.role)
-        {
+        if let Some(prior) = transcript.turns.iter().rev().find(|t| t.role == spec.role) {
             if is_repetitive(&prior.content, &response) {
                 self.renderer.print_repetition_warning(&spec.role);
             }
@@ -127,8 +126,11 @@ impl Orchestrator {
             &context,
         );
 
-        self.renderer
-            .print_turn_header(&ParticipantRole::Moderator, &TurnStage::ModeratorReport, "Moderator");
+        self.renderer.print_turn_header(
+            &ParticipantRole::Moderator,
+            &TurnStage::ModeratorReport,
+            "Moderator",
+        );
 
         let raw = moderator
             .send_prompt(&prompt)
@@ -172,11 +174,11 @@ fn build_turn_prompt(
 /// from the same model, suggesting the model is repeating itself.
 fn is_repetitive(prior: &str, current: &str) -> bool {
     let stop_words: HashSet<&str> = [
-        "the", "a", "an", "is", "it", "in", "of", "and", "or", "to", "that", "this", "with",
-        "for", "as", "by", "on", "at", "be", "are", "was", "were", "has", "have", "had", "but",
-        "not", "from", "they", "their", "its", "i", "my", "we", "our", "you", "your", "do",
-        "did", "will", "would", "can", "could", "should", "may", "might", "also", "more",
-        "than", "so", "if", "about", "which", "there", "when", "just", "both",
+        "the", "a", "an", "is", "it", "in", "of", "and", "or", "to", "that", "this", "with", "for",
+        "as", "by", "on", "at", "be", "are", "was", "were", "has", "have", "had", "but", "not",
+        "from", "they", "their", "its", "i", "my", "we", "our", "you", "your", "do", "did", "will",
+        "would", "can", "could", "should", "may", "might", "also", "more", "than", "so", "if",
+        "about", "which", "there", "when", "just", "both",
     ]
     .iter()
     .copied()
@@ -220,18 +222,17 @@ fn parse_moderator_report(raw: &str) -> ModeratorReport {
     };
 
     let verification_text = get("Claims Requiring Verification");
-    let verification_flags = if verification_text.is_empty()
-        || verification_text.to_lowercase().starts_with("none")
-    {
-        vec![]
-    } else {
-        verification_text
-            .lines()
-            .filter(|l| !l.trim().is_empty())
-            .map(|l| l.trim_start_matches(['-', '*', '•', ' ']).to_string())
-            .filter(|l| !l.is_empty())
-            .collect()
-    };
+    let verification_flags =
+        if verification_text.is_empty() || verification_text.to_lowercase().starts_with("none") {
+            vec![]
+        } else {
+            verification_text
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(|l| l.trim_start_matches(['-', '*', '•', ' ']).to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        };
 
     let confidence = parse_confidence(&get("Confidence Level"));
 
@@ -295,7 +296,10 @@ pub fn resolve_client(
         "claude" => env::var("ANTHROPIC_API_KEY").or_else(|_| env::var("CLAUDE_API_KEY")),
         other => return Err(format!("Unknown provider: {other}").into()),
     }
-    .map_err(|_| {
+    .ok()
+    .and_then(|value| KeyRotation::from_list(&value).next_key())
+    .map(|(_, key)| key)
+    .ok_or_else(|| {
         let env_hint = match spec.provider.as_str() {
             "openai" => "OPENAI_API_KEY or CHATGPT_API_KEY",
             "gemini" => "GEMINI_API_KEY",
@@ -321,13 +325,17 @@ pub fn resolve_auto_moderator(
         let client = create_client("gemini", &key, "gemini-2.5-flash", config)?;
         return Ok(Some(client));
     }
-    if let Ok(key) = env::var("ANTHROPIC_API_KEY").or_else(|_| env::var("CLAUDE_API_KEY")) {
-        let client = create_client("claude", &key, "claude-sonnet-4-6", config)?;
-        return Ok(Some(client));
+    if let Ok(keys) = env::var("ANTHROPIC_API_KEY").or_else(|_| env::var("CLAUDE_API_KEY")) {
+        if let Some((_, key)) = KeyRotation::from_list(&keys).next_key() {
+            let client = create_client("claude", &key, "claude-sonnet-4-6", config)?;
+            return Ok(Some(client));
+        }
     }
-    if let Ok(key) = env::var("OPENAI_API_KEY").or_else(|_| env::var("CHATGPT_API_KEY")) {
-        let client = create_client("openai", &key, "gpt-4o", config)?;
-        return Ok(Some(client));
+    if let Ok(keys) = env::var("OPENAI_API_KEY").or_else(|_| env::var("CHATGPT_API_KEY")) {
+        if let Some((_, key)) = KeyRotation::from_list(&keys).next_key() {
+            let client = create_client("openai", &key, "gpt-4o", config)?;
+            return Ok(Some(client));
+        }
     }
     Ok(None)
 }
@@ -21,11 +21,7 @@ impl DebateRenderer {
         }
         println!();
         println!("╔{}╗", "═".repeat(WIDE - 2));
-        println!(
-            "║{:^width$}║",
-            "ChatDelta — Debate Mode",
-            width = WIDE - 2
-        );
+        println!("║{:^width$}║", "ChatDelta — Debate Mode", width = WIDE - 2);
         println!("╚{}╝", "═".repeat(WIDE - 2));
         println!();
         println!("  Protocol  : {}", config.protocol);
@@ -133,10 +129,7 @@ impl DebateRenderer {
             Some(m) => md.push_str(&format!("- **Moderator:** {}\n", m)),
             None => md.push_str("- **Moderator:** auto-detected\n"),
         }
-        md.push_str(&format!(
-            "- **Protocol:** {}\n",
-            transcript.config.protocol
-        ));
+        md.push_str(&format!("- **Protocol:** {}\n", transcript.config.protocol));
         md.push_str(&format!("- **Rounds:** {}\n", transcript.config.rounds));
         md.push_str(&format!(
             "- **Proposition:** {}\n",
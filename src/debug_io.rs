@@ -0,0 +1,246 @@
+//! Raw request/response capture for ChatDelta CLI (`--debug-io`)
+//!
+//! chatdelta doesn't expose the HTTP bodies it sends, so each request is reconstructed
+//! from the client configuration and prompt in the provider's wire format. API keys are
+//! redacted to their last four characters.
+
+use crate::cli::{Args, AI_NAMES};
+use crate::providers::default_base_url;
+use chatdelta::{AiResponse, ClientError};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One model's request and its outcome
+pub struct Exchange<'a> {
+    /// Display name of the AI (ChatGPT, Gemini, Claude)
+    pub name: &'a str,
+    pub model: &'a str,
+    /// API key the client was created with; None for mock clients
+    pub key: Option<&'a str>,
+    pub prompt: &'a str,
+    pub result: &'a Result<AiResponse, ClientError>,
+}
+
+/// Mask an API key, keeping only its last four characters when it is long enough to
+/// make that safe
+pub fn redact_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() > 8 {
+        format!(
+            "****{}",
+            chars[chars.len() - 4..].iter().collect::<String>()
+        )
+    } else {
+        "****".to_string()
+    }
+}
+
+/// The HTTP request the provider's API receives, as method, URL, headers and JSON body
+pub fn request_json(args: &Args, exchange: &Exchange) -> Value {
+    let ai = AI_NAMES
+        .iter()
+        .copied()
+        .find(|ai| Args::display_name(ai) == exchange.name)
+        .unwrap_or("gpt");
+    let key = exchange
+        .key
+        .map_or_else(|| "(none)".to_string(), redact_key);
+    let system = args.system_prompt.as_deref();
+    let base = args
+        .base_url(ai)
+        .unwrap_or_else(|| default_base_url(ai).to_string());
+
+    let (url, headers, body) = match ai {
+        "gemini" => {
+            let mut body = json!({
+                "contents": [{ "role": "user", "parts": [{ "text": exchange.prompt }] }],
+                "generationConfig": { "maxOutputTokens": args.max_tokens, "temperature": args.temperature },
+            });
+            if let Some(system) = system {
+                body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+            }
+            let url = format!(
+                "{}/models/{}:generateContent?key={}",
+                base, exchange.model, key
+            );
+            (url, json!({ "Content-Type": "application/json" }), body)
+        }
+        "claude" => {
+            let mut body = json!({
+                "model": exchange.model,
+                "max_tokens": args.max_tokens,
+                "temperature": args.temperature,
+                "messages": [{ "role": "user", "content": exchange.prompt }],
+            });
+            if let Some(system) = system {
+                body["system"] = json!(system);
+            }
+            let headers = json!({
+                "Content-Type": "application/json",
+                "x-api-key": key,
+                "anthropic-version": "2023-06-01",
+            });
+            (format!("{}/messages", base), headers, body)
+        }
+        _ => {
+            let mut messages = Vec::new();
+            if let Some(system) = system {
+                messages.push(json!({ "role": "system", "content": system }));
+            }
+            messages.push(json!({ "role": "user", "content": exchange.prompt }));
+            let body = json!({
+                "model": exchange.model,
+                "messages": messages,
+                "max_tokens": args.max_tokens,
+                "temperature": args.temperature,
+            });
+            let headers = json!({
+                "Content-Type": "application/json",
+                "Authorization": format!("Bearer {}", key),
+            });
+            (format!("{}/chat/completions", base), headers, body)
+        }
+    };
+
+    json!({
+        "reconstructed": true,
+        "method": "POST",
+        "url": url,
+        "headers": headers,
+        "body": body,
+    })
+}
+
+/// The response content and metadata, or the error
+pub fn response_json(result: &Result<AiResponse, ClientError>) -> Value {
+    match result {
+        Ok(response) => json!({
+            "content": response.content,
+            "model_used": response.metadata.model_used,
+            "prompt_tokens": response.metadata.prompt_tokens,
+            "completion_tokens": response.metadata.completion_tokens,
+            "total_tokens": response.metadata.total_tokens,
+            "finish_reason": response.metadata.finish_reason,
+            "latency_ms": response.metadata.latency_ms,
+        }),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+/// Write an exchange to `debug/` in the run directory, or to stderr without --output-dir.
+/// Returns the file written, if any.
+pub fn record(
+    args: &Args,
+    run_dir: Option<&Path>,
+    exchange: &Exchange,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let document = json!({
+        "model": exchange.model,
+        "request": request_json(args, exchange),
+        "response": response_json(exchange.result),
+    });
+    let text = serde_json::to_string_pretty(&document)?;
+
+    match run_dir {
+        Some(run_dir) => {
+            let dir = run_dir.join("debug");
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{}.json", exchange.name.to_lowercase()));
+            fs::write(&path, text)?;
+            Ok(Some(path))
+        }
+        None => {
+            eprintln!(
+                "--- debug-io: {} ({}) ---\n{}",
+                exchange.name, exchange.model, text
+            );
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chatdelta::ResponseMetadata;
+    use clap::Parser;
+
+    fn answer(content: &str) -> Result<AiResponse, ClientError> {
+        Ok(AiResponse {
+            content: content.to_string(),
+            metadata: ResponseMetadata::default(),
+        })
+    }
+
+    #[test]
+    fn debug_file_is_written_with_key_redacted() {
+        let base = std::env::temp_dir().join(format!("chatdelta-debug-{}", uuid::Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--debug-io",
+            "--output-dir",
+            base.to_str().unwrap(),
+            "--system-prompt",
+            "Be brief",
+            "Hi",
+        ])
+        .unwrap();
+        let result = answer("Hello");
+        let exchange = Exchange {
+            name: "ChatGPT",
+            model: "gpt-4o",
+            key: Some("sk-live-secret-9876"),
+            prompt: "Hi",
+            result: &result,
+        };
+
+        let run_dir = base.join("run");
+        let path = record(&args, Some(&run_dir), &exchange)
+            .unwrap()
+            .expect("written to the run's debug directory");
+        assert_eq!(path, run_dir.join("debug").join("chatgpt.json"));
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("sk-live-secret"));
+
+        let document: Value = serde_json::from_str(&text).unwrap();
+        let request = &document["request"];
+        assert_eq!(request["headers"]["Authorization"], "Bearer ****9876");
+        assert_eq!(request["body"]["model"], "gpt-4o");
+        assert_eq!(request["body"]["messages"][0]["content"], "Be brief");
+        assert_eq!(request["body"]["messages"][1]["content"], "Hi");
+        assert_eq!(document["response"]["content"], "Hello");
+        fs::remove_dir_all(base).ok();
+    }
+
+    #[test]
+    fn keys_are_redacted_in_urls_and_headers() {
+        let args = Args::try_parse_from(["chatdelta", "Hi"]).unwrap();
+        let result = answer("");
+        let request = |name, key| {
+            request_json(
+                &args,
+                &Exchange {
+                    name,
+                    model: "m",
+                    key,
+                    prompt: "Hi",
+                    result: &result,
+                },
+            )
+        };
+
+        let gemini = request("Gemini", Some("AIzaSecretGeminiKey1234"));
+        assert!(gemini["url"]
+            .as_str()
+            .unwrap()
+            .ends_with(":generateContent?key=****1234"));
+        let claude = request("Claude", Some("short"));
+        assert_eq!(claude["headers"]["x-api-key"], "****");
+        assert_eq!(claude["body"]["messages"][0]["content"], "Hi");
+        assert_eq!(
+            request("ChatGPT", None)["headers"]["Authorization"],
+            "Bearer (none)"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Error classification for ChatDelta CLI
+//!
+//! Sorts client errors into broad categories so that error messages can suggest a fix and
+//! the query path can decide whether a failure is worth retrying against a fallback model.
+
+use chatdelta::{ClientError, NetworkError};
+
+/// Broad cause of a failed request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Auth,
+    RateLimit,
+    Timeout,
+    Server,
+    Other,
+}
+
+impl ErrorCategory {
+    /// Transient failures that another attempt (or another model) may not hit
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorCategory::Timeout | ErrorCategory::Server)
+    }
+
+    /// Actionable message shown in place of the raw error, where there is one
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorCategory::Auth => Some("Invalid API key — check your environment variables"),
+            ErrorCategory::RateLimit => Some("Rate limit exceeded — retry after a moment"),
+            ErrorCategory::Timeout => Some("Request timed out — try --timeout with a higher value"),
+            ErrorCategory::Server | ErrorCategory::Other => None,
+        }
+    }
+
+    /// Short tag for --compact-errors
+    pub fn tag(self) -> &'static str {
+        match self {
+            ErrorCategory::Auth => "401",
+            ErrorCategory::RateLimit => "429",
+            ErrorCategory::Timeout => "timeout",
+            ErrorCategory::Server => "server error",
+            ErrorCategory::Other => "error",
+        }
+    }
+}
+
+/// One line covering every failed model for --compact-errors, e.g.
+/// `2 models failed: ChatGPT (429), Claude (401)`; None when nothing failed
+pub fn compact_summary(failures: &[(String, ErrorCategory)]) -> Option<String> {
+    if failures.is_empty() {
+        return None;
+    }
+    let listed: Vec<String> = failures
+        .iter()
+        .map(|(name, category)| format!("{} ({})", name, category.tag()))
+        .collect();
+    Some(format!(
+        "{} model{} failed: {}",
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" },
+        listed.join(", ")
+    ))
+}
+
+/// Classify a client error
+pub fn classify(error: &ClientError) -> ErrorCategory {
+    if let ClientError::Network(NetworkError::Timeout) = error {
+        return ErrorCategory::Timeout;
+    }
+    classify_message(&error.to_string())
+}
+
+/// Classify an error by its message, for errors that only surface as text
+pub fn classify_message(msg: &str) -> ErrorCategory {
+    if msg.contains("401") || msg.contains("Unauthorized") || msg.contains("invalid_api_key") {
+        ErrorCategory::Auth
+    } else if msg.contains("429") || msg.contains("rate") || msg.contains("RateLimit") {
+        ErrorCategory::RateLimit
+    } else if msg.contains("timeout") || msg.contains("Timeout") || msg.contains("timed out") {
+        ErrorCategory::Timeout
+    } else if [
+        "500",
+        "502",
+        "503",
+        "504",
+        "Internal Server Error",
+        "Service Unavailable",
+        "overloaded",
+    ]
+    .iter()
+    .any(|pattern| msg.contains(pattern))
+    {
+        ErrorCategory::Server
+    } else {
+        ErrorCategory::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_common_failures() {
+        assert_eq!(
+            classify(&ClientError::Network(NetworkError::Timeout)),
+            ErrorCategory::Timeout
+        );
+        assert_eq!(
+            classify_message("HTTP 401 Unauthorized"),
+            ErrorCategory::Auth
+        );
+        assert_eq!(
+            classify_message("429 Too Many Requests"),
+            ErrorCategory::RateLimit
+        );
+        assert_eq!(
+            classify_message("503 Service Unavailable"),
+            ErrorCategory::Server
+        );
+        assert_eq!(classify_message("model not found"), ErrorCategory::Other);
+    }
+
+    #[test]
+    fn compact_summary_tags_each_failure() {
+        let failures = [
+            ("ChatGPT", classify_message("429 Too Many Requests")),
+            (
+                "Gemini",
+                classify(&ClientError::Network(NetworkError::Timeout)),
+            ),
+            ("Claude", classify_message("HTTP 401 Unauthorized")),
+            ("Mistral", classify_message("503 Service Unavailable")),
+            ("Local", classify_message("model not found")),
+        ]
+        .map(|(name, category)| (name.to_string(), category));
+        assert_eq!(
+            compact_summary(&failures).unwrap(),
+            "5 models failed: ChatGPT (429), Gemini (timeout), Claude (401), Mistral (server error), Local (error)"
+        );
+        assert_eq!(
+            compact_summary(&failures[2..3]).unwrap(),
+            "1 model failed: Claude (401)"
+        );
+        assert_eq!(compact_summary(&[]), None);
+    }
+
+    #[test]
+    fn only_timeouts_and_server_errors_are_retryable() {
+        assert!(ErrorCategory::Timeout.is_retryable());
+        assert!(ErrorCategory::Server.is_retryable());
+        assert!(!ErrorCategory::Auth.is_retryable());
+        assert!(!ErrorCategory::RateLimit.is_retryable());
+        assert!(!ErrorCategory::Other.is_retryable());
+    }
+}
@@ -0,0 +1,277 @@
+//! Word-level response diffs for ChatDelta CLI (`--diff`)
+//!
+//! Responses are compared word by word; the result renders as ANSI color on a terminal,
+//! as `[-deleted-]{+inserted+}` markup elsewhere, and as structured chunks in JSON.
+
+use serde::Serialize;
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether a run of text is shared, only in the second response, or only in the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of consecutive words with the same kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffChunk {
+    #[serde(rename = "type")]
+    pub kind: ChunkKind,
+    pub text: String,
+}
+
+/// Diff `from` against `to` by words (each word keeps its trailing whitespace), using the
+/// longest common subsequence
+pub fn diff_words(from: &str, to: &str) -> Vec<DiffChunk> {
+    let a: Vec<&str> = from.split_inclusive(char::is_whitespace).collect();
+    let b: Vec<&str> = to.split_inclusive(char::is_whitespace).collect();
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    align(&a, &b, &mut ops);
+
+    // Within each changed stretch, what was removed comes before what was added
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let (mut deleted, mut inserted) = (String::new(), String::new());
+    for (kind, word) in ops {
+        match kind {
+            ChunkKind::Delete => deleted.push_str(word),
+            ChunkKind::Insert => inserted.push_str(word),
+            ChunkKind::Equal => {
+                flush_changes(&mut chunks, &mut deleted, &mut inserted);
+                match chunks.last_mut() {
+                    Some(last) if last.kind == ChunkKind::Equal => last.text.push_str(word),
+                    _ => chunks.push(DiffChunk {
+                        kind,
+                        text: word.to_string(),
+                    }),
+                }
+            }
+        }
+    }
+    flush_changes(&mut chunks, &mut deleted, &mut inserted);
+    chunks
+}
+
+/// Append the pending deletion and insertion, if any, as chunks
+fn flush_changes(chunks: &mut Vec<DiffChunk>, deleted: &mut String, inserted: &mut String) {
+    for (kind, text) in [(ChunkKind::Delete, deleted), (ChunkKind::Insert, inserted)] {
+        if !text.is_empty() {
+            chunks.push(DiffChunk {
+                kind,
+                text: std::mem::take(text),
+            });
+        }
+    }
+}
+
+/// Append an edit script turning `a` into `b` to `ops`, following a longest common
+/// subsequence. Hirschberg's divide and conquer keeps memory linear in the input.
+fn align<'a>(a: &[&'a str], b: &[&'a str], ops: &mut Vec<(ChunkKind, &'a str)>) {
+    // Shared ends need no table
+    let prefix = a.iter().zip(b).take_while(|(x, y)| same_word(x, y)).count();
+    ops.extend(b[..prefix].iter().map(|w| (ChunkKind::Equal, *w)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| same_word(x, y))
+        .count();
+    let (core_a, core_b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    let inserts = |words: &[&'a str], ops: &mut Vec<(ChunkKind, &'a str)>| {
+        ops.extend(words.iter().map(|w| (ChunkKind::Insert, *w)));
+    };
+    match core_a {
+        [] => inserts(core_b, ops),
+        [word] => match core_b.iter().position(|w| same_word(word, w)) {
+            Some(pos) => {
+                inserts(&core_b[..pos], ops);
+                ops.push((ChunkKind::Equal, core_b[pos]));
+                inserts(&core_b[pos + 1..], ops);
+            }
+            None => {
+                ops.push((ChunkKind::Delete, word));
+                inserts(core_b, ops);
+            }
+        },
+        _ if core_b.is_empty() => ops.extend(core_a.iter().map(|w| (ChunkKind::Delete, *w))),
+        _ => {
+            // Split `a` in half and `b` where the two halves' LCS lengths add up best
+            let mid = core_a.len() / 2;
+            let front = lcs_row(&core_a[..mid], core_b, false);
+            let back = lcs_row(&core_a[mid..], core_b, true);
+            let m = core_b.len();
+            let split = (0..=m)
+                .rev()
+                .max_by_key(|&k| front[k] + back[m - k])
+                .unwrap_or(0);
+            align(&core_a[..mid], &core_b[..split], ops);
+            align(&core_a[mid..], &core_b[split..], ops);
+        }
+    }
+    ops.extend(b[b.len() - suffix..].iter().map(|w| (ChunkKind::Equal, *w)));
+}
+
+/// LCS lengths of `a` against each prefix of `b`, or with `reverse` of the reversed words
+/// against each reversed suffix. Two rolling rows, as in `compare::similarity`.
+fn lcs_row(a: &[&str], b: &[&str], reverse: bool) -> Vec<u32> {
+    let at = |i: usize, len: usize| if reverse { len - 1 - i } else { i };
+    let mut prev = vec![0u32; b.len() + 1];
+    let mut curr = vec![0u32; b.len() + 1];
+    for i in 0..a.len() {
+        let word = a[at(i, a.len())];
+        for j in 0..b.len() {
+            curr[j + 1] = if same_word(word, b[at(j, b.len())]) {
+                prev[j] + 1
+            } else {
+                curr[j].max(prev[j + 1])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev
+}
+
+/// Words match regardless of the whitespace that follows them
+fn same_word(a: &str, b: &str) -> bool {
+    a.trim_end() == b.trim_end()
+}
+
+/// Plain rendering for pipes and files: `[-deleted-]` and `{+inserted+}`
+pub fn render_plain(chunks: &[DiffChunk]) -> String {
+    render(chunks, |kind, text| match kind {
+        ChunkKind::Equal => text.to_string(),
+        ChunkKind::Insert => format!("{{+{}+}}", text),
+        ChunkKind::Delete => format!("[-{}-]", text),
+    })
+}
+
+/// Terminal rendering: green insertions, red deletions, dim shared context
+pub fn render_colored(chunks: &[DiffChunk]) -> String {
+    render(chunks, |kind, text| {
+        let color = match kind {
+            ChunkKind::Equal => DIM,
+            ChunkKind::Insert => GREEN,
+            ChunkKind::Delete => RED,
+        };
+        format!("{}{}{}", color, text, RESET)
+    })
+}
+
+/// Render each chunk, keeping trailing whitespace outside the markup
+fn render(chunks: &[DiffChunk], mark: impl Fn(ChunkKind, &str) -> String) -> String {
+    chunks
+        .iter()
+        .map(|chunk| {
+            let text = chunk.text.trim_end();
+            format!("{}{}", mark(chunk.kind, text), &chunk.text[text.len()..])
+        })
+        .collect()
+}
+
+/// `text` in bold green, as --highlight-agreement marks consensus sentences on a terminal
+pub fn render_highlight(text: &str) -> String {
+    format!("{}{}{}{}", BOLD, GREEN, text, RESET)
+}
+
+/// Whether to color output for a --color setting (auto, always, never). `auto` colors a
+/// terminal unless NO_COLOR is set to a non-empty value.
+pub fn use_color(mode: &str, is_terminal: bool) -> bool {
+    match mode {
+        "always" => true,
+        "never" => false,
+        _ => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(chunks: &[DiffChunk]) -> Vec<(ChunkKind, &str)> {
+        chunks.iter().map(|c| (c.kind, c.text.as_str())).collect()
+    }
+
+    /// One side of a diff, rebuilt from its chunks
+    fn side(chunks: &[DiffChunk], skip: ChunkKind) -> String {
+        chunks
+            .iter()
+            .filter(|c| c.kind != skip)
+            .map(|c| c.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn diffs_by_word() {
+        let chunks = diff_words("Rust is fast and safe", "Rust is safe and fun");
+        assert_eq!(
+            chunks[0],
+            DiffChunk {
+                kind: ChunkKind::Equal,
+                text: "Rust is ".to_string()
+            }
+        );
+        assert!(chunks.iter().any(|c| c.kind == ChunkKind::Insert));
+        assert!(chunks.iter().any(|c| c.kind == ChunkKind::Delete));
+        // The chunks replay into both sides
+        assert_eq!(
+            side(&chunks, ChunkKind::Insert).trim_end(),
+            "Rust is fast and safe"
+        );
+        assert_eq!(
+            side(&chunks, ChunkKind::Delete).trim_end(),
+            "Rust is safe and fun"
+        );
+    }
+
+    #[test]
+    fn long_diffs_keep_the_longest_common_subsequence() {
+        let from: String = (0..400).map(|i| format!("w{} ", i % 37)).collect();
+        let to: String = (0..300).map(|i| format!("w{} ", (i * 7) % 41)).collect();
+        let chunks = diff_words(&from, &to);
+        assert_eq!(side(&chunks, ChunkKind::Insert), from);
+        assert_eq!(side(&chunks, ChunkKind::Delete), to);
+
+        let a: Vec<&str> = from.split_inclusive(' ').collect();
+        let b: Vec<&str> = to.split_inclusive(' ').collect();
+        let shared: usize = chunks
+            .iter()
+            .filter(|c| c.kind == ChunkKind::Equal)
+            .map(|c| c.text.split_inclusive(' ').count())
+            .sum();
+        assert_eq!(shared as u32, lcs_row(&a, &b, false)[b.len()]);
+        // Each changed stretch is at most one deletion followed by one insertion
+        assert!(chunks.windows(2).all(|w| w[0].kind != w[1].kind));
+        assert!(chunks
+            .windows(2)
+            .all(|w| (w[0].kind, w[1].kind) != (ChunkKind::Insert, ChunkKind::Delete)));
+    }
+
+    #[test]
+    fn identical_text_is_one_equal_chunk() {
+        let chunks = diff_words("same words here", "same words here");
+        assert_eq!(kinds(&chunks), vec![(ChunkKind::Equal, "same words here")]);
+    }
+
+    #[test]
+    fn plain_and_colored_rendering() {
+        let chunks = diff_words("the cat sat", "the dog sat");
+        assert_eq!(render_plain(&chunks), "the [-cat-] {+dog+} sat");
+        let colored = render_colored(&chunks);
+        assert!(colored.contains("\x1b[31mcat\x1b[0m"));
+        assert!(colored.contains("\x1b[32mdog\x1b[0m"));
+        assert!(!render_plain(&chunks).contains('\x1b'));
+
+        assert!(use_color("always", false));
+        assert!(!use_color("never", true));
+        assert!(!use_color("auto", false));
+    }
+}
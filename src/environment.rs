@@ -0,0 +1,131 @@
+//! Environment report for ChatDelta CLI (`--test`)
+//!
+//! Printed before the connection probes so a failing test comes with the context a bug
+//! report needs: where each API key was found, the resolved model and endpoint, and the
+//! CLI and library versions. Keys are shown only as their last four characters.
+
+use crate::cli::{Args, AI_NAMES};
+use crate::debug_io::redact_key;
+use crate::keys;
+use crate::providers::default_base_url;
+
+/// chatdelta version this binary was built against, taken from Cargo.lock by the build
+/// script ("unknown" when no lockfile was found)
+pub fn library_version() -> &'static str {
+    env!("CHATDELTA_VERSION")
+}
+
+/// One AI's configuration as resolved for this run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderEnv {
+    /// Display name (ChatGPT, Gemini, Claude)
+    pub name: &'static str,
+    /// Where the key was found and the key redacted, or None when no key is set
+    pub key: Option<(String, String)>,
+    pub model: String,
+    pub endpoint: String,
+    /// Whether the endpoint comes from a base URL override
+    pub endpoint_overridden: bool,
+}
+
+/// Resolve each selected AI's key source, model and endpoint. `var` looks up
+/// environment variables.
+pub fn gather(args: &Args, var: impl Fn(&str) -> Option<String>) -> Vec<ProviderEnv> {
+    AI_NAMES
+        .iter()
+        .filter(|ai| args.should_use_ai(ai))
+        .map(|ai| {
+            let base_url = args.base_url(ai);
+            ProviderEnv {
+                name: Args::display_name(ai),
+                key: keys::key_source(args, ai, &var)
+                    .map(|(source, key)| (source, redact_key(&key))),
+                model: args.model_for(ai).to_string(),
+                endpoint_overridden: base_url.is_some(),
+                endpoint: base_url.unwrap_or_else(|| default_base_url(ai).to_string()),
+            }
+        })
+        .collect()
+}
+
+/// The report as aligned text lines
+pub fn render(providers: &[ProviderEnv], cli_version: &str, library_version: &str) -> String {
+    let mut out = format!(
+        "Environment: chatdelta-cli {}, chatdelta {}\n",
+        cli_version, library_version
+    );
+    for provider in providers {
+        let key = match &provider.key {
+            Some((source, redacted)) => format!("{} ({})", redacted, source),
+            None => "not set".to_string(),
+        };
+        let endpoint = if provider.endpoint_overridden {
+            format!("{} (override)", provider.endpoint)
+        } else {
+            provider.endpoint.clone()
+        };
+        out.push_str(&format!(
+            "  {:<8} key: {:<36} model: {:<28} endpoint: {}\n",
+            provider.name, key, provider.model, endpoint
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn report_shows_key_sources_redacted() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--anthropic-keys",
+            "sk-ant-first-key-4321,sk-ant-second-key-8765",
+            "--gemini-base-url",
+            "http://localhost:8080",
+            "Hi",
+        ])
+        .unwrap();
+        let env =
+            |name: &str| (name == "CHATGPT_API_KEY").then(|| "sk-proj-abcdefgh1234".to_string());
+
+        let providers = gather(&args, env);
+        assert_eq!(providers.len(), 3);
+        assert_eq!(
+            providers[0].key,
+            Some(("env CHATGPT_API_KEY".to_string(), "****1234".to_string()))
+        );
+        assert_eq!(providers[1].key, None);
+        assert_eq!(
+            providers[2].key,
+            Some(("--anthropic-keys".to_string(), "****4321".to_string()))
+        );
+
+        let report = render(&providers, "1.2.3", "0.8.2");
+        assert!(report.starts_with("Environment: chatdelta-cli 1.2.3, chatdelta 0.8.2\n"));
+        assert!(!report.contains("abcdefgh") && !report.contains("first-key"));
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(
+            lines[1].contains("ChatGPT") && lines[1].contains("****1234 (env CHATGPT_API_KEY)")
+        );
+        assert!(lines[1].contains("endpoint: https://api.openai.com/v1"));
+        assert!(
+            lines[2].contains("key: not set")
+                && lines[2].contains("http://localhost:8080 (override)")
+        );
+        assert!(lines[3].contains("model: claude-sonnet-4-6"));
+    }
+
+    #[test]
+    fn library_version_comes_from_the_lockfile() {
+        let version = library_version();
+        assert_ne!(version, "unknown");
+        assert!(
+            version.split('.').all(|part| part.parse::<u32>().is_ok()),
+            "{}",
+            version
+        );
+    }
+}
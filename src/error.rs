@@ -0,0 +1,108 @@
+//! Failure categories and exit codes for ChatDelta CLI
+//!
+//! Errors that scripts may want to tell apart are raised as a `CliError`; anything else
+//! is `Other` and exits with 1.
+
+use std::error::Error;
+use std::fmt;
+
+/// Exit codes, shown after `--help`
+pub const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  other error
+  2  invalid arguments or input
+  3  no AI clients available (missing API keys, --only/--exclude)
+  4  no successful responses from any model, or fewer than --min-success
+  5  --test: some API connections failed
+  6  --max-runtime exceeded
+  7  --fail-if-disagree or --compare-to-file check failed";
+
+#[derive(Debug)]
+pub enum CliError {
+    /// Invalid arguments or unusable input
+    Validation(String),
+    /// No client could be created
+    NoClients(String),
+    /// Every model failed
+    NoResponses(String),
+    /// `--test` found failing connections
+    ConnectionsFailed(String),
+    /// `--max-runtime` was reached
+    Timeout(String),
+    /// A consensus or regression check failed
+    CheckFailed(String),
+    Other(Box<dyn Error>),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Other(_) => 1,
+            CliError::Validation(_) => 2,
+            CliError::NoClients(_) => 3,
+            CliError::NoResponses(_) => 4,
+            CliError::ConnectionsFailed(_) => 5,
+            CliError::Timeout(_) => 6,
+            CliError::CheckFailed(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Validation(message)
+            | CliError::NoClients(message)
+            | CliError::NoResponses(message)
+            | CliError::ConnectionsFailed(message)
+            | CliError::Timeout(message)
+            | CliError::CheckFailed(message) => f.write_str(message),
+            CliError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Recover the category of a boxed error; errors raised without one are `Other`
+impl From<Box<dyn Error>> for CliError {
+    fn from(e: Box<dyn Error>) -> Self {
+        match e.downcast::<CliError>() {
+            Ok(e) => *e,
+            Err(e) => CliError::Other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_category_has_its_documented_code() {
+        let cases = [
+            (CliError::Other("boom".into()), 1),
+            (CliError::Validation(String::new()), 2),
+            (CliError::NoClients(String::new()), 3),
+            (CliError::NoResponses(String::new()), 4),
+            (CliError::ConnectionsFailed(String::new()), 5),
+            (CliError::Timeout(String::new()), 6),
+            (CliError::CheckFailed(String::new()), 7),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.exit_code(), code, "{:?}", error);
+            assert!(EXIT_CODES_HELP.contains(&format!("  {}  ", code)));
+        }
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_category() {
+        let boxed: Box<dyn Error> = CliError::NoResponses("none".to_string()).into();
+        let recovered = CliError::from(boxed);
+        assert_eq!(recovered.exit_code(), 4);
+        assert_eq!(recovered.to_string(), "none");
+
+        let plain: Box<dyn Error> = "Failed to read prompt file".into();
+        assert_eq!(CliError::from(plain).exit_code(), 1);
+    }
+}
@@ -0,0 +1,61 @@
+//! Response extraction for ChatDelta CLI (`--json-path-extract`)
+
+use crate::json_util;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+/// Check that a JSONPath expression is well formed
+pub fn validate_path(path: &str) -> Result<(), String> {
+    JsonPath::parse(path)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid JSONPath '{}': {}", path, e))
+}
+
+/// Find the JSON in `response` (see `json_util::extract_json`) and return the value
+/// at `path`. Strings come back unquoted; other values and multiple matches as JSON.
+pub fn extract_json_path(response: &str, path: &str) -> Result<String, String> {
+    let json_path =
+        JsonPath::parse(path).map_err(|e| format!("Invalid JSONPath '{}': {}", path, e))?;
+    let document = json_util::parse_json(response)?;
+
+    let matches = json_path.query(&document).all();
+    match matches.as_slice() {
+        [] => Err(format!("no value at {}", path)),
+        [Value::String(s)] => Ok(s.clone()),
+        [value] => Ok(value.to_string()),
+        values => Ok(Value::Array(values.iter().map(|v| (*v).clone()).collect()).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_field_from_valid_json() {
+        let response = r#"{"answer": "42", "confidence": 0.9, "tags": ["a", "b"]}"#;
+        assert_eq!(extract_json_path(response, "$.answer").unwrap(), "42");
+        assert_eq!(extract_json_path(response, "$.confidence").unwrap(), "0.9");
+        assert_eq!(
+            extract_json_path(response, "$.tags[*]").unwrap(),
+            r#"["a","b"]"#
+        );
+    }
+
+    #[test]
+    fn extracts_from_fenced_json() {
+        let response = "```json\n{\"answer\": \"Paris\"}\n```";
+        assert_eq!(extract_json_path(response, "$.answer").unwrap(), "Paris");
+        let response = "Here you go:\n```json\n{\"answer\": \"Rome\"}\n```";
+        assert_eq!(extract_json_path(response, "$.answer").unwrap(), "Rome");
+    }
+
+    #[test]
+    fn invalid_json_or_missing_field_is_an_error() {
+        let err = extract_json_path("The answer is 42.", "$.answer").unwrap_err();
+        assert!(err.contains("not valid JSON"), "{}", err);
+        assert!(extract_json_path(r#"{"other": 1}"#, "$.answer").is_err());
+        assert!(validate_path("$.answer").is_ok());
+        assert!(validate_path("answer[").is_err());
+    }
+}
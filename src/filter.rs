@@ -0,0 +1,79 @@
+//! Response filtering for ChatDelta CLI (`--response-filter`)
+//!
+//! Each pattern is matched line by line: matching spans are cut out, and a line left
+//! empty by the cut is dropped entirely, so `^Disclaimer:.*` removes a disclaimer line
+//! while `\(as an AI\)` only trims a phrase. Lines no pattern matches are kept as they are.
+
+use regex::Regex;
+
+/// Compiled --response-filter patterns
+#[derive(Debug)]
+pub struct ResponseFilter {
+    patterns: Vec<Regex>,
+}
+
+impl ResponseFilter {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| format!("Invalid --response-filter '{}': {}", pattern, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ResponseFilter { patterns })
+    }
+
+    /// `text` with matching spans removed and emptied lines dropped
+    pub fn apply(&self, text: &str) -> String {
+        let mut kept = Vec::new();
+        for line in text.lines() {
+            if !self.patterns.iter().any(|pattern| pattern.is_match(line)) {
+                kept.push(line.to_string());
+                continue;
+            }
+            let cut = self
+                .patterns
+                .iter()
+                .fold(line.to_string(), |line, pattern| {
+                    pattern.replace_all(&line, "").into_owned()
+                });
+            if !cut.trim().is_empty() {
+                kept.push(cut);
+            }
+        }
+        let mut filtered = kept.join("\n");
+        if text.ends_with('\n') && !filtered.is_empty() {
+            filtered.push('\n');
+        }
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_lines_are_removed_and_the_rest_kept() {
+        let filter = ResponseFilter::new(&[
+            r"^Disclaimer:.*".to_string(),
+            r"\s*\(as an AI\)".to_string(),
+        ])
+        .unwrap();
+        let response = "Rust is a systems language (as an AI).\nDisclaimer: not legal advice.\n\n  - fast\n  - safe\n";
+        assert_eq!(
+            filter.apply(response),
+            "Rust is a systems language.\n\n  - fast\n  - safe\n"
+        );
+
+        let untouched = "No boilerplate here.\n\nJust an answer.";
+        assert_eq!(filter.apply(untouched), untouched);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let err = ResponseFilter::new(&["(unclosed".to_string()]).unwrap_err();
+        assert!(err.contains("--response-filter '(unclosed'"), "{}", err);
+    }
+}
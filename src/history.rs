@@ -0,0 +1,180 @@
+//! Prompt history and line editing for conversation mode
+//!
+//! Each entry typed in conversation mode is appended to `~/.chatdelta/history` and loaded
+//! again at startup; only the newest `MAX_ENTRIES` are kept. With the `readline` feature (on by default) input gets line editing
+//! and up/down history; without it, lines are read from stdin as before.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Entries kept in the history file; older ones are dropped as new ones are added
+pub const MAX_ENTRIES: usize = 1_000;
+
+/// Default history file: ~/.chatdelta/history
+pub fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".chatdelta").join("history"))
+}
+
+/// One history line per entry; backslashes and newlines are escaped so multi-line
+/// entries stay on a single line
+fn encode(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn decode(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => entry.push('\n'),
+                Some(other) => entry.push(other),
+                None => entry.push('\\'),
+            }
+        } else {
+            entry.push(c);
+        }
+    }
+    entry
+}
+
+/// Entries from the history file, oldest first (none if the file doesn't exist yet)
+pub fn load(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter(|l| !l.is_empty())
+                .map(decode)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Append one entry to the history file, creating it and its directory if needed
+pub fn append(path: &Path, entry: &str) -> io::Result<()> {
+    append_capped(path, entry, MAX_ENTRIES)
+}
+
+/// Append an entry, then rewrite the file with only the newest `max_entries` when it
+/// holds more
+fn append_capped(path: &Path, entry: &str, max_entries: usize) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", encode(entry))?;
+
+    let content = fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+    if lines.len() > max_entries {
+        let kept = &lines[lines.len() - max_entries..];
+        fs::write(
+            path,
+            kept.iter()
+                .map(|line| format!("{}\n", line))
+                .collect::<String>(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Reads conversation input, with line editing when the `readline` feature is enabled
+pub struct LineReader {
+    #[cfg(feature = "readline")]
+    editor: Option<rustyline::DefaultEditor>,
+}
+
+impl LineReader {
+    /// Create a reader whose in-memory history starts with `entries`
+    pub fn new(entries: &[String]) -> Self {
+        #[cfg(feature = "readline")]
+        {
+            // Fall back to plain stdin if the terminal can't be set up
+            let editor = rustyline::DefaultEditor::new().ok().map(|mut editor| {
+                for entry in entries {
+                    let _ = editor.add_history_entry(entry.as_str());
+                }
+                editor
+            });
+            LineReader { editor }
+        }
+        #[cfg(not(feature = "readline"))]
+        {
+            let _ = entries;
+            LineReader {}
+        }
+    }
+
+    /// Read one line; None at end of input (Ctrl-D)
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        #[cfg(feature = "readline")]
+        if let Some(editor) = self.editor.as_mut() {
+            use rustyline::error::ReadlineError;
+            return match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                    }
+                    Ok(Some(line))
+                }
+                // Ctrl-C clears the current line rather than ending the session
+                Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+                Err(ReadlineError::Eof) => Ok(None),
+                Err(ReadlineError::Io(e)) => Err(e),
+                Err(e) => Err(io::Error::other(e)),
+            };
+        }
+
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_through_history_file() {
+        let path = std::env::temp_dir()
+            .join(format!("chatdelta-history-{}", uuid::Uuid::new_v4()))
+            .join("history");
+
+        assert!(load(&path).is_empty());
+        append(&path, "What is a monad?").unwrap();
+        append(&path, "save notes.md").unwrap();
+        append(&path, "line one\nline two with a \\ backslash").unwrap();
+
+        assert_eq!(
+            load(&path),
+            vec![
+                "What is a monad?".to_string(),
+                "save notes.md".to_string(),
+                "line one\nline two with a \\ backslash".to_string(),
+            ]
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn history_keeps_only_the_newest_entries() {
+        let path = std::env::temp_dir()
+            .join(format!("chatdelta-history-{}", uuid::Uuid::new_v4()))
+            .join("history");
+
+        for i in 0..5 {
+            append_capped(&path, &format!("entry {}", i), 3).unwrap();
+        }
+        assert_eq!(load(&path), vec!["entry 2", "entry 3", "entry 4"]);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,120 @@
+//! JSON recovery from model responses for ChatDelta CLI
+//!
+//! Models wrap JSON in different ways: a Markdown code fence, a sentence of prose before
+//! or after it, or nothing at all. Every feature that reads JSON out of a response
+//! (`--json-path-extract`, `--tool-schema` and `--summary-format json`) goes through
+//! `extract_json` so they all accept the same shapes.
+
+use serde_json::Value;
+
+/// Remove a Markdown code fence wrapped around the whole response
+pub fn strip_code_fence(response: &str) -> &str {
+    let trimmed = response.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let body = rest.split_once('\n').map_or("", |(_, body)| body);
+            body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+        }
+        None => trimmed,
+    }
+}
+
+/// Byte index just past the object or array opening at `start`, honoring strings and
+/// escapes, or None when it never closes
+fn balanced_end(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(start + offset + c.len_utf8());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The JSON in a response: the whole response when it is JSON (a surrounding code fence is
+/// allowed), otherwise the first balanced object or array in it that parses
+pub fn extract_json(response: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(strip_code_fence(response)) {
+        return Some(value);
+    }
+    response
+        .char_indices()
+        .filter(|(_, c)| *c == '{' || *c == '[')
+        .find_map(|(start, _)| {
+            let end = balanced_end(response, start)?;
+            serde_json::from_str(&response[start..end]).ok()
+        })
+}
+
+/// `extract_json`, with the parse error of the whole response when no JSON is found
+pub fn parse_json(response: &str) -> Result<Value, String> {
+    extract_json(response).ok_or_else(|| {
+        match serde_json::from_str::<Value>(strip_code_fence(response)) {
+            Err(e) => format!("response is not valid JSON: {}", e),
+            Ok(_) => "response is not valid JSON".to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn extracts_bare_fenced_and_prose_wrapped_json() {
+        assert_eq!(extract_json(r#" {"a": 1} "#), Some(json!({"a": 1})));
+        assert_eq!(extract_json("[1, 2]"), Some(json!([1, 2])));
+        assert_eq!(
+            extract_json("```json\n{\"a\": 1}\n```"),
+            Some(json!({"a": 1}))
+        );
+        assert_eq!(
+            extract_json("Sure! Here is the result:\n```json\n{\"a\": {\"b\": [1, {\"c\": 2}]}}\n```\nLet me know."),
+            Some(json!({"a": {"b": [1, {"c": 2}]}}))
+        );
+        assert_eq!(
+            extract_json("The answer is {\"city\": \"Kyoto\"}. Hope that helps."),
+            Some(json!({"city": "Kyoto"}))
+        );
+    }
+
+    #[test]
+    fn strings_with_braces_and_escaped_quotes_do_not_end_the_value() {
+        let response = r#"Result: {"quote": "she said \"}{\" twice", "path": "C:\\dir\\"} done"#;
+        assert_eq!(
+            extract_json(response),
+            Some(json!({"quote": "she said \"}{\" twice", "path": "C:\\dir\\"}))
+        );
+    }
+
+    #[test]
+    fn skips_prose_brackets_that_are_not_json() {
+        assert_eq!(
+            extract_json("Fill in {name} [optional], then: [\"x\", \"y\"]"),
+            Some(json!(["x", "y"]))
+        );
+        assert_eq!(extract_json("No JSON here {at all"), None);
+        let err = parse_json("The answer is 42.").unwrap_err();
+        assert!(err.starts_with("response is not valid JSON: "), "{}", err);
+    }
+}
@@ -0,0 +1,136 @@
+//! API key rotation for ChatDelta CLI
+//!
+//! `OPENAI_API_KEY` / `ANTHROPIC_API_KEY` (or `--openai-keys` / `--anthropic-keys`) may hold a
+//! comma-separated list of keys; each client creation takes the next key in round-robin order.
+
+use crate::cli::Args;
+use crate::providers::Provider;
+use std::env;
+
+/// Round-robin rotation over one or more API keys for a single provider
+#[derive(Debug, Clone, Default)]
+pub struct KeyRotation {
+    keys: Vec<String>,
+    next: usize,
+}
+
+impl KeyRotation {
+    /// Build a rotation from individual keys, ignoring blank entries
+    pub fn new<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        KeyRotation {
+            keys: keys
+                .into_iter()
+                .map(|k| k.as_ref().trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect(),
+            next: 0,
+        }
+    }
+
+    /// Build a rotation from a comma-separated list (a single key is the trivial case)
+    pub fn from_list(value: &str) -> Self {
+        Self::new(value.split(','))
+    }
+
+    /// This rotation, starting `offset` keys in (wrapping around)
+    pub fn starting_at(mut self, offset: usize) -> Self {
+        self.next = offset;
+        self
+    }
+
+    /// Return the next key and its index, advancing the rotation
+    pub fn next_key(&mut self) -> Option<(usize, String)> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let index = self.next % self.keys.len();
+        self.next = index + 1;
+        Some((index, self.keys[index].clone()))
+    }
+}
+
+/// OpenAI keys from --openai-keys, falling back to OPENAI_API_KEY or CHATGPT_API_KEY
+pub fn openai_keys(args: &Args) -> KeyRotation {
+    if !args.openai_keys.is_empty() {
+        return KeyRotation::new(&args.openai_keys).starting_at(args.key_offset);
+    }
+    env_keys(Provider::Gpt).starting_at(args.key_offset)
+}
+
+/// Anthropic keys from --anthropic-keys, falling back to ANTHROPIC_API_KEY or CLAUDE_API_KEY
+pub fn anthropic_keys(args: &Args) -> KeyRotation {
+    if !args.anthropic_keys.is_empty() {
+        return KeyRotation::new(&args.anthropic_keys).starting_at(args.key_offset);
+    }
+    env_keys(Provider::Claude).starting_at(args.key_offset)
+}
+
+/// Keys from the first of a provider's environment variables that is set
+fn env_keys(provider: Provider) -> KeyRotation {
+    provider
+        .env_vars()
+        .iter()
+        .find_map(|name| env::var(name).ok())
+        .map(|v| KeyRotation::from_list(&v))
+        .unwrap_or_default()
+}
+
+/// Where an AI's (gpt, gemini, claude) key is configured — the flag or environment
+/// variable — and the first key found there. `var` looks up environment variables.
+pub fn key_source(
+    args: &Args,
+    ai_name: &str,
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<(String, String)> {
+    let provider = Provider::for_ai(ai_name);
+    let (flag, flag_keys): (&str, &[String]) = match provider {
+        Provider::Gpt => ("--openai-keys", &args.openai_keys),
+        Provider::Gemini => ("", &[]),
+        Provider::Claude => ("--anthropic-keys", &args.anthropic_keys),
+    };
+    if let Some((_, key)) = KeyRotation::new(flag_keys).next_key() {
+        return Some((flag.to_string(), key));
+    }
+    provider.env_vars().iter().find_map(|name| {
+        let (_, key) = KeyRotation::from_list(&var(name)?).next_key()?;
+        Some((format!("env {}", name), key))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_in_order_and_wraps() {
+        let mut rotation = KeyRotation::from_list("k1, k2,k3");
+
+        let served: Vec<usize> = (0..7).map(|_| rotation.next_key().unwrap().0).collect();
+        assert_eq!(served, vec![0, 1, 2, 0, 1, 2, 0]);
+
+        let mut shifted = KeyRotation::from_list("k1,k2,k3").starting_at(4);
+        assert_eq!(shifted.next_key().unwrap().1, "k2");
+    }
+
+    #[test]
+    fn single_key_is_always_served() {
+        let mut rotation = KeyRotation::from_list("only-key");
+        for _ in 0..3 {
+            assert_eq!(rotation.next_key(), Some((0, "only-key".to_string())));
+        }
+    }
+
+    #[test]
+    fn blank_entries_are_ignored() {
+        let mut rotation = KeyRotation::from_list(" ,k1,,");
+        assert_eq!(rotation.next_key().unwrap().1, "k1");
+        assert_eq!(rotation.next_key().unwrap().0, 0);
+
+        let mut empty = KeyRotation::from_list("");
+        assert!(empty.next_key().is_none());
+    }
+}
@@ -1,12 +1,14 @@
 //! Comprehensive logging functionality for ChatDelta CLI
 
 use crate::cli::Args;
-use chrono::{DateTime, Utc};
+use crate::cost;
+use crate::query::FallbackAttempt;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
@@ -18,6 +20,9 @@ pub struct LogEntry {
     pub prompt: String,
     pub responses: HashMap<String, ModelResponse>,
     pub summary: Option<String>,
+    /// Each model's summary when --summary-models is used
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub summaries: BTreeMap<String, String>,
     pub metrics: Option<PerformanceMetrics>,
     pub errors: Vec<ErrorEntry>,
 }
@@ -30,6 +35,24 @@ pub struct ModelResponse {
     pub tokens_used: Option<u32>,
     pub success: bool,
     pub error: Option<String>,
+    /// Index of the rotated API key that served this request (never the key itself)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_index: Option<usize>,
+    /// Why the model stopped generating (stop, length, content_filter, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    /// SHA-256 of the response text (hex), recorded with --with-hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_hash: Option<String>,
+    /// The response before --response-filter changed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unfiltered: Option<String>,
+    /// The failed primary attempt, when this response came from the fallback model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<FallbackAttempt>,
+    /// Whether this model answered first with --race
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub race_winner: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,26 +72,73 @@ pub struct ErrorEntry {
     pub error_type: String,
     pub message: String,
     pub retry_attempt: Option<u32>,
+    /// Every model that hit this error, when --coalesce-errors merged several entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
 }
 
+/// Merge errors with the same type and message into one entry listing the affected
+/// models (joined in `model`, in full in `models`), keeping first-seen order
+pub fn coalesce_errors(errors: &[ErrorEntry]) -> Vec<ErrorEntry> {
+    let mut groups: Vec<Vec<&ErrorEntry>> = Vec::new();
+    for error in errors {
+        match groups.iter_mut().find(|group| {
+            group[0].error_type == error.error_type && group[0].message == error.message
+        }) {
+            Some(group) => group.push(error),
+            None => groups.push(vec![error]),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|group| {
+            if group.len() == 1 {
+                return group[0].clone();
+            }
+            let models: Vec<String> = group.iter().map(|error| error.model.clone()).collect();
+            ErrorEntry {
+                timestamp: group
+                    .iter()
+                    .map(|error| error.timestamp)
+                    .min()
+                    .unwrap_or(group[0].timestamp),
+                model: models.join(", "),
+                error_type: group[0].error_type.clone(),
+                message: group[0].message.clone(),
+                retry_attempt: group.iter().filter_map(|error| error.retry_attempt).max(),
+                models,
+            }
+        })
+        .collect()
+}
+
+/// Structured log directory: --log-dir, or ~/.chatdelta/logs by default
+pub fn log_dir(args: &Args) -> PathBuf {
+    args.log_dir.clone().unwrap_or_else(|| {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".chatdelta")
+            .join("logs")
+    })
+}
+
+/// Extension of provisional records for interactions still in progress (or interrupted)
+const PARTIAL_EXTENSION: &str = "partial";
+
 pub struct Logger {
     log_dir: PathBuf,
     session_id: String,
     log_format: String,
     enable_metrics: bool,
     enable_errors: bool,
+    coalesce_errors: bool,
     current_entry: Option<LogEntry>,
     start_time: Option<Instant>,
 }
 
 impl Logger {
     pub fn new(args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
-        let log_dir = args.log_dir.clone().unwrap_or_else(|| {
-            dirs::home_dir()
-                .unwrap_or_else(|| PathBuf::from("."))
-                .join(".chatdelta")
-                .join("logs")
-        });
+        let log_dir = log_dir(args);
 
         // Create log directory if it doesn't exist
         fs::create_dir_all(&log_dir)?;
@@ -84,13 +154,17 @@ impl Logger {
             log_format: args.log_format.clone(),
             enable_metrics: args.log_metrics,
             enable_errors: args.log_errors,
+            coalesce_errors: args.coalesce_errors,
             current_entry: None,
             start_time: None,
         })
     }
 
-    pub fn start_interaction(&mut self, prompt: &str) {
-        let interaction_id = Uuid::new_v4().to_string();
+    /// Begin a log entry; `interaction_id` (--interaction-id) is used as given, without
+    /// checking that it is unique
+    pub fn start_interaction(&mut self, prompt: &str, interaction_id: Option<&str>) {
+        let interaction_id =
+            interaction_id.map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
         self.start_time = Some(Instant::now());
 
         self.current_entry = Some(LogEntry {
@@ -100,6 +174,7 @@ impl Logger {
             prompt: prompt.to_string(),
             responses: HashMap::new(),
             summary: None,
+            summaries: BTreeMap::new(),
             metrics: None,
             errors: Vec::new(),
         });
@@ -111,6 +186,7 @@ impl Logger {
         response: Result<&str, &str>,
         response_time: Duration,
         tokens_used: Option<u32>,
+        finish_reason: Option<&str>,
     ) {
         if let Some(entry) = &mut self.current_entry {
             let model_response = ModelResponse {
@@ -123,12 +199,119 @@ impl Logger {
                 tokens_used,
                 success: response.is_ok(),
                 error: response.err().map(|e| e.to_string()),
+                key_index: None,
+                finish_reason: finish_reason.map(str::to_string),
+                response_hash: None,
+                unfiltered: None,
+                fallback: None,
+                race_winner: false,
             };
 
             entry
                 .responses
                 .insert(model_name.to_string(), model_response);
         }
+        if let Err(error) = self.flush_partial() {
+            tracing::warn!(%error, "failed to write the partial log record");
+        }
+    }
+
+    /// Where the in-progress interaction's provisional record is kept
+    fn partial_path(&self, entry: &LogEntry) -> PathBuf {
+        let id: String = entry
+            .interaction_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.log_dir.join(format!("{}.{}", id, PARTIAL_EXTENSION))
+    }
+
+    /// Overwrite the interaction's provisional record with everything logged so far. It is
+    /// JSON whatever the --log-format, and `finalize_interaction` removes it, so a record
+    /// left behind belongs to a run that never finished.
+    pub fn flush_partial(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(entry) = &self.current_entry else {
+            return Ok(());
+        };
+        let path = self.partial_path(entry);
+        // Written aside and renamed, so a crash mid-write keeps the previous record
+        let staging = path.with_extension(format!("{}.tmp", PARTIAL_EXTENSION));
+        fs::write(&staging, serde_json::to_string_pretty(entry)?)?;
+        fs::rename(staging, path)?;
+        Ok(())
+    }
+
+    /// Record which rotated API key index served a model's request
+    pub fn set_key_index(&mut self, model_name: &str, index: usize) {
+        if let Some(entry) = &mut self.current_entry {
+            if let Some(response) = entry.responses.get_mut(model_name) {
+                response.key_index = Some(index);
+            }
+        }
+    }
+
+    /// Record the SHA-256 hash of a model's response
+    pub fn set_response_hash(&mut self, model_name: &str, hash: &str) {
+        if let Some(entry) = &mut self.current_entry {
+            if let Some(response) = entry.responses.get_mut(model_name) {
+                response.response_hash = Some(hash.to_string());
+            }
+        }
+    }
+
+    /// Replace the outcome and finish reason of a response logged as it arrived, keeping
+    /// its response time and tokens. Returns false when the model hasn't been logged yet.
+    pub fn set_response(
+        &mut self,
+        model_name: &str,
+        response: Result<&str, &str>,
+        finish_reason: Option<&str>,
+    ) -> bool {
+        let Some(logged) = self
+            .current_entry
+            .as_mut()
+            .and_then(|entry| entry.responses.get_mut(model_name))
+        else {
+            return false;
+        };
+        logged.response = response.unwrap_or_default().to_string();
+        logged.success = response.is_ok();
+        logged.error = response.err().map(str::to_string);
+        logged.finish_reason = finish_reason.map(str::to_string);
+        true
+    }
+
+    /// Record a model's response as it was before --response-filter
+    pub fn set_unfiltered(&mut self, model_name: &str, original: &str) {
+        if let Some(entry) = &mut self.current_entry {
+            if let Some(response) = entry.responses.get_mut(model_name) {
+                response.unfiltered = Some(original.to_string());
+            }
+        }
+    }
+
+    /// Mark the model that answered first with --race
+    pub fn set_race_winner(&mut self, model_name: &str) {
+        if let Some(entry) = &mut self.current_entry {
+            if let Some(response) = entry.responses.get_mut(model_name) {
+                response.race_winner = true;
+            }
+        }
+    }
+
+    /// Record that a model's primary request failed and its fallback model was tried
+    pub fn set_fallback(&mut self, attempt: &FallbackAttempt) {
+        if let Some(entry) = &mut self.current_entry {
+            if let Some(response) = entry.responses.get_mut(&attempt.name) {
+                response.fallback = Some(attempt.clone());
+            }
+        }
     }
 
     pub fn log_error(
@@ -146,6 +329,7 @@ impl Logger {
                     error_type: error_type.to_string(),
                     message: message.to_string(),
                     retry_attempt,
+                    models: Vec::new(),
                 });
             }
         }
@@ -157,11 +341,21 @@ impl Logger {
         }
     }
 
+    /// Record the summary produced by one of the --summary-models
+    pub fn add_model_summary(&mut self, model_name: &str, summary: &str) {
+        if let Some(entry) = &mut self.current_entry {
+            entry
+                .summaries
+                .insert(model_name.to_string(), summary.to_string());
+        }
+    }
+
     pub fn finalize_interaction(
         &mut self,
         summary_time: Option<Duration>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(mut entry) = self.current_entry.take() {
+            let partial = self.partial_path(&entry);
             if self.enable_metrics {
                 let total_time = self.start_time.map(|t| t.elapsed()).unwrap_or_default();
                 let successful = entry.responses.values().filter(|r| r.success).count() as u32;
@@ -183,11 +377,25 @@ impl Logger {
             }
 
             self.write_log_entry(&entry)?;
+            if partial.exists() {
+                fs::remove_file(partial)?;
+            }
         }
         Ok(())
     }
 
     fn write_log_entry(&self, entry: &LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+        // Grouping happens only in what is written; the entry itself keeps every error
+        let coalesced;
+        let entry = if self.coalesce_errors && entry.errors.len() > 1 {
+            coalesced = LogEntry {
+                errors: coalesce_errors(&entry.errors),
+                ..entry.clone()
+            };
+            &coalesced
+        } else {
+            entry
+        };
         let filename = match self.log_format.as_str() {
             "json" => format!("{}.json", entry.timestamp.format("%Y%m%d")),
             "structured" => format!("{}.log", entry.timestamp.format("%Y%m%d")),
@@ -218,6 +426,22 @@ impl Logger {
                     if let Some(tokens) = response.tokens_used {
                         writeln!(file, "Tokens: {}", tokens)?;
                     }
+                    if let Some(index) = response.key_index {
+                        writeln!(file, "Key Index: {}", index)?;
+                    }
+                    if let Some(reason) = &response.finish_reason {
+                        writeln!(file, "Finish Reason: {}", reason)?;
+                    }
+                    if let Some(hash) = &response.response_hash {
+                        writeln!(file, "Response Hash: {}", hash)?;
+                    }
+                    if let Some(fallback) = &response.fallback {
+                        writeln!(
+                            file,
+                            "Fallback: {} failed ({}); answered by {}",
+                            fallback.primary_model, fallback.primary_error, fallback.fallback_model
+                        )?;
+                    }
                     if response.success {
                         writeln!(file, "Response: {}", response.response)?;
                     } else if let Some(error) = &response.error {
@@ -226,8 +450,15 @@ impl Logger {
                     writeln!(file)?;
                 }
 
-                if let Some(summary) = &entry.summary {
-                    writeln!(file, "--- SUMMARY ---")?;
+                if entry.summaries.is_empty() {
+                    if let Some(summary) = &entry.summary {
+                        writeln!(file, "--- SUMMARY ---")?;
+                        writeln!(file, "{}", summary)?;
+                        writeln!(file)?;
+                    }
+                }
+                for (model, summary) in &entry.summaries {
+                    writeln!(file, "--- SUMMARY ({}) ---", model)?;
                     writeln!(file, "{}", summary)?;
                     writeln!(file)?;
                 }
@@ -318,15 +549,698 @@ pub struct LogStats {
 
 impl LogStats {
     pub fn size_human_readable(&self) -> String {
-        const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
-        let mut size = self.total_size_bytes as f64;
-        let mut unit_index = 0;
+        format_size(self.total_size_bytes)
+    }
+}
+
+/// A byte count in B, KB, MB or GB with two decimals
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
+
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    format!("{:.2} {}", size, UNITS[unit_index])
+}
+
+/// Per-model totals in a --report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelReport {
+    pub requests: u32,
+    pub successes: u32,
+    pub success_rate: f64,
+    /// Mean response time of successful requests
+    pub average_latency_ms: Option<f64>,
+    pub tokens: u64,
+}
+
+/// Aggregate statistics across logged interactions (--report)
+#[derive(Debug, Default, Serialize)]
+pub struct LogReport {
+    pub interactions: usize,
+    pub models: BTreeMap<String, ModelReport>,
+    /// Priced at today's list prices for the configured models; unpriced models add nothing
+    pub estimated_cost_usd: f64,
+    /// Model names by number of requests, most first
+    pub most_used: Vec<String>,
+}
+
+impl LogReport {
+    /// Plain-text report for the terminal
+    pub fn render(&self) -> String {
+        let mut out = format!("Interactions: {}\n\n", self.interactions);
+        out.push_str(&format!(
+            "{:<20} {:>8} {:>9} {:>12} {:>10}\n",
+            "Model", "Requests", "Success", "Avg latency", "Tokens"
+        ));
+        out.push_str(&format!("{}\n", "\u{2500}".repeat(63)));
+        for name in &self.most_used {
+            let m = &self.models[name];
+            let latency = m
+                .average_latency_ms
+                .map_or_else(|| "-".to_string(), |ms| format!("{:.0}ms", ms));
+            out.push_str(&format!(
+                "{:<20} {:>8} {:>8.1}% {:>12} {:>10}\n",
+                name,
+                m.requests,
+                m.success_rate * 100.0,
+                latency,
+                m.tokens
+            ));
+        }
+        out.push_str(&format!(
+            "\nEstimated cost: ${:.4}\n",
+            self.estimated_cost_usd
+        ));
+        out
+    }
+}
+
+/// Parse --since: an RFC 3339 timestamp or a YYYY-MM-DD date (midnight UTC)
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|_| {
+            format!(
+                "Invalid --since '{}': use YYYY-MM-DD or an RFC 3339 timestamp",
+                value
+            )
+        })
+}
+
+/// Read every entry from the JSON logs in `dir`, oldest file first, keeping those at or
+/// after `since`. Provisional records left by interrupted runs are included, after the
+/// logs. A missing directory has no entries; other log formats are not read.
+pub fn read_log_entries(
+    dir: &Path,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<LogEntry>, Box<dyn std::error::Error>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "json" || ext == PARTIAL_EXTENSION)
+        })
+        .collect();
+    // Logs first, then partial records, each group by name
+    files.sort_by_key(|path| {
+        (
+            path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION),
+            path.clone(),
+        )
+    });
+
+    let mut entries = Vec::new();
+    for path in files {
+        let content = fs::read_to_string(&path)?;
+        // Each file holds a run of pretty-printed entries, one appended per interaction
+        // (a partial record holds just one)
+        for entry in serde_json::Deserializer::from_str(&content).into_iter::<LogEntry>() {
+            let entry = entry
+                .map_err(|e| format!("Failed to parse log file '{}': {}", path.display(), e))?;
+            if since.is_none_or(|since| entry.timestamp >= since) {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Aggregate log entries into a report. `models` maps each logged model name to the
+/// model ID used for pricing; token counts are split into the prompt's estimated size
+/// as input and the remainder as output.
+pub fn aggregate(entries: &[LogEntry], models: &HashMap<String, String>) -> LogReport {
+    let mut report = LogReport {
+        interactions: entries.len(),
+        ..Default::default()
+    };
+    let mut latency_totals: HashMap<&str, (u128, u32)> = HashMap::new();
+
+    for entry in entries {
+        let prompt_tokens = cost::estimate_tokens(&entry.prompt);
+        for (name, response) in &entry.responses {
+            let model = report.models.entry(name.clone()).or_default();
+            model.requests += 1;
+            if !response.success {
+                continue;
+            }
+            model.successes += 1;
+            let latency = latency_totals.entry(name).or_default();
+            latency.0 += response.response_time_ms;
+            latency.1 += 1;
+
+            let Some(tokens) = response.tokens_used else {
+                continue;
+            };
+            model.tokens += u64::from(tokens);
+            let pricing = models
+                .get(name)
+                .and_then(|id| cost::price_for(cost::PRICING, id));
+            if let Some(p) = pricing {
+                let input = prompt_tokens.min(tokens);
+                report.estimated_cost_usd += (f64::from(input) * p.input_per_mtok
+                    + f64::from(tokens - input) * p.output_per_mtok)
+                    / 1_000_000.0;
+            }
+        }
+    }
+
+    for (name, model) in report.models.iter_mut() {
+        model.success_rate = f64::from(model.successes) / f64::from(model.requests);
+        model.average_latency_ms = latency_totals
+            .get(name.as_str())
+            .map(|(total, count)| *total as f64 / f64::from(*count));
+    }
+
+    let mut most_used: Vec<&String> = report.models.keys().collect();
+    most_used.sort_by(|a, b| {
+        report.models[*b]
+            .requests
+            .cmp(&report.models[*a].requests)
+            .then(a.cmp(b))
+    });
+    report.most_used = most_used.into_iter().cloned().collect();
+    report
+}
+
+/// One session found in the logs (--list-sessions)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub interactions: usize,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub models: BTreeSet<String>,
+}
+
+/// The parts of a logged interaction a session listing needs, from any log format
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub models: Vec<String>,
+}
+
+impl From<&LogEntry> for SessionRecord {
+    fn from(entry: &LogEntry) -> Self {
+        SessionRecord {
+            session_id: entry.session_id.clone(),
+            timestamp: entry.timestamp,
+            models: entry.responses.keys().cloned().collect(),
+        }
+    }
+}
+
+/// Read an interaction record from every log in `dir` in any format (json, structured,
+/// simple). Unparseable parts of a file are skipped; a missing directory has no records.
+pub fn read_session_records(dir: &Path) -> Result<Vec<SessionRecord>, Box<dyn std::error::Error>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut records = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        if !path.is_file() || !matches!(ext, "json" | "log" | "txt") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        match ext {
+            "json" => records.extend(
+                serde_json::Deserializer::from_str(&content)
+                    .into_iter::<LogEntry>()
+                    .map_while(Result::ok)
+                    .map(|entry| SessionRecord::from(&entry)),
+            ),
+            "log" => records.extend(parse_structured_log(&content)),
+            _ => records.extend(parse_simple_log(&content)),
+        }
+    }
+    Ok(records)
+}
+
+/// Timestamps as written by `Display` for `DateTime<Utc>` in text logs
+fn parse_log_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let naive = value.trim().strip_suffix(" UTC")?;
+    NaiveDateTime::parse_from_str(naive, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Records from a `--log-format structured` file
+fn parse_structured_log(content: &str) -> Vec<SessionRecord> {
+    content
+        .split("=== INTERACTION ")
+        .skip(1)
+        .filter_map(|block| {
+            let mut timestamp = None;
+            let mut session_id = None;
+            let mut models = Vec::new();
+            for line in block.lines() {
+                if let Some(value) = line.strip_prefix("Timestamp: ") {
+                    timestamp = parse_log_timestamp(value);
+                } else if let Some(value) = line.strip_prefix("Session: ") {
+                    session_id = Some(value.trim().to_string());
+                } else if let Some(name) = line
+                    .strip_prefix("--- ")
+                    .and_then(|l| l.strip_suffix(" ---"))
+                {
+                    if !matches!(name, "METRICS" | "ERRORS") && !name.starts_with("SUMMARY") {
+                        models.push(name.to_string());
+                    }
+                }
+            }
+            Some(SessionRecord {
+                session_id: session_id?,
+                timestamp: timestamp?,
+                models,
+            })
+        })
+        .collect()
+}
+
+/// Records from a simple-format (`.txt`) log
+fn parse_simple_log(content: &str) -> Vec<SessionRecord> {
+    let mut records: Vec<SessionRecord> = Vec::new();
+    let mut in_entry = false;
 
-        while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-            size /= 1024.0;
-            unit_index += 1;
+    for line in content.lines() {
+        // [<timestamp>] Session: <id> | Interaction: <id>
+        let header = line
+            .strip_prefix('[')
+            .and_then(|l| l.split_once("] Session: "))
+            .and_then(|(ts, rest)| Some((parse_log_timestamp(ts)?, rest.split(" | ").next()?)));
+        if let Some((timestamp, session_id)) = header {
+            records.push(SessionRecord {
+                session_id: session_id.trim().to_string(),
+                timestamp,
+                models: Vec::new(),
+            });
+            in_entry = true;
+        } else if line == "---" {
+            in_entry = false;
+        } else if let (true, Some(record)) = (in_entry, records.last_mut()) {
+            if let Some(model) = line
+                .strip_suffix(": SUCCESS")
+                .or_else(|| line.strip_suffix(": FAILED"))
+            {
+                record.models.push(model.to_string());
+            }
         }
+    }
+    records
+}
+
+/// Group records by session, most recently active first
+pub fn index_sessions(records: &[SessionRecord]) -> Vec<SessionSummary> {
+    let mut sessions: HashMap<&str, SessionSummary> = HashMap::new();
+    for record in records {
+        let session = sessions
+            .entry(&record.session_id)
+            .or_insert_with(|| SessionSummary {
+                session_id: record.session_id.clone(),
+                interactions: 0,
+                first: record.timestamp,
+                last: record.timestamp,
+                models: BTreeSet::new(),
+            });
+        session.interactions += 1;
+        session.first = session.first.min(record.timestamp);
+        session.last = session.last.max(record.timestamp);
+        session.models.extend(record.models.iter().cloned());
+    }
+
+    let mut sessions: Vec<SessionSummary> = sessions.into_values().collect();
+    sessions.sort_by(|a, b| {
+        b.last
+            .cmp(&a.last)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    sessions
+}
+
+/// Plain-text session listing for the terminal
+pub fn render_sessions(sessions: &[SessionSummary]) -> String {
+    let mut out = format!(
+        "{:<36}  {:>5}  {:<16}  {:<16}  {}\n",
+        "Session", "Count", "First", "Last", "Models"
+    );
+    for session in sessions {
+        out.push_str(&format!(
+            "{:<36}  {:>5}  {:<16}  {:<16}  {}\n",
+            session.session_id,
+            session.interactions,
+            session.first.format("%Y-%m-%d %H:%M"),
+            session.last.format("%Y-%m-%d %H:%M"),
+            session
+                .models
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn finish_reason_is_written_to_json_log() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-log-{}", Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+
+        let mut logger = Logger::new(&args).unwrap();
+        logger.start_interaction("Hi", None);
+        logger.log_model_response(
+            "ChatGPT",
+            Ok("Truncated"),
+            Duration::from_millis(5),
+            None,
+            Some("length"),
+        );
+        logger.log_model_response("Claude", Err("boom"), Duration::from_millis(5), None, None);
+        logger.finalize_interaction(None).unwrap();
+
+        let file = fs::read_dir(&log_dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let entry: LogEntry = serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        assert_eq!(
+            entry.responses["ChatGPT"].finish_reason.as_deref(),
+            Some("length")
+        );
+        assert_eq!(entry.responses["Claude"].finish_reason, None);
+
+        fs::remove_dir_all(&log_dir).ok();
+    }
+
+    #[test]
+    fn final_responses_keep_the_arrival_time() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-log-{}", Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+
+        let mut logger = Logger::new(&args).unwrap();
+        logger.start_interaction("Hi", None);
+        logger.log_model_response(
+            "ChatGPT",
+            Ok("  Raw  "),
+            Duration::from_millis(120),
+            Some(42),
+            None,
+        );
+        assert!(logger.set_response("ChatGPT", Ok("Cleaned"), Some("stop")));
+        assert!(!logger.set_response("Claude", Ok("Never arrived"), None));
+        logger.finalize_interaction(None).unwrap();
+
+        let entry = &read_log_entries(&log_dir, None).unwrap()[0];
+        let response = &entry.responses["ChatGPT"];
+        assert_eq!(response.response, "Cleaned");
+        assert_eq!(response.response_time_ms, 120);
+        assert_eq!(response.tokens_used, Some(42));
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+        assert!(!entry.responses.contains_key("Claude"));
+
+        fs::remove_dir_all(&log_dir).ok();
+    }
+
+    #[test]
+    fn responses_logged_before_a_crash_are_recoverable() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-log-{}", Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+
+        // Two of three responses are in when the process dies: the logger is never finalized
+        let mut logger = Logger::new(&args).unwrap();
+        logger.start_interaction("Hi", Some("run/1"));
+        logger.log_model_response("ChatGPT", Ok("First"), Duration::from_millis(5), None, None);
+        logger.log_model_response("Gemini", Err("boom"), Duration::from_millis(5), None, None);
+        drop(logger);
+
+        let recovered = read_log_entries(&log_dir, None).unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].interaction_id, "run/1");
+        assert_eq!(recovered[0].responses["ChatGPT"].response, "First");
+        assert!(!recovered[0].responses["Gemini"].success);
+        assert!(!recovered[0].responses.contains_key("Claude"));
+
+        // A finished interaction replaces its partial record
+        let mut logger = Logger::new(&args).unwrap();
+        logger.start_interaction("Hi again", None);
+        logger.log_model_response("Claude", Ok("Done"), Duration::from_millis(5), None, None);
+        logger.finalize_interaction(None).unwrap();
+        let entries = read_log_entries(&log_dir, None).unwrap();
+        let prompts: Vec<&str> = entries.iter().map(|e| e.prompt.as_str()).collect();
+        assert_eq!(prompts, vec!["Hi again", "Hi"]);
+
+        fs::remove_dir_all(&log_dir).ok();
+    }
+
+    #[test]
+    fn identical_errors_are_coalesced_when_written() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-log-{}", Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--log-errors",
+            "--coalesce-errors",
+            "Hi",
+        ])
+        .unwrap();
+
+        let mut logger = Logger::new(&args).unwrap();
+        logger.start_interaction("Hi", Some("trace-42"));
+        for model in ["ChatGPT", "Gemini", "Claude"] {
+            logger.log_error(model, "NETWORK", "connection refused", None);
+        }
+        logger.log_error("Claude", "RATE_LIMIT", "slow down", Some(1));
+        logger.finalize_interaction(None).unwrap();
+
+        let file = fs::read_dir(&log_dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let entry: LogEntry = serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        assert_eq!(entry.errors.len(), 2);
+        assert_eq!(entry.interaction_id, "trace-42");
+        assert_eq!(entry.errors[0].models, vec!["ChatGPT", "Gemini", "Claude"]);
+        assert_eq!(entry.errors[0].model, "ChatGPT, Gemini, Claude");
+        assert_eq!(entry.errors[0].message, "connection refused");
+        assert_eq!(entry.errors[1].model, "Claude");
+        assert!(
+            entry.errors[1].models.is_empty(),
+            "distinct errors stay as they were"
+        );
+
+        fs::remove_dir_all(&log_dir).ok();
+    }
+
+    fn fixture_entry(
+        day: u32,
+        prompt: &str,
+        responses: &[(&str, bool, u128, Option<u32>)],
+    ) -> LogEntry {
+        LogEntry {
+            timestamp: format!("2026-03-{:02}T12:00:00Z", day).parse().unwrap(),
+            session_id: "s".to_string(),
+            interaction_id: Uuid::new_v4().to_string(),
+            prompt: prompt.to_string(),
+            responses: responses
+                .iter()
+                .map(|&(name, success, ms, tokens)| {
+                    let response = ModelResponse {
+                        model_name: name.to_string(),
+                        response: if success {
+                            "ok".to_string()
+                        } else {
+                            String::new()
+                        },
+                        response_time_ms: ms,
+                        tokens_used: tokens,
+                        success,
+                        error: (!success).then(|| "timeout".to_string()),
+                        key_index: None,
+                        finish_reason: None,
+                        response_hash: None,
+                        unfiltered: None,
+                        fallback: None,
+                        race_winner: false,
+                    };
+                    (name.to_string(), response)
+                })
+                .collect(),
+            summary: None,
+            summaries: BTreeMap::new(),
+            metrics: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn fixture_entries() -> Vec<LogEntry> {
+        vec![
+            fixture_entry(
+                1,
+                "Hi",
+                &[
+                    ("ChatGPT", true, 400, Some(1_000_000)),
+                    ("Claude", true, 800, None),
+                ],
+            ),
+            fixture_entry(
+                2,
+                "Hi",
+                &[
+                    ("ChatGPT", false, 30_000, None),
+                    ("Claude", true, 600, Some(20)),
+                ],
+            ),
+            fixture_entry(3, "Hi", &[("ChatGPT", true, 200, Some(10))]),
+        ]
+    }
+
+    #[test]
+    fn aggregates_success_latency_cost_and_usage() {
+        let models = HashMap::from([
+            ("ChatGPT".to_string(), "gpt-4o".to_string()),
+            ("Claude".to_string(), "claude-sonnet-4-6".to_string()),
+        ]);
+        let report = aggregate(&fixture_entries(), &models);
+
+        assert_eq!(report.interactions, 3);
+        assert_eq!(report.most_used, vec!["ChatGPT", "Claude"]);
+
+        let gpt = &report.models["ChatGPT"];
+        assert_eq!((gpt.requests, gpt.successes, gpt.tokens), (3, 2, 1_000_010));
+        assert!((gpt.success_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(gpt.average_latency_ms, Some(300.0));
+
+        let claude = &report.models["Claude"];
+        assert_eq!(claude.success_rate, 1.0);
+        assert_eq!(claude.average_latency_ms, Some(700.0));
+
+        // "Hi" is one prompt token; the rest of each count is priced as output
+        let expected = (2.0 * 2.50 + 1_000_008.0 * 10.00 + 1.0 * 3.00 + 19.0 * 15.00) / 1_000_000.0;
+        assert!((report.estimated_cost_usd - expected).abs() < 1e-9);
+        assert!(report.render().contains("ChatGPT"));
+    }
+
+    #[test]
+    fn reads_json_logs_since_a_date() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-report-{}", Uuid::new_v4()));
+        assert!(read_log_entries(&log_dir, None).unwrap().is_empty());
+
+        fs::create_dir_all(&log_dir).unwrap();
+        let mut content = String::new();
+        for entry in fixture_entries() {
+            content.push_str(&serde_json::to_string_pretty(&entry).unwrap());
+            content.push('\n');
+        }
+        fs::write(log_dir.join("20260301.json"), content).unwrap();
+        fs::write(log_dir.join("20260301.txt"), "simple format is skipped").unwrap();
+
+        assert_eq!(read_log_entries(&log_dir, None).unwrap().len(), 3);
+        let since = parse_since("2026-03-02").unwrap();
+        assert_eq!(read_log_entries(&log_dir, Some(since)).unwrap().len(), 2);
+        assert!(parse_since("2026-03-02T00:00:00Z").is_ok());
+        assert!(parse_since("last week").is_err());
+
+        fs::remove_dir_all(&log_dir).ok();
+    }
+
+    #[test]
+    fn groups_sessions_across_log_formats() {
+        let log_dir = std::env::temp_dir().join(format!("chatdelta-sessions-{}", Uuid::new_v4()));
+        assert!(read_session_records(&log_dir).unwrap().is_empty());
+        fs::create_dir_all(&log_dir).unwrap();
+
+        // Session "alpha" in JSON; "beta" in the structured and simple text formats
+        let mut json = String::new();
+        for mut entry in fixture_entries().into_iter().take(2) {
+            entry.session_id = "alpha".to_string();
+            json.push_str(&serde_json::to_string_pretty(&entry).unwrap());
+            json.push('\n');
+        }
+        fs::write(log_dir.join("20260301.json"), json).unwrap();
+        fs::write(
+            log_dir.join("20260305.log"),
+            "=== INTERACTION i1 ===\nTimestamp: 2026-03-05 09:30:00.123 UTC\nSession: beta\n\
+             Prompt: Hi\n\n--- Gemini ---\nSuccess: true\n\n--- SUMMARY ---\nok\n\n\
+             --- METRICS ---\nTotal Time: 5ms\n\n==========\n\n",
+        )
+        .unwrap();
+        fs::write(
+            log_dir.join("20260306.txt"),
+            "[2026-03-06 10:00:00 UTC] Session: beta | Interaction: i2\nPrompt: Hi\n\
+             Claude: FAILED\nSummary: none\n---\nnot a log line\n",
+        )
+        .unwrap();
+
+        let sessions = index_sessions(&read_session_records(&log_dir).unwrap());
+        assert_eq!(sessions.len(), 2);
+
+        let beta = &sessions[0];
+        assert_eq!(beta.session_id, "beta");
+        assert_eq!(beta.interactions, 2);
+        assert_eq!(beta.first.to_rfc3339(), "2026-03-05T09:30:00.123+00:00");
+        assert_eq!(beta.last.to_rfc3339(), "2026-03-06T10:00:00+00:00");
+        assert_eq!(beta.models.iter().collect::<Vec<_>>(), ["Claude", "Gemini"]);
+
+        let alpha = &sessions[1];
+        assert_eq!(alpha.interactions, 2);
+        assert_eq!(alpha.first.to_rfc3339(), "2026-03-01T12:00:00+00:00");
+        assert_eq!(alpha.models.len(), 2);
+        assert!(render_sessions(&sessions).contains("beta"));
 
-        format!("{:.2} {}", size, UNITS[unit_index])
+        fs::remove_dir_all(&log_dir).ok();
     }
 }
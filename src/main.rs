@@ -3,52 +3,257 @@
 //! A command-line tool for querying multiple AI APIs and summarizing their responses.
 
 use chatdelta::{
-    create_client, execute_parallel, execute_parallel_with_metadata, generate_summary, AiClient,
-    ChatSession, ClientConfig, Message, RetryStrategy, StreamChunk,
+    AiClient, AiResponse, ChatSession, ClientConfig, ClientError, RetryStrategy, StreamChunk,
 };
 use clap::Parser;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
 
+mod artifacts;
+mod batch;
+mod cache;
+mod chunk;
 mod cli;
+mod compare;
+mod config;
+mod conversation;
+mod cost;
 mod debate;
+mod debug_io;
+mod diagnostics;
+mod diff;
+mod environment;
+mod error;
+mod extract;
+mod filter;
+mod history;
+mod json_util;
+mod keys;
 mod logging;
 mod metrics_display;
+mod models;
 mod output;
+mod pager;
+mod pii;
+mod prompt;
+mod providers;
+mod query;
+mod refusal;
+mod roles;
+mod self_check;
+mod stats;
+mod status;
+mod summary;
+mod tone;
+mod tools;
+mod trace;
+mod warnings;
 
 use cli::{Args, Commands, DebateArgs};
+use error::CliError;
 use logging::Logger;
-use output::{log_interaction, output_results};
+use output::{log_interaction, output_results, ResponseMeta};
+use providers::{create_client, Provider};
+
+/// Main application logic. Warnings are reported when the run ends, and with
+/// --split-output the outcome is written to --status-file whether or not it succeeded.
+async fn run(args: Args) -> Result<(), CliError> {
+    run_reporting_to(args, &mut io::stderr()).await
+}
+
+/// `run`, writing the closing --elapsed line to `stderr`
+async fn run_reporting_to(args: Args, stderr: &mut dyn Write) -> Result<(), CliError> {
+    let started = std::time::Instant::now();
+    let show_elapsed = args.elapsed && !args.quiet;
+    let status_file = args.status_file.clone().filter(|_| args.split_output);
+    let stats_file = args.stats_file.clone();
+    let mut warnings = warnings::Warnings::from_args(&args);
+    let mut status = status::RunStatus::default();
+
+    let result = execute(args, &mut warnings, &mut status)
+        .await
+        .map_err(CliError::from);
+
+    status.finish(&result, warnings.messages().len());
+    if let Some(path) = stats_file {
+        let record = stats::StatsRecord::new(&status, started.elapsed());
+        if let Err(e) = stats::append(&path, &record) {
+            warnings.push(e);
+        }
+    }
+    // A status file that can't be written is reported without masking the run's outcome
+    if let Some(path) = status_file {
+        if let Err(e) = status::write_status_file(&path, &status) {
+            warnings.push(e.to_string());
+        }
+    }
+    // Warnings print when dropped; the elapsed time comes after them
+    drop(warnings);
+    if let Some(line) = elapsed_line(show_elapsed, result.is_ok(), started.elapsed()) {
+        let _ = writeln!(stderr, "{}", line);
+    }
+    result
+}
+
+/// The --elapsed line for a run that took `elapsed`; None when it isn't shown
+fn elapsed_line(show: bool, succeeded: bool, elapsed: Duration) -> Option<String> {
+    let seconds = elapsed.as_secs_f64();
+    show.then(|| match succeeded {
+        true => format!("Completed in {:.1}s", seconds),
+        false => format!("Failed after {:.1}s", seconds),
+    })
+}
+
+/// Run the query (or special command) described by `args`
+async fn execute(
+    mut args: Args,
+    warnings: &mut warnings::Warnings,
+    status: &mut status::RunStatus,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Offline self-check reports invalid arguments itself, so it runs before validation
+    if args.test && args.no_network {
+        return self_check::run(&args);
+    }
+
+    // Model aliases must be known before --only is validated
+    if let Some(path) = &args.model_map_file {
+        args.model_map =
+            config::load_model_map(path, &Args::group_names()).map_err(CliError::Validation)?;
+    }
+    for repeat in args.normalize_selection() {
+        warnings.push(repeat);
+    }
+    if let Some(warning) = args.timeout_warning() {
+        warnings.push(warning);
+    }
 
-/// Main application logic
-async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
     // Validate arguments first
-    args.validate()?;
+    args.validate().map_err(CliError::Validation)?;
+    // --race keeps a single answer, so there is nothing to summarize
+    if args.race {
+        args.no_summary = true;
+    }
+    let file_stamp = args.timestamp_filenames.then(output::file_stamp);
+    for ai in cli::AI_NAMES {
+        tracing::debug!(
+            ai,
+            model = args.model_for(ai),
+            selected = args.should_use_ai(ai),
+            "resolved model"
+        );
+    }
+
+    // --role fills in the system prompt and prefix before the prompt is assembled
+    roles::apply(&mut args).map_err(CliError::Validation)?;
 
     // Handle reading prompt from stdin or file
-    if args.prompt.as_deref() == Some("-") {
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer)?;
-        args.prompt = Some(buffer.trim().to_string());
-        if args.prompt.as_ref().map_or(true, |p| p.is_empty()) {
-            return Err("No prompt provided via stdin".into());
+    let mut template_vars = HashMap::new();
+    let prompt_from_stdin = args.prompt.as_deref() == Some("-");
+    if prompt_from_stdin {
+        let buffer = prompt::read_piped(io::stdin(), io::stdin().is_terminal())?;
+        args.prompt = Some(match args.prompt_base64 {
+            true => prompt::decode_base64(&buffer, args.no_trim).map_err(CliError::Validation)?,
+            false => prompt::finish(&buffer, args.no_trim),
+        });
+        if args.prompt.as_ref().map_or(true, |p| p.trim().is_empty()) {
+            return Err(CliError::Validation("No prompt provided via stdin".to_string()).into());
         }
     } else if let Some(prompt_file) = &args.prompt_file {
         let content = fs::read_to_string(prompt_file)
             .map_err(|e| format!("Failed to read prompt file: {}", e))?;
-        args.prompt = Some(content.trim().to_string());
-        if args.prompt.as_ref().map_or(true, |p| p.is_empty()) {
-            return Err("Prompt file is empty".into());
+        let parsed = prompt::parse_prompt_file(&content, &args.input_format)?;
+        // An explicit --system-prompt takes precedence over the file's system field
+        if args.system_prompt.is_none() {
+            args.system_prompt = parsed.system;
+        }
+        template_vars = parsed.vars;
+        let mut user = prompt::finish(&parsed.user, args.no_trim);
+        if args.merge_stdin_with_file {
+            user = prompt::merge_piped(
+                &user,
+                io::stdin(),
+                io::stdin().is_terminal(),
+                &args.stdin_position,
+                &args.stdin_separator,
+                args.no_trim,
+            )?;
+        }
+        args.prompt = Some(user);
+        if args.prompt.as_ref().map_or(true, |p| p.trim().is_empty()) {
+            return Err(CliError::Validation("Prompt file is empty".to_string()).into());
+        }
+    } else if let (Some(dir), Some(name)) = (&args.prompt_template_dir, &args.template_name) {
+        let path = prompt::find_template(dir, name).map_err(CliError::Validation)?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?;
+        args.prompt = Some(prompt::finish(&content, args.no_trim));
+        if args.prompt.as_ref().map_or(true, |p| p.trim().is_empty()) {
+            return Err(CliError::Validation(format!("Template '{}' is empty", name)).into());
+        }
+    } else if let (true, Some(encoded)) = (args.prompt_base64, &args.prompt) {
+        let decoded = prompt::decode_base64(encoded, args.no_trim).map_err(CliError::Validation)?;
+        if decoded.trim().is_empty() {
+            return Err(CliError::Validation(
+                "Decoded --prompt-base64 prompt is empty".to_string(),
+            )
+            .into());
+        }
+        args.prompt = Some(decoded);
+    } else if let Some(reference) = &args.prompt_from_git_diff {
+        // --prompt-prefix stands in for the default review instruction here
+        let diff_prompt = prompt::git_diff_prompt(
+            Some(reference.as_str()),
+            args.prompt_prefix.as_deref(),
+            prompt::run_command,
+        )?;
+        args.prompt = Some(diff_prompt);
+    }
+    if let (Some(prefix), None) = (&args.prompt_prefix, &args.prompt_from_git_diff) {
+        if let Some(p) = args.prompt.as_mut() {
+            *p = format!("{}\n\n{}", prefix, p);
+        }
+    }
+
+    // Piped stdin fills a {{stdin}} (or --stdin-into) placeholder instead of replacing the prompt
+    let stdin_target = args
+        .prompt
+        .as_deref()
+        .filter(|_| !prompt_from_stdin && !args.merge_stdin_with_file)
+        .and_then(|p| prompt::stdin_target(p, args.stdin_into.as_deref()));
+    if let Some(name) = stdin_target {
+        let value = prompt::read_piped_value(io::stdin(), io::stdin().is_terminal(), args.no_trim)?;
+        if value.is_none() {
+            warnings.push(format!(
+                "No piped input for {{{{{}}}}}; it was left empty",
+                name
+            ));
+        }
+        template_vars.insert(name, value.unwrap_or_default());
+    }
+
+    // Apply template variables (--var overrides values from a structured prompt file)
+    template_vars.extend(prompt::parse_vars(&args.vars)?);
+    if let Some(sp) = args.system_prompt.as_mut() {
+        if !template_vars.is_empty() {
+            *sp = prompt::render_template(sp, &template_vars);
         }
     }
+    finish_prompt(&mut args, &template_vars)?;
 
     // Handle special commands
     if args.list_models {
-        print_available_models();
+        print_available_models(&args)?;
+        return Ok(());
+    }
+
+    if args.print_output_schema {
+        print!("{}", output::OUTPUT_SCHEMA);
         return Ok(());
     }
 
@@ -63,113 +268,345 @@ async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
         return run_doctor(&args);
     }
 
-    // Handle conversation mode
+    if args.report {
+        return run_report(&args);
+    }
+
+    if args.list_sessions {
+        return list_sessions(&args);
+    }
+
+    if args.list_roles {
+        let roles = roles::load(args.role_file.as_deref()).map_err(CliError::Validation)?;
+        print!("{}", roles::render_list(&roles));
+        return Ok(());
+    }
+
+    if let (true, Some(dir)) = (args.list_templates, &args.prompt_template_dir) {
+        for name in prompt::list_templates(dir)? {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    if args.cache_stats
+        || args.cache_prune
+        || args.cache_clear
+        || args.cache_export.is_some()
+        || args.cache_import.is_some()
+    {
+        return run_cache_command(&args);
+    }
+
+    if let Some(path) = args.batch.clone() {
+        return run_batch(args, &path, file_stamp, warnings, status).await;
+    }
+
+    // Handle conversation mode (interactive, so exempt from --max-runtime)
     if args.conversation {
         return run_conversation_mode(&args).await;
     }
 
-    // Create client configuration using the builder pattern
-    let mut config_builder = ClientConfig::builder()
-        .timeout(Duration::from_secs(args.timeout))
-        .retries(args.retries)
-        .max_tokens(args.max_tokens);
+    run_query(args, file_stamp, warnings, status).await
+}
 
-    if let Some(temp) = args.temperature {
-        config_builder = config_builder.temperature(temp);
+/// Fill the prompt's placeholders, strip its comment lines and run the PII check; the
+/// result is the effective prompt sent and logged
+fn finish_prompt(args: &mut Args, template_vars: &HashMap<String, String>) -> Result<(), CliError> {
+    let Some(p) = args.prompt.as_mut() else {
+        return Ok(());
+    };
+    if !template_vars.is_empty() {
+        *p = prompt::render_template(p, template_vars);
     }
 
-    // Set retry strategy
-    let retry_strategy = match args.retry_strategy.as_str() {
-        "linear" => RetryStrategy::Linear(Duration::from_secs(1)),
-        "fixed" => RetryStrategy::Fixed(Duration::from_secs(2)),
-        _ => RetryStrategy::Exponential(Duration::from_secs(1)),
-    };
-    config_builder = config_builder.retry_strategy(retry_strategy);
+    if args.strip_comments {
+        *p = prompt::finish(
+            &prompt::strip_comments(p, &args.comment_prefix),
+            args.no_trim,
+        );
+        if p.trim().is_empty() {
+            return Err(CliError::Validation(
+                "Prompt is empty after stripping comment lines".to_string(),
+            ));
+        }
+    }
 
-    // Wire up system prompt (available since chatdelta 0.4.0)
-    if let Some(ref system_prompt) = args.system_prompt {
-        config_builder = config_builder.system_message(system_prompt);
+    // Governance check: flag (or with --pii-block, refuse) prompts that look like they hold PII
+    if args.pii_scan || args.pii_block {
+        if let Some(error) = pii::check(p, args.pii_block, args.allow_pii, args.quiet) {
+            return Err(CliError::Validation(error));
+        }
     }
+    Ok(())
+}
 
-    let config = config_builder.build();
+/// Query the selected models with the resolved prompt in `args` and report the results
+async fn run_query(
+    mut args: Args,
+    file_stamp: Option<String>,
+    warnings: &mut warnings::Warnings,
+    status: &mut status::RunStatus,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Global deadline covering the query and summary phases
+    let deadline = args
+        .max_runtime
+        .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs));
+
+    // --force-summary-model-only narrows the run to the summarizer and condenses its answer
+    if args.force_summary_model_only {
+        let ai = summary_model_only(&args, |name| env::var(name).ok()).ok_or_else(|| {
+            CliError::NoClients(
+                "--force-summary-model-only found no selected model with an API key".to_string(),
+            )
+        })?;
+        if !args.quiet {
+            eprintln!(
+                "Querying only {}, the summary model, and condensing its answer",
+                Args::display_name(ai)
+            );
+        }
+        args.only = vec![ai.to_string()];
+        args.exclude.clear();
+        args.summarize_single = true;
+    }
 
     // Create AI clients based on available API keys and user selection
-    let mut clients: Vec<Box<dyn AiClient>> = Vec::new();
-
-    if args.should_use_ai("gpt") {
-        let openai_key = env::var("OPENAI_API_KEY")
-            .or_else(|_| env::var("CHATGPT_API_KEY"));
+    // --mock answers with offline mock clients in place of the real APIs
+    let mock_mode = args.use_mock();
+    let mut clients: Vec<Box<dyn AiClient>> = providers::mock_clients(&args);
+    let mut openai_keys = keys::openai_keys(&args);
+    let mut anthropic_keys = keys::anthropic_keys(&args);
+    // Which rotated key index served each client, recorded in the structured log
+    let mut key_indices: Vec<(String, usize)> = Vec::new();
+    // The key each client was created with, redacted into --debug-io request captures
+    let mut client_keys: HashMap<String, String> = HashMap::new();
+    // Per-client fallback models, keyed by the client's display name
+    let mut fallbacks: HashMap<String, Box<dyn AiClient>> = HashMap::new();
+    // With a shared retry budget the CLI retries itself, so the library must not
+    let retry_policy = query::RetryPolicy::from_args(&args);
+    let query_retries = if retry_policy.is_some() {
+        0
+    } else {
+        args.retries
+    };
 
-        if let Ok(key) = openai_key {
-            match create_client("openai", &key, &args.gpt_model, config.clone()) {
-                Ok(client) => clients.push(client),
-                Err(e) => {
-                    if !args.quiet {
-                        eprintln!("Warning: Failed to create ChatGPT client: {}", e);
-                    }
+    if !mock_mode && args.should_use_ai("gpt") {
+        if let Some((index, key)) = openai_keys.next_key() {
+            let config = client_config(&args, "gpt", query_retries);
+            match create_client(
+                Provider::Gpt.client_id(),
+                &key,
+                &args.gpt_model,
+                with_timeout_cap(&args, config.clone()),
+            ) {
+                Ok(client) => {
+                    key_indices.push((client.name().to_string(), index));
+                    client_keys.insert(client.name().to_string(), key.clone());
+                    let fallback_model = args.fallback_model_for("gpt");
+                    add_fallback(
+                        fallback_model,
+                        Provider::Gpt.client_id(),
+                        &key,
+                        config,
+                        client.as_ref(),
+                        &mut fallbacks,
+                        warnings,
+                    );
+                    clients.push(client);
                 }
+                Err(e) => skip_provider(
+                    &args,
+                    "gpt",
+                    format!("Failed to create ChatGPT client: {}", e),
+                    warnings,
+                )?,
             }
-        } else if !args.quiet {
-            eprintln!("Warning: OPENAI_API_KEY or CHATGPT_API_KEY not set, skipping ChatGPT");
+        } else {
+            skip_provider(
+                &args,
+                "gpt",
+                "OPENAI_API_KEY or CHATGPT_API_KEY not set, skipping ChatGPT".into(),
+                warnings,
+            )?;
         }
     }
 
-    if args.should_use_ai("gemini") {
-        if let Ok(key) = env::var("GEMINI_API_KEY") {
-            match create_client("gemini", &key, &args.gemini_model, config.clone()) {
-                Ok(client) => clients.push(client),
-                Err(e) => {
-                    if !args.quiet {
-                        eprintln!("Warning: Failed to create Gemini client: {}", e);
-                    }
+    if !mock_mode && args.should_use_ai("gemini") {
+        if let Ok(key) = env::var(Provider::Gemini.env_var()) {
+            let config = client_config(&args, "gemini", query_retries);
+            match create_client(
+                Provider::Gemini.client_id(),
+                &key,
+                &args.gemini_model,
+                with_timeout_cap(&args, config.clone()),
+            ) {
+                Ok(client) => {
+                    client_keys.insert(client.name().to_string(), key.clone());
+                    let fallback_model = args.fallback_model_for("gemini");
+                    add_fallback(
+                        fallback_model,
+                        Provider::Gemini.client_id(),
+                        &key,
+                        config,
+                        client.as_ref(),
+                        &mut fallbacks,
+                        warnings,
+                    );
+                    clients.push(client);
                 }
+                Err(e) => skip_provider(
+                    &args,
+                    "gemini",
+                    format!("Failed to create Gemini client: {}", e),
+                    warnings,
+                )?,
             }
-        } else if !args.quiet {
-            eprintln!("Warning: GEMINI_API_KEY not set, skipping Gemini");
+        } else {
+            skip_provider(
+                &args,
+                "gemini",
+                "GEMINI_API_KEY not set, skipping Gemini".into(),
+                warnings,
+            )?;
         }
     }
 
-    if args.should_use_ai("claude") {
-        let anthropic_key = env::var("ANTHROPIC_API_KEY")
-            .or_else(|_| env::var("CLAUDE_API_KEY"));
+    if !mock_mode && args.should_use_ai("claude") {
+        if let Some((index, key)) = anthropic_keys.next_key() {
+            let config = client_config(&args, "claude", query_retries);
+            match create_client(
+                Provider::Claude.client_id(),
+                &key,
+                &args.claude_model,
+                with_timeout_cap(&args, config.clone()),
+            ) {
+                Ok(client) => {
+                    key_indices.push((client.name().to_string(), index));
+                    client_keys.insert(client.name().to_string(), key.clone());
+                    let fallback_model = args.fallback_model_for("claude");
+                    add_fallback(
+                        fallback_model,
+                        Provider::Claude.client_id(),
+                        &key,
+                        config,
+                        client.as_ref(),
+                        &mut fallbacks,
+                        warnings,
+                    );
+                    clients.push(client);
+                }
+                Err(e) => skip_provider(
+                    &args,
+                    "claude",
+                    format!("Failed to create Claude client: {}", e),
+                    warnings,
+                )?,
+            }
+        } else {
+            skip_provider(
+                &args,
+                "claude",
+                "ANTHROPIC_API_KEY or CLAUDE_API_KEY not set, skipping Claude".into(),
+                warnings,
+            )?;
+        }
+    }
 
-        if let Ok(key) = anthropic_key {
-            match create_client("claude", &key, &args.claude_model, config.clone()) {
-                Ok(client) => clients.push(client),
-                Err(e) => {
-                    if !args.quiet {
-                        eprintln!("Warning: Failed to create Claude client: {}", e);
-                    }
+    // --model-map-file aliases picked with --only each get a client named after the alias
+    for (alias, entry) in args.selected_aliases().into_iter().filter(|_| !mock_mode) {
+        let ai = entry.ai();
+        let key = match ai {
+            "gpt" => openai_keys.next_key(),
+            "gemini" => env::var(Provider::Gemini.env_var())
+                .ok()
+                .map(|key| (0, key)),
+            _ => anthropic_keys.next_key(),
+        };
+        let Some((index, key)) = key else {
+            skip_provider(
+                &args,
+                ai,
+                format!(
+                    "No {} API key for model alias '{}', skipping it",
+                    Args::display_name(ai),
+                    alias
+                ),
+                warnings,
+            )?;
+            continue;
+        };
+        let config = with_timeout_cap(&args, client_config(&args, ai, query_retries));
+        match create_client(Provider::for_ai(ai).client_id(), &key, &entry.model, config) {
+            Ok(client) => {
+                if ai != "gemini" {
+                    key_indices.push((alias.to_string(), index));
                 }
+                client_keys.insert(alias.to_string(), key);
+                clients.push(Box::new(providers::alias::AliasedClient::new(
+                    alias, client,
+                )));
             }
-        } else if !args.quiet {
-            eprintln!("Warning: ANTHROPIC_API_KEY or CLAUDE_API_KEY not set, skipping Claude");
+            Err(e) => skip_provider(
+                &args,
+                ai,
+                format!("Failed to create client for model alias '{}': {}", alias, e),
+                warnings,
+            )?,
         }
     }
 
     if clients.is_empty() {
-        return Err(
-            "No AI clients available. Check your API keys and --only/--exclude settings.".into(),
-        );
+        return Err(CliError::NoClients(
+            "No AI clients available. Check your API keys and --only/--exclude settings."
+                .to_string(),
+        )
+        .into());
+    }
+
+    // --warmup pays connection setup up front; its replies and errors are discarded
+    if args.warmup {
+        if args.verbose && !args.quiet {
+            eprintln!(
+                "\u{1f525} Warming up {} connection{}...",
+                clients.len(),
+                if clients.len() == 1 { "" } else { "s" }
+            );
+        }
+        for warning in query::warm_up(&clients, deadline).await {
+            warnings.push(warning);
+        }
+    }
+
+    if let Some(runs) = args.benchmark {
+        return run_benchmark(&args, clients, runs).await;
     }
 
     // Streaming path: single-model only, prints tokens as they arrive
     if args.stream {
-        if clients.len() > 1 && !args.quiet {
-            eprintln!(
-                "Note: --stream requires a single model. Use --only to select one. \
-                 Falling back to parallel mode."
+        if clients.len() > 1 {
+            warnings.push(
+                "--stream requires a single model. Use --only to select one. \
+                 Falling back to parallel mode.",
             );
         } else if clients.len() == 1 {
             let client = clients.remove(0);
             let prompt = args.prompt.as_ref().ok_or("No prompt provided")?.clone();
+            if let Some(path) = &args.save_prompt {
+                save_prompt(path, &prompt)?;
+            }
 
+            let mut out = result_writer(&args)?;
             if args.show_usage {
                 // Streaming doesn't return metadata; use send_prompt_with_metadata instead
                 let name = client.name().to_string();
                 let response = client.send_prompt_with_metadata(&prompt).await?;
-                println!("{}", response.content);
-                print_usage_table(&[(name, response.metadata.total_tokens, response.metadata.latency_ms)]);
+                writeln!(out, "{}", response.content)?;
+                print_usage_table(&[(
+                    name,
+                    response.metadata.total_tokens,
+                    response.metadata.latency_ms,
+                )]);
             } else {
                 let (tx, mut rx) = mpsc::unbounded_channel::<StreamChunk>();
                 tokio::spawn(async move {
@@ -177,39 +614,73 @@ async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
                         eprintln!("Stream error: {}", e);
                     }
                 });
-                use std::io::Write;
-                while let Some(chunk) = rx.recv().await {
-                    print!("{}", chunk.content);
-                    io::stdout().flush().ok();
-                    if chunk.finished {
-                        println!();
-                        break;
-                    }
+                // Dropping the receiver on a write error stops the stream; what was
+                // written so far stays in place
+                if let Err((written, e)) = write_stream(&mut rx, &mut *out).await {
+                    return Err(format!(
+                        "Stopped streaming after {} bytes: failed to write output: {}",
+                        written, e
+                    )
+                    .into());
                 }
             }
             return Ok(());
         }
     }
 
+    // Load the regression baseline up front so a bad path fails before any API calls
+    let baseline = match args.compare_to_file {
+        Some(ref path) => Some(compare::load_baseline(path)?),
+        None => None,
+    };
+    let tool_schema = match args.tool_schema {
+        Some(ref path) => Some(tools::ToolSchema::load(path)?),
+        None => None,
+    };
+
     // Initialize comprehensive logger
     let mut logger = if args.log_metrics || args.log_errors || args.log_dir.is_some() {
-        Some(Logger::new(&args)?)
+        match Logger::new(&args) {
+            Ok(logger) => Some(logger),
+            Err(e) => {
+                logging_failure(
+                    &args,
+                    warnings,
+                    format!("Structured logging disabled: {}", e),
+                )?;
+                None
+            }
+        }
     } else {
         None
     };
 
+    // Cost guardrail: estimate before spending anything
+    if let Some(limit) = args.cost_limit {
+        check_cost_limit(&args, &clients, limit, warnings)?;
+    }
+
     // Query each model with the same prompt in parallel
+    // Progress and status go to stderr so stdout carries only the result
+    let retry_note = match args.retries {
+        0 => String::new(),
+        _ if !args.verbose => String::new(),
+        1 => " (up to 1 retry each)".to_string(),
+        n => format!(" (up to {} retries each)", n),
+    };
     if !args.quiet && args.progress {
-        println!(
-            "\u{1f504} Querying {} AI model{}...",
+        eprintln!(
+            "\u{1f504} Querying {} AI model{}{}...",
             clients.len(),
-            if clients.len() == 1 { "" } else { "s" }
+            if clients.len() == 1 { "" } else { "s" },
+            retry_note
         );
     } else if !args.quiet {
-        println!(
-            "Querying {} AI model{}...",
+        eprintln!(
+            "Querying {} AI model{}{}...",
             clients.len(),
-            if clients.len() == 1 { "" } else { "s" }
+            if clients.len() == 1 { "" } else { "s" },
+            retry_note
         );
     }
 
@@ -222,155 +693,931 @@ async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
         } else {
             prompt.clone()
         };
-        println!("\u{1f4dd} Prompt: {}", preview);
+        eprintln!("\u{1f4dd} Prompt: {}", preview);
     }
 
     // Start logging interaction
     if let Some(ref mut logger) = logger {
-        logger.start_interaction(prompt);
+        logger.start_interaction(prompt, args.interaction_id.as_deref());
+    }
+
+    // chatdelta has no native tool-calling API yet, so every provider gets the
+    // schema as prompt instructions and the call is parsed from the reply
+    let query_prompt = match &tool_schema {
+        Some(schema) => schema.instruct(prompt),
+        None => prompt.clone(),
+    };
+    if let Some(path) = &args.save_prompt {
+        save_prompt(path, &query_prompt)?;
+    }
+
+    // --chunk-prompt splits a prompt that overflows a model's context window, once per model
+    let chunking = args.chunk_prompt.then(|| {
+        let config = chunk::ChunkConfig {
+            overlap_tokens: args.chunk_overlap,
+            reply_tokens: args.max_tokens,
+        };
+        let instructions = tool_schema.as_ref().map(tools::ToolSchema::instructions);
+        let models = clients
+            .iter()
+            .chain(fallbacks.values())
+            .map(|client| client.model());
+        Arc::new(chunk::ChunkPlans::new(
+            config,
+            prompt,
+            instructions.as_deref(),
+            models,
+        ))
+    });
+    if let (Some(plans), true) = (&chunking, args.verbose && !args.quiet) {
+        for client in &clients {
+            if let Some(parts) = plans.parts(client.model()) {
+                eprintln!(
+                    "\u{2702}\u{fe0f} Splitting the prompt into {} parts for {} ({})",
+                    parts.len(),
+                    client.name(),
+                    client.model()
+                );
+            }
+        }
+    }
+
+    let mut client_models: HashMap<String, String> = clients
+        .iter()
+        .map(|c| (c.name().to_string(), c.model().to_string()))
+        .collect();
+
+    let response_filter = match args.response_filters.is_empty() {
+        true => None,
+        false => Some(
+            filter::ResponseFilter::new(&args.response_filters).map_err(CliError::Validation)?,
+        ),
+    };
+
+    // --retry-on-refusal re-sends a refused prompt to an AI left out of the run
+    let refusal_detector = match args.retry_on_refusal {
+        true => Some(
+            refusal::RefusalDetector::new(&args.refusal_patterns).map_err(CliError::Validation)?,
+        ),
+        false => None,
+    };
+
+    // --cache answers repeated requests from disk; only the misses are sent
+    let response_cache = args.cache.then(|| {
+        cache::ResponseCache::new(cache::cache_dir(args.cache_dir.as_deref()), args.cache_ttl)
+    });
+    let mut cache_keys: HashMap<String, String> = HashMap::new();
+    let mut cache_hits = Vec::new();
+    if let Some(response_cache) = &response_cache {
+        let mut misses = Vec::with_capacity(clients.len());
+        for (index, client) in clients.into_iter().enumerate() {
+            let key = cache::cache_key(&args, client.name(), client.model(), &query_prompt);
+            match response_cache.get(&key) {
+                Some(entry) => {
+                    if args.verbose && !args.quiet {
+                        eprintln!(
+                            "\u{267b}\u{fe0f} Using cached response for {}",
+                            client.name()
+                        );
+                    }
+                    cache_hits.push((index, client.name().to_string(), entry.into_response()));
+                }
+                None => {
+                    cache_keys.insert(client.name().to_string(), key);
+                    misses.push(client);
+                }
+            }
+        }
+        clients = misses;
+    }
+
+    // --eager-summary: summarize alongside the queries, starting once two responses are in
+    let mut eager_task = None;
+    let mut arrivals = None;
+    if args.eager_summary && !args.no_summary && clients.len() + cache_hits.len() > 1 {
+        let client = if mock_mode {
+            providers::mock_summary_client(&args)
+        } else {
+            summary_client(&args, &[], &mut openai_keys, &mut anthropic_keys)
+        };
+        if let Some(client) = client {
+            let (tx, rx) = mpsc::unbounded_channel();
+            for (_, name, response) in &cache_hits {
+                let _ = tx.send((name.clone(), filtered(&response_filter, &response.content)));
+            }
+            let chunk_size = args.summary_chunk_size;
+            let format = args.resolved_summary_format();
+            let original = args.summary_prompt_context().map(str::to_string);
+            eager_task = Some(tokio::spawn(async move {
+                summary::eager_summary(&*client, rx, chunk_size, format, original.as_deref()).await
+            }));
+            arrivals = Some(tx);
+        }
     }
 
     let query_start = std::time::Instant::now();
-    let (results, usage_rows) = if args.show_usage {
-        let raw = execute_parallel_with_metadata(clients, prompt).await;
-        let mut plain: Vec<(String, Result<String, _>)> = Vec::new();
-        let mut usage: Vec<(String, Option<u32>, Option<u64>)> = Vec::new();
-        for (name, result) in raw {
+    // Each response is logged as it arrives, so the partial record keeps it if the run dies
+    // before the rest are in, and feeds --eager-summary
+    let arrival_log = &mut logger;
+    let arrival_filter = &response_filter;
+    let mut on_arrival = move |name: &str, result: &Result<AiResponse, ClientError>| {
+        if let (Some(arrivals), Ok(response)) = (&arrivals, result) {
+            let _ = arrivals.send((
+                name.to_string(),
+                filtered(arrival_filter, &response.content),
+            ));
+        }
+        if let Some(logger) = arrival_log.as_mut() {
+            let elapsed = query_start.elapsed();
             match result {
-                Ok(r) => {
-                    usage.push((name.clone(), r.metadata.total_tokens, r.metadata.latency_ms));
-                    plain.push((name, Ok(r.content)));
-                }
-                Err(e) => plain.push((name, Err(e))),
+                Ok(response) => logger.log_model_response(
+                    name,
+                    Ok(&response.content),
+                    elapsed,
+                    response.metadata.total_tokens,
+                    response.metadata.finish_reason.as_deref(),
+                ),
+                Err(e) => logger.log_model_response(name, Err(&e.to_string()), elapsed, None, None),
             }
         }
-        (plain, usage)
+    };
+    let (query_results, race) = if args.race {
+        let raced = query::race(
+            clients,
+            &query_prompt,
+            deadline,
+            retry_policy.as_ref(),
+            chunking,
+        )
+        .await;
+        (raced.query, Some((raced.winner, raced.cancelled)))
     } else {
-        (execute_parallel(clients, prompt).await, Vec::new())
+        let results = query::query_all(
+            clients,
+            fallbacks,
+            &query_prompt,
+            deadline,
+            retry_policy.as_ref(),
+            chunking,
+            Some(&mut on_arrival),
+        )
+        .await;
+        (results, None)
     };
+    // Closes --eager-summary's channel now that every response is in
+    drop(on_arrival);
+    let query::QueryResults {
+        results: mut raw,
+        unfinished,
+        fallbacks: fallback_attempts,
+    } = query_results;
     let query_duration = query_start.elapsed();
 
+    // --race: report the winner and record the requests it cancelled
+    if let Some((Some(winner), cancelled)) = &race {
+        if !args.quiet {
+            match cancelled.is_empty() {
+                true => eprintln!("\u{1f3c1} {} answered first", winner),
+                false => eprintln!(
+                    "\u{1f3c1} {} answered first; cancelled {}",
+                    winner,
+                    cancelled.join(", ")
+                ),
+            }
+        }
+        if let Some(ref mut logger) = logger {
+            let message = format!("Cancelled after {} answered first", winner);
+            for name in cancelled {
+                logger.log_model_response(name, Err(&message), query_duration, None, None);
+                logger.log_error(name, "RACE_CANCELLED", &message, None);
+            }
+        }
+    }
+    // Cached responses rejoin the results in their client's place
+    for (index, name, response) in cache_hits {
+        raw.insert(index.min(raw.len()), (name, Ok(response)));
+    }
+
+    if let Some(detector) = &refusal_detector {
+        let spares = if mock_mode {
+            providers::mock_unselected_clients(&args)
+        } else {
+            unselected_clients(&args, &mut openai_keys, &mut anthropic_keys)
+        };
+        let retry = refusal::retry_refusals(&mut raw, spares, &query_prompt, detector);
+        match query::with_deadline(deadline, retry).await {
+            Ok(retries) => {
+                for retry in &retries {
+                    warnings.push(format!(
+                        "{} refused the prompt; retried with {}",
+                        retry.refused, retry.retried_with
+                    ));
+                    client_models.insert(retry.label.clone(), retry.model.clone());
+                    if let Some(ref mut logger) = logger {
+                        let message =
+                            format!("Refused the prompt; retried with {}", retry.retried_with);
+                        logger.log_error(&retry.refused, "REFUSAL", &message, None);
+                    }
+                }
+                if args.hide_refusals {
+                    raw.retain(|(name, _)| !retries.iter().any(|retry| &retry.refused == name));
+                }
+            }
+            Err(_) => {
+                warnings.push("--max-runtime reached before refused prompts could be retried")
+            }
+        }
+    }
+
+    for attempt in &fallback_attempts {
+        warnings.push(format!(
+            "{} ({}) failed: {}; retried with {}",
+            attempt.name, attempt.primary_model, attempt.primary_error, attempt.fallback_model
+        ));
+    }
+
+    if let Some(tracker) = retry_policy
+        .as_ref()
+        .and_then(|policy| policy.tracker.as_ref())
+    {
+        eprint!("{}", tracker.render(&raw));
+    }
+    if let (Some(policy), true, Some(_)) = (&retry_policy, args.verbose, args.retry_budget) {
+        eprintln!(
+            "\u{1f501} Retry budget: {} of {} remaining",
+            policy.budget.remaining(),
+            args.retry_budget.unwrap_or_default()
+        );
+    }
+
+    // Artifacts of this run, debug-io dumps included, share one --output-dir subdirectory
+    let run_dir = args
+        .output_dir
+        .as_ref()
+        .map(|base| artifacts::run_dir(base, &args));
+
+    // --debug-io: chatdelta doesn't expose raw bodies, so requests are reconstructed
+    if args.debug_io {
+        for (name, result) in &raw {
+            let model = fallback_attempts
+                .iter()
+                .find(|attempt| &attempt.name == name)
+                .map_or_else(
+                    || client_models[name].as_str(),
+                    |attempt| attempt.fallback_model.as_str(),
+                );
+            let exchange = debug_io::Exchange {
+                name,
+                model,
+                key: client_keys.get(name).map(String::as_str),
+                prompt: &query_prompt,
+                result,
+            };
+            if let (Some(path), true) = (
+                debug_io::record(&args, run_dir.as_deref(), &exchange)?,
+                args.verbose,
+            ) {
+                eprintln!(
+                    "\u{1f50d} Wrote {} request/response to {}",
+                    name,
+                    path.display()
+                );
+            }
+        }
+    }
+
+    // --metrics-file: per-provider counts, latency and tokens for monitoring
+    if let Some(path) = &args.metrics_file {
+        let elapsed = query_duration.as_millis() as u64;
+        let mut metrics = metrics_display::CliMetrics::new();
+        for (name, result) in &raw {
+            match result {
+                Ok(r) => metrics.record_success(
+                    name,
+                    r.metadata.latency_ms.unwrap_or(elapsed),
+                    r.metadata.total_tokens,
+                ),
+                Err(_) => metrics.record_failure(name, elapsed),
+            }
+        }
+        metrics.save_to_file(path, args.resolved_metrics_format())?;
+    }
+
+    let mut results: Vec<(String, Result<String, _>)> = Vec::new();
+    let mut usage_rows: Vec<(String, Option<u32>, Option<u64>)> = Vec::new();
+    let mut response_meta: HashMap<String, ResponseMeta> = HashMap::new();
+    for (name, result) in raw {
+        if args.stats_file.is_some() {
+            let model = client_models.get(&name).map_or("", String::as_str);
+            status.models.push(stats::ModelOutcome::new(
+                &name,
+                model,
+                &query_prompt,
+                &result,
+            ));
+        }
+        match result {
+            Ok(r) => {
+                // A fallback model's answer isn't cached under the primary model's key
+                let primary_answered =
+                    !fallback_attempts.iter().any(|attempt| attempt.name == name);
+                if let (Some(response_cache), Some(key), true) =
+                    (&response_cache, cache_keys.get(&name), primary_answered)
+                {
+                    let entry = cache::CacheEntry::new(&client_models[&name], &r);
+                    if let Err(e) = response_cache.put(key, &entry) {
+                        warnings.push(format!("Failed to cache the {} response: {}", name, e));
+                    }
+                }
+                if args.show_usage {
+                    usage_rows.push((name.clone(), r.metadata.total_tokens, r.metadata.latency_ms));
+                }
+                response_meta.insert(
+                    name.clone(),
+                    ResponseMeta {
+                        latency_ms: r.metadata.latency_ms,
+                        total_tokens: r.metadata.total_tokens,
+                        finish_reason: resolve_finish_reason(
+                            r.metadata.finish_reason.as_deref(),
+                            r.metadata.completion_tokens,
+                            args.max_tokens,
+                        ),
+                        response_hash: args.with_hash.then(|| output::response_hash(&r.content)),
+                        throughput: output::throughput(
+                            r.metadata.total_tokens,
+                            r.metadata.latency_ms,
+                        ),
+                        tool_call: None,
+                    },
+                );
+                results.push((name, Ok(r.content)));
+            }
+            Err(e) => results.push((name, Err(e))),
+        }
+    }
+
+    let answered = results.len();
     let mut responses = Vec::new();
+    let mut compact_failures = Vec::new();
 
     for (name, result) in results {
         match result {
             Ok(reply) => {
+                // With --tool-schema, the parsed call arguments stand in for the free text
+                let reply = match &tool_schema {
+                    Some(schema) => match tools::parse_tool_call(&reply, schema) {
+                        Ok(call) => {
+                            let arguments = call.arguments.to_string();
+                            if let Some(meta) = response_meta.get_mut(&name) {
+                                meta.tool_call = Some(call);
+                            }
+                            arguments
+                        }
+                        Err(e) => {
+                            warnings.push(format!(
+                                "{} did not return a {} tool call ({}); showing the full response",
+                                name, schema.name, e
+                            ));
+                            reply
+                        }
+                    },
+                    None => reply,
+                };
+
+                // Replace the response with the extracted JSON value when requested
+                let reply = match &args.json_path_extract {
+                    Some(path) => match extract::extract_json_path(&reply, path) {
+                        Ok(value) => value,
+                        Err(e) if args.strict_extract => {
+                            if !args.quiet {
+                                eprintln!(
+                                    "\u{2717} {} error: {} extraction failed: {}",
+                                    name, path, e
+                                );
+                            }
+                            if let Some(ref mut logger) = logger {
+                                let message = format!("{} extraction failed: {}", path, e);
+                                if !logger.set_response(&name, Err(&message), None) {
+                                    logger.log_model_response(
+                                        &name,
+                                        Err(&message),
+                                        query_duration,
+                                        None,
+                                        None,
+                                    );
+                                }
+                                logger.log_error(&name, "EXTRACT_ERROR", &message, None);
+                            }
+                            continue;
+                        }
+                        Err(e) => {
+                            warnings.push(format!(
+                                "{} extraction failed for {} ({}); showing the full response",
+                                path, name, e
+                            ));
+                            reply
+                        }
+                    },
+                    None => reply,
+                };
+
+                // --response-filter: cut boilerplate, keeping the original for the log
+                let (reply, unfiltered) = match &response_filter {
+                    Some(filter) => {
+                        let filtered = filter.apply(&reply);
+                        match filtered == reply {
+                            true => (reply, None),
+                            false => (filtered, Some(reply)),
+                        }
+                    }
+                    None => (reply, None),
+                };
+                let finish_reason = response_meta
+                    .get(&name)
+                    .and_then(|m| m.finish_reason.as_deref());
                 if args.verbose {
-                    println!("\u{2705} Received response from {} ({} chars)", name, reply.len());
+                    match finish_reason {
+                        Some(reason) => eprintln!(
+                            "\u{2705} Received response from {} ({} chars, finish reason: {})",
+                            name,
+                            reply.len(),
+                            reason
+                        ),
+                        None => eprintln!(
+                            "\u{2705} Received response from {} ({} chars)",
+                            name,
+                            reply.len()
+                        ),
+                    }
+                }
+                if finish_reason.is_some_and(is_length_cutoff) {
+                    warnings.push(format!(
+                        "{} response was cut off at the token limit — try a higher --max-tokens",
+                        name
+                    ));
                 }
 
                 // Save individual response if requested
                 if let Some(dir) = &args.save_responses {
-                    save_individual_response(dir, &name, &reply)?;
+                    let echoed = args.echo_prompt.then_some(prompt.as_str());
+                    save_individual_response(dir, &name, &reply, echoed, file_stamp.as_deref())?;
                 }
 
-                // Log successful response
+                // Log the final response; responses logged on arrival keep their own timing
                 if let Some(ref mut logger) = logger {
-                    logger.log_model_response(&name, Ok(&reply), query_duration, None);
+                    if !logger.set_response(&name, Ok(&reply), finish_reason) {
+                        let tokens = response_meta.get(&name).and_then(|m| m.total_tokens);
+                        logger.log_model_response(
+                            &name,
+                            Ok(&reply),
+                            query_duration,
+                            tokens,
+                            finish_reason,
+                        );
+                    }
+                    if let Some(hash) = response_meta
+                        .get(&name)
+                        .and_then(|m| m.response_hash.as_deref())
+                    {
+                        logger.set_response_hash(&name, hash);
+                    }
+                    if let Some(original) = &unfiltered {
+                        logger.set_unfiltered(&name, original);
+                    }
                 }
 
                 responses.push((name, reply));
             }
             Err(e) => {
-                if !args.quiet {
+                if args.compact_errors {
+                    compact_failures.push((name.clone(), diagnostics::classify(&e)));
+                } else if !args.quiet {
                     // Provide actionable error messages based on common patterns
-                    let msg = e.to_string();
-                    if msg.contains("401") || msg.contains("Unauthorized") || msg.contains("invalid_api_key") {
-                        eprintln!("\u{2717} {} error: Invalid API key — check your environment variables", name);
-                    } else if msg.contains("429") || msg.contains("rate") || msg.contains("RateLimit") {
-                        eprintln!("\u{2717} {} error: Rate limit exceeded — retry after a moment", name);
-                    } else if msg.contains("timeout") || msg.contains("Timeout") {
-                        eprintln!("\u{2717} {} error: Request timed out — try --timeout with a higher value", name);
-                    } else {
-                        eprintln!("\u{2717} {} error: {}", name, e);
+                    match diagnostics::classify(&e).hint() {
+                        Some(hint) => eprintln!("\u{2717} {} error: {}", name, hint),
+                        None => eprintln!("\u{2717} {} error: {}", name, e),
                     }
                 }
 
                 // Log error
                 if let Some(ref mut logger) = logger {
-                    logger.log_model_response(&name, Err(&e.to_string()), query_duration, None);
+                    if !logger.set_response(&name, Err(&e.to_string()), None) {
+                        logger.log_model_response(
+                            &name,
+                            Err(&e.to_string()),
+                            query_duration,
+                            None,
+                            None,
+                        );
+                    }
                     logger.log_error(&name, "API_ERROR", &e.to_string(), None);
                 }
             }
         }
     }
 
+    if let (Some(line), false) = (diagnostics::compact_summary(&compact_failures), args.quiet) {
+        eprintln!("\u{2717} {}", line);
+    }
+
+    if let Some(ref mut logger) = logger {
+        for (name, index) in &key_indices {
+            logger.set_key_index(name, *index);
+        }
+        for attempt in &fallback_attempts {
+            logger.set_fallback(attempt);
+            logger.log_error(&attempt.name, "FALLBACK", &attempt.primary_error, None);
+        }
+        if let Some((Some(winner), _)) = &race {
+            logger.set_race_winner(winner);
+        }
+    }
+
+    // A batch adds each prompt's counts to the same status
+    let models_queried = answered + unfinished.len();
+    status.models_queried += models_queried;
+    status.responses += responses.len();
+    status.failures += models_queried - responses.len();
+
+    // Deadline hit during the queries: keep what finished, record the rest, and bail out
+    if !unfinished.is_empty() {
+        warnings.push(format!(
+            "--max-runtime of {}s reached; cancelled {}",
+            args.max_runtime.unwrap_or_default(),
+            unfinished.join(", ")
+        ));
+        if let Some(mut logger) = logger {
+            for name in &unfinished {
+                logger.log_model_response(
+                    name,
+                    Err("Cancelled by --max-runtime"),
+                    query_duration,
+                    None,
+                    None,
+                );
+                logger.log_error(
+                    name,
+                    "DEADLINE_EXCEEDED",
+                    "Cancelled by --max-runtime",
+                    None,
+                );
+            }
+            logger.finalize_interaction(None)?;
+        }
+        return Err(CliError::Timeout(format!(
+            "Exceeded --max-runtime of {}s ({} of {} model{} finished)",
+            args.max_runtime.unwrap_or_default(),
+            responses.len(),
+            responses.len() + unfinished.len(),
+            if responses.len() + unfinished.len() == 1 {
+                ""
+            } else {
+                "s"
+            }
+        ))
+        .into());
+    }
+
     if responses.is_empty() {
-        return Err("No successful responses from any AI models".into());
+        return Err(CliError::NoResponses(
+            "No successful responses from any AI models".to_string(),
+        )
+        .into());
     }
 
     if !args.quiet {
-        println!(
+        eprintln!(
             "\u{2713} Received {} response{}",
             responses.len(),
             if responses.len() == 1 { "" } else { "s" }
         );
     }
 
-    // Generate summary if requested and we have multiple responses
-    let (digest, summary_duration) = if !args.no_summary && responses.len() > 1 {
-        if !args.quiet {
-            println!("Generating summary...");
+    // Consensus gate: every pair of responses must be at least --agreement-threshold similar
+    if args.fail_if_disagree {
+        if responses.len() < 2 {
+            if let Some(mut logger) = logger {
+                logger.finalize_interaction(None)?;
+            }
+            return Err(CliError::CheckFailed(
+                "--fail-if-disagree needs at least two responses to compare".to_string(),
+            )
+            .into());
+        }
+        let diverged = compare::disagreements(&responses, args.agreement_threshold);
+        if !diverged.is_empty() {
+            eprintln!(
+                "Models disagree (threshold {:.2}):",
+                args.agreement_threshold
+            );
+            for (a, b, score) in &diverged {
+                eprintln!("  \u{2717} {} vs {}: similarity {:.2}", a, b, score);
+            }
+            if let Some(mut logger) = logger {
+                for (a, b, score) in &diverged {
+                    let message = format!("{} vs {}: similarity {:.2}", a, b, score);
+                    logger.log_error("consensus", "DISAGREEMENT", &message, None);
+                }
+                logger.finalize_interaction(None)?;
+            }
+            return Err(CliError::CheckFailed(format!(
+                "Consensus check failed: {} model pair{} below agreement threshold {:.2}",
+                diverged.len(),
+                if diverged.len() == 1 { "" } else { "s" },
+                args.agreement_threshold
+            ))
+            .into());
         }
+    }
 
-        let summary_start = std::time::Instant::now();
+    // Generate summary if requested and we have multiple responses
+    // With --deadline-aware-summary, skip a summary that likely can't finish before --max-runtime
+    // A lone survivor of a multi-model query is summarized only with --summarize-single
+    let summary_wanted = !args.no_summary
+        && (responses.len() > 1 || (args.summarize_single && responses.len() == 1));
+    if responses.len() == 1 && answered > 1 && !summary_wanted && !args.no_summary && !args.quiet {
+        eprintln!(
+            "Only {} responded, so there is nothing to summarize; showing its response \
+             (use --summarize-single to condense it)",
+            responses[0].0
+        );
+    }
+    let skip_summary = summary_wanted
+        && args.deadline_aware_summary
+        && deadline.is_some_and(|deadline| {
+            let slowest = response_meta
+                .values()
+                .filter_map(|m| m.latency_ms)
+                .max()
+                .map(Duration::from_millis)
+                .or(Some(query_duration));
+            let estimate = summary::estimate_summary_duration(slowest);
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            let skip = summary::should_skip_summary(remaining, estimate);
+            if skip {
+                warnings.push(format!(
+                    "Skipped summary \u{2014} {:.1}s left before --max-runtime, summary estimated at {:.1}s",
+                    remaining.as_secs_f64(),
+                    estimate.as_secs_f64()
+                ));
+            }
+            skip
+        });
+    if skip_summary {
+        if let Some(ref mut logger) = logger {
+            logger.log_error(
+                "summary",
+                "SKIPPED_DEADLINE",
+                "Skipped to stay within --max-runtime",
+                None,
+            );
+        }
+    }
 
-        // Try to use Gemini for summary, fall back to Claude, then OpenAI
-        let summary_client = if let Ok(key) = env::var("GEMINI_API_KEY") {
-            create_client("gemini", &key, &args.gemini_model, config.clone()).ok()
-        } else if let Ok(key) = env::var("ANTHROPIC_API_KEY")
-            .or_else(|_| env::var("CLAUDE_API_KEY")) {
-            create_client("claude", &key, &args.claude_model, config.clone()).ok()
-        } else if let Ok(key) = env::var("OPENAI_API_KEY")
-            .or_else(|_| env::var("CHATGPT_API_KEY")) {
-            create_client("openai", &key, &args.gpt_model, config).ok()
+    let mut summary_timed_out = false;
+    let run_summary = summary_wanted && !skip_summary;
+    let (digest, summary_duration) = if run_summary && !args.summary_models.is_empty() {
+        // --summary-models: every listed model summarizes in parallel; all summaries are shown
+        if args.show_summary_status() {
+            eprintln!(
+                "Generating summaries with {}...",
+                args.summary_models.join(", ")
+            );
+        }
+
+        let summary_start = std::time::Instant::now();
+        let summary_clients = if mock_mode {
+            providers::mock_summary_clients(&args)
         } else {
-            None
+            summary_model_clients(&args, &mut openai_keys, &mut anthropic_keys, warnings)
         };
+        let summarize = summary::parallel_summaries(
+            &responses,
+            summary_clients,
+            args.summary_chunk_size,
+            args.resolved_summary_format(),
+            args.summary_prompt_context(),
+        );
+        match query::with_deadline(deadline, summarize).await {
+            Err(_) => {
+                summary_timed_out = true;
+                (None, None)
+            }
+            Ok(results) => {
+                let mut summaries = Vec::new();
+                for (name, result) in results {
+                    match result {
+                        Ok(summary) => {
+                            if let Some(ref mut logger) = logger {
+                                logger.add_model_summary(&name, &summary);
+                            }
+                            summaries.push((name, summary));
+                        }
+                        Err(e) => {
+                            warnings.push(format!("Summary by {} failed: {}", name, e));
+                            if let Some(ref mut logger) = logger {
+                                logger.log_error(&name, "GENERATION_ERROR", &e.to_string(), None);
+                            }
+                        }
+                    }
+                }
 
-        if let Some(client) = summary_client {
-            match generate_summary(&*client, &responses).await {
-                Ok(summary) => {
-                    let duration = summary_start.elapsed();
-                    if !args.quiet {
-                        println!("\u{2713} Summary generated");
+                // Mini-comparison: how closely the summaries agree with each other
+                if args.verbose {
+                    for (i, (a_name, a)) in summaries.iter().enumerate() {
+                        for (b_name, b) in &summaries[i + 1..] {
+                            eprintln!(
+                                "\u{1f50d} Summary agreement {} vs {}: {:.2}",
+                                a_name,
+                                b_name,
+                                compare::similarity(a, b)
+                            );
+                        }
                     }
+                }
 
-                    // Log summary
+                if summaries.is_empty() {
+                    (None, None)
+                } else {
+                    let digest = summary::label_summaries(&summaries);
                     if let Some(ref mut logger) = logger {
-                        logger.set_summary(&summary);
+                        logger.set_summary(&digest);
                     }
+                    (Some(digest), Some(summary_start.elapsed()))
+                }
+            }
+        }
+    } else if run_summary {
+        if args.show_summary_status() {
+            eprintln!("Generating summary...");
+        }
+
+        let summary_start = std::time::Instant::now();
 
-                    (Some(summary), Some(duration))
+        // --eager-summary has been drafting since the second response arrived; a regular
+        // summary runs only when it didn't get two responses
+        let staged = match eager_task.take() {
+            Some(task) => match query::with_deadline(deadline, task).await {
+                Err(_) => {
+                    summary_timed_out = true;
+                    None
                 }
-                Err(e) => {
-                    if !args.quiet {
-                        eprintln!("Warning: Summary generation failed: {}", e);
-                    }
+                Ok(joined) => joined.ok().flatten(),
+            },
+            None => None,
+        };
 
-                    // Log summary error
-                    if let Some(ref mut logger) = logger {
-                        logger.log_error("summary", "GENERATION_ERROR", &e.to_string(), None);
+        let outcome = if summary_timed_out {
+            None
+        } else if let Some(staged) = staged {
+            Some(staged.map(|staged| {
+                if args.verbose && !args.quiet {
+                    eprintln!(
+                        "\u{26a1} Summary drafted from {}{}",
+                        staged.drafted_from.join(", "),
+                        if staged.refined_with.is_empty() {
+                            String::new()
+                        } else {
+                            format!(", refined with {}", staged.refined_with.join(", "))
+                        }
+                    );
+                }
+                staged.summary
+            }))
+        } else {
+            let client = if mock_mode {
+                providers::mock_summary_client(&args)
+            } else {
+                let answered: Vec<&str> = cli::AI_NAMES
+                    .iter()
+                    .copied()
+                    .filter(|ai| {
+                        responses
+                            .iter()
+                            .any(|(name, _)| name == Args::display_name(ai))
+                    })
+                    .collect();
+                summary_client(&args, &answered, &mut openai_keys, &mut anthropic_keys)
+            };
+            match client {
+                Some(client) => {
+                    // Large response sets are summarized in groups, then the group summaries are combined
+                    let summarize = summary::hierarchical_summary(
+                        &responses,
+                        &*client,
+                        args.summary_chunk_size,
+                        args.resolved_summary_format(),
+                        args.summary_prompt_context(),
+                    );
+                    match query::with_deadline(deadline, summarize).await {
+                        Err(_) => {
+                            summary_timed_out = true;
+                            None
+                        }
+                        Ok(result) => Some(result),
                     }
+                }
+                None => {
+                    report_unavailable_summary_model(&args, warnings, &mut logger);
+                    None
+                }
+            }
+        };
 
-                    (None, None)
+        match outcome {
+            Some(Ok(summary)) => {
+                let duration = summary_start.elapsed();
+                if args.show_summary_status() {
+                    eprintln!("\u{2713} Summary generated");
+                }
+
+                // Log summary
+                if let Some(ref mut logger) = logger {
+                    logger.set_summary(&summary);
                 }
+
+                (Some(summary), Some(duration))
             }
-        } else {
-            (None, None)
+            Some(Err(e)) => {
+                warnings.push(format!("Summary generation failed: {}", e));
+
+                // Log summary error
+                if let Some(ref mut logger) = logger {
+                    logger.log_error("summary", "GENERATION_ERROR", &e.to_string(), None);
+                }
+
+                (None, None)
+            }
+            None => (None, None),
         }
     } else {
         (None, None)
     };
+    if let Some(task) = eager_task {
+        task.abort();
+    }
+
+    // Deadline hit while summarizing: the responses are logged, the summary is abandoned
+    if summary_timed_out {
+        warnings.push(format!(
+            "--max-runtime of {}s reached during summary generation",
+            args.max_runtime.unwrap_or_default()
+        ));
+        if let Some(mut logger) = logger {
+            logger.log_error(
+                "summary",
+                "DEADLINE_EXCEEDED",
+                "Cancelled by --max-runtime",
+                None,
+            );
+            logger.finalize_interaction(None)?;
+        }
+        return Err(CliError::Timeout(format!(
+            "Exceeded --max-runtime of {}s while generating the summary",
+            args.max_runtime.unwrap_or_default()
+        ))
+        .into());
+    }
+
+    if args.only_summary && digest.is_none() {
+        return Err(
+            "--only-summary requires a summary, but none was produced (at least two successful responses are needed)"
+                .into(),
+        );
+    }
+
+    if args.hide_responses && digest.is_none() {
+        warnings.push("--hide-responses is set but no summary was produced; the responses are only in the logs");
+    }
 
     // Output results
+    let mut out = result_writer(&args)?;
     if args.raw {
         // Raw output mode - just print responses
-        for (_, response) in &responses {
-            println!("{}", response);
+        if args.hides_responses() {
+            if let Some(digest) = &digest {
+                writeln!(out, "{}", digest)?;
+            }
+        } else {
+            for (_, response) in &responses {
+                writeln!(out, "{}", response)?;
+            }
         }
+        out.flush()?;
     } else {
-        output_results(&args, &responses, digest.as_deref())?;
+        output_results(
+            &args,
+            &responses,
+            digest.as_deref(),
+            &response_meta,
+            warnings.messages(),
+            &mut *out,
+        )?;
+        out.flush()?;
+        // JSON output carries the warnings itself
+        if args.format == "json" {
+            warnings.mark_reported();
+        }
     }
 
     // Show token usage table if requested
@@ -379,15 +1626,37 @@ async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Log interaction if requested (legacy simple logging)
-    log_interaction(&args, &responses, digest.as_deref())?;
+    log_interaction(&args, &responses, digest.as_deref(), file_stamp.as_deref())?;
+
+    // Collect this run's artifacts in one directory
+    if let Some(dir) = &run_dir {
+        let run_artifacts = artifacts::RunArtifacts {
+            prompt,
+            responses: &responses,
+            digest: digest.as_deref(),
+            meta: &response_meta,
+            query_duration,
+            summary_duration,
+        };
+        artifacts::write_run_artifacts(dir, &args, &run_artifacts)?;
+        if !args.quiet {
+            eprintln!("\u{2713} Run artifacts written to {}", dir.display());
+        }
+    }
 
     // Finalize comprehensive logging
     if let Some(mut logger) = logger {
-        logger.finalize_interaction(summary_duration)?;
+        if let Err(e) = logger.finalize_interaction(summary_duration) {
+            logging_failure(
+                &args,
+                warnings,
+                format!("Failed to write the structured log: {}", e),
+            )?;
+        }
 
         if !args.quiet && (args.log_metrics || args.log_errors || args.log_dir.is_some()) {
             if let Ok(stats) = logger.get_log_stats() {
-                println!(
+                eprintln!(
                     "\u{2713} Logged to structured logs ({} files, {})",
                     stats.total_files,
                     stats.size_human_readable()
@@ -396,206 +1665,965 @@ async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    Ok(())
-}
+    // Regression check against the saved baseline
+    if let Some(baseline) = baseline {
+        let results = compare::compare_to_baseline(&responses, &baseline, args.compare_tolerance);
+        let failed = results
+            .iter()
+            .filter(|(_, status)| !status.passed())
+            .count();
+
+        eprintln!(
+            "\nBaseline comparison (tolerance {:.2}):",
+            args.compare_tolerance
+        );
+        for (model, result) in &results {
+            match result {
+                compare::BaselineStatus::Match(score) => {
+                    eprintln!(
+                        "  \u{2713} {}: matches baseline (similarity {:.2})",
+                        model, score
+                    )
+                }
+                compare::BaselineStatus::Differs(score) => {
+                    eprintln!(
+                        "  \u{2717} {}: differs from baseline (similarity {:.2})",
+                        model, score
+                    )
+                }
+                compare::BaselineStatus::MissingBaseline => {
+                    eprintln!("  - {}: no baseline response, skipped", model)
+                }
+                compare::BaselineStatus::MissingResponse => {
+                    eprintln!("  \u{2717} {}: no response this run", model)
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(CliError::CheckFailed(format!(
+                "Regression check failed: {} model{} diverged from baseline",
+                failed,
+                if failed == 1 { "" } else { "s" }
+            ))
+            .into());
+        }
+    }
+
+    // --min-success: the partial results are out, but too few models answered to pass
+    if let Some(required) = args
+        .min_success
+        .filter(|&required| responses.len() < required)
+    {
+        return Err(CliError::NoResponses(format!(
+            "Only {} of {} models succeeded; --min-success requires {}",
+            responses.len(),
+            models_queried,
+            required
+        ))
+        .into());
+    }
 
-/// Save individual response to a file
-fn save_individual_response(
-    dir: &Path,
-    model: &str,
-    response: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    fs::create_dir_all(dir)?;
-    let filename = format!("{}.txt", model.to_lowercase().replace(' ', "_"));
-    let path = dir.join(filename);
-    fs::write(&path, response)?;
     Ok(())
 }
 
-/// Print a token-usage / latency table for --show-usage
-fn print_usage_table(rows: &[(String, Option<u32>, Option<u64>)]) {
-    println!("\n{:<20} {:>8}  {:>10}", "Model", "Tokens", "Latency");
-    println!("{}", "─".repeat(42));
-    for (name, tokens, latency_ms) in rows {
-        let tok = tokens.map_or_else(|| "—".to_string(), |t| t.to_string());
-        let lat = latency_ms.map_or_else(|| "—".to_string(), |ms| format!("{}ms", ms));
-        println!("{:<20} {:>8}  {:>10}", name, tok, lat);
+/// Finish reason for a response: the provider's own value, or `length` inferred when
+/// the completion used the whole --max-tokens budget and no reason was reported
+fn resolve_finish_reason(
+    reported: Option<&str>,
+    completion_tokens: Option<u32>,
+    max_tokens: u32,
+) -> Option<String> {
+    match (reported, completion_tokens) {
+        (Some(reason), _) => Some(reason.to_string()),
+        (None, Some(used)) if used >= max_tokens => Some("length".to_string()),
+        _ => None,
     }
-    println!();
 }
 
-/// Print available models
-fn print_available_models() {
-    println!("\u{1f916} Available AI Models\n");
-    println!("OpenAI:");
-    println!("  \u{2022} gpt-5.4         (flagship reasoning \u{2014} most capable)");
-    println!("  \u{2022} o3              (strong reasoning, complex tasks)");
-    println!("  \u{2022} gpt-4o          (fast, highly capable \u{2014} default)");
-    println!("  \u{2022} gpt-4o-mini     (faster, cheaper)\n");
-
-    println!("Google Gemini:");
-    println!("  \u{2022} gemini-3.1-pro        (most capable, reasoning-first)");
-    println!("  \u{2022} gemini-2.5-flash      (fast, cost-efficient \u{2014} default)");
-    println!("  \u{2022} gemini-2.5-flash-lite (fastest, highest throughput)\n");
-
-    println!("Anthropic Claude:");
-    println!("  \u{2022} claude-opus-4-6              (most capable)");
-    println!("  \u{2022} claude-sonnet-4-6            (balanced \u{2014} default)");
-    println!("  \u{2022} claude-haiku-4-5-20251001    (fast, lightweight)\n");
+/// Estimate the query cost and enforce --cost-limit, asking for confirmation on a terminal
+fn check_cost_limit(
+    args: &Args,
+    clients: &[Box<dyn AiClient>],
+    limit: f64,
+    warnings: &mut warnings::Warnings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{IsTerminal, Write};
 
-    println!("\u{1f4a1} Set your preferred models with:");
-    println!("   --gpt-model, --gemini-model, --claude-model");
-}
+    let input = format!(
+        "{}{}",
+        args.system_prompt.as_deref().unwrap_or_default(),
+        args.prompt.as_deref().unwrap_or_default()
+    );
+    let models: Vec<&str> = clients.iter().map(|c| c.model()).collect();
+    let (estimate, unpriced) = cost::estimate_cost(
+        cost::PRICING,
+        &models,
+        cost::estimate_tokens(&input),
+        args.max_tokens,
+    );
 
-/// Check API key configuration and provide setup guidance
-fn run_doctor(_args: &Args) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\u{1f3e5} ChatDelta Doctor - API Key Configuration Check\n");
-    println!("\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\n");
+    if !unpriced.is_empty() {
+        warnings.push(format!(
+            "No pricing known for {}; excluded from the cost estimate",
+            unpriced.join(", ")
+        ));
+    }
+    if args.verbose {
+        eprintln!(
+            "\u{1f4b0} Estimated cost: ${:.4} (limit ${:.4})",
+            estimate, limit
+        );
+    }
 
-    let mut all_configured = true;
-    let mut configured_count = 0;
+    let interactive = io::stdin().is_terminal();
+    match cost::decide(estimate, limit, interactive, args.yes) {
+        cost::CostDecision::Proceed => Ok(()),
+        cost::CostDecision::Confirm => {
+            eprint!(
+                "Estimated cost ${:.4} exceeds --cost-limit ${:.4}. Proceed? [y/N] ",
+                estimate, limit
+            );
+            io::stderr().flush().ok();
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                Ok(())
+            } else {
+                Err(CliError::Validation(
+                    "Aborted: estimated cost exceeds --cost-limit".to_string(),
+                )
+                .into())
+            }
+        }
+        cost::CostDecision::Abort => Err(CliError::Validation(format!(
+            "Estimated cost ${:.4} exceeds --cost-limit ${:.4} (pass --yes to run anyway)",
+            estimate, limit
+        ))
+        .into()),
+    }
+}
 
-    let openai_key = env::var("OPENAI_API_KEY")
-        .or_else(|_| env::var("CHATGPT_API_KEY"));
+/// Run each prompt in a --batch file as its own query, once the pre-flight estimate is
+/// confirmed. `args` are already validated; each prompt gets the prefix, --var values,
+/// comment stripping and PII check a single prompt would. A failing prompt doesn't stop
+/// the rest; the batch fails if any did.
+async fn run_batch(
+    args: Args,
+    path: &Path,
+    file_stamp: Option<String>,
+    warnings: &mut warnings::Warnings,
+    status: &mut status::RunStatus,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| {
+        CliError::Validation(format!(
+            "Failed to read batch file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let prompts = batch::read_prompts(&text);
+    if prompts.is_empty() {
+        return Err(
+            CliError::Validation(format!("Batch file {} has no prompts", path.display())).into(),
+        );
+    }
 
-    match openai_key {
-        Ok(key) => {
-            if !key.is_empty() {
-                let var_name = if env::var("OPENAI_API_KEY").is_ok() {
-                    "OPENAI_API_KEY"
-                } else {
-                    "CHATGPT_API_KEY"
-                };
-                println!("\u{2713} OpenAI API Key: Configured ({})", var_name);
-                configured_count += 1;
-            } else {
-                println!("\u{2717} OpenAI API Key: Empty");
-                all_configured = false;
+    let estimate = batch::estimate(&args, &prompts, cost::PRICING);
+    eprint!("{}", batch::render(&estimate));
+    match batch::decide(io::stdin().is_terminal(), args.yes) {
+        cost::CostDecision::Proceed => {}
+        cost::CostDecision::Confirm => {
+            eprint!(
+                "Run {} requests? [y/N] ",
+                estimate.requests + estimate.summary_requests
+            );
+            io::stderr().flush().ok();
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                return Err(
+                    CliError::Validation("Aborted: batch not confirmed".to_string()).into(),
+                );
             }
         }
-        Err(_) => {
-            println!("\u{2717} OpenAI API Key: Not found");
-            println!("  \u{2192} Get your API key at: https://platform.openai.com/api-keys");
-            println!("  \u{2192} Set it with: export OPENAI_API_KEY=your-key-here");
-            println!("  \u{2192} Or: export CHATGPT_API_KEY=your-key-here\n");
-            all_configured = false;
+        cost::CostDecision::Abort => {
+            return Err(CliError::Validation(
+                "Batch runs need confirmation; pass --yes to run without a terminal".to_string(),
+            )
+            .into());
         }
     }
 
-    match env::var("GEMINI_API_KEY") {
-        Ok(key) => {
-            if !key.is_empty() {
-                println!("\u{2713} Gemini API Key: Configured");
-                configured_count += 1;
-            } else {
-                println!("\u{2717} Gemini API Key: Empty");
-                all_configured = false;
-            }
+    let template_vars = prompt::parse_vars(&args.vars)?;
+    let mut failed = 0;
+    for (index, prompt) in prompts.into_iter().enumerate() {
+        if !args.quiet {
+            eprintln!("\n[{}/{}] {}", index + 1, estimate.prompts, prompt);
         }
-        Err(_) => {
-            println!("\u{2717} Gemini API Key: Not found");
-            println!("  \u{2192} Get your API key at: https://makersuite.google.com/app/apikey");
-            println!("  \u{2192} Set it with: export GEMINI_API_KEY=your-key-here\n");
-            all_configured = false;
+        let mut prompt_args = args.clone();
+        prompt_args.batch = None;
+        prompt_args.prompt = Some(match &args.prompt_prefix {
+            Some(prefix) => format!("{}\n\n{}", prefix, prompt),
+            None => prompt,
+        });
+        prompt_args.interaction_id = args
+            .interaction_id
+            .as_ref()
+            .map(|id| format!("{}-{}", id, index + 1));
+        // Each prompt starts one key further along, spreading the batch over rotated keys
+        prompt_args.key_offset = index;
+        let result = match finish_prompt(&mut prompt_args, &template_vars) {
+            Ok(()) => run_query(prompt_args, file_stamp.clone(), warnings, status).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: prompt {} failed: {}", index + 1, e);
+            failed += 1;
         }
     }
+    if failed > 0 {
+        return Err(format!("{} of {} batch prompts failed", failed, estimate.prompts).into());
+    }
+    Ok(())
+}
+
+/// A structured log that can't be written is a warning, or an error with --strict-logging
+fn logging_failure(
+    args: &Args,
+    warnings: &mut warnings::Warnings,
+    message: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if args.strict_logging {
+        return Err(message.into());
+    }
+    warnings.push(message);
+    Ok(())
+}
 
-    let anthropic_key = env::var("ANTHROPIC_API_KEY")
-        .or_else(|_| env::var("CLAUDE_API_KEY"));
+/// Whether a finish reason means the output hit the token limit
+/// (OpenAI reports `length`, Anthropic `max_tokens`, Gemini `MAX_TOKENS`)
+fn is_length_cutoff(reason: &str) -> bool {
+    reason.eq_ignore_ascii_case("length") || reason.eq_ignore_ascii_case("max_tokens")
+}
 
-    match anthropic_key {
-        Ok(key) => {
-            if !key.is_empty() {
-                let var_name = if env::var("ANTHROPIC_API_KEY").is_ok() {
-                    "ANTHROPIC_API_KEY"
-                } else {
-                    "CLAUDE_API_KEY"
-                };
-                println!("\u{2713} Anthropic API Key: Configured ({})", var_name);
-                configured_count += 1;
-            } else {
-                println!("\u{2717} Anthropic API Key: Empty");
-                all_configured = false;
-            }
-        }
-        Err(_) => {
-            println!("\u{2717} Anthropic API Key: Not found");
-            println!("  \u{2192} Get your API key at: https://console.anthropic.com/settings/keys");
-            println!("  \u{2192} Set it with: export ANTHROPIC_API_KEY=your-key-here");
-            println!("  \u{2192} Or: export CLAUDE_API_KEY=your-key-here\n");
-            all_configured = false;
+/// The AI --force-summary-model-only queries: the first selected AI, in summary
+/// preference order, that has an API key (any selected AI with --mock). `var` looks up
+/// environment variables.
+fn summary_model_only(args: &Args, var: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    let selected: Vec<&str> = cli::AI_NAMES
+        .iter()
+        .copied()
+        .filter(|ai| args.should_use_ai(ai))
+        .collect();
+    summary::summary_ai_order(&[], &selected)
+        .into_iter()
+        .filter(|ai| selected.contains(ai))
+        .find(|ai| args.use_mock() || keys::key_source(args, ai, &var).is_some())
+}
+
+/// AIs to try for the summary: --summary-model first, then a provider that answered (or
+/// at least was queried) over any other. --no-fallback-summary leaves only --summary-model.
+fn summary_order(args: &Args, answered: &[&str]) -> Vec<&'static str> {
+    let queried: Vec<&str> = cli::AI_NAMES
+        .iter()
+        .copied()
+        .filter(|ai| args.should_use_ai(ai))
+        .collect();
+    let mut order = summary::summary_ai_order(answered, &queried);
+    if let Some(chosen) = args.summary_model.as_deref() {
+        order.sort_by_key(|ai| *ai != chosen);
+        if args.no_fallback_summary {
+            order.retain(|ai| *ai == chosen);
         }
     }
+    order
+}
 
-    println!("\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}");
-    println!("\u{1f4ca} Summary: {}/3 API keys configured", configured_count);
+/// Summary client for a run: the first AI in `summary_order` that has a key
+fn summary_client(
+    args: &Args,
+    answered: &[&str],
+    openai_keys: &mut keys::KeyRotation,
+    anthropic_keys: &mut keys::KeyRotation,
+) -> Option<Box<dyn AiClient>> {
+    summary_order(args, answered).into_iter().find_map(|ai| {
+        let key = match ai {
+            "gemini" => env::var(Provider::Gemini.env_var()).ok(),
+            "claude" => anthropic_keys.next_key().map(|(_, key)| key),
+            _ => openai_keys.next_key().map(|(_, key)| key),
+        }?;
+        tracing::debug!(ai, "summary provider chosen");
+        let config = client_config(args, ai, args.retries);
+        create_client(
+            Provider::for_ai(ai).client_id(),
+            &key,
+            args.model_for(ai),
+            config,
+        )
+        .ok()
+    })
+}
 
-    if all_configured {
-        println!("\n\u{2713} All API keys are configured! You're ready to use ChatDelta.");
-        println!("  Run 'chatdelta --test' to verify API connections.");
-    } else if configured_count > 0 {
-        println!("\n\u{26a0}\u{fe0f}  Some API keys are missing. ChatDelta will work with configured providers.");
-        println!("  You need at least one API key to use ChatDelta.");
-    } else {
-        println!("\n\u{2717} No API keys configured. Please set up at least one API key to use ChatDelta.");
-        return Err("No API keys configured".into());
+/// With --no-fallback-summary, record that --summary-model couldn't be used, as a warning
+/// and in the error log
+fn report_unavailable_summary_model(
+    args: &Args,
+    warnings: &mut warnings::Warnings,
+    logger: &mut Option<Logger>,
+) {
+    let Some(ai) = args
+        .summary_model
+        .as_deref()
+        .filter(|_| args.no_fallback_summary)
+    else {
+        return;
+    };
+    let message = format!(
+        "Summary model '{}' is unavailable (no API key or client error); no summary produced (--no-fallback-summary)",
+        ai
+    );
+    if let Some(logger) = logger {
+        logger.log_error("summary", "SUMMARY_MODEL_UNAVAILABLE", &message, None);
     }
+    warnings.push(message);
+}
 
-    Ok(())
+/// Summary clients for each AI in --summary-models that has an API key
+fn summary_model_clients(
+    args: &Args,
+    openai_keys: &mut keys::KeyRotation,
+    anthropic_keys: &mut keys::KeyRotation,
+    warnings: &mut warnings::Warnings,
+) -> Vec<Box<dyn AiClient>> {
+    let mut clients = Vec::new();
+    for ai in &args.summary_models {
+        let key = match ai.as_str() {
+            "gpt" => openai_keys.next_key().map(|(_, key)| key),
+            "gemini" => env::var(Provider::Gemini.env_var()).ok(),
+            _ => anthropic_keys.next_key().map(|(_, key)| key),
+        };
+        let Some(key) = key else {
+            warnings.push(format!(
+                "No API key for summary model '{}', skipping it",
+                ai
+            ));
+            continue;
+        };
+        let config = client_config(args, ai, args.retries);
+        match create_client(
+            Provider::for_ai(ai).client_id(),
+            &key,
+            args.model_for(ai),
+            config,
+        ) {
+            Ok(client) => clients.push(client),
+            Err(e) => warnings.push(format!("Failed to create {} summary client: {}", ai, e)),
+        }
+    }
+    clients
 }
 
-/// Test API connections
-async fn test_connections(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+/// A response as --response-filter leaves it
+fn filtered(filter: &Option<filter::ResponseFilter>, content: &str) -> String {
+    match filter {
+        Some(filter) => filter.apply(content),
+        None => content.to_string(),
+    }
+}
+
+/// Clients for the AIs left out of the run that have a key, for --retry-on-refusal
+fn unselected_clients(
+    args: &Args,
+    openai_keys: &mut keys::KeyRotation,
+    anthropic_keys: &mut keys::KeyRotation,
+) -> Vec<Box<dyn AiClient>> {
+    cli::AI_NAMES
+        .iter()
+        .filter(|ai| !args.should_use_ai(ai))
+        .filter_map(|&ai| {
+            let key = match ai {
+                "gpt" => openai_keys.next_key().map(|(_, key)| key),
+                "gemini" => env::var(Provider::Gemini.env_var()).ok(),
+                _ => anthropic_keys.next_key().map(|(_, key)| key),
+            }?;
+            let config = client_config(args, ai, args.retries);
+            create_client(
+                Provider::for_ai(ai).client_id(),
+                &key,
+                args.model_for(ai),
+                config,
+            )
+            .ok()
+        })
+        .collect()
+}
+
+/// Create the fallback client for an AI when a fallback model is configured.
+/// It shares the primary's key and configuration and is keyed by the primary's display name.
+fn add_fallback(
+    model: Option<&str>,
+    provider: &str,
+    key: &str,
+    config: ClientConfig,
+    primary: &dyn AiClient,
+    fallbacks: &mut HashMap<String, Box<dyn AiClient>>,
+    warnings: &mut warnings::Warnings,
+) {
+    let Some(model) = model else {
+        return;
+    };
+    match create_client(provider, key, model, config) {
+        Ok(fallback) => {
+            fallbacks.insert(primary.name().to_string(), fallback);
+        }
+        Err(e) => warnings.push(format!(
+            "Failed to create {} fallback client ({}): {}",
+            primary.name(),
+            model,
+            e
+        )),
+    }
+}
+
+/// Build the client configuration for one AI (gpt, gemini, claude).
+/// Shared settings come from the CLI flags; the base URL override is per provider.
+fn client_config(args: &Args, ai_name: &str, retries: u32) -> ClientConfig {
     let mut config_builder = ClientConfig::builder()
-        .timeout(Duration::from_secs(args.timeout))
-        .retries(0)
+        .timeout(args.request_timeout())
+        .retries(retries)
         .max_tokens(args.max_tokens);
 
     if let Some(temp) = args.temperature {
         config_builder = config_builder.temperature(temp);
     }
 
+    // Set retry strategy (a per-provider override wins over --retry-strategy)
+    let retry_strategy = match args.retry_strategy_for(ai_name) {
+        "linear" => RetryStrategy::Linear(Duration::from_secs(1)),
+        "fixed" => RetryStrategy::Fixed(Duration::from_secs(2)),
+        _ => RetryStrategy::Exponential(Duration::from_secs(1)),
+    };
+    config_builder = config_builder.retry_strategy(retry_strategy);
+
+    // Wire up system prompt (available since chatdelta 0.4.0)
     if let Some(ref system_prompt) = args.system_prompt {
         config_builder = config_builder.system_message(system_prompt);
     }
 
-    let config = config_builder.build();
+    // Route through a proxy or gateway when a base URL override is configured
+    if let Some(base_url) = args.base_url(ai_name) {
+        config_builder = config_builder.base_url(base_url);
+    }
 
-    let test_prompt = "Hello, please respond with just 'OK' to confirm you're working.";
-    let mut all_passed = true;
+    config_builder.build()
+}
 
-    if args.should_use_ai("gpt") {
-        let openai_key = env::var("OPENAI_API_KEY")
-            .or_else(|_| env::var("CHATGPT_API_KEY"));
+/// Raise a queried model's client timeout to --max-timeout under --timeout-escalation.
+/// The per-attempt timeouts are then applied in the query layer, so the client must allow
+/// the longest of them; fallback, summary and other clients keep the request timeout.
+fn with_timeout_cap(args: &Args, mut config: ClientConfig) -> ClientConfig {
+    if args.timeout_escalation {
+        config.timeout = args.timeout_cap();
+    }
+    config
+}
 
-        match openai_key {
-            Ok(key) => match create_client("openai", &key, &args.gpt_model, config.clone()) {
-                Ok(client) => match client.send_prompt(test_prompt).await {
-                    Ok(_) => println!("\u{2713} ChatGPT connection successful"),
-                    Err(e) => {
-                        println!("\u{2717} ChatGPT connection failed: {}", e);
-                        all_passed = false;
-                    }
-                },
-                Err(e) => {
-                    println!("\u{2717} ChatGPT client creation failed: {}", e);
-                    all_passed = false;
-                }
-            },
-            Err(_) => {
-                println!("\u{2717} ChatGPT: OPENAI_API_KEY or CHATGPT_API_KEY not set");
-                all_passed = false;
-            }
-        }
+/// Skip a provider that can't be used, with a warning. Under --strict-models a provider
+/// named in --only is an error instead.
+fn skip_provider(
+    args: &Args,
+    ai_name: &str,
+    reason: String,
+    warnings: &mut warnings::Warnings,
+) -> Result<(), String> {
+    if args.strict_models && args.explicitly_selected(ai_name) {
+        return Err(format!(
+            "{} (--strict-models: {} was requested with --only)",
+            reason, ai_name
+        ));
     }
+    warnings.push(reason);
+    Ok(())
+}
 
-    if args.should_use_ai("gemini") {
-        match env::var("GEMINI_API_KEY") {
-            Ok(key) => match create_client("gemini", &key, &args.gemini_model, config.clone()) {
-                Ok(client) => match client.send_prompt(test_prompt).await {
-                    Ok(_) => println!("\u{2713} Gemini connection successful"),
-                    Err(e) => {
-                        println!("\u{2717} Gemini connection failed: {}", e);
+/// Prompt for --benchmark when none is given
+const BENCHMARK_PROMPT: &str = "Reply with the single word: pong";
+
+/// Send the prompt `runs` times to each client and report latency percentiles and success
+/// rate per model (--benchmark). No summary is generated.
+async fn run_benchmark(
+    args: &Args,
+    clients: Vec<Box<dyn AiClient>>,
+    runs: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let prompt: Arc<str> = Arc::from(args.prompt.as_deref().unwrap_or(BENCHMARK_PROMPT));
+    let names: Vec<String> = clients.iter().map(|c| c.name().to_string()).collect();
+    let clients: Vec<Arc<dyn AiClient>> = clients.into_iter().map(Arc::from).collect();
+    let semaphore = Arc::new(Semaphore::new(
+        args.max_concurrency.unwrap_or(clients.len()),
+    ));
+
+    if !args.quiet {
+        eprintln!(
+            "Benchmarking {} model{} with {} run{} each...",
+            clients.len(),
+            if clients.len() == 1 { "" } else { "s" },
+            runs,
+            if runs == 1 { "" } else { "s" }
+        );
+    }
+
+    // Interleave models so permits are handed out round-robin
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..runs {
+        for client in &clients {
+            let (client, prompt, semaphore) = (
+                Arc::clone(client),
+                Arc::clone(&prompt),
+                Arc::clone(&semaphore),
+            );
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let started = std::time::Instant::now();
+                let result = client.send_prompt_with_metadata(&prompt).await;
+                let elapsed = started.elapsed().as_millis() as u64;
+                let outcome = match result {
+                    Ok(r) => Ok((
+                        r.metadata.latency_ms.unwrap_or(elapsed),
+                        r.metadata.total_tokens,
+                    )),
+                    Err(_) => Err(elapsed),
+                };
+                (client.name().to_string(), outcome)
+            });
+        }
+    }
+
+    let mut metrics = metrics_display::CliMetrics::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined? {
+            (name, Ok((latency, tokens))) => metrics.record_success(&name, latency, tokens),
+            (name, Err(latency)) => metrics.record_failure(&name, latency),
+        }
+    }
+
+    if let Some(path) = &args.metrics_file {
+        metrics.save_to_file(path, args.resolved_metrics_format())?;
+    }
+
+    let rows: Vec<_> = names
+        .iter()
+        .map(|name| metrics_display::BenchmarkRow::new(&metrics, name, runs))
+        .collect();
+    let mut out = result_writer(args)?;
+    if args.format == "json" {
+        let report = serde_json::json!({ "prompt": &*prompt, "runs": runs, "models": rows });
+        writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+    } else {
+        write!(out, "{}", metrics_display::render_benchmark(&rows))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Where the result goes: the --output file, the pager with --paginate on a terminal, or
+/// stdout
+fn result_writer(args: &Args) -> Result<Box<dyn Write>, String> {
+    match &args.output {
+        Some(path) => fs::File::create(path)
+            .map(|file| Box::new(file) as Box<dyn Write>)
+            .map_err(|e| format!("Failed to create output file '{}': {}", path.display(), e)),
+        None if args.paginate && !args.quiet && io::stdout().is_terminal() => {
+            let command = pager::pager_command(std::env::var("PAGER").ok());
+            let (out, paged) = pager::writer_or_stdout(&command);
+            if !paged && args.verbose {
+                eprintln!("Could not start pager '{}'; writing to stdout", command);
+            }
+            Ok(out)
+        }
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+/// Write streamed chunks as they arrive, flushing each one so an --output file can be
+/// followed with `tail -f`. Returns the bytes written, or the error and how far it got.
+async fn write_stream(
+    rx: &mut mpsc::UnboundedReceiver<StreamChunk>,
+    out: &mut dyn Write,
+) -> Result<usize, (usize, io::Error)> {
+    let mut written = 0;
+    while let Some(chunk) = rx.recv().await {
+        out.write_all(chunk.content.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|e| (written, e))?;
+        written += chunk.content.len();
+        if chunk.finished {
+            writeln!(out)
+                .and_then(|_| out.flush())
+                .map_err(|e| (written, e))?;
+            break;
+        }
+    }
+    Ok(written)
+}
+
+/// Write the prompt as it is sent to --save-prompt, creating missing directories
+fn save_prompt(path: &Path, prompt: &str) -> Result<(), String> {
+    let write = || -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, prompt)
+    };
+    write().map_err(|e| format!("Failed to save prompt to '{}': {}", path.display(), e))
+}
+
+/// Save individual response to a file, `-<stamp>` suffixed with --timestamp-filenames.
+/// With --echo-prompt the prompt comes first, under headers like text output's
+/// --include-prompt, so the file stands on its own.
+fn save_individual_response(
+    dir: &Path,
+    model: &str,
+    response: &str,
+    prompt: Option<&str>,
+    stamp: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let filename = format!("{}.txt", model.to_lowercase().replace(' ', "_"));
+    let path = output::stamped_path(&dir.join(filename), stamp);
+    match prompt {
+        Some(prompt) => fs::write(
+            &path,
+            format!(
+                "=== Prompt ===\n{}\n\n=== {} ===\n{}",
+                prompt, model, response
+            ),
+        )?,
+        None => fs::write(&path, response)?,
+    }
+    Ok(())
+}
+
+/// Print a token-usage / latency table for --show-usage (to stderr, beside the result)
+fn print_usage_table(rows: &[(String, Option<u32>, Option<u64>)]) {
+    eprintln!("\n{:<20} {:>8}  {:>10}", "Model", "Tokens", "Latency");
+    eprintln!("{}", "─".repeat(42));
+    for (name, tokens, latency_ms) in rows {
+        let tok = tokens.map_or_else(|| "—".to_string(), |t| t.to_string());
+        let lat = latency_ms.map_or_else(|| "—".to_string(), |ms| format!("{}ms", ms));
+        eprintln!("{:<20} {:>8}  {:>10}", name, tok, lat);
+    }
+    eprintln!();
+}
+
+/// Print available models; with --detailed, their capabilities as a table or --format json
+fn print_available_models(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.detailed && args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(models::MODELS)?);
+        return Ok(());
+    }
+    println!("\u{1f916} Available AI Models\n");
+    if args.detailed {
+        println!("{}", models::render_table(models::MODELS));
+    } else {
+        for (ai, heading) in [
+            ("gpt", "OpenAI"),
+            ("gemini", "Google Gemini"),
+            ("claude", "Anthropic Claude"),
+        ] {
+            println!("{}:", heading);
+            let listed: Vec<_> = models::MODELS.iter().filter(|m| m.provider == ai).collect();
+            let width = listed.iter().map(|m| m.id.len()).max().unwrap_or(0);
+            for m in listed {
+                println!("  \u{2022} {:<width$}  ({})", m.id, m.note, width = width);
+            }
+            println!();
+        }
+    }
+
+    println!("\u{1f4a1} Set your preferred models with:");
+    println!("   --gpt-model, --gemini-model, --claude-model");
+    Ok(())
+}
+
+/// Print aggregate statistics across the JSON logs (--report), as text or --format json
+fn run_report(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = logging::log_dir(args);
+    let since = args
+        .since
+        .as_deref()
+        .map(logging::parse_since)
+        .transpose()?;
+    let entries = logging::read_log_entries(&dir, since)?;
+
+    // Costs are priced at the currently configured model for each provider
+    let models: HashMap<String, String> = cli::AI_NAMES
+        .iter()
+        .map(|ai| {
+            (
+                Args::display_name(ai).to_string(),
+                args.model_for(ai).to_string(),
+            )
+        })
+        .collect();
+    let report = logging::aggregate(&entries, &models);
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if entries.is_empty() {
+        println!(
+            "No logged interactions found in {} (reports read logs written with --log-format json)",
+            dir.display()
+        );
+    } else {
+        print!("{}", report.render());
+    }
+    Ok(())
+}
+
+/// --cache-stats, --cache-prune, --cache-clear, --cache-import and --cache-export; a
+/// missing cache directory is a no-op
+fn run_cache_command(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = cache::cache_dir(args.cache_dir.as_deref());
+    if let Some(tarball) = &args.cache_import {
+        println!("{}", cache::import(&dir, tarball)?.render());
+    }
+    if args.cache_stats {
+        match cache::stats(&dir)? {
+            Some(stats) if args.format == "json" => {
+                println!("{}", serde_json::to_string_pretty(&stats)?)
+            }
+            Some(stats) => {
+                println!("Response cache: {}", dir.display());
+                println!("  Entries: {}", stats.entries);
+                println!("  Size:    {}", stats.size_human_readable());
+                if let Some(oldest) = stats.oldest_entry {
+                    println!("  Oldest:  {}", oldest.format("%Y-%m-%d %H:%M:%S UTC"));
+                }
+            }
+            None => println!("No response cache at {}", dir.display()),
+        }
+    }
+    if args.cache_prune {
+        match cache::prune(&dir, args.cache_ttl, chrono::Utc::now())? {
+            Some(removed) => println!(
+                "Pruned {} cached response{} older than {}s",
+                removed,
+                if removed == 1 { "" } else { "s" },
+                args.cache_ttl
+            ),
+            None => println!("No response cache at {}; nothing to prune", dir.display()),
+        }
+    }
+    if args.cache_clear {
+        match cache::clear(&dir)? {
+            Some(removed) => println!(
+                "Cleared {} cached response{} from {}",
+                removed,
+                if removed == 1 { "" } else { "s" },
+                dir.display()
+            ),
+            None => println!("No response cache at {}; nothing to clear", dir.display()),
+        }
+    }
+    if let Some(tarball) = &args.cache_export {
+        let exported = cache::export(&dir, tarball)?;
+        println!(
+            "Exported {} cached response{} to {}",
+            exported,
+            if exported == 1 { "" } else { "s" },
+            tarball.display()
+        );
+    }
+    Ok(())
+}
+
+/// Print the sessions found in the logs (--list-sessions), as text or --format json
+fn list_sessions(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = logging::log_dir(args);
+    let sessions = logging::index_sessions(&logging::read_session_records(&dir)?);
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+    } else if sessions.is_empty() {
+        println!("No logged sessions found in {}", dir.display());
+    } else {
+        print!("{}", logging::render_sessions(&sessions));
+    }
+    Ok(())
+}
+
+/// Check API key configuration and provide setup guidance
+fn run_doctor(_args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    println!("\u{1f3e5} ChatDelta Doctor - API Key Configuration Check\n");
+    println!("\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\n");
+
+    let mut all_configured = true;
+    let mut configured_count = 0;
+
+    let openai_key = env::var("OPENAI_API_KEY").or_else(|_| env::var("CHATGPT_API_KEY"));
+
+    match openai_key {
+        Ok(key) => {
+            if !key.is_empty() {
+                let var_name = if env::var("OPENAI_API_KEY").is_ok() {
+                    "OPENAI_API_KEY"
+                } else {
+                    "CHATGPT_API_KEY"
+                };
+                println!("\u{2713} OpenAI API Key: Configured ({})", var_name);
+                configured_count += 1;
+            } else {
+                println!("\u{2717} OpenAI API Key: Empty");
+                all_configured = false;
+            }
+        }
+        Err(_) => {
+            println!("\u{2717} OpenAI API Key: Not found");
+            println!("  \u{2192} Get your API key at: https://platform.openai.com/api-keys");
+            println!("  \u{2192} Set it with: export OPENAI_API_KEY=your-key-here");
+            println!("  \u{2192} Or: export CHATGPT_API_KEY=your-key-here\n");
+            all_configured = false;
+        }
+    }
+
+    match env::var(Provider::Gemini.env_var()) {
+        Ok(key) => {
+            if !key.is_empty() {
+                println!("\u{2713} Gemini API Key: Configured");
+                configured_count += 1;
+            } else {
+                println!("\u{2717} Gemini API Key: Empty");
+                all_configured = false;
+            }
+        }
+        Err(_) => {
+            println!("\u{2717} Gemini API Key: Not found");
+            println!("  \u{2192} Get your API key at: https://makersuite.google.com/app/apikey");
+            println!("  \u{2192} Set it with: export GEMINI_API_KEY=your-key-here\n");
+            all_configured = false;
+        }
+    }
+
+    let anthropic_key = env::var("ANTHROPIC_API_KEY").or_else(|_| env::var("CLAUDE_API_KEY"));
+
+    match anthropic_key {
+        Ok(key) => {
+            if !key.is_empty() {
+                let var_name = if env::var("ANTHROPIC_API_KEY").is_ok() {
+                    "ANTHROPIC_API_KEY"
+                } else {
+                    "CLAUDE_API_KEY"
+                };
+                println!("\u{2713} Anthropic API Key: Configured ({})", var_name);
+                configured_count += 1;
+            } else {
+                println!("\u{2717} Anthropic API Key: Empty");
+                all_configured = false;
+            }
+        }
+        Err(_) => {
+            println!("\u{2717} Anthropic API Key: Not found");
+            println!("  \u{2192} Get your API key at: https://console.anthropic.com/settings/keys");
+            println!("  \u{2192} Set it with: export ANTHROPIC_API_KEY=your-key-here");
+            println!("  \u{2192} Or: export CLAUDE_API_KEY=your-key-here\n");
+            all_configured = false;
+        }
+    }
+
+    println!("\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}\u{2501}");
+    println!(
+        "\u{1f4ca} Summary: {}/3 API keys configured",
+        configured_count
+    );
+
+    if all_configured {
+        println!("\n\u{2713} All API keys are configured! You're ready to use ChatDelta.");
+        println!("  Run 'chatdelta --test' to verify API connections.");
+    } else if configured_count > 0 {
+        println!("\n\u{26a0}\u{fe0f}  Some API keys are missing. ChatDelta will work with configured providers.");
+        println!("  You need at least one API key to use ChatDelta.");
+    } else {
+        println!("\n\u{2717} No API keys configured. Please set up at least one API key to use ChatDelta.");
+        return Err(CliError::NoClients("No API keys configured".to_string()).into());
+    }
+
+    Ok(())
+}
+
+/// Client configuration for --test: --test-retries instead of --retries, so a transient
+/// blip can be tolerated without slowing down a real failure report by default
+fn test_client_config(args: &Args, ai_name: &str) -> ClientConfig {
+    client_config(args, ai_name, args.test_retries)
+}
+
+/// The first API key configured for an AI (gpt, gemini, claude)
+fn provider_key(args: &Args, ai: &str) -> Option<String> {
+    match ai {
+        "gpt" => keys::openai_keys(args).next_key().map(|(_, key)| key),
+        "gemini" => env::var(Provider::Gemini.env_var()).ok(),
+        _ => keys::anthropic_keys(args).next_key().map(|(_, key)| key),
+    }
+}
+
+/// Send the --test-prompt probe to a client, returning its reply
+async fn probe_connection(client: &dyn AiClient, args: &Args) -> Result<String, ClientError> {
+    client.send_prompt(&args.test_prompt).await
+}
+
+/// Test API connections
+async fn test_connections(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let providers = environment::gather(args, |name| std::env::var(name).ok());
+    println!(
+        "{}",
+        environment::render(
+            &providers,
+            env!("CARGO_PKG_VERSION"),
+            environment::library_version()
+        )
+    );
+    let mut all_passed = true;
+
+    if args.should_use_ai("gpt") {
+        match keys::openai_keys(args).next_key() {
+            Some((_, key)) => match create_client(
+                Provider::Gpt.client_id(),
+                &key,
+                &args.gpt_model,
+                test_client_config(args, "gpt"),
+            ) {
+                Ok(client) => match probe_connection(client.as_ref(), args).await {
+                    Ok(_) => println!("\u{2713} ChatGPT connection successful"),
+                    Err(e) => {
+                        println!("\u{2717} ChatGPT connection failed: {}", e);
+                        all_passed = false;
+                    }
+                },
+                Err(e) => {
+                    println!("\u{2717} ChatGPT client creation failed: {}", e);
+                    all_passed = false;
+                }
+            },
+            None => {
+                println!("\u{2717} ChatGPT: OPENAI_API_KEY or CHATGPT_API_KEY not set");
+                all_passed = false;
+            }
+        }
+    }
+
+    if args.should_use_ai("gemini") {
+        match env::var(Provider::Gemini.env_var()) {
+            Ok(key) => match create_client(
+                Provider::Gemini.client_id(),
+                &key,
+                &args.gemini_model,
+                test_client_config(args, "gemini"),
+            ) {
+                Ok(client) => match probe_connection(client.as_ref(), args).await {
+                    Ok(_) => println!("\u{2713} Gemini connection successful"),
+                    Err(e) => {
+                        println!("\u{2717} Gemini connection failed: {}", e);
                         all_passed = false;
                     }
                 },
@@ -612,12 +2640,14 @@ async fn test_connections(args: &Args) -> Result<(), Box<dyn std::error::Error>>
     }
 
     if args.should_use_ai("claude") {
-        let anthropic_key = env::var("ANTHROPIC_API_KEY")
-            .or_else(|_| env::var("CLAUDE_API_KEY"));
-
-        match anthropic_key {
-            Ok(key) => match create_client("claude", &key, &args.claude_model, config.clone()) {
-                Ok(client) => match client.send_prompt(test_prompt).await {
+        match keys::anthropic_keys(args).next_key() {
+            Some((_, key)) => match create_client(
+                Provider::Claude.client_id(),
+                &key,
+                &args.claude_model,
+                test_client_config(args, "claude"),
+            ) {
+                Ok(client) => match probe_connection(client.as_ref(), args).await {
                     Ok(_) => println!("\u{2713} Claude connection successful"),
                     Err(e) => {
                         println!("\u{2717} Claude connection failed: {}", e);
@@ -629,18 +2659,52 @@ async fn test_connections(args: &Args) -> Result<(), Box<dyn std::error::Error>>
                     all_passed = false;
                 }
             },
-            Err(_) => {
+            None => {
                 println!("\u{2717} Claude: ANTHROPIC_API_KEY or CLAUDE_API_KEY not set");
                 all_passed = false;
             }
         }
     }
 
+    for (alias, entry) in args.selected_aliases() {
+        let ai = entry.ai();
+        let Some(key) = provider_key(args, ai) else {
+            println!(
+                "\u{2717} {}: no {} API key for this model alias",
+                alias,
+                Args::display_name(ai)
+            );
+            all_passed = false;
+            continue;
+        };
+        match create_client(
+            Provider::for_ai(ai).client_id(),
+            &key,
+            &entry.model,
+            test_client_config(args, ai),
+        ) {
+            Ok(client) => match probe_connection(client.as_ref(), args).await {
+                Ok(_) => println!("\u{2713} {} ({}) connection successful", alias, entry.model),
+                Err(e) => {
+                    println!(
+                        "\u{2717} {} ({}) connection failed: {}",
+                        alias, entry.model, e
+                    );
+                    all_passed = false;
+                }
+            },
+            Err(e) => {
+                println!("\u{2717} {} client creation failed: {}", alias, e);
+                all_passed = false;
+            }
+        }
+    }
+
     if all_passed {
         println!("\n\u{2713} All API connections working properly");
         Ok(())
     } else {
-        Err("Some API connections failed".into())
+        Err(CliError::ConnectionsFailed("Some API connections failed".to_string()).into())
     }
 }
 
@@ -648,6 +2712,14 @@ async fn test_connections(args: &Args) -> Result<(), Box<dyn std::error::Error>>
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    // --log-level: tracing of the CLI's own behavior on stderr, apart from the interaction logs
+    if let Some(level) = &args.log_level {
+        if let Err(e) = trace::init(level) {
+            eprintln!("Error: {e}");
+            std::process::exit(CliError::Validation(e).exit_code());
+        }
+    }
+
     // Route to debate subcommand if present
     if let Some(command) = args.command {
         let result = match command {
@@ -655,16 +2727,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 run_debate(debate_args).await
             }
         };
-        if let Err(e) = result {
+        if let Err(e) = result.map_err(CliError::from) {
             eprintln!("Error: {e}");
-            std::process::exit(1);
+            std::process::exit(e.exit_code());
         }
         return Ok(());
     }
 
     if let Err(e) = run(args).await {
         eprintln!("Error: {e}");
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
     Ok(())
 }
@@ -674,13 +2746,12 @@ async fn run_debate(args: DebateArgs) -> Result<(), Box<dyn std::error::Error>>
     use debate::{DebateConfig, DebateProtocol, ModelSpec, Orchestrator};
     use std::str::FromStr;
 
-    args.validate()?;
+    args.validate().map_err(CliError::Validation)?;
 
     // Resolve proposition from --prompt, --prompt-file, or stdin
     let proposition = if let Some(ref p) = args.prompt {
         if p == "-" {
-            let mut buf = String::new();
-            io::stdin().read_to_string(&mut buf)?;
+            let buf = prompt::read_piped(io::stdin(), io::stdin().is_terminal())?;
             let s = buf.trim().to_string();
             if s.is_empty() {
                 return Err("No proposition provided via stdin".into());
@@ -690,8 +2761,8 @@ async fn run_debate(args: DebateArgs) -> Result<(), Box<dyn std::error::Error>>
             p.clone()
         }
     } else if let Some(ref path) = args.prompt_file {
-        let content = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read prompt file: {}", e))?;
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read prompt file: {}", e))?;
         let s = content.trim().to_string();
         if s.is_empty() {
             return Err("Prompt file is empty".into());
@@ -793,97 +2864,134 @@ async fn run_debate(args: DebateArgs) -> Result<(), Box<dyn std::error::Error>>
 
 /// Run interactive conversation mode
 async fn run_conversation_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
-    use std::io::Write;
-
     println!("\u{1f5e8}\u{fe0f}  ChatDelta Conversation Mode");
     println!("Type 'exit' or 'quit' to end the conversation");
     println!("Type 'clear' to reset the conversation history");
-    println!("Type 'save' to save the conversation to a file");
+    println!("Type 'save' (or 'save <path>', .md for Markdown) to save the conversation");
     println!();
 
-    // Create client configuration with system_message and retry strategy
-    let mut config_builder = ClientConfig::builder()
-        .timeout(Duration::from_secs(args.timeout))
-        .retries(args.retries)
-        .max_tokens(args.max_tokens);
-
-    if let Some(temp) = args.temperature {
-        config_builder = config_builder.temperature(temp);
+    if args.system_prompt.is_some() && !args.quiet {
+        println!("\u{1f4cb} System prompt active");
     }
 
-    let retry_strategy = match args.retry_strategy.as_str() {
-        "linear" => RetryStrategy::Linear(Duration::from_secs(1)),
-        "fixed" => RetryStrategy::Fixed(Duration::from_secs(2)),
-        _ => RetryStrategy::Exponential(Duration::from_secs(1)),
-    };
-    config_builder = config_builder.retry_strategy(retry_strategy);
-
-    // Wire up system prompt into ClientConfig (available since chatdelta 0.4.0)
-    if let Some(ref system_prompt) = args.system_prompt {
-        config_builder = config_builder.system_message(system_prompt);
-        if !args.quiet {
-            println!("\u{1f4cb} System prompt active");
-        }
-    }
-
-    let config = config_builder.build();
-
     // Create a client (prefer GPT for conversation mode)
     let client: Box<dyn AiClient> = if args.should_use_ai("gpt") {
-        let openai_key = env::var("OPENAI_API_KEY")
-            .or_else(|_| env::var("CHATGPT_API_KEY"));
-
-        if let Ok(key) = openai_key {
-            create_client("openai", &key, &args.gpt_model, config)?
+        if let Some((_, key)) = keys::openai_keys(args).next_key() {
+            let config = client_config(args, "gpt", args.retries);
+            create_client(Provider::Gpt.client_id(), &key, &args.gpt_model, config)?
         } else {
-            return Err(
+            return Err(CliError::NoClients(
                 "Conversation mode requires at least one API key (OPENAI_API_KEY or CHATGPT_API_KEY recommended)"
-                    .into(),
-            );
+                    .to_string(),
+            )
+            .into());
         }
     } else if args.should_use_ai("gemini") {
-        if let Ok(key) = env::var("GEMINI_API_KEY") {
-            create_client("gemini", &key, &args.gemini_model, config)?
+        if let Ok(key) = env::var(Provider::Gemini.env_var()) {
+            let config = client_config(args, "gemini", args.retries);
+            create_client(
+                Provider::Gemini.client_id(),
+                &key,
+                &args.gemini_model,
+                config,
+            )?
         } else {
-            return Err("Conversation mode requires at least one API key".into());
+            return Err(CliError::NoClients(
+                "Conversation mode requires at least one API key".to_string(),
+            )
+            .into());
         }
     } else if args.should_use_ai("claude") {
-        let anthropic_key = env::var("ANTHROPIC_API_KEY")
-            .or_else(|_| env::var("CLAUDE_API_KEY"));
-
-        if let Ok(key) = anthropic_key {
-            create_client("anthropic", &key, &args.claude_model, config)?
+        if let Some((_, key)) = keys::anthropic_keys(args).next_key() {
+            let config = client_config(args, "claude", args.retries);
+            create_client(
+                Provider::Claude.client_id(),
+                &key,
+                &args.claude_model,
+                config,
+            )?
         } else {
-            return Err("Conversation mode requires at least one API key".into());
+            return Err(CliError::NoClients(
+                "Conversation mode requires at least one API key".to_string(),
+            )
+            .into());
         }
+    } else if let Some(&(alias, entry)) = args.selected_aliases().first() {
+        // Only model aliases were selected; talk to the first of them
+        let ai = entry.ai();
+        let key = provider_key(args, ai).ok_or_else(|| {
+            CliError::NoClients(format!(
+                "Conversation mode needs a {} API key for model alias '{}'",
+                Args::display_name(ai),
+                alias
+            ))
+        })?;
+        let config = client_config(args, ai, args.retries);
+        let client = create_client(Provider::for_ai(ai).client_id(), &key, &entry.model, config)?;
+        Box::new(providers::alias::AliasedClient::new(alias, client))
     } else {
-        return Err("No AI clients available for conversation mode".into());
+        return Err(CliError::NoClients(
+            "No AI clients available for conversation mode".to_string(),
+        )
+        .into());
     };
 
-    // Create a ChatSession
+    // Create a ChatSession; the transcript tracks the same turns with timestamps
+    let provider = client.name().to_string();
+    let model = client.model().to_string();
+    let saved_file = |session: &ChatSession| {
+        conversation::ConversationFile::new(&provider, &model, &session.history().messages)
+    };
     let mut session = ChatSession::new(client);
+    let mut transcript: Vec<conversation::Turn> = Vec::new();
 
     // Load conversation history if specified
     if let Some(ref path) = args.load_conversation {
-        let json = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read conversation file '{}': {}", path.display(), e))?;
-        let messages: Vec<Message> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse conversation file '{}': {}", path.display(), e))?;
+        let file = conversation::ConversationFile::load(path)?;
+        if let Some(saved_model) = file.model.as_deref().filter(|m| *m != model) {
+            if !args.quiet {
+                eprintln!(
+                    "Note: {} was saved with {}; continuing with {}",
+                    path.display(),
+                    saved_model,
+                    model
+                );
+            }
+        }
+        let messages = file.messages;
         let count = messages.len();
+        transcript = conversation::turns_from_history(&messages);
         session.load_history(messages);
         if !args.quiet {
-            println!("\u{1f4c1} Loaded {} messages from: {}", count, path.display());
+            println!(
+                "\u{1f4c1} Loaded {} messages from: {}",
+                count,
+                path.display()
+            );
         }
     }
 
+    // Prompt history persists across sessions in ~/.chatdelta/history
+    let history_path = history::history_path();
+    let past_entries = history_path
+        .as_deref()
+        .map(history::load)
+        .unwrap_or_default();
+    let mut reader = history::LineReader::new(&past_entries);
+
     // Main conversation loop
     loop {
-        print!("> ");
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let Some(input) = reader.read_line("> ")? else {
+            // End of input (Ctrl-D) ends the session like 'exit'
+            println!();
+            break;
+        };
         let input = input.trim();
+        if !input.is_empty() {
+            if let Some(path) = &history_path {
+                let _ = history::append(path, input);
+            }
+        }
 
         match input.to_lowercase().as_str() {
             "exit" | "quit" => {
@@ -892,17 +3000,29 @@ async fn run_conversation_mode(args: &Args) -> Result<(), Box<dyn std::error::Er
             }
             "clear" => {
                 session.clear();
+                transcript.clear();
                 println!("\u{1f504} Conversation cleared");
                 continue;
             }
             "save" => {
+                if args.save_conversation.is_none() && args.export_transcript.is_none() {
+                    println!("\u{26a0}\u{fe0f}  No save path specified. Use --save-conversation <path> or 'save <path>'");
+                }
                 if let Some(ref path) = args.save_conversation {
-                    let json = serde_json::to_string_pretty(&session.history().messages)?;
-                    fs::write(path, &json)?;
+                    conversation::save(path, &saved_file(&session), &transcript)?;
                     println!("\u{1f4be} Conversation saved to: {}", path.display());
-                } else {
-                    println!("\u{26a0}\u{fe0f}  No save path specified. Use --save-conversation <path>");
                 }
+                if let Some(ref path) = args.export_transcript {
+                    conversation::export_transcript(path, &transcript)?;
+                    println!("\u{1f4dd} Transcript exported to: {}", path.display());
+                }
+                continue;
+            }
+            cmd if cmd.starts_with("save ") => {
+                // Keep the path's original case; the format follows its extension
+                let path = Path::new(input[5..].trim());
+                conversation::save(path, &saved_file(&session), &transcript)?;
+                println!("\u{1f4be} Conversation saved to: {}", path.display());
                 continue;
             }
             "" => continue,
@@ -911,9 +3031,12 @@ async fn run_conversation_mode(args: &Args) -> Result<(), Box<dyn std::error::Er
 
         println!("\u{1f914} Thinking...");
 
+        let user_turn = conversation::Turn::now("user", input);
         match session.send(input).await {
             Ok(response) => {
                 println!("\n{}\n", response);
+                transcript.push(user_turn);
+                transcript.push(conversation::Turn::now("assistant", &response));
             }
             Err(e) => {
                 eprintln!("\u{274c} Error: {}", e);
@@ -921,14 +3044,19 @@ async fn run_conversation_mode(args: &Args) -> Result<(), Box<dyn std::error::Er
         }
     }
 
-    // Save conversation on exit if requested
+    // Save conversation and transcript on exit if requested
     if let Some(ref path) = args.save_conversation {
-        let json = serde_json::to_string_pretty(&session.history().messages)?;
-        fs::write(path, &json)?;
+        conversation::save(path, &saved_file(&session), &transcript)?;
         if !args.quiet {
             println!("\u{1f4be} Conversation saved to: {}", path.display());
         }
     }
+    if let Some(ref path) = args.export_transcript {
+        conversation::export_transcript(path, &transcript)?;
+        if !args.quiet {
+            println!("\u{1f4dd} Transcript exported to: {}", path.display());
+        }
+    }
 
     Ok(())
 }
@@ -972,6 +3100,14 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_detailed_requires_list_models() {
+        assert!(Args::try_parse_from(["chatdelta", "--detailed", "Hi"]).is_err());
+        let args = Args::try_parse_from(["chatdelta", "--list-models", "--detailed", "-f", "json"])
+            .unwrap();
+        assert!(args.detailed && args.validate().is_ok());
+    }
+
     #[test]
     fn test_debate_args_parsing() {
         let args = Args::try_parse_from([
@@ -1016,6 +3152,1830 @@ mod tests {
         assert!(too_many_rounds.validate().is_err());
     }
 
+    #[test]
+    fn test_base_url_overrides_per_provider() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--gpt-base-url",
+            "https://gateway.internal/openai/v1",
+            "--gemini-base-url",
+            "https://gateway.internal/gemini",
+            "--claude-base-url",
+            "http://localhost:4000",
+            "Hello",
+        ])
+        .expect("Should parse base URL flags");
+        args.validate().expect("Base URLs should validate");
+
+        assert_eq!(
+            args.base_url("gpt").as_deref(),
+            Some("https://gateway.internal/openai/v1")
+        );
+        assert_eq!(
+            args.base_url("gemini").as_deref(),
+            Some("https://gateway.internal/gemini")
+        );
+        assert_eq!(
+            args.base_url("claude").as_deref(),
+            Some("http://localhost:4000")
+        );
+        assert_eq!(args.base_url("unknown"), None);
+
+        let invalid = Args::try_parse_from(["chatdelta", "--claude-base-url", "localhost", "Hi"])
+            .expect("Should parse");
+        assert!(invalid.validate().is_err());
+    }
+
+    /// Fresh temporary directory for a mock run's logs and saved responses
+    fn mock_run_dir(label: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("chatdelta-{}-{}", label, uuid::Uuid::new_v4()))
+    }
+
+    /// Read the single JSON log entry written by a mock run
+    fn read_log_entry(log_dir: &Path) -> logging::LogEntry {
+        let file = fs::read_dir(log_dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_response_filter_cuts_responses_but_logs_the_original() {
+        let dir = mock_run_dir("response-filter");
+        let log_dir = dir.join("logs");
+        let output = dir.join("out.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--only",
+            "gpt,claude",
+            "--format",
+            "json",
+            "--output",
+            output.to_str().unwrap(),
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--response-filter",
+            r"^\[mock gpt-4o\] ",
+            "What is Rust?",
+        ])
+        .unwrap();
+
+        run(args).await.unwrap();
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(doc["responses"]["ChatGPT"], "Response to: What is Rust?");
+        assert_eq!(
+            doc["responses"]["Claude"],
+            "[mock claude-sonnet-4-6] Response to: What is Rust?"
+        );
+
+        let entry = read_log_entry(&log_dir);
+        assert_eq!(
+            entry.responses["ChatGPT"].response,
+            "Response to: What is Rust?"
+        );
+        assert_eq!(
+            entry.responses["ChatGPT"].unfiltered.as_deref(),
+            Some("[mock gpt-4o] Response to: What is Rust?")
+        );
+        assert!(entry.responses["Claude"].unfiltered.is_none());
+
+        let invalid =
+            Args::try_parse_from(["chatdelta", "--response-filter", "(unclosed", "Hi"]).unwrap();
+        assert!(matches!(run(invalid).await, Err(CliError::Validation(_))));
+        // Streamed tokens are printed before a line could be filtered
+        assert!(
+            Args::try_parse_from(["chatdelta", "--stream", "--response-filter", "x", "Hi"])
+                .is_err()
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_echo_prompt_heads_saved_responses() {
+        let dir = mock_run_dir("echo-prompt");
+        let saved = dir.join("responses");
+        let plain = dir.join("plain");
+        for (target, echo) in [(&saved, true), (&plain, false)] {
+            let mut argv = vec![
+                "chatdelta",
+                "--mock",
+                "--quiet",
+                "--only",
+                "claude",
+                "--save-responses",
+                target.to_str().unwrap(),
+            ];
+            if echo {
+                argv.push("--echo-prompt");
+            }
+            argv.push("What is a borrow checker?");
+            run(Args::try_parse_from(argv).unwrap())
+                .await
+                .expect("mock run should succeed");
+        }
+
+        let text = fs::read_to_string(saved.join("claude.txt")).unwrap();
+        assert!(
+            text.starts_with("=== Prompt ===\nWhat is a borrow checker?\n\n=== Claude ===\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with("Response to: What is a borrow checker?"),
+            "{}",
+            text
+        );
+        // Without the flag the file holds only the response
+        let plain = fs::read_to_string(plain.join("claude.txt")).unwrap();
+        assert!(!plain.contains("=== Prompt ==="));
+        assert!(text.ends_with(&plain));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_mock_run_end_to_end() {
+        let dir = mock_run_dir("mock-run");
+        let log_dir = dir.join("logs");
+        let saved = dir.join("responses");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--format",
+            "json",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--log-metrics",
+            "--save-responses",
+            saved.to_str().unwrap(),
+            "What is Rust?",
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+
+        let entry = read_log_entry(&log_dir);
+        assert_eq!(entry.responses.len(), 3);
+        assert!(entry.responses.values().all(|r| r.success));
+        assert!(entry.responses["Claude"].response.contains("What is Rust?"));
+        assert!(
+            entry.summary.is_some(),
+            "summary should come from the mock summarizer"
+        );
+        assert_eq!(entry.metrics.unwrap().successful_responses, 3);
+        assert!(saved.join("chatgpt.txt").exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_race_keeps_one_answer_and_logs_the_rest() {
+        let dir = mock_run_dir("race");
+        let log_dir = dir.join("logs");
+        let output = dir.join("out.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gpt",
+            "--quiet",
+            "--race",
+            "--format",
+            "json",
+            "--output",
+            output.to_str().unwrap(),
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--log-errors",
+            "What is Rust?",
+        ])
+        .unwrap();
+
+        run(args)
+            .await
+            .expect("a race with one working model should succeed");
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        let answered: Vec<&String> = doc["responses"].as_object().unwrap().keys().collect();
+        assert_eq!(answered.len(), 1, "{}", doc);
+        assert!(doc.get("summary").is_none());
+
+        let entry = read_log_entry(&log_dir);
+        let winners: Vec<&String> = entry
+            .responses
+            .iter()
+            .filter(|(_, r)| r.race_winner)
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(winners, [answered[0]]);
+        assert!(entry.responses[answered[0]].success);
+        assert!(
+            entry.errors.iter().all(|e| e.model != *answered[0]),
+            "the winner isn't an error"
+        );
+        // Cached answers would be paid for again and printed next to the winner
+        assert!(Args::try_parse_from(["chatdelta", "--race", "--cache", "Hi"]).is_err());
+        assert!(entry.summary.is_none());
+        for cancelled in entry
+            .errors
+            .iter()
+            .filter(|e| e.error_type == "RACE_CANCELLED")
+        {
+            assert!(!entry.responses[&cancelled.model].success);
+        }
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_save_prompt_writes_rendered_template() {
+        let dir = mock_run_dir("save-prompt");
+        let templates = dir.join("templates");
+        fs::create_dir_all(&templates).unwrap();
+        fs::write(
+            templates.join("review.md"),
+            "Review this {{LANG}} code for {{FOCUS}}.\n",
+        )
+        .unwrap();
+        let saved = dir.join("nested").join("prompt.txt");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--no-summary",
+            "--output",
+            dir.join("out.txt").to_str().unwrap(),
+            "--prompt-template-dir",
+            templates.to_str().unwrap(),
+            "--template-name",
+            "review",
+            "--var",
+            "LANG=Rust",
+            "--var",
+            "FOCUS=safety",
+            "--save-prompt",
+            saved.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+        assert_eq!(
+            fs::read_to_string(&saved).unwrap().trim_end(),
+            "Review this Rust code for safety."
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_split_output_writes_status_file() {
+        let dir = mock_run_dir("split-output");
+        fs::create_dir_all(&dir).unwrap();
+        let status_path = dir.join("status.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gemini",
+            "--quiet",
+            "--no-summary",
+            "--format",
+            "json",
+            "--split-output",
+            "--status-file",
+            status_path.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+
+        let status: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&status_path).unwrap()).unwrap();
+        assert_eq!(status["success"], true);
+        assert_eq!(status["exit_reason"], "success");
+        assert_eq!(status["models_queried"], 3);
+        assert_eq!(status["responses"], 2);
+        assert_eq!(status["failures"], 1);
+
+        // A failed run still records why it exited
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gpt,gemini,claude",
+            "--quiet",
+            "--split-output",
+            "--status-file",
+            status_path.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+        assert!(run(args).await.is_err());
+        let status: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&status_path).unwrap()).unwrap();
+        assert_eq!(status["success"], false);
+        assert_eq!(status["exit_code"], 4);
+        assert_eq!(
+            status["exit_reason"],
+            "No successful responses from any AI models"
+        );
+
+        // An unwritable status file doesn't replace the run's own result
+        let unwritable = dir.join("missing").join("status.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gpt,gemini,claude",
+            "--quiet",
+            "--split-output",
+            "--status-file",
+            unwritable.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+        assert!(matches!(run(args).await, Err(CliError::NoResponses(_))));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--no-summary",
+            "--split-output",
+            "--status-file",
+            unwritable.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+        assert!(run(args).await.is_ok());
+
+        assert!(Args::try_parse_from(["chatdelta", "--split-output", "Hi"]).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_summary_models_log_each_summary() {
+        let dir = mock_run_dir("summary-models");
+        let log_dir = dir.join("logs");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--summary-models",
+            "gpt,claude",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Compare these",
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+
+        let entry = read_log_entry(&log_dir);
+        let models: Vec<&str> = entry.summaries.keys().map(String::as_str).collect();
+        assert_eq!(models, vec!["ChatGPT", "Claude"]);
+        let summary = entry.summary.unwrap();
+        assert!(summary.contains("Summary by ChatGPT:"));
+        assert!(summary.contains("Summary by Claude:"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fail_if_disagree_gates_on_agreement() {
+        // Mock responses differ only in the echoed model name, so they largely agree
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--no-summary",
+            "--fail-if-disagree",
+            "--agreement-threshold",
+            "0.5",
+            "What is Rust?",
+        ])
+        .unwrap();
+        run(args)
+            .await
+            .expect("similar responses should pass the consensus gate");
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--no-summary",
+            "--fail-if-disagree",
+            "--agreement-threshold",
+            "1.0",
+            "What is Rust?",
+        ])
+        .unwrap();
+        let err = run(args)
+            .await
+            .expect_err("differing responses should fail");
+        assert!(err
+            .to_string()
+            .starts_with("Consensus check failed: 3 model pairs"));
+        assert_eq!(err.exit_code(), 7);
+
+        // A single response can't be compared: the check fails, but the run is still logged
+        let dir = mock_run_dir("consensus-single");
+        let log_dir = dir.join("logs");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--only",
+            "gpt",
+            "--fail-if-disagree",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "What is Rust?",
+        ])
+        .unwrap();
+        let err = run(args).await.expect_err("one response can't agree");
+        assert_eq!(err.exit_code(), 7);
+        let names: Vec<_> = fs::read_dir(&log_dir)
+            .unwrap()
+            .map(|file| file.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 1);
+        assert!(
+            !names[0].to_string_lossy().ends_with(".partial"),
+            "{:?}",
+            names
+        );
+        let entry = read_log_entry(&log_dir);
+        assert!(entry.responses.contains_key("ChatGPT"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_connection_probe_uses_test_flags() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--test",
+            "--test-retries",
+            "3",
+            "--test-prompt",
+            "ping from ci",
+        ])
+        .unwrap();
+
+        assert_eq!(test_client_config(&args, "gpt").retries, 3);
+
+        let client = providers::mock::MockClient::new("ChatGPT", "gpt-4o", Duration::ZERO, false);
+        let reply = probe_connection(&client, &args).await.unwrap();
+        assert!(reply.ends_with("Response to: ping from ci"));
+
+        let defaults = Args::try_parse_from(["chatdelta", "--test"]).unwrap();
+        assert_eq!(test_client_config(&defaults, "gpt").retries, 0);
+        assert!(defaults
+            .test_prompt
+            .starts_with("Hello, please respond with just 'OK'"));
+    }
+
+    #[test]
+    fn test_quiet_summary_keeps_query_progress() {
+        let args = Args::try_parse_from(["chatdelta", "--quiet-summary", "Hi"]).unwrap();
+        assert!(!args.show_summary_status());
+        assert!(!args.quiet, "query progress should still be shown");
+
+        let default = Args::try_parse_from(["chatdelta", "Hi"]).unwrap();
+        assert!(default.show_summary_status());
+
+        let quiet = Args::try_parse_from(["chatdelta", "--quiet", "Hi"]).unwrap();
+        assert!(!quiet.show_summary_status());
+    }
+
+    fn stream_chunk(content: &str, finished: bool) -> StreamChunk {
+        StreamChunk {
+            content: content.to_string(),
+            finished,
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_writes_accumulate_in_output_file() {
+        let path = env::temp_dir().join(format!("chatdelta-stream-{}.txt", uuid::Uuid::new_v4()));
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--stream",
+            "--output",
+            path.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for (content, finished) in [("Once ", false), ("upon ", false), ("a time.", true)] {
+            tx.send(stream_chunk(content, finished)).unwrap();
+        }
+        let mut out = result_writer(&args).unwrap();
+        let written = write_stream(&mut rx, &mut *out).await.unwrap();
+        drop(out);
+
+        assert_eq!(written, "Once upon a time.".len());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Once upon a time.\n");
+        fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_stream_write_error_reports_progress() {
+        /// Accepts `limit` bytes, then fails like a full disk
+        struct FullDisk {
+            limit: usize,
+        }
+        impl Write for FullDisk {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if buf.len() > self.limit {
+                    return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+                }
+                self.limit -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(stream_chunk("first ", false)).unwrap();
+        tx.send(stream_chunk("second", true)).unwrap();
+        let (written, e) = write_stream(&mut rx, &mut FullDisk { limit: 8 })
+            .await
+            .unwrap_err();
+        assert_eq!(written, 6);
+        assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+    }
+
+    #[tokio::test]
+    async fn test_single_survivor_json_output() {
+        let dir = mock_run_dir("single-survivor");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("result.json");
+        let run_with = |extra: &[&str]| {
+            let base = [
+                "chatdelta",
+                "--mock",
+                "--mock-fail",
+                "gpt,gemini",
+                "--quiet",
+                "--format",
+                "json",
+                "--output",
+                output.to_str().unwrap(),
+            ];
+            Args::try_parse_from([&base[..], extra, &["Hello"]].concat()).unwrap()
+        };
+        let read_output = || -> serde_json::Value {
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap()
+        };
+
+        run(run_with(&[]))
+            .await
+            .expect("one survivor is still a success");
+        let doc = read_output();
+        assert!(doc["responses"]["Claude"]
+            .as_str()
+            .unwrap()
+            .contains("Hello"));
+        assert!(doc.get("summary").is_none());
+
+        run(run_with(&["--summarize-single"])).await.unwrap();
+        let doc = read_output();
+        assert!(doc["responses"]["Claude"].is_string());
+        assert!(
+            doc["summary"].is_string(),
+            "--summarize-single condenses the lone response"
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_summary_model_only_picks_the_summarizer() {
+        let keys = |set: &'static [&'static str]| {
+            move |name: &str| set.contains(&name).then(|| "key".to_string())
+        };
+        let args = Args::try_parse_from(["chatdelta", "--force-summary-model-only", "Hi"]).unwrap();
+        assert_eq!(
+            summary_model_only(&args, keys(&["GEMINI_API_KEY", "OPENAI_API_KEY"])),
+            Some("gemini")
+        );
+        assert_eq!(
+            summary_model_only(&args, keys(&["OPENAI_API_KEY"])),
+            Some("gpt")
+        );
+        assert_eq!(summary_model_only(&args, keys(&[])), None);
+
+        // Only the selected models are candidates
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--force-summary-model-only",
+            "--exclude",
+            "gemini",
+            "Hi",
+        ])
+        .unwrap();
+        assert_eq!(
+            summary_model_only(&args, keys(&["GEMINI_API_KEY", "ANTHROPIC_API_KEY"])),
+            Some("claude")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_summary_model_only_condenses_one_answer() {
+        let dir = mock_run_dir("summary-model-only");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("out.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--force-summary-model-only",
+            "--format",
+            "json",
+            "--output",
+            output.to_str().unwrap(),
+            "Explain ownership",
+        ])
+        .unwrap();
+
+        run(args).await.expect("the summary model answers");
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        let responses = doc["responses"].as_object().unwrap();
+        assert_eq!(responses.keys().collect::<Vec<_>>(), vec!["Gemini"]);
+        assert!(doc["summary"].is_string(), "the lone answer is condensed");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_per_provider_retry_strategies() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--retry-strategy",
+            "linear",
+            "--gpt-retry-strategy",
+            "exponential",
+            "--claude-retry-strategy",
+            "fixed",
+            "Hi",
+        ])
+        .unwrap();
+        assert!(args.validate().is_ok());
+
+        let strategy = |ai| client_config(&args, ai, args.retries).retry_strategy;
+        assert!(matches!(strategy("gpt"), RetryStrategy::Exponential(_)));
+        assert!(matches!(strategy("claude"), RetryStrategy::Fixed(_)));
+        // Gemini has no override, so it keeps the global strategy
+        assert!(matches!(strategy("gemini"), RetryStrategy::Linear(_)));
+
+        let invalid =
+            Args::try_parse_from(["chatdelta", "--gemini-retry-strategy", "jittery", "Hi"])
+                .unwrap();
+        let err = invalid.validate().unwrap_err();
+        assert!(err.contains("--gemini-retry-strategy"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_mock_benchmark_reports_each_model() {
+        let dir = mock_run_dir("benchmark");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("benchmark.json");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gemini",
+            "--benchmark",
+            "4",
+            "--max-concurrency",
+            "2",
+            "--quiet",
+            "--format",
+            "json",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        run(args).await.expect("benchmark runs without a prompt");
+
+        let report: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(report["runs"], 4);
+        let models = report["models"].as_array().unwrap();
+        assert_eq!(models.len(), 3);
+        let gpt = models.iter().find(|m| m["model"] == "ChatGPT").unwrap();
+        assert_eq!(gpt["success_rate"], 100.0);
+        assert_eq!(gpt["latency"]["count"], 4);
+        let gemini = models.iter().find(|m| m["model"] == "Gemini").unwrap();
+        assert_eq!(gemini["success_rate"], 0.0);
+        assert!(gemini["latency"].is_null());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_unwritable_log_dir_only_warns() {
+        let dir = mock_run_dir("unwritable-logs");
+        fs::create_dir_all(&dir).unwrap();
+        // A log directory beneath a regular file can't be created, even by root
+        let blocker = dir.join("not-a-dir");
+        fs::write(&blocker, "").unwrap();
+        let log_dir = blocker.join("logs");
+        let output = dir.join("out.txt");
+        let argv = |strict: bool| {
+            let mut argv = vec![
+                "chatdelta",
+                "--mock",
+                "--quiet",
+                "--no-summary",
+                "--log-dir",
+                log_dir.to_str().unwrap(),
+                "--output",
+                output.to_str().unwrap(),
+            ];
+            if strict {
+                argv.push("--strict-logging");
+            }
+            argv.push("Hi");
+            Args::try_parse_from(argv).unwrap()
+        };
+
+        let mut warnings = warnings::Warnings::new(false, true);
+        let mut status = status::RunStatus::default();
+        execute(argv(false), &mut warnings, &mut status)
+            .await
+            .unwrap();
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .contains("Response to: Hi"));
+        assert_eq!(warnings.messages().len(), 1);
+        assert!(
+            warnings.messages()[0].starts_with("Structured logging disabled"),
+            "{:?}",
+            warnings.messages()
+        );
+
+        let mut warnings = warnings::Warnings::new(false, true);
+        assert!(execute(argv(true), &mut warnings, &mut status)
+            .await
+            .is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_strict_models_fails_for_requested_providers() {
+        let reason = || "ANTHROPIC_API_KEY or CLAUDE_API_KEY not set, skipping Claude".to_string();
+        let mut warnings = warnings::Warnings::new(false, true);
+
+        let strict =
+            Args::try_parse_from(["chatdelta", "--only", "gpt,claude", "--strict-models", "Hi"])
+                .unwrap();
+        let err = skip_provider(&strict, "claude", reason(), &mut warnings).unwrap_err();
+        assert!(err.contains("ANTHROPIC_API_KEY"), "{}", err);
+        assert!(err.contains("--strict-models"), "{}", err);
+
+        // Providers pulled in by a group, or by default, are still skipped with a warning
+        let grouped =
+            Args::try_parse_from(["chatdelta", "--only", "all", "--strict-models", "Hi"]).unwrap();
+        assert!(skip_provider(&grouped, "claude", reason(), &mut warnings).is_ok());
+        let lenient = Args::try_parse_from(["chatdelta", "--only", "claude", "Hi"]).unwrap();
+        assert!(skip_provider(&lenient, "claude", reason(), &mut warnings).is_ok());
+        assert_eq!(warnings.messages().len(), 2);
+    }
+
+    #[test]
+    fn test_no_fallback_summary_reports_a_missing_summary_model() {
+        let parse = |flags: &[&str]| {
+            Args::try_parse_from([&["chatdelta"], flags, &["Hi"]].concat()).unwrap()
+        };
+        let mut openai_keys = keys::KeyRotation::new(["sk-test"]);
+        let mut anthropic_keys = keys::KeyRotation::default();
+        let mut warnings = warnings::Warnings::new(false, true);
+
+        // No Claude key: the lenient run falls back to ChatGPT, the strict one produces nothing
+        let lenient = parse(&["--only", "gpt", "--summary-model", "claude"]);
+        assert_eq!(
+            summary_order(&lenient, &["gpt"]),
+            vec!["claude", "gpt", "gemini"]
+        );
+        report_unavailable_summary_model(&lenient, &mut warnings, &mut None);
+        assert!(warnings.messages().is_empty());
+
+        let strict = parse(&[
+            "--only",
+            "gpt",
+            "--summary-model",
+            "claude",
+            "--no-fallback-summary",
+        ]);
+        assert_eq!(summary_order(&strict, &["gpt"]), vec!["claude"]);
+        assert!(summary_client(&strict, &["gpt"], &mut openai_keys, &mut anthropic_keys).is_none());
+        report_unavailable_summary_model(&strict, &mut warnings, &mut None);
+        assert_eq!(warnings.messages().len(), 1);
+        assert!(
+            warnings.messages()[0].contains("'claude' is unavailable"),
+            "{:?}",
+            warnings.messages()
+        );
+
+        assert!(Args::try_parse_from(["chatdelta", "--no-fallback-summary", "Hi"]).is_err());
+        assert!(parse(&["--summary-model", "llama"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_summary_format_defaults_to_output_format() {
+        let parse = |flags: &[&str]| {
+            Args::try_parse_from([&["chatdelta"], flags, &["Hi"]].concat()).unwrap()
+        };
+        assert_eq!(parse(&[]).resolved_summary_format(), "text");
+        assert_eq!(
+            parse(&["--format", "markdown"]).resolved_summary_format(),
+            "markdown"
+        );
+        assert_eq!(
+            parse(&["--format", "ndjson"]).resolved_summary_format(),
+            "json"
+        );
+        assert_eq!(
+            parse(&["--format", "json", "--summary-format", "text"]).resolved_summary_format(),
+            "text"
+        );
+        assert!(parse(&["--summary-format", "yaml"]).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hide_responses_keeps_them_in_the_log() {
+        let dir = mock_run_dir("hide-responses");
+        let log_dir = dir.join("logs");
+        let output = dir.join("result.txt");
+        fs::create_dir_all(&dir).unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--hide-responses",
+            "--output",
+            output.to_str().unwrap(),
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Explain ownership",
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+
+        let printed = fs::read_to_string(&output).unwrap();
+        let entry = read_log_entry(&log_dir);
+        assert_eq!(entry.responses.len(), 3);
+        for response in entry.responses.values() {
+            assert!(response.success && !response.response.is_empty());
+            assert!(
+                !printed.contains(&response.response),
+                "response leaked into output: {}",
+                printed
+            );
+        }
+        let summary = entry
+            .summary
+            .expect("summary is generated from the hidden responses");
+        assert!(printed.contains(summary.trim()));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_exit_with_validation_code() {
+        let args = Args::try_parse_from(["chatdelta", "--mock", "--summary-format", "yaml", "Hi"])
+            .unwrap();
+        let err = run(args).await.unwrap_err();
+        assert!(matches!(err, CliError::Validation(_)));
+        assert_eq!(err.exit_code(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_filenames_keep_earlier_runs() {
+        let dir = mock_run_dir("timestamp-filenames");
+        let saved = dir.join("responses");
+        let log = dir.join("run.log");
+        let run_twice = |extra: Option<&'static str>| {
+            let argv: Vec<String> = ["chatdelta", "--mock", "--quiet", "--only", "gpt"]
+                .into_iter()
+                .chain(extra)
+                .map(String::from)
+                .chain([
+                    "--save-responses".to_string(),
+                    saved.to_str().unwrap().to_string(),
+                    "--log".to_string(),
+                    log.to_str().unwrap().to_string(),
+                    "Hi".to_string(),
+                ])
+                .collect();
+            async move {
+                for _ in 0..2 {
+                    run(Args::try_parse_from(&argv).unwrap()).await.unwrap();
+                }
+            }
+        };
+        let names = |dir: &Path| -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        run_twice(None).await;
+        assert_eq!(names(&saved), vec!["chatgpt.txt"]);
+        assert_eq!(names(&dir), vec!["responses", "run.log"]);
+        fs::remove_dir_all(&dir).ok();
+
+        run_twice(Some("--timestamp-filenames")).await;
+        let saved_files = names(&saved);
+        assert_eq!(saved_files.len(), 2);
+        assert!(saved_files
+            .iter()
+            .all(|f| f.starts_with("chatgpt-") && f.ends_with(".txt")));
+        let logs: Vec<String> = names(&dir)
+            .into_iter()
+            .filter(|f| f.starts_with("run-"))
+            .collect();
+        assert_eq!(logs.len(), 2);
+        assert!(logs.iter().all(|f| f.ends_with(".log")));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_model_map_aliases_get_their_own_clients() {
+        let dir = mock_run_dir("model-map");
+        let log_dir = dir.join("logs");
+        let map = dir.join("models.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &map,
+            "reviewer = { provider = \"claude\", model = \"claude-3-5-sonnet-20241022\" }\n\
+             drafter = { provider = \"claude\", model = \"claude-3-5-haiku-20241022\" }\n",
+        )
+        .unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--model-map-file",
+            map.to_str().unwrap(),
+            "--only",
+            "reviewer,drafter,gpt",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "Review this",
+        ])
+        .unwrap();
+
+        run(args).await.expect("aliases should validate and answer");
+
+        let entry = read_log_entry(&log_dir);
+        let mut names: Vec<&str> = entry.responses.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["ChatGPT", "drafter", "reviewer"]);
+        assert!(entry.responses["reviewer"]
+            .response
+            .contains("claude-3-5-sonnet-20241022"));
+        assert!(entry.responses["drafter"]
+            .response
+            .contains("claude-3-5-haiku-20241022"));
+
+        // An alias alone is a valid selection
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--no-summary",
+            "--model-map-file",
+            map.to_str().unwrap(),
+            "--only",
+            "reviewer",
+            "Review this",
+        ])
+        .unwrap();
+        run(args).await.expect("a lone alias should answer");
+
+        // Aliases run only through --only, so --exclude can't name one
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--model-map-file",
+            map.to_str().unwrap(),
+            "--exclude",
+            "drafter",
+            "Review this",
+        ])
+        .unwrap();
+        match run(args).await {
+            Err(CliError::Validation(e)) => assert!(e.contains("--exclude drafter"), "{}", e),
+            other => panic!("expected a validation error, got {:?}", other.err()),
+        }
+
+        let unknown =
+            Args::try_parse_from(["chatdelta", "--mock", "--only", "reviewer", "Hi"]).unwrap();
+        assert!(
+            matches!(run(unknown).await, Err(CliError::Validation(_))),
+            "aliases need the map"
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_yes_runs_every_prompt() {
+        let dir = mock_run_dir("batch");
+        let prompts = dir.join("prompts.txt");
+        let out = dir.join("runs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&prompts, "# two questions\nWhat is Rust?\nWhat is Go?\n").unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--yes",
+            "--batch",
+            prompts.to_str().unwrap(),
+            "--output-dir",
+            out.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        run(args).await.expect("--yes skips the confirmation");
+
+        let mut sent: Vec<String> = fs::read_dir(&out)
+            .unwrap()
+            .map(|run_dir| fs::read_to_string(run_dir.unwrap().path().join("prompt.txt")).unwrap())
+            .collect();
+        sent.sort();
+        assert_eq!(sent, vec!["What is Go?", "What is Rust?"]);
+
+        // Single-file outputs would keep only the last prompt
+        for flag in [
+            "--log",
+            "--save-responses",
+            "--metrics-file",
+            "--save-prompt",
+        ] {
+            let parsed = Args::try_parse_from(["chatdelta", "--batch", "prompts.txt", flag, "out"]);
+            assert!(parsed.is_err(), "{} should conflict with --batch", flag);
+        }
+        // Other prompt sources would replace every batch prompt
+        for extra in [
+            &["--prompt-template-dir", "tpl", "--template-name", "t"][..],
+            &["--prompt-from-git-diff"],
+            &["--conversation"],
+            &["--split-output", "--status-file", "status.json"],
+        ] {
+            let argv = ["chatdelta", "--batch", "prompts.txt"].iter().chain(extra);
+            let parsed = Args::try_parse_from(argv);
+            assert!(parsed.is_err(), "{:?} should conflict with --batch", extra);
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_batch_prepares_each_prompt_and_adds_up_the_status() {
+        let dir = mock_run_dir("batch-prepare");
+        let prompts = dir.join("prompts.txt");
+        let logs = dir.join("logs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&prompts, "About {{topic}}\nMore on {{topic}}\n").unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--yes",
+            "--no-summary",
+            "--batch",
+            prompts.to_str().unwrap(),
+            "--prompt-prefix",
+            "Briefly:",
+            "--var",
+            "topic=Rust",
+            "--connect-timeout",
+            "3",
+            "--log-dir",
+            logs.to_str().unwrap(),
+            "--log-format",
+            "json",
+        ])
+        .unwrap();
+
+        let mut warnings = warnings::Warnings::new(false, true);
+        let mut status = status::RunStatus::default();
+        execute(args, &mut warnings, &mut status).await.unwrap();
+
+        let mut sent: Vec<String> = logging::read_log_entries(&logs, None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.prompt)
+            .collect();
+        sent.sort();
+        assert_eq!(sent, ["Briefly:\n\nAbout Rust", "Briefly:\n\nMore on Rust"]);
+        let timeout_warnings = warnings
+            .messages()
+            .iter()
+            .filter(|m| m.contains("--connect-timeout"))
+            .count();
+        assert_eq!(timeout_warnings, 1, "{:?}", warnings.messages());
+        // Two prompts to the three mock models
+        assert_eq!(status.models_queried, 6);
+        assert_eq!(status.responses, 6);
+        assert_eq!(status.failures, 0);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_batch_is_a_validation_error() {
+        let dir = mock_run_dir("batch-unconfirmed");
+        let prompts = dir.join("prompts.txt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&prompts, "What is Rust?\n").unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--batch",
+            prompts.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        let result = run(args).await;
+
+        assert!(
+            matches!(result, Err(CliError::Validation(_))),
+            "{:?}",
+            result
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_batch_derives_indexed_interaction_ids() {
+        let dir = mock_run_dir("batch-ids");
+        let prompts = dir.join("prompts.txt");
+        let logs = dir.join("logs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&prompts, "What is Rust?\nWhat is Go?\n").unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--yes",
+            "--batch",
+            prompts.to_str().unwrap(),
+            "--log-dir",
+            logs.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--interaction-id",
+            "trace-7",
+        ])
+        .unwrap();
+
+        run(args).await.unwrap();
+
+        let mut ids: Vec<(String, String)> = logging::read_log_entries(&logs, None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.interaction_id, entry.prompt))
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![
+                ("trace-7-1".to_string(), "What is Rust?".to_string()),
+                ("trace-7-2".to_string(), "What is Go?".to_string()),
+            ]
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_min_success_fails_run_but_keeps_results() {
+        let dir = mock_run_dir("min-success");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("result.txt");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gemini",
+            "--quiet",
+            "--no-summary",
+            "--min-success",
+            "3",
+            "--output",
+            output.to_str().unwrap(),
+            "Hi",
+        ])
+        .unwrap();
+
+        let error = run(args).await.unwrap_err();
+        assert_eq!(error.exit_code(), 4);
+        assert_eq!(
+            error.to_string(),
+            "Only 2 of 3 models succeeded; --min-success requires 3"
+        );
+        // The two answers are still written
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.contains("=== ChatGPT ===") && written.contains("=== Claude ==="));
+
+        let output = output.to_str().unwrap();
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--min-success",
+            "2",
+            "--output",
+            output,
+            "Hi",
+        ])
+        .unwrap();
+        run(args).await.expect("three answers meet --min-success 2");
+
+        // More than the selected models can never pass
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--only",
+            "gpt,claude",
+            "--min-success",
+            "3",
+            "Hi",
+        ])
+        .unwrap();
+        assert!(args.validate().unwrap_err().contains("--min-success"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_elapsed_line_only_with_flag() {
+        // The last stderr line of a mock run with `flags`
+        async fn closing_line(flags: &[&str]) -> (bool, String) {
+            let argv = [&["chatdelta", "--mock", "--no-summary"][..], flags].concat();
+            let mut stderr = Vec::new();
+            let result = run_reporting_to(Args::try_parse_from(argv).unwrap(), &mut stderr).await;
+            (result.is_ok(), String::from_utf8(stderr).unwrap())
+        }
+
+        let (ok, stderr) = closing_line(&["--elapsed", "Hi"]).await;
+        assert!(ok);
+        assert!(
+            stderr.starts_with("Completed in ") && stderr.ends_with("s\n"),
+            "{:?}",
+            stderr
+        );
+        let (ok, stderr) = closing_line(&["--elapsed", "--temperature", "5", "Hi"]).await;
+        assert!(!ok);
+        assert!(stderr.starts_with("Failed after "), "{:?}", stderr);
+
+        assert_eq!(closing_line(&["Hi"]).await.1, "");
+        assert_eq!(closing_line(&["--elapsed", "--quiet", "Hi"]).await.1, "");
+        assert_eq!(
+            elapsed_line(true, true, Duration::from_millis(3420)).as_deref(),
+            Some("Completed in 3.4s")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_dir_artifacts_for_mock_run() {
+        let base = mock_run_dir("output-dir");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--session-id",
+            "exp-42",
+            "--debug-io",
+            "--output-dir",
+            base.to_str().unwrap(),
+            "Compare sorting algorithms",
+        ])
+        .unwrap();
+
+        run(args).await.expect("mock run should succeed");
+
+        // The debug-io dumps land in the run directory, not beside it
+        let entries: Vec<_> = fs::read_dir(&base).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        let run_dir = entries.into_iter().next().unwrap().unwrap().path();
+        let dir_name = run_dir.file_name().unwrap().to_string_lossy().to_string();
+        assert!(
+            dir_name.ends_with("-exp-42"),
+            "unexpected run dir {}",
+            dir_name
+        );
+        assert!(run_dir.join("debug").join("claude.json").exists());
+
+        assert_eq!(
+            fs::read_to_string(run_dir.join("prompt.txt")).unwrap(),
+            "Compare sorting algorithms"
+        );
+        for file in ["chatgpt.txt", "gemini.txt", "claude.txt"] {
+            assert!(
+                run_dir.join("responses").join(file).exists(),
+                "missing {}",
+                file
+            );
+        }
+        assert!(run_dir.join("summary.txt").exists());
+
+        let metrics: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(run_dir.join("metrics.json")).unwrap())
+                .unwrap();
+        assert_eq!(metrics["successful_responses"], 3);
+        let config: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(run_dir.join("config.json")).unwrap())
+                .unwrap();
+        assert_eq!(config["models"]["gpt"], "gpt-4o");
+
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_no_trim_sends_prompt_file_verbatim() {
+        let dir = mock_run_dir("no-trim");
+        fs::create_dir_all(&dir).unwrap();
+        let prompt_file = dir.join("completion.txt");
+        fs::write(&prompt_file, "def add(a, b):\n    ").unwrap();
+
+        for (no_trim, expected) in [(true, "def add(a, b):\n    "), (false, "def add(a, b):")] {
+            let log_dir = dir.join(format!("logs-{}", no_trim));
+            let mut argv = vec![
+                "chatdelta",
+                "--mock",
+                "--quiet",
+                "--log-dir",
+                log_dir.to_str().unwrap(),
+                "--log-format",
+                "json",
+                "--prompt-file",
+                prompt_file.to_str().unwrap(),
+            ];
+            if no_trim {
+                argv.push("--no-trim");
+            }
+
+            run(Args::try_parse_from(argv).unwrap()).await.unwrap();
+            assert_eq!(read_log_entry(&log_dir).prompt, expected);
+        }
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_role_applies_its_system_prompt_and_prefix() {
+        let mut args =
+            Args::try_parse_from(["chatdelta", "--role", "reviewer", "fn main() {}"]).unwrap();
+        roles::apply(&mut args).unwrap();
+        assert!(args
+            .system_prompt
+            .as_deref()
+            .unwrap()
+            .starts_with("You are a senior software engineer"));
+        assert_eq!(args.prompt_prefix.as_deref(), Some("Review the following:"));
+
+        // Explicit flags win over the role's
+        let mut args = Args::try_parse_from([
+            "chatdelta",
+            "--role",
+            "reviewer",
+            "--system-prompt",
+            "Be brief.",
+            "Hi",
+        ])
+        .unwrap();
+        roles::apply(&mut args).unwrap();
+        assert_eq!(args.system_prompt.as_deref(), Some("Be brief."));
+
+        let dir = mock_run_dir("role");
+        let log_dir = dir.join("logs");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--role",
+            "reviewer",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "fn main() {}",
+        ])
+        .unwrap();
+        run(args).await.unwrap();
+        assert_eq!(
+            read_log_entry(&log_dir).prompt,
+            "Review the following:\n\nfn main() {}"
+        );
+
+        let unknown =
+            Args::try_parse_from(["chatdelta", "--mock", "--role", "poet", "Hi"]).unwrap();
+        assert!(matches!(run(unknown).await, Err(CliError::Validation(_))));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_prompt_base64_is_decoded_before_sending() {
+        let dir = mock_run_dir("prompt-base64");
+        let log_dir = dir.join("logs");
+        // "echo 'it''s $5 & <done>'"
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--prompt-base64",
+            "ZWNobyAnaXQnJ3MgJDUgJiA8ZG9uZT4nCg==",
+        ])
+        .unwrap();
+        run(args).await.unwrap();
+        assert_eq!(read_log_entry(&log_dir).prompt, "echo 'it''s $5 & <done>'");
+
+        let invalid =
+            Args::try_parse_from(["chatdelta", "--mock", "--prompt-base64", "%%%"]).unwrap();
+        assert!(matches!(run(invalid).await, Err(CliError::Validation(_))));
+        assert!(Args::try_parse_from(["chatdelta", "--prompt-base64"]).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cost_limit_bypassed_with_yes() {
+        // Without --yes this would abort, or prompt when the test runs on a terminal
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--quiet",
+            "--cost-limit",
+            "0.000001",
+            "--yes",
+            "Hello",
+        ])
+        .unwrap();
+        run(args).await.expect("--yes bypasses the cost limit");
+    }
+
+    #[tokio::test]
+    async fn test_mock_run_with_failing_provider() {
+        let dir = mock_run_dir("mock-fail");
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "claude",
+            "--quiet",
+            "--log-dir",
+            dir.to_str().unwrap(),
+            "--log-format",
+            "json",
+            "--log-errors",
+            "Hello",
+        ])
+        .unwrap();
+
+        run(args)
+            .await
+            .expect("two of three mock providers succeed");
+
+        let entry = read_log_entry(&dir);
+        assert!(entry.responses["ChatGPT"].success);
+        assert!(!entry.responses["Claude"].success);
+        assert!(entry.errors.iter().any(|e| e.model == "Claude"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_stats_file_appends_anonymized_records() {
+        let dir = mock_run_dir("stats");
+        fs::create_dir_all(&dir).unwrap();
+        let stats_path = dir.join("stats").join("usage.jsonl");
+        let prompt = "My very private question";
+        for _ in 0..2 {
+            let args = Args::try_parse_from([
+                "chatdelta",
+                "--mock",
+                "--mock-fail",
+                "claude",
+                "--quiet",
+                "--no-summary",
+                "--output",
+                dir.join("out.txt").to_str().unwrap(),
+                "--stats-file",
+                stats_path.to_str().unwrap(),
+                prompt,
+            ])
+            .unwrap();
+            run(args)
+                .await
+                .expect("two of three mock providers succeed");
+        }
+
+        let text = fs::read_to_string(&stats_path).unwrap();
+        assert!(
+            !text.contains("private question") && !text.contains("[mock"),
+            "{}",
+            text
+        );
+        let records: Vec<serde_json::Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        let record = &records[1];
+        let keys: Vec<&String> = record.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            vec![
+                "duration_ms",
+                "estimated_cost_usd",
+                "exit_code",
+                "failures",
+                "models",
+                "responses",
+                "success",
+                "timestamp"
+            ]
+        );
+        assert_eq!(record["success"], true);
+        assert_eq!(
+            (record["responses"].as_u64(), record["failures"].as_u64()),
+            (Some(2), Some(1))
+        );
+        let models = record["models"].as_array().unwrap();
+        assert_eq!(models.len(), 3);
+        let claude = models.iter().find(|m| m["name"] == "Claude").unwrap();
+        assert_eq!(claude["success"], false);
+        assert!(record["estimated_cost_usd"].as_f64().unwrap() > 0.0);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_mock_run_all_failing_is_an_error() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-fail",
+            "gpt,gemini,claude",
+            "--quiet",
+            "Hello",
+        ])
+        .unwrap();
+
+        let err = run(args).await.unwrap_err();
+        assert!(err.to_string().contains("No successful responses"));
+        assert_eq!(err.exit_code(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_mock_run_respects_max_runtime() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--mock",
+            "--mock-delay",
+            "10000",
+            "--max-runtime",
+            "1",
+            "--quiet",
+            "Hello",
+        ])
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let err = run(args).await.unwrap_err();
+        assert!(err.to_string().contains("--max-runtime"));
+        assert_eq!(err.exit_code(), 6);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_only_provider_groups() {
+        let args = Args::try_parse_from(["chatdelta", "--only", "fast", "Hi"]).unwrap();
+        args.validate().expect("fast group should validate");
+        // Only Gemini's default model (gemini-2.5-flash) is a fast variant
+        assert!(!args.should_use_ai("gpt"));
+        assert!(args.should_use_ai("gemini"));
+        assert!(!args.should_use_ai("claude"));
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--gpt-model",
+            "gpt-4o-mini",
+            "--claude-model",
+            "claude-haiku-4-5-20251001",
+            "--only",
+            "fast",
+            "Hi",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.expand_ai_names(&args.only).unwrap(),
+            vec!["gpt", "gemini", "claude"]
+        );
+
+        let args = Args::try_parse_from(["chatdelta", "--exclude", "all", "Hi"]).unwrap();
+        assert!(!args.should_use_ai("claude"));
+
+        // Per-name selection still works alongside groups
+        let args = Args::try_parse_from(["chatdelta", "--only", "claude,flagship", "Hi"]).unwrap();
+        assert!(args.should_use_ai("claude"));
+        assert!(!args.should_use_ai("gpt"));
+
+        let args = Args::try_parse_from(["chatdelta", "--only", "cheap", "Hi"]).unwrap();
+        let err = args.validate().unwrap_err();
+        assert!(err.contains("Unknown AI or group 'cheap'"), "{}", err);
+    }
+
+    #[test]
+    fn test_selection_names_are_normalized_and_deduplicated() {
+        let mut args =
+            Args::try_parse_from(["chatdelta", "--only", "GPT,gpt, Claude,claude,gpt", "Hi"])
+                .unwrap();
+        let repeats = args.normalize_selection();
+        assert_eq!(args.only, vec!["gpt", "claude"]);
+        assert_eq!(
+            repeats,
+            vec![
+                "--only lists 'gpt' more than once; using it once",
+                "--only lists 'claude' more than once; using it once",
+            ]
+        );
+        args.validate().expect("normalized names should validate");
+        assert!(args.should_use_ai("gpt") && args.should_use_ai("claude"));
+        assert!(!args.should_use_ai("gemini"));
+
+        let mut args =
+            Args::try_parse_from(["chatdelta", "--exclude", "Gemini,FAST", "Hi"]).unwrap();
+        assert!(args.normalize_selection().is_empty());
+        assert_eq!(args.exclude, vec!["gemini", "fast"]);
+        args.validate().expect("uppercase groups should validate");
+        assert!(!args.should_use_ai("gemini"));
+
+        // A model alias is matched as written
+        let mut args =
+            Args::try_parse_from(["chatdelta", "--only", "FastGPT,FastGPT", "Hi"]).unwrap();
+        args.model_map.insert(
+            "FastGPT".to_string(),
+            config::ModelAlias {
+                provider: "openai".to_string(),
+                model: "gpt-4o-mini".to_string(),
+            },
+        );
+        assert_eq!(args.normalize_selection().len(), 1);
+        assert_eq!(args.only, vec!["FastGPT"]);
+    }
+
+    #[test]
+    fn test_timeout_escalation_raises_only_query_clients() {
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--timeout",
+            "30",
+            "--retries",
+            "2",
+            "--timeout-escalation",
+            "Hi",
+        ])
+        .unwrap();
+        assert_eq!(
+            client_config(&args, "gpt", 0).timeout,
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            with_timeout_cap(&args, client_config(&args, "gpt", 0)).timeout,
+            Duration::from_secs(120)
+        );
+
+        let args = Args::try_parse_from(["chatdelta", "--timeout", "30", "Hi"]).unwrap();
+        assert_eq!(
+            with_timeout_cap(&args, client_config(&args, "gpt", 0)).timeout,
+            Duration::from_secs(30)
+        );
+
+        for flag in ["--stream", "--test", "--conversation"] {
+            assert!(Args::try_parse_from([
+                "chatdelta",
+                "--retries",
+                "2",
+                "--timeout-escalation",
+                flag,
+                "Hi"
+            ])
+            .is_err());
+        }
+        assert!(Args::try_parse_from([
+            "chatdelta",
+            "--retries",
+            "2",
+            "--timeout-escalation",
+            "--benchmark",
+            "3"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_timeout_reaches_client_config() {
+        let args = Args::try_parse_from(["chatdelta", "--timeout", "20", "Hi"]).unwrap();
+        assert_eq!(
+            client_config(&args, "gpt", 0).timeout,
+            Duration::from_secs(20)
+        );
+        assert!(args.timeout_warning().is_none());
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--connect-timeout",
+            "5",
+            "--read-timeout",
+            "300",
+            "Hi",
+        ])
+        .unwrap();
+        args.validate().unwrap();
+        assert_eq!(
+            client_config(&args, "claude", 0).timeout,
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            test_client_config(&args, "gpt").timeout,
+            Duration::from_secs(300)
+        );
+        // The library has one timeout, so the connect timeout is reported rather than applied
+        let warning = args.timeout_warning().unwrap();
+        assert!(
+            warning.contains("--connect-timeout 5s") && warning.contains("300s"),
+            "{}",
+            warning
+        );
+
+        let args = Args::try_parse_from(["chatdelta", "--read-timeout", "0", "Hi"]).unwrap();
+        assert!(args.validate().is_err());
+    }
+
+    #[test]
+    fn test_finish_reason_length_heuristic() {
+        // A reported reason always wins
+        assert_eq!(
+            resolve_finish_reason(Some("stop"), Some(1024), 1024).as_deref(),
+            Some("stop")
+        );
+        // Unreported reason at the token cap is inferred as a length cutoff
+        assert_eq!(
+            resolve_finish_reason(None, Some(1024), 1024).as_deref(),
+            Some("length")
+        );
+        // Under the cap, or without token counts, nothing is inferred
+        assert_eq!(resolve_finish_reason(None, Some(300), 1024), None);
+        assert_eq!(resolve_finish_reason(None, None, 1024), None);
+
+        assert!(is_length_cutoff("length"));
+        assert!(is_length_cutoff("max_tokens"));
+        assert!(is_length_cutoff("MAX_TOKENS"));
+        assert!(!is_length_cutoff("stop"));
+        assert!(!is_length_cutoff("content_filter"));
+    }
+
     #[test]
     fn test_stream_flag_parsing() {
         let args = Args::try_parse_from(["chatdelta", "--stream", "--only", "claude", "Hello"])
@@ -1107,7 +5067,10 @@ mod tests {
         assert_eq!(session.history().messages[0].role, "user");
 
         // Send a message — history grows and mock response is consumed
-        let r1 = session.send("What is the capital of France?").await.unwrap();
+        let r1 = session
+            .send("What is the capital of France?")
+            .await
+            .unwrap();
         assert_eq!(r1, "The capital of France is Paris.");
         assert_eq!(session.history().messages.len(), 4);
 
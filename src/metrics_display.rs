@@ -2,9 +2,10 @@
 //! Uses the core library's ClientMetrics for consistent performance tracking
 
 use chatdelta::{ClientMetrics, MetricsSnapshot};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Write;
-use chrono::{DateTime, Utc};
 
 /// Metrics collector for the CLI session
 pub struct CliMetrics {
@@ -14,6 +15,8 @@ pub struct CliMetrics {
     session_metrics: ClientMetrics,
     /// Session start time
     start_time: DateTime<Utc>,
+    /// Latency of each successful request per provider, for percentiles
+    latencies: HashMap<String, Vec<u64>>,
 }
 
 impl CliMetrics {
@@ -23,9 +26,10 @@ impl CliMetrics {
             provider_metrics: HashMap::new(),
             session_metrics: ClientMetrics::new(),
             start_time: Utc::now(),
+            latencies: HashMap::new(),
         }
     }
-    
+
     /// Get or create metrics for a provider
     pub fn get_provider_metrics(&mut self, provider: &str) -> ClientMetrics {
         self.provider_metrics
@@ -33,33 +37,49 @@ impl CliMetrics {
             .or_insert_with(ClientMetrics::new)
             .clone()
     }
-    
+
     /// Record a successful API call
     pub fn record_success(&mut self, provider: &str, latency_ms: u64, tokens: Option<u32>) {
-        if let Some(metrics) = self.provider_metrics.get(provider) {
-            metrics.record_request(true, latency_ms, tokens);
-        }
-        self.session_metrics.record_request(true, latency_ms, tokens);
+        self.get_provider_metrics(provider)
+            .record_request(true, latency_ms, tokens);
+        self.session_metrics
+            .record_request(true, latency_ms, tokens);
+        self.latencies
+            .entry(provider.to_string())
+            .or_default()
+            .push(latency_ms);
     }
-    
+
     /// Record a failed API call
     pub fn record_failure(&mut self, provider: &str, latency_ms: u64) {
-        if let Some(metrics) = self.provider_metrics.get(provider) {
-            metrics.record_request(false, latency_ms, None);
-        }
+        self.get_provider_metrics(provider)
+            .record_request(false, latency_ms, None);
         self.session_metrics.record_request(false, latency_ms, None);
     }
-    
+
+    /// Latency distribution of a provider's successful calls
+    pub fn latency_stats(&self, provider: &str) -> Option<LatencyStats> {
+        let mut sorted = self.latencies.get(provider)?.clone();
+        sorted.sort_unstable();
+        Some(LatencyStats {
+            count: sorted.len(),
+            min_ms: *sorted.first()?,
+            median_ms: percentile(&sorted, 50.0)?,
+            p95_ms: percentile(&sorted, 95.0)?,
+            max_ms: *sorted.last()?,
+        })
+    }
+
     /// Get session summary
     pub fn get_session_summary(&self) -> SessionSummary {
         let session_stats = self.session_metrics.get_stats();
         let duration = Utc::now().signed_duration_since(self.start_time);
-        
+
         let mut provider_summaries = Vec::new();
         for (name, metrics) in &self.provider_metrics {
             provider_summaries.push((name.clone(), metrics.get_stats()));
         }
-        
+
         SessionSummary {
             duration_seconds: duration.num_seconds() as u64,
             total_requests: session_stats.requests_total,
@@ -69,34 +89,38 @@ impl CliMetrics {
             provider_stats: provider_summaries,
         }
     }
-    
+
     /// Display metrics in a formatted table
     pub fn display_metrics(&self, verbose: bool) {
         let summary = self.get_session_summary();
-        
+
         println!("\n📊 Performance Metrics");
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        
+
         // Session overview
         println!("📍 Session Duration: {}s", summary.duration_seconds);
         println!("📍 Total Requests: {}", summary.total_requests);
         println!("📍 Success Rate: {:.1}%", summary.success_rate);
-        let latency_str = summary.average_latency_ms
+        let latency_str = summary
+            .average_latency_ms
             .map(|l| format!("{:.0}ms", l))
             .unwrap_or_else(|| "N/A".to_string());
         println!("📍 Avg Latency: {}", latency_str);
-        
+
         if summary.total_tokens > 0 {
             println!("📍 Total Tokens: {}", summary.total_tokens);
         }
-        
+
         if verbose && !summary.provider_stats.is_empty() {
             println!("\n🔍 Per-Provider Breakdown:");
             for (provider, stats) in &summary.provider_stats {
                 println!("\n  {}:", provider);
-                println!("    • Requests: {} (Success: {:.1}%)", 
-                    stats.requests_total, stats.success_rate);
-                let latency = stats.average_latency_ms
+                println!(
+                    "    • Requests: {} (Success: {:.1}%)",
+                    stats.requests_total, stats.success_rate
+                );
+                let latency = stats
+                    .average_latency_ms
                     .map(|l| format!("{:.0}ms", l))
                     .unwrap_or_else(|| "N/A".to_string());
                 println!("    • Avg Latency: {}", latency);
@@ -108,10 +132,10 @@ impl CliMetrics {
                 }
             }
         }
-        
+
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     }
-    
+
     /// Export metrics as JSON
     pub fn export_json(&self) -> serde_json::Value {
         let summary = self.get_session_summary();
@@ -137,17 +161,189 @@ impl CliMetrics {
             }).collect::<serde_json::Map<_, _>>(),
         })
     }
-    
-    /// Save metrics to file
-    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
-        let json = self.export_json();
+
+    /// Export metrics in the Prometheus text exposition format, labeled by provider
+    pub fn export_prometheus(&self) -> String {
+        let summary = self.get_session_summary();
+        let mut providers: Vec<&(String, MetricsSnapshot)> =
+            summary.provider_stats.iter().collect();
+        providers.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = String::new();
+        let counters: [PrometheusCounter; 4] = [
+            ("chatdelta_requests_total", "Requests sent", |s| {
+                s.requests_total
+            }),
+            (
+                "chatdelta_requests_successful_total",
+                "Requests that succeeded",
+                |s| s.requests_successful,
+            ),
+            (
+                "chatdelta_requests_failed_total",
+                "Requests that failed",
+                |s| s.requests_failed,
+            ),
+            ("chatdelta_tokens_total", "Tokens used", |s| {
+                s.total_tokens_used
+            }),
+        ];
+        for (name, help, value) in counters {
+            out.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} counter\n",
+                name, help, name
+            ));
+            for (provider, stats) in &providers {
+                out.push_str(&format!(
+                    "{}{{provider=\"{}\"}} {}\n",
+                    name,
+                    label_value(provider),
+                    value(stats)
+                ));
+            }
+        }
+
+        out.push_str(
+            "# HELP chatdelta_latency_average_ms Average request latency in milliseconds\n",
+        );
+        out.push_str("# TYPE chatdelta_latency_average_ms gauge\n");
+        for (provider, stats) in &providers {
+            if let Some(average) = stats.average_latency_ms {
+                out.push_str(&format!(
+                    "chatdelta_latency_average_ms{{provider=\"{}\"}} {}\n",
+                    label_value(provider),
+                    average
+                ));
+            }
+        }
+
+        out.push_str(
+            "# HELP chatdelta_latency_ms Latency of successful requests in milliseconds\n",
+        );
+        out.push_str("# TYPE chatdelta_latency_ms gauge\n");
+        for (provider, _) in &providers {
+            let Some(latency) = self.latency_stats(provider) else {
+                continue;
+            };
+            let stats = [
+                ("min", latency.min_ms),
+                ("median", latency.median_ms),
+                ("p95", latency.p95_ms),
+                ("max", latency.max_ms),
+            ];
+            for (stat, value) in stats {
+                out.push_str(&format!(
+                    "chatdelta_latency_ms{{provider=\"{}\",stat=\"{}\"}} {}\n",
+                    label_value(provider),
+                    stat,
+                    value
+                ));
+            }
+        }
+
+        out.push_str("# HELP chatdelta_session_duration_seconds Time since the session started\n");
+        out.push_str("# TYPE chatdelta_session_duration_seconds gauge\n");
+        out.push_str(&format!(
+            "chatdelta_session_duration_seconds {}\n",
+            summary.duration_seconds
+        ));
+        out
+    }
+
+    /// Save metrics to file as `json` or `prometheus`
+    pub fn save_to_file(
+        &self,
+        path: &std::path::Path,
+        format: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let mut file = std::fs::File::create(path)?;
-        serde_json::to_writer_pretty(&mut file, &json)?;
+        if format == "prometheus" {
+            file.write_all(self.export_prometheus().as_bytes())?;
+        } else {
+            serde_json::to_writer_pretty(&mut file, &self.export_json())?;
+        }
         file.flush()?;
         Ok(())
     }
 }
 
+/// Counter name, help text and the snapshot field it reports
+type PrometheusCounter = (&'static str, &'static str, fn(&MetricsSnapshot) -> u64);
+
+/// A Prometheus label value with backslashes, quotes and newlines escaped
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Latency distribution of successful requests, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Nearest-rank percentile (0-100) of ascending samples; None when there are none
+pub fn percentile(sorted: &[u64], p: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// One model's line in a --benchmark report
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRow {
+    pub model: String,
+    pub runs: u32,
+    /// Share of runs that succeeded, 0-100
+    pub success_rate: f64,
+    /// Latency of the successful runs; None when every run failed
+    pub latency: Option<LatencyStats>,
+}
+
+impl BenchmarkRow {
+    pub fn new(metrics: &CliMetrics, model: &str, runs: u32) -> Self {
+        let latency = metrics.latency_stats(model);
+        let successes = latency.map_or(0, |l| l.count);
+        BenchmarkRow {
+            model: model.to_string(),
+            runs,
+            success_rate: successes as f64 * 100.0 / f64::from(runs.max(1)),
+            latency,
+        }
+    }
+}
+
+/// Comparison table for --benchmark
+pub fn render_benchmark(rows: &[BenchmarkRow]) -> String {
+    let mut out = format!(
+        "{:<20} {:>5} {:>8} {:>9} {:>9} {:>9} {:>9}\n",
+        "Model", "Runs", "Success", "Min", "Median", "P95", "Max"
+    );
+    out.push_str(&format!("{}\n", "\u{2500}".repeat(74)));
+    for row in rows {
+        let ms = |value: Option<u64>| value.map_or_else(|| "-".to_string(), |v| format!("{}ms", v));
+        out.push_str(&format!(
+            "{:<20} {:>5} {:>7.1}% {:>9} {:>9} {:>9} {:>9}\n",
+            row.model,
+            row.runs,
+            row.success_rate,
+            ms(row.latency.map(|l| l.min_ms)),
+            ms(row.latency.map(|l| l.median_ms)),
+            ms(row.latency.map(|l| l.p95_ms)),
+            ms(row.latency.map(|l| l.max_ms)),
+        ));
+    }
+    out
+}
+
 /// Summary of session metrics
 #[derive(Debug, Clone)]
 pub struct SessionSummary {
@@ -163,4 +359,91 @@ impl Default for CliMetrics {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_of_known_latencies() {
+        let sorted: Vec<u64> = (1..=20).map(|n| n * 10).collect();
+        assert_eq!(percentile(&sorted, 50.0), Some(100));
+        assert_eq!(percentile(&sorted, 95.0), Some(190));
+        assert_eq!(percentile(&sorted, 100.0), Some(200));
+        assert_eq!(percentile(&sorted, 0.0), Some(10));
+        assert_eq!(percentile(&[42], 95.0), Some(42));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn prometheus_export_has_labeled_metrics() {
+        let mut metrics = CliMetrics::new();
+        metrics.record_success("Claude", 120, Some(40));
+        metrics.record_success("Claude", 80, Some(30));
+        metrics.record_failure("Gemini", 30_000);
+        metrics.record_success("My \"GPT\"", 50, None);
+
+        let text = metrics.export_prometheus();
+        let mut typed = Vec::new();
+        let mut samples = Vec::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert!(matches!(kind, "counter" | "gauge"), "{}", line);
+                typed.push(name.to_string());
+            } else if !line.starts_with("# HELP ") {
+                // name{labels} value
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                assert!(value.parse::<f64>().is_ok(), "{}", line);
+                let name = series.split('{').next().unwrap();
+                assert!(typed.iter().any(|t| t == name), "{} has no TYPE line", name);
+                assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+                samples.push(series.to_string());
+            }
+        }
+
+        for name in [
+            "chatdelta_requests_total",
+            "chatdelta_requests_successful_total",
+            "chatdelta_requests_failed_total",
+            "chatdelta_tokens_total",
+            "chatdelta_latency_ms",
+            "chatdelta_session_duration_seconds",
+        ] {
+            assert!(typed.iter().any(|t| t == name), "missing {}", name);
+        }
+        assert!(samples.contains(&"chatdelta_requests_total{provider=\"Gemini\"}".to_string()));
+        assert!(
+            samples.contains(&"chatdelta_requests_failed_total{provider=\"Claude\"}".to_string())
+        );
+        assert!(
+            samples.contains(&"chatdelta_requests_total{provider=\"My \\\"GPT\\\"\"}".to_string())
+        );
+        assert!(text.contains("chatdelta_latency_ms{provider=\"Claude\",stat=\"max\"} 120\n"));
+        assert!(text.contains("chatdelta_latency_ms{provider=\"Claude\",stat=\"min\"} 80\n"));
+    }
+
+    #[test]
+    fn benchmark_rows_from_recorded_calls() {
+        let mut metrics = CliMetrics::new();
+        for latency in [300, 100, 500, 200, 400] {
+            metrics.record_success("Claude", latency, None);
+        }
+        metrics.record_failure("Claude", 30_000);
+        metrics.record_failure("Gemini", 30_000);
+
+        let claude = BenchmarkRow::new(&metrics, "Claude", 6);
+        let stats = claude.latency.unwrap();
+        assert_eq!(
+            (stats.min_ms, stats.median_ms, stats.p95_ms, stats.max_ms),
+            (100, 300, 500, 500)
+        );
+        assert!((claude.success_rate - 500.0 / 6.0).abs() < 1e-9);
+
+        let gemini = BenchmarkRow::new(&metrics, "Gemini", 1);
+        assert_eq!(gemini.success_rate, 0.0);
+        assert!(gemini.latency.is_none());
+        assert!(render_benchmark(&[claude, gemini]).contains("Gemini"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Built-in model metadata for ChatDelta CLI (`--list-models --detailed`)
+//!
+//! One row per well-known model: its provider, context window, whether it takes images
+//! and tools, and a rough cost tier. Prompt chunking and the model map consult the same
+//! table, falling back to name-based guesses for models not listed.
+
+use serde::Serialize;
+
+/// Relative price band, from the published per-token list prices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostTier {
+    Low,
+    Medium,
+    High,
+}
+
+impl CostTier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CostTier::Low => "$",
+            CostTier::Medium => "$$",
+            CostTier::High => "$$$",
+        }
+    }
+}
+
+/// What ChatDelta knows about a model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelInfo {
+    pub id: &'static str,
+    /// The AI serving it: gpt, gemini or claude
+    pub provider: &'static str,
+    pub context_window: u32,
+    pub vision: bool,
+    pub tools: bool,
+    pub cost_tier: CostTier,
+    /// Short description for the model list
+    pub note: &'static str,
+}
+
+const fn model(
+    id: &'static str,
+    provider: &'static str,
+    context_window: u32,
+    vision: bool,
+    cost_tier: CostTier,
+    note: &'static str,
+) -> ModelInfo {
+    ModelInfo {
+        id,
+        provider,
+        context_window,
+        vision,
+        tools: true,
+        cost_tier,
+        note,
+    }
+}
+
+/// Known models, grouped by provider in `--list-models` order
+pub const MODELS: &[ModelInfo] = &[
+    model(
+        "gpt-5.4",
+        "gpt",
+        400_000,
+        true,
+        CostTier::Medium,
+        "flagship reasoning \u{2014} most capable",
+    ),
+    model(
+        "o3",
+        "gpt",
+        200_000,
+        true,
+        CostTier::Medium,
+        "strong reasoning, complex tasks",
+    ),
+    model(
+        "gpt-4o",
+        "gpt",
+        128_000,
+        true,
+        CostTier::Medium,
+        "fast, highly capable \u{2014} default",
+    ),
+    model(
+        "gpt-4o-mini",
+        "gpt",
+        128_000,
+        true,
+        CostTier::Low,
+        "faster, cheaper",
+    ),
+    model(
+        "gemini-3.1-pro",
+        "gemini",
+        1_048_576,
+        true,
+        CostTier::Medium,
+        "most capable, reasoning-first",
+    ),
+    model(
+        "gemini-2.5-flash",
+        "gemini",
+        1_048_576,
+        true,
+        CostTier::Low,
+        "fast, cost-efficient \u{2014} default",
+    ),
+    model(
+        "gemini-2.5-flash-lite",
+        "gemini",
+        1_048_576,
+        true,
+        CostTier::Low,
+        "fastest, highest throughput",
+    ),
+    model(
+        "claude-opus-4-6",
+        "claude",
+        200_000,
+        true,
+        CostTier::High,
+        "most capable",
+    ),
+    model(
+        "claude-sonnet-4-6",
+        "claude",
+        200_000,
+        true,
+        CostTier::Medium,
+        "balanced \u{2014} default",
+    ),
+    model(
+        "claude-haiku-4-5-20251001",
+        "claude",
+        200_000,
+        true,
+        CostTier::Low,
+        "fast, lightweight",
+    ),
+];
+
+/// Metadata for a known model, matched case-insensitively by exact id
+pub fn lookup(model: &str) -> Option<&'static ModelInfo> {
+    MODELS
+        .iter()
+        .find(|info| info.id.eq_ignore_ascii_case(model))
+}
+
+/// Rendered as an aligned table for `--list-models --detailed`
+pub fn render_table(models: &[ModelInfo]) -> String {
+    let id_width = models
+        .iter()
+        .map(|m| m.id.len())
+        .max()
+        .unwrap_or(0)
+        .max("MODEL".len());
+    let mut out = format!(
+        "{:<id$}  {:<8}  {:>9}  {:<6}  {:<5}  {}\n",
+        "MODEL",
+        "PROVIDER",
+        "CONTEXT",
+        "VISION",
+        "TOOLS",
+        "COST",
+        id = id_width
+    );
+    for m in models {
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+        out.push_str(&format!(
+            "{:<id$}  {:<8}  {:>9}  {:<6}  {:<5}  {}\n",
+            m.id,
+            m.provider,
+            format_window(m.context_window),
+            yes_no(m.vision),
+            yes_no(m.tools),
+            m.cost_tier.as_str(),
+            id = id_width
+        ));
+    }
+    out
+}
+
+/// Context window for display: 128K, 1M
+fn format_window(tokens: u32) -> String {
+    if tokens >= 1_000_000 {
+        format!("{}M", tokens / 1_000_000)
+    } else {
+        format!("{}K", tokens / 1_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost;
+
+    /// The cost tier implied by a model's output list price
+    fn priced_tier(model: &str) -> Option<CostTier> {
+        cost::price_for(cost::PRICING, model).map(|p| match p.output_per_mtok {
+            o if o <= 5.0 => CostTier::Low,
+            o if o <= 15.0 => CostTier::Medium,
+            _ => CostTier::High,
+        })
+    }
+
+    #[test]
+    fn known_models_have_capability_fields() {
+        let gpt = lookup("GPT-4o").unwrap();
+        assert_eq!(
+            (gpt.provider, gpt.context_window, gpt.vision, gpt.tools),
+            ("gpt", 128_000, true, true)
+        );
+        assert_eq!(
+            lookup("gemini-2.5-flash").unwrap().context_window,
+            1_048_576
+        );
+        assert_eq!(lookup("claude-opus-4-6").unwrap().cost_tier, CostTier::High);
+        assert!(lookup("llama-3").is_none());
+
+        let json = serde_json::to_value(lookup("o3").unwrap()).unwrap();
+        for field in [
+            "id",
+            "provider",
+            "context_window",
+            "vision",
+            "tools",
+            "cost_tier",
+        ] {
+            assert!(json.get(field).is_some(), "missing {}", field);
+        }
+        assert_eq!(json["cost_tier"], "medium");
+    }
+
+    #[test]
+    fn cost_tiers_follow_list_prices() {
+        for info in MODELS {
+            assert_eq!(priced_tier(info.id), Some(info.cost_tier), "{}", info.id);
+        }
+    }
+
+    #[test]
+    fn table_is_aligned() {
+        let table = render_table(MODELS);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), MODELS.len() + 1);
+        let provider_column = lines[0].find("PROVIDER").unwrap();
+        assert!(lines[1..]
+            .iter()
+            .all(|line| line[provider_column..].starts_with(|c: char| c.is_alphabetic())));
+        let lite = lines
+            .iter()
+            .find(|line| line.starts_with("gemini-2.5-flash-lite "))
+            .unwrap();
+        assert!(
+            lite.ends_with("gemini           1M  yes     yes    $"),
+            "{}",
+            lite
+        );
+    }
+}
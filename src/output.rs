@@ -1,75 +1,407 @@
 //! Output formatting for ChatDelta CLI
 
-use crate::cli::Args;
+use crate::cli::{Args, AI_NAMES};
+use crate::compare::{self, ConsensusPhrase};
+use crate::diff::{self, DiffChunk};
+use crate::json_util;
+use crate::tone::{self, ToneScore};
+use crate::tools::ToolCall;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-/// Output results in the specified format
+/// Per-model details reported alongside each response
+#[derive(Debug, Clone, Default)]
+pub struct ResponseMeta {
+    pub latency_ms: Option<u64>,
+    pub total_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+    pub response_hash: Option<String>,
+    /// Tokens per second, when both token count and latency are known
+    pub throughput: Option<f64>,
+    /// Function call parsed from the response with --tool-schema
+    pub tool_call: Option<ToolCall>,
+}
+
+/// Tokens per second from a token count and a latency in milliseconds.
+/// None when either is missing or the latency is zero.
+pub fn throughput(tokens: Option<u32>, latency_ms: Option<u64>) -> Option<f64> {
+    match (tokens, latency_ms) {
+        (Some(tokens), Some(ms)) if ms > 0 => Some(f64::from(tokens) * 1000.0 / ms as f64),
+        _ => None,
+    }
+}
+
+/// Hex-encoded SHA-256 of a response, stable across runs for identical text
+pub fn response_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// Stamp for --timestamp-filenames: UTC time plus a short random suffix, so runs started
+/// in the same second still get their own files
+pub fn file_stamp() -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    format!(
+        "{}-{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        &id[..6]
+    )
+}
+
+/// `path` with `-<stamp>` before its extension (log.txt becomes log-<stamp>.txt), or
+/// unchanged without a stamp
+pub fn stamped_path(path: &Path, stamp: Option<&str>) -> PathBuf {
+    let Some(stamp) = stamp else {
+        return path.to_path_buf();
+    };
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |s| s.to_string_lossy().to_string());
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, stamp, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, stamp),
+    };
+    path.with_file_name(name)
+}
+
+/// Output results in the specified format to `out` (stdout in a normal run).
+/// Only the formatted result is written here; progress and status go to stderr.
 pub fn output_results(
     args: &Args,
     responses: &[(String, String)],
     digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+    warnings: &[String],
+    out: &mut dyn Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // --only-summary and --hide-responses suppress the individual responses in every format
+    let responses = if args.hides_responses() {
+        &[]
+    } else {
+        responses
+    };
+
+    // --render-tables lays out delimited data for reading; JSON keeps the raw text
+    let tabulated: Vec<(String, String)>;
+    let responses = match args.format.as_str() {
+        "text" | "markdown" if args.render_tables => {
+            let markdown = args.format == "markdown";
+            tabulated = responses
+                .iter()
+                .map(|(name, response)| (name.clone(), render_tables(response, markdown)))
+                .collect();
+            &tabulated
+        }
+        _ => responses,
+    };
+
     match args.format.as_str() {
-        "json" => output_json(args, responses, digest),
-        "markdown" => output_markdown(args, responses, digest),
-        _ => output_text(args, responses, digest),
+        "json" => output_json(args, responses, digest, meta, warnings, out),
+        "ndjson" => output_ndjson(responses, digest, meta, out),
+        "markdown" => output_markdown(args, responses, digest, out),
+        _ => output_text(args, responses, digest, meta, out),
     }
 }
 
-/// Output in JSON format
-fn output_json(
-    args: &Args,
+/// Output one JSON record per line: a record per model response, then the summary
+fn output_ndjson(
     responses: &[(String, String)],
     digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+    out: &mut dyn Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut json_output = serde_json::Map::new();
-    if let Some(prompt) = args.prompt.as_ref() {
-        json_output.insert(
-            "prompt".to_string(),
-            serde_json::Value::String(prompt.clone()),
-        );
+    for line in ndjson_lines(responses, digest, meta)? {
+        writeln!(out, "{}", line)?;
     }
+    Ok(())
+}
 
-    let mut responses_obj = serde_json::Map::new();
+/// Build the NDJSON records; each line is an independently parseable JSON object
+fn ndjson_lines(
+    responses: &[(String, String)],
+    digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+) -> Result<Vec<String>, serde_json::Error> {
+    let mut lines = Vec::with_capacity(responses.len() + 1);
     for (name, response) in responses {
-        responses_obj.insert(name.clone(), serde_json::Value::String(response.clone()));
+        let mut record = serde_json::json!({
+            "model": name,
+            "response": response,
+            "latency_ms": meta.get(name).and_then(|m| m.latency_ms),
+        });
+        if let Some(hash) = meta.get(name).and_then(|m| m.response_hash.as_ref()) {
+            record["response_hash"] = serde_json::Value::String(hash.clone());
+        }
+        lines.push(serde_json::to_string(&record)?);
     }
-    json_output.insert(
-        "responses".to_string(),
-        serde_json::Value::Object(responses_obj),
-    );
-
     if let Some(summary) = digest {
-        json_output.insert(
-            "summary".to_string(),
-            serde_json::Value::String(summary.to_string()),
-        );
+        lines.push(serde_json::to_string(
+            &serde_json::json!({ "summary": summary }),
+        )?);
     }
+    Ok(lines)
+}
+
+/// Version of the `--format json` contract; bumped on breaking changes
+pub const OUTPUT_VERSION: &str = "1.0";
+
+/// JSON Schema for `--format json` output, printed by --print-output-schema. It is kept by
+/// hand; the tests validate a document with every section filled in against it.
+pub const OUTPUT_SCHEMA: &str = include_str!("../schema/output.schema.json");
+
+/// The `--format json` document. Keys are emitted in field order; optional sections are
+/// omitted unless their flag asked for them.
+#[derive(Debug, Serialize)]
+struct JsonOutput {
+    output_version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<String>,
+    responses: JsonResponses,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_hash: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call: Option<BTreeMap<String, ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens_per_second: Option<BTreeMap<String, f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tone: Option<BTreeMap<String, ToneScore>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    consensus: Option<Vec<ConsensusPhrase>>,
+    /// Summary text, or the parsed object for a --summary-format json summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<Vec<ResponseDiff>>,
+    #[serde(flatten)]
+    metadata: Option<RunMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Responses keyed by model name, or in query order with --json-ordered-array
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum JsonResponses {
+    ByModel(BTreeMap<String, String>),
+    Ordered(Vec<OrderedResponse>),
+}
+
+/// One --json-ordered-array record
+#[derive(Debug, Serialize)]
+struct OrderedResponse {
+    model: String,
+    response: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call: Option<ToolCall>,
+}
+
+/// A --diff entry: `to` compared word by word against `from`
+#[derive(Debug, Serialize)]
+struct ResponseDiff {
+    from: String,
+    to: String,
+    chunks: Vec<DiffChunk>,
+}
+
+/// Run details added by --attach-metadata
+#[derive(Debug, Serialize)]
+struct RunMetadata {
+    timestamp: String,
+    models: BTreeMap<String, String>,
+    temperature: Option<f32>,
+    retry_strategy: String,
+    usage: BTreeMap<String, ModelUsage>,
+}
 
-    println!("{}", serde_json::to_string_pretty(&json_output)?);
+#[derive(Debug, Serialize)]
+struct ModelUsage {
+    latency_ms: Option<u64>,
+    tokens: Option<u32>,
+}
+
+/// Output in JSON format
+fn output_json(
+    args: &Args,
+    responses: &[(String, String)],
+    digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+    warnings: &[String],
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let json_output = json_document(args, responses, digest, meta, warnings);
+    writeln!(out, "{}", serde_json::to_string_pretty(&json_output)?)?;
     Ok(())
 }
 
+/// Build the JSON result document
+fn json_document(
+    args: &Args,
+    responses: &[(String, String)],
+    digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+    warnings: &[String],
+) -> JsonOutput {
+    let model_meta = |name: &str| meta.get(name).cloned().unwrap_or_default();
+
+    let responses_section = if args.json_ordered_array {
+        // One object per response, in query order, so runs diff cleanly
+        let ordered = responses
+            .iter()
+            .map(|(name, response)| {
+                let m = model_meta(name);
+                OrderedResponse {
+                    model: name.clone(),
+                    response: response.clone(),
+                    response_hash: m.response_hash.filter(|_| args.with_hash),
+                    tool_call: m.tool_call,
+                }
+            })
+            .collect();
+        JsonResponses::Ordered(ordered)
+    } else {
+        JsonResponses::ByModel(responses.iter().cloned().collect())
+    };
+    let keyed = !args.json_ordered_array;
+
+    // --attach-metadata makes the document self-describing for archived results
+    let metadata = args.attach_metadata.then(|| RunMetadata {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        models: AI_NAMES
+            .iter()
+            .filter(|ai| args.should_use_ai(ai))
+            .map(|ai| {
+                (
+                    Args::display_name(ai).to_string(),
+                    args.model_for(ai).to_string(),
+                )
+            })
+            .collect(),
+        temperature: args.temperature,
+        retry_strategy: args.retry_strategy.clone(),
+        usage: responses
+            .iter()
+            .map(|(name, _)| {
+                let m = model_meta(name);
+                let usage = ModelUsage {
+                    latency_ms: m.latency_ms,
+                    tokens: m.total_tokens,
+                };
+                (name.clone(), usage)
+            })
+            .collect(),
+    });
+
+    JsonOutput {
+        output_version: OUTPUT_VERSION,
+        prompt: args.prompt.clone(),
+        responses: responses_section,
+        response_hash: (args.with_hash && keyed)
+            .then(|| by_model(responses, meta, |m| m.response_hash.clone())),
+        tool_call: (args.tool_schema.is_some() && keyed)
+            .then(|| by_model(responses, meta, |m| m.tool_call.clone())),
+        tokens_per_second: args.show_throughput.then(|| {
+            by_model(responses, meta, |m| {
+                m.throughput.map(|rate| (rate * 10.0).round() / 10.0)
+            })
+        }),
+        tone: args.annotate_tone.then(|| {
+            responses
+                .iter()
+                .map(|(name, response)| (name.clone(), tone::classify(response)))
+                .collect()
+        }),
+        consensus: args
+            .highlight_agreement
+            .then(|| compare::consensus(responses)),
+        summary: digest.map(|summary| {
+            structured_summary(args, summary)
+                .unwrap_or_else(|| serde_json::Value::String(summary.to_string()))
+        }),
+        diff: args.diff.then(|| {
+            response_diffs(responses)
+                .into_iter()
+                .map(|(from, to, chunks)| ResponseDiff {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    chunks,
+                })
+                .collect()
+        }),
+        metadata,
+        warnings: warnings.to_vec(),
+    }
+}
+
+/// Per-model values for a section keyed by model name, leaving out models without one
+fn by_model<T>(
+    responses: &[(String, String)],
+    meta: &HashMap<String, ResponseMeta>,
+    value: impl Fn(&ResponseMeta) -> Option<T>,
+) -> BTreeMap<String, T> {
+    responses
+        .iter()
+        .filter_map(|(name, _)| Some((name.clone(), value(meta.get(name)?)?)))
+        .collect()
+}
+
 /// Output in Markdown format
 fn output_markdown(
     args: &Args,
     responses: &[(String, String)],
     digest: Option<&str>,
+    out: &mut dyn Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("# ChatDelta Results\n");
+    writeln!(out, "# ChatDelta Results\n")?;
     if let Some(prompt) = args.prompt.as_ref() {
-        println!("**Prompt:** {}\n", prompt);
+        writeln!(out, "**Prompt:** {}\n", prompt)?;
     }
 
+    let summary_first = args.summary_position == "top";
+    let write_summary = |out: &mut dyn Write| -> std::io::Result<()> {
+        if let Some(summary) = digest {
+            writeln!(out, "## Summary\n")?;
+            match structured_summary(args, summary) {
+                Some(value) => writeln!(out, "```json\n{}\n```\n", pretty_json(&value))?,
+                None => writeln!(out, "{}\n", summary)?,
+            }
+        }
+        Ok(())
+    };
+
+    if summary_first {
+        write_summary(out)?;
+    }
+
+    let consensus = consensus_phrases(args, responses);
     for (name, response) in responses {
-        println!("## {}\n", name);
-        println!("{}\n", response);
+        writeln!(out, "## {}\n", name)?;
+        let bold = |sentence: &str| format!("**{}**", sentence);
+        writeln!(
+            out,
+            "{}\n",
+            compare::mark_consensus(name, response, &consensus, bold)
+        )?;
     }
 
-    if let Some(summary) = digest {
-        println!("## Summary\n");
-        println!("{}\n", summary);
+    if args.highlight_agreement && responses.len() > 1 {
+        writeln!(out, "## Consensus\n")?;
+        writeln!(out, "{}\n", consensus_list(&consensus))?;
+    }
+
+    if args.diff {
+        for (from, to, chunks) in response_diffs(responses) {
+            writeln!(out, "## Diff: {} \u{2192} {}\n", from, to)?;
+            writeln!(out, "{}\n", diff::render_plain(&chunks))?;
+        }
+    }
+
+    if !summary_first {
+        write_summary(out)?;
     }
 
     Ok(())
@@ -80,42 +412,272 @@ fn output_text(
     args: &Args,
     responses: &[(String, String)],
     digest: Option<&str>,
+    meta: &HashMap<String, ResponseMeta>,
+    out: &mut dyn Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let width = wrap_width(&args.wrap);
+    let wrap = |text: &str| wrap_text(text, width);
+    let digest = digest.map(|summary| match structured_summary(args, summary) {
+        Some(value) => pretty_json(&value),
+        None => summary.to_string(),
+    });
+    let digest = digest.as_deref();
+    // Color only when the result is going to a terminal
+    let to_terminal = args.output.is_none() && std::io::stdout().is_terminal();
+    let colored = diff::use_color(&args.color, to_terminal);
+    let consensus = consensus_phrases(args, responses);
+
+    // --include-prompt makes archived text output self-contained, as markdown always is
+    if let Some(prompt) = args.prompt.as_ref().filter(|_| args.include_prompt) {
+        writeln!(out, "=== Prompt ===")?;
+        writeln!(out, "{}\n", wrap(prompt))?;
+    }
+
     if responses.len() == 1 {
-        // Single response, just print it
-        println!("{}", responses[0].1);
+        // A single response prints cleanly at any verbosity; with --summarize-single the
+        // condensed version replaces it, or follows it when verbose
+        let response = wrap(&responses[0].1);
+        match digest {
+            Some(summary) if args.verbose => {
+                writeln!(out, "{}\n", response)?;
+                writeln!(out, "=== Summary ===")?;
+                writeln!(out, "{}", wrap(summary))?;
+            }
+            Some(summary) => writeln!(out, "{}", wrap(summary))?,
+            None => writeln!(out, "{}", response)?,
+        }
     } else {
-        // Multiple responses, show them separately
+        // With --summary-position top, verbose output leads with the summary
+        let summary_first = args.verbose && args.summary_position == "top";
+        if let (true, Some(summary)) = (summary_first, digest) {
+            writeln!(out, "=== Summary ===")?;
+            writeln!(out, "{}\n", wrap(summary))?;
+        }
+
+        // Multiple responses, show them separately; without a summary they're all shown
         for (name, response) in responses {
-            if args.verbose {
-                println!("=== {} ===", name);
-                println!("{}\n", response);
+            if args.verbose || digest.is_none() {
+                let rate = meta.get(name).and_then(|m| m.throughput);
+                let notes: Vec<String> = rate
+                    .filter(|_| args.show_throughput)
+                    .map(|rate| format!("{:.1} tok/s", rate))
+                    .into_iter()
+                    .chain(args.annotate_tone.then(|| tone::classify(response).label()))
+                    .collect();
+                match notes.is_empty() {
+                    true => writeln!(out, "=== {} ===", name)?,
+                    false => writeln!(out, "=== {} ({}) ===", name, notes.join(", "))?,
+                }
+                let response = match colored {
+                    true => {
+                        compare::mark_consensus(name, response, &consensus, diff::render_highlight)
+                    }
+                    false => response.clone(),
+                };
+                writeln!(out, "{}\n", wrap(&response))?;
             }
         }
 
         if let Some(summary) = digest {
             if !args.verbose {
-                println!("{}", summary);
-            } else {
-                println!("=== Summary ===");
-                println!("{}", summary);
+                writeln!(out, "{}", wrap(summary))?;
+            } else if !summary_first {
+                writeln!(out, "=== Summary ===")?;
+                writeln!(out, "{}", wrap(summary))?;
             }
-        } else if !args.verbose {
-            // No summary, show the first response
-            println!("{}", responses[0].1);
+        }
+    }
+
+    if args.highlight_agreement && responses.len() > 1 {
+        writeln!(out, "\n=== Consensus ===")?;
+        writeln!(out, "{}", wrap(&consensus_list(&consensus)))?;
+    }
+
+    if args.diff {
+        for (from, to, chunks) in response_diffs(responses) {
+            let rendered = if colored {
+                diff::render_colored(&chunks)
+            } else {
+                diff::render_plain(&chunks)
+            };
+            writeln!(out, "\n=== Diff: {} \u{2192} {} ===", from, to)?;
+            writeln!(out, "{}", rendered)?;
         }
     }
 
     Ok(())
 }
 
+/// Sentences shared by most responses, with --highlight-agreement
+fn consensus_phrases(args: &Args, responses: &[(String, String)]) -> Vec<ConsensusPhrase> {
+    match args.highlight_agreement {
+        true => compare::consensus(responses),
+        false => Vec::new(),
+    }
+}
+
+/// The consensus section: one bullet per shared sentence with the models sharing it
+fn consensus_list(phrases: &[ConsensusPhrase]) -> String {
+    if phrases.is_empty() {
+        return "No sentence is shared by a majority of the responses.".to_string();
+    }
+    phrases
+        .iter()
+        .map(|phrase| format!("- {} ({})", phrase.text, phrase.models.join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Word diffs of each response against the first, for --diff
+fn response_diffs(responses: &[(String, String)]) -> Vec<(&str, &str, Vec<DiffChunk>)> {
+    let Some((first, base)) = responses.first() else {
+        return Vec::new();
+    };
+    responses[1..]
+        .iter()
+        .map(|(name, response)| {
+            (
+                first.as_str(),
+                name.as_str(),
+                diff::diff_words(base, response),
+            )
+        })
+        .collect()
+}
+
+/// A --summary-format json summary parsed as JSON; None for other formats or when the
+/// model didn't return valid JSON, in which case the raw text is shown
+fn structured_summary(args: &Args, summary: &str) -> Option<serde_json::Value> {
+    if args.resolved_summary_format() != "json" {
+        return None;
+    }
+    json_util::extract_json(summary)
+}
+
+/// Pretty-printed JSON for display
+fn pretty_json(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+/// Resolve a --wrap value (`none`, `auto`, or a column count) to a wrap width
+fn wrap_width(spec: &str) -> Option<usize> {
+    match spec {
+        "none" => None,
+        "auto" => terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize),
+        n => n.parse().ok(),
+    }
+}
+
+/// Parse a block of lines as a table when every line has the same number of tabs, commas
+/// or semicolons; a header and one row are enough. A single line, or lines ending a
+/// sentence, are prose and give None.
+fn parse_table(block: &str) -> Option<Vec<Vec<String>>> {
+    let lines: Vec<&str> = block
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.len() < 2 || lines.iter().any(|line| line.ends_with(['.', '!', '?'])) {
+        return None;
+    }
+    ['\t', ',', ';'].into_iter().find_map(|delimiter| {
+        let count = lines[0].matches(delimiter).count();
+        if count == 0
+            || lines
+                .iter()
+                .any(|line| line.matches(delimiter).count() != count)
+        {
+            return None;
+        }
+        Some(
+            lines
+                .iter()
+                .map(|line| {
+                    line.split(delimiter)
+                        .map(|cell| cell.trim().to_string())
+                        .collect()
+                })
+                .collect(),
+        )
+    })
+}
+
+/// Aligned columns under a ruled header
+fn render_table_text(rows: &[Vec<String>]) -> String {
+    let columns = rows[0].len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| {
+            rows.iter()
+                .map(|row| row[c].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: Vec<String>| cells.join("  ").trim_end().to_string();
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (index, row) in rows.iter().enumerate() {
+        lines.push(line(
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, &w)| format!("{:<w$}", cell, w = w))
+                .collect(),
+        ));
+        if index == 0 {
+            lines.push(line(widths.iter().map(|&w| "\u{2500}".repeat(w)).collect()));
+        }
+    }
+    lines.join("\n")
+}
+
+/// A markdown table with the first row as its header
+fn render_table_markdown(rows: &[Vec<String>]) -> String {
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (index, row) in rows.iter().enumerate() {
+        lines.push(line(
+            row.iter().map(|cell| cell.replace('|', "\\|")).collect(),
+        ));
+        if index == 0 {
+            lines.push(line(vec!["---".to_string(); row.len()]));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Render each blank-line-separated block of delimited data in `text` as a table (a
+/// markdown table when `markdown`), leaving everything else as it was
+fn render_tables(text: &str, markdown: bool) -> String {
+    text.split("\n\n")
+        .map(|block| match parse_table(block) {
+            Some(rows) if markdown => render_table_markdown(&rows),
+            Some(rows) => render_table_text(&rows),
+            None => block.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Wrap each line of `text` to `width` columns, keeping existing line breaks and blank lines
+fn wrap_text(text: &str, width: Option<usize>) -> String {
+    match width {
+        Some(width) => text
+            .lines()
+            .map(|line| textwrap::fill(line, width))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => text.to_string(),
+    }
+}
+
 /// Log the interaction to a file
 pub fn log_interaction(
     args: &Args,
     responses: &[(String, String)],
     digest: Option<&str>,
+    stamp: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if let Some(path) = &args.log {
+        let path = &stamped_path(path, stamp);
         match File::create(path) {
             Ok(mut file) => {
                 if let Some(prompt) = args.prompt.as_ref() {
@@ -128,7 +690,7 @@ pub fn log_interaction(
                     let _ = writeln!(file, "Summary:\n{}\n", summary);
                 }
                 if !args.quiet {
-                    println!("✓ Conversation logged to {}", path.display());
+                    eprintln!("✓ Conversation logged to {}", path.display());
                 }
             }
             Err(e) => {
@@ -144,3 +706,874 @@ pub fn log_interaction(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_responses() -> Vec<(String, String)> {
+        vec![
+            (
+                "ChatGPT".to_string(),
+                "Line one\nwith \"quotes\"".to_string(),
+            ),
+            ("Claude".to_string(), "Second answer".to_string()),
+        ]
+    }
+
+    /// The JSON document as a generic value, for inspection
+    fn doc_value(
+        args: &Args,
+        responses: &[(String, String)],
+        digest: Option<&str>,
+        meta: &HashMap<String, ResponseMeta>,
+        warnings: &[String],
+    ) -> serde_json::Value {
+        serde_json::to_value(json_document(args, responses, digest, meta, warnings)).unwrap()
+    }
+
+    #[test]
+    fn ndjson_lines_parse_individually() {
+        let mut meta = HashMap::new();
+        meta.insert(
+            "ChatGPT".to_string(),
+            ResponseMeta {
+                latency_ms: Some(420),
+                ..Default::default()
+            },
+        );
+
+        let lines = ndjson_lines(&sample_responses(), Some("Both agree"), &meta).unwrap();
+        assert_eq!(lines.len(), 3);
+
+        let records: Vec<serde_json::Value> = lines
+            .iter()
+            .map(|l| {
+                assert!(!l.contains('\n'));
+                serde_json::from_str(l).expect("each line should be valid JSON")
+            })
+            .collect();
+        assert_eq!(records[0]["model"], "ChatGPT");
+        assert_eq!(records[0]["latency_ms"], 420);
+        assert!(records[1]["latency_ms"].is_null());
+        assert_eq!(records[2]["summary"], "Both agree");
+    }
+
+    #[test]
+    fn only_summary_json_has_no_responses() {
+        use clap::Parser;
+
+        let document = |argv: &[&str]| -> serde_json::Value {
+            let args = Args::try_parse_from(argv).unwrap();
+            let mut out = Vec::new();
+            output_results(
+                &args,
+                &sample_responses(),
+                Some("The models agree."),
+                &HashMap::new(),
+                &[],
+                &mut out,
+            )
+            .unwrap();
+            serde_json::from_slice(&out).unwrap()
+        };
+
+        let doc = document(&["chatdelta", "--only-summary", "--format", "json", "Hi"]);
+        assert_eq!(doc["summary"], "The models agree.");
+        assert!(doc["responses"].as_object().unwrap().is_empty());
+
+        let full = document(&["chatdelta", "--format", "json", "Hi"]);
+        assert_eq!(full["responses"].as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn identical_responses_hash_identically() {
+        let a = response_hash("Paris is the capital of France.");
+        let b = response_hash("Paris is the capital of France.");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, response_hash("Paris is the capital of France"));
+        assert_eq!(
+            response_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn with_hash_adds_response_hash_to_json() {
+        use clap::Parser;
+
+        let responses = sample_responses();
+        let meta: HashMap<String, ResponseMeta> = responses
+            .iter()
+            .map(|(name, text)| {
+                let m = ResponseMeta {
+                    response_hash: Some(response_hash(text)),
+                    ..Default::default()
+                };
+                (name.clone(), m)
+            })
+            .collect();
+
+        let args =
+            Args::try_parse_from(["chatdelta", "--with-hash", "--format", "json", "Hi"]).unwrap();
+        let doc = doc_value(&args, &responses, None, &meta, &[]);
+        assert_eq!(
+            doc["response_hash"]["Claude"],
+            response_hash("Second answer")
+        );
+
+        let plain = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        assert!(doc_value(&plain, &responses, None, &meta, &[])
+            .get("response_hash")
+            .is_none());
+    }
+
+    #[test]
+    fn ordered_array_follows_response_order() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--json-ordered-array",
+            "--format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+        let responses = vec![
+            ("ChatGPT".to_string(), "A".to_string()),
+            ("Gemini".to_string(), "B".to_string()),
+            ("Claude".to_string(), "C".to_string()),
+        ];
+
+        let doc = doc_value(&args, &responses, Some("All agree"), &HashMap::new(), &[]);
+        let models: Vec<&str> = doc["responses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["model"].as_str().unwrap())
+            .collect();
+        assert_eq!(models, vec!["ChatGPT", "Gemini", "Claude"]);
+        assert_eq!(doc["responses"][2]["response"], "C");
+        assert_eq!(doc["summary"], "All agree");
+    }
+
+    fn text_output(flags: &[&str], responses: &[(String, String)], digest: Option<&str>) -> String {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([&["chatdelta"], flags, &["Hi"]].concat()).unwrap();
+        let mut out = Vec::new();
+        output_results(&args, responses, digest, &HashMap::new(), &[], &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn stamped_paths_keep_their_extension() {
+        let path = Path::new("logs/run.txt");
+        assert_eq!(stamped_path(path, None), path);
+        assert_eq!(
+            stamped_path(path, Some("20260101-120000-abc123")),
+            Path::new("logs/run-20260101-120000-abc123.txt")
+        );
+        assert_eq!(
+            stamped_path(Path::new("transcript"), Some("s")),
+            Path::new("transcript-s")
+        );
+        assert_ne!(file_stamp(), file_stamp());
+    }
+
+    #[test]
+    fn text_output_includes_prompt_only_when_asked() {
+        let responses = sample_responses();
+        assert!(!text_output(&[], &responses, None).contains("Hi"));
+
+        let with_prompt = text_output(&["--include-prompt"], &responses, None);
+        assert!(with_prompt.starts_with("=== Prompt ===\nHi\n\n"));
+        assert!(with_prompt.contains("Second answer"));
+
+        let lone = vec![("Claude".to_string(), "Only answer".to_string())];
+        assert_eq!(
+            text_output(&["--include-prompt"], &lone, None),
+            "=== Prompt ===\nHi\n\nOnly answer\n"
+        );
+    }
+
+    #[test]
+    fn single_survivor_text_output() {
+        let lone = vec![("Claude".to_string(), "Only answer".to_string())];
+        assert_eq!(text_output(&[], &lone, None), "Only answer\n");
+        assert_eq!(text_output(&["--verbose"], &lone, None), "Only answer\n");
+
+        let condensed = text_output(&["--summarize-single"], &lone, Some("Short"));
+        assert_eq!(condensed, "Short\n");
+        let verbose = text_output(&["--summarize-single", "--verbose"], &lone, Some("Short"));
+        assert_eq!(verbose, "Only answer\n\n=== Summary ===\nShort\n");
+    }
+
+    #[test]
+    fn unsummarized_text_output_shows_every_response() {
+        let text = text_output(&["--no-summary"], &sample_responses(), None);
+        assert!(text.contains("=== ChatGPT ===\nLine one"));
+        assert!(text.contains("=== Claude ===\nSecond answer"));
+    }
+
+    #[test]
+    fn json_output_writes_only_the_document() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        let warnings = vec!["GEMINI_API_KEY not set, skipping Gemini".to_string()];
+        let mut stdout = Vec::new();
+        output_results(
+            &args,
+            &sample_responses(),
+            Some("Both agree"),
+            &HashMap::new(),
+            &warnings,
+            &mut stdout,
+        )
+        .unwrap();
+
+        let text = String::from_utf8(stdout).unwrap();
+        let doc: serde_json::Value =
+            serde_json::from_str(&text).expect("stdout should hold exactly one JSON document");
+        assert_eq!(doc["summary"], "Both agree");
+        assert_eq!(doc["responses"]["Claude"], "Second answer");
+    }
+
+    #[test]
+    fn highlight_agreement_marks_consensus_in_every_format() {
+        use clap::Parser;
+
+        let responses = vec![
+            (
+                "ChatGPT".to_string(),
+                "Paris is the capital of France. It is large.".to_string(),
+            ),
+            (
+                "Gemini".to_string(),
+                "The capital of France is Paris.".to_string(),
+            ),
+            (
+                "Claude".to_string(),
+                "Paris is the capital of France!".to_string(),
+            ),
+        ];
+        let text = text_output(
+            &["--highlight-agreement", "--color", "never"],
+            &responses,
+            None,
+        );
+        assert!(
+            text.ends_with(
+                "=== Consensus ===\n- Paris is the capital of France. (ChatGPT, Claude)\n"
+            ),
+            "{}",
+            text
+        );
+        let colored = text_output(
+            &["--highlight-agreement", "--color", "always"],
+            &responses,
+            None,
+        );
+        assert!(
+            colored.contains(&diff::render_highlight("Paris is the capital of France!")),
+            "{}",
+            colored
+        );
+
+        let markdown = text_output(
+            &["--highlight-agreement", "--format", "markdown"],
+            &responses,
+            None,
+        );
+        assert!(
+            markdown.contains("## ChatGPT\n\n**Paris is the capital of France.** It is large."),
+            "{}",
+            markdown
+        );
+        assert!(markdown
+            .contains("## Consensus\n\n- Paris is the capital of France. (ChatGPT, Claude)"));
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--highlight-agreement",
+            "--format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+        let doc = doc_value(&args, &responses, None, &HashMap::new(), &[]);
+        assert_eq!(
+            doc["consensus"][0]["models"],
+            serde_json::json!(["ChatGPT", "Claude"])
+        );
+        assert!(doc["consensus"][0].get("wordings").is_none());
+        assert!(text_output(&[], &responses, None)
+            .find("Consensus")
+            .is_none());
+    }
+
+    #[test]
+    fn annotate_tone_labels_verbose_text_and_json() {
+        use clap::Parser;
+
+        let responses = vec![
+            (
+                "ChatGPT".to_string(),
+                "This is definitely a great idea.".to_string(),
+            ),
+            ("Claude".to_string(), "It might be risky.".to_string()),
+        ];
+        let text = text_output(&["--annotate-tone", "--verbose"], &responses, Some("Mixed"));
+        assert!(
+            text.contains("=== ChatGPT (tone: confident, positive) ==="),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("=== Claude (tone: cautious, negative) ==="),
+            "{}",
+            text
+        );
+
+        let args = Args::try_parse_from(["chatdelta", "--annotate-tone", "--format", "json", "Hi"])
+            .unwrap();
+        let doc = doc_value(&args, &responses, None, &HashMap::new(), &[]);
+        assert_eq!(doc["tone"]["ChatGPT"]["tone"], "confident");
+        assert_eq!(doc["tone"]["Claude"]["sentiment"], "negative");
+        assert_eq!(doc["tone"]["ChatGPT"]["score"], 0.5);
+
+        let args = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        assert!(doc_value(&args, &responses, None, &HashMap::new(), &[])
+            .get("tone")
+            .is_none());
+    }
+
+    #[test]
+    fn throughput_from_tokens_and_latency() {
+        assert_eq!(throughput(Some(500), Some(2_000)), Some(250.0));
+        assert_eq!(throughput(Some(90), Some(600)), Some(150.0));
+        assert_eq!(throughput(None, Some(600)), None);
+        assert_eq!(throughput(Some(90), None), None);
+        assert_eq!(throughput(Some(90), Some(0)), None);
+    }
+
+    #[test]
+    fn show_throughput_adds_rates_to_json() {
+        use clap::Parser;
+
+        let mut meta = HashMap::new();
+        meta.insert(
+            "ChatGPT".to_string(),
+            ResponseMeta {
+                throughput: throughput(Some(1_000), Some(3_000)),
+                ..Default::default()
+            },
+        );
+        let args =
+            Args::try_parse_from(["chatdelta", "--show-throughput", "--format", "json", "Hi"])
+                .unwrap();
+
+        let doc = doc_value(&args, &sample_responses(), None, &meta, &[]);
+        assert_eq!(doc["tokens_per_second"]["ChatGPT"], 333.3);
+        assert!(doc["tokens_per_second"].get("Claude").is_none());
+    }
+
+    #[test]
+    fn summary_position_top_leads_markdown() {
+        use clap::Parser;
+
+        let render = |position: &str| {
+            let args = Args::try_parse_from([
+                "chatdelta",
+                "--format",
+                "markdown",
+                "--summary-position",
+                position,
+                "Hi",
+            ])
+            .unwrap();
+            let mut out = Vec::new();
+            output_results(
+                &args,
+                &sample_responses(),
+                Some("Both agree"),
+                &HashMap::new(),
+                &[],
+                &mut out,
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let top = render("top");
+        assert!(top.find("## Summary").unwrap() < top.find("## ChatGPT").unwrap());
+        assert_eq!(top.matches("## Summary").count(), 1);
+
+        let bottom = render("bottom");
+        assert!(bottom.find("## Summary").unwrap() > bottom.find("## Claude").unwrap());
+    }
+
+    #[test]
+    fn attach_metadata_enriches_json() {
+        use clap::Parser;
+
+        let mut meta = HashMap::new();
+        meta.insert(
+            "Claude".to_string(),
+            ResponseMeta {
+                latency_ms: Some(640),
+                total_tokens: Some(212),
+                ..Default::default()
+            },
+        );
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--attach-metadata",
+            "--temperature",
+            "0.5",
+            "--only",
+            "gpt,claude",
+            "--format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+
+        let doc = doc_value(&args, &sample_responses(), Some("Both agree"), &meta, &[]);
+        assert!(doc["timestamp"].is_string());
+        assert_eq!(doc["models"]["ChatGPT"], "gpt-4o");
+        assert_eq!(doc["models"]["Claude"], "claude-sonnet-4-6");
+        assert!(doc["models"].get("Gemini").is_none());
+        assert_eq!(doc["temperature"], 0.5);
+        assert_eq!(doc["retry_strategy"], "exponential");
+        assert_eq!(doc["usage"]["Claude"]["latency_ms"], 640);
+        assert_eq!(doc["usage"]["Claude"]["tokens"], 212);
+        assert_eq!(doc["summary"], "Both agree");
+
+        let lean = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        let doc = doc_value(&lean, &sample_responses(), Some("Both agree"), &meta, &[]);
+        for key in [
+            "timestamp",
+            "models",
+            "temperature",
+            "retry_strategy",
+            "usage",
+        ] {
+            assert!(doc.get(key).is_none(), "lean JSON should omit {}", key);
+        }
+    }
+
+    #[test]
+    fn json_surfaces_parsed_tool_calls() {
+        use clap::Parser;
+
+        let mut meta = HashMap::new();
+        meta.insert(
+            "ChatGPT".to_string(),
+            ResponseMeta {
+                tool_call: Some(ToolCall {
+                    name: "get_weather".to_string(),
+                    arguments: serde_json::json!({ "city": "Oslo" }),
+                }),
+                ..Default::default()
+            },
+        );
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--tool-schema",
+            "weather.json",
+            "--format",
+            "json",
+            "Hi",
+        ])
+        .unwrap();
+        let doc = doc_value(&args, &sample_responses(), None, &meta, &[]);
+        assert_eq!(doc["tool_call"]["ChatGPT"]["name"], "get_weather");
+        assert_eq!(doc["tool_call"]["ChatGPT"]["arguments"]["city"], "Oslo");
+        assert!(doc["tool_call"].get("Claude").is_none());
+    }
+
+    #[test]
+    fn json_includes_collected_warnings() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        let warnings = vec![
+            "GEMINI_API_KEY not set, skipping Gemini".to_string(),
+            "Summary generation failed: timeout".to_string(),
+        ];
+        let doc = doc_value(&args, &sample_responses(), None, &HashMap::new(), &warnings);
+        assert_eq!(doc["warnings"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            doc["warnings"][0],
+            "GEMINI_API_KEY not set, skipping Gemini"
+        );
+
+        let clean = doc_value(&args, &sample_responses(), None, &HashMap::new(), &[]);
+        assert!(clean.get("warnings").is_none());
+    }
+
+    #[test]
+    fn delimited_data_renders_as_tables() {
+        let csv =
+            "Here are the results:\n\nlanguage,year,typing\nRust,2015,static\nPython,1991,dynamic";
+        assert_eq!(
+            render_tables(csv, false),
+            "Here are the results:\n\nlanguage  year  typing\n\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}  \u{2500}\u{2500}\u{2500}\u{2500}  \u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\nRust      2015  static\nPython    1991  dynamic"
+        );
+        assert_eq!(
+            render_tables("name\tscore\nAda\t10", true),
+            "| name | score |\n| --- | --- |\n| Ada | 10 |"
+        );
+
+        let responses = vec![("ChatGPT".to_string(), "a,b\n1,2".to_string())];
+        let text = text_output(&["--render-tables"], &responses, None);
+        assert!(text.contains("a  b\n"));
+        assert_eq!(text_output(&[], &responses, None), "a,b\n1,2\n");
+    }
+
+    #[test]
+    fn prose_is_not_mistaken_for_a_table() {
+        let prose = "Rust, like C++, is fast.\nIt is also safe, and it is fun.";
+        assert_eq!(render_tables(prose, false), prose);
+        assert_eq!(
+            render_tables("One line, with commas, only", true),
+            "One line, with commas, only"
+        );
+        let ragged = "Pros: speed, safety\nCons: compile times";
+        assert_eq!(render_tables(ragged, false), ragged);
+    }
+
+    #[test]
+    fn wrap_text_at_fixed_width() {
+        let text = "The quick brown fox jumps over the lazy dog\n\nSecond paragraph";
+        let wrapped = wrap_text(text, wrap_width("20"));
+        assert_eq!(
+            wrapped,
+            "The quick brown fox\njumps over the lazy\ndog\n\nSecond paragraph"
+        );
+        assert!(wrapped.lines().all(|l| l.len() <= 20));
+    }
+
+    #[test]
+    fn wrap_none_leaves_text_unchanged() {
+        let text = "A very long line that would otherwise be wrapped somewhere sensible.\n\n";
+        assert_eq!(wrap_width("none"), None);
+        assert_eq!(wrap_text(text, None), text);
+    }
+
+    #[test]
+    fn ndjson_omits_summary_line_when_absent() {
+        let lines = ndjson_lines(&sample_responses(), None, &HashMap::new()).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| !l.contains("\"summary\"")));
+    }
+
+    #[test]
+    fn diff_output_plain_and_json_chunks() {
+        let responses = vec![
+            ("ChatGPT".to_string(), "the cat sat".to_string()),
+            ("Claude".to_string(), "the dog sat".to_string()),
+        ];
+        let text = text_output(&["--diff", "--color", "never"], &responses, None);
+        assert!(text.contains("=== Diff: ChatGPT \u{2192} Claude ===\nthe [-cat-] {+dog+} sat\n"));
+        assert!(!text.contains('\x1b'));
+        assert!(
+            text_output(&["--diff", "--color", "always"], &responses, None).contains("\x1b[32mdog")
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&text_output(
+            &["--diff", "--format", "json"],
+            &responses,
+            None,
+        ))
+        .unwrap();
+        let diff = &json["diff"][0];
+        assert_eq!(diff["from"], "ChatGPT");
+        assert_eq!(diff["to"], "Claude");
+        let types: Vec<&str> = diff["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["equal", "delete", "insert", "equal"]);
+    }
+
+    #[test]
+    fn summary_format_controls_summary_rendering() {
+        let summary = "```json\n{\"agreement\": [\"Rust is fast\"]}\n```";
+
+        // A json summary is pretty-printed in text output
+        let text = text_output(
+            &["--summary-format", "json"],
+            &sample_responses(),
+            Some(summary),
+        );
+        assert!(
+            text.ends_with("{\n  \"agreement\": [\n    \"Rust is fast\"\n  ]\n}\n"),
+            "{}",
+            text
+        );
+
+        // --format json asks for a json summary by default and embeds it as an object
+        let json: serde_json::Value = serde_json::from_str(&text_output(
+            &["--format", "json"],
+            &sample_responses(),
+            Some(summary),
+        ))
+        .unwrap();
+        assert_eq!(json["summary"]["agreement"][0], "Rust is fast");
+        let json: serde_json::Value = serde_json::from_str(&text_output(
+            &["--format", "json", "--summary-format", "markdown"],
+            &sample_responses(),
+            Some("### Agreement\n- fast"),
+        ))
+        .unwrap();
+        assert_eq!(json["summary"], "### Agreement\n- fast");
+
+        // Unparseable json summaries fall back to the raw text
+        let markdown = text_output(
+            &["--format", "markdown", "--summary-format", "json"],
+            &sample_responses(),
+            Some("not json"),
+        );
+        assert!(markdown.contains("## Summary\n\nnot json\n"));
+    }
+
+    /// Check `value` against the subset of JSON Schema the output schema uses
+    fn validate(
+        value: &serde_json::Value,
+        schema: &serde_json::Value,
+        root: &serde_json::Value,
+    ) -> Result<(), String> {
+        use serde_json::Value;
+
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/$defs/");
+            return validate(value, &root["$defs"][name], root);
+        }
+        if let Some(types) = schema.get("type") {
+            let allowed: Vec<&str> = match types {
+                Value::Array(list) => list.iter().filter_map(Value::as_str).collect(),
+                other => other.as_str().into_iter().collect(),
+            };
+            let matches = |t: &str| match t {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "number" => value.is_number(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "null" => value.is_null(),
+                _ => false,
+            };
+            if !allowed.iter().any(|t| matches(t)) {
+                return Err(format!("{} is not of type {:?}", value, allowed));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if value != expected {
+                return Err(format!("{} is not {}", value, expected));
+            }
+        }
+        if let Some(options) = schema["enum"].as_array() {
+            if !options.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, options));
+            }
+        }
+        if let (Some(pattern), Some(text)) = (schema["pattern"].as_str(), value.as_str()) {
+            if !regex::Regex::new(pattern).unwrap().is_match(text) {
+                return Err(format!("{} does not match {}", value, pattern));
+            }
+        }
+        if let Some(options) = schema["oneOf"].as_array() {
+            let passing = options
+                .iter()
+                .filter(|s| validate(value, s, root).is_ok())
+                .count();
+            if passing != 1 {
+                return Err(format!(
+                    "{} matches {} of the oneOf schemas",
+                    value, passing
+                ));
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for field in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(field.as_str().unwrap()) {
+                    return Err(format!("missing required field {}", field));
+                }
+            }
+            for (key, item) in object {
+                match (
+                    schema["properties"].get(key),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(property), _) => validate(item, property, root),
+                    (None, Some(Value::Bool(false))) => Err(format!("unexpected field {}", key)),
+                    (None, Some(extra)) if extra.is_object() => validate(item, extra, root),
+                    _ => Ok(()),
+                }
+                .map_err(|e| format!("{}: {}", key, e))?;
+            }
+        }
+        if let (Some(items), Some(schema)) = (value.as_array(), schema.get("items")) {
+            items
+                .iter()
+                .try_for_each(|item| validate(item, schema, root))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn json_output_is_versioned_and_matches_schema() {
+        use clap::Parser;
+
+        let schema: serde_json::Value =
+            serde_json::from_str(OUTPUT_SCHEMA).expect("schema is valid JSON");
+        let mut meta = HashMap::new();
+        meta.insert(
+            "ChatGPT".to_string(),
+            ResponseMeta {
+                latency_ms: Some(420),
+                total_tokens: Some(90),
+                response_hash: Some(response_hash("Line one")),
+                throughput: Some(214.28),
+                tool_call: Some(ToolCall {
+                    name: "lookup".to_string(),
+                    arguments: serde_json::json!({ "q": "x" }),
+                }),
+                ..Default::default()
+            },
+        );
+        let flags = [
+            "--with-hash",
+            "--show-throughput",
+            "--annotate-tone",
+            "--attach-metadata",
+            "--diff",
+            "--tool-schema",
+            "tool.json",
+            "--format",
+            "json",
+        ];
+        let warnings = ["GEMINI_API_KEY not set, skipping Gemini".to_string()];
+        for ordered in [false, true] {
+            let extra: &[&str] = if ordered {
+                &["--json-ordered-array"]
+            } else {
+                &[]
+            };
+            let args = Args::try_parse_from([&["chatdelta"][..], &flags, extra, &["Hi"]].concat())
+                .unwrap();
+            let mut out = Vec::new();
+            output_results(
+                &args,
+                &sample_responses(),
+                Some("Both agree"),
+                &meta,
+                &warnings,
+                &mut out,
+            )
+            .unwrap();
+            let text = String::from_utf8(out).unwrap();
+
+            // The version leads the document
+            assert!(
+                text.starts_with("{\n  \"output_version\": \"1.0\","),
+                "{}",
+                text
+            );
+            let doc: serde_json::Value = serde_json::from_str(&text).unwrap();
+            validate(&doc, &schema, &schema).unwrap();
+        }
+
+        let lean = Args::try_parse_from(["chatdelta", "--format", "json", "Hi"]).unwrap();
+        let doc = doc_value(&lean, &sample_responses(), None, &HashMap::new(), &[]);
+        validate(&doc, &schema, &schema).unwrap();
+
+        // The validator does reject documents that break the contract
+        let mut unversioned = doc.clone();
+        unversioned
+            .as_object_mut()
+            .unwrap()
+            .remove("output_version");
+        assert!(validate(&unversioned, &schema, &schema).is_err());
+        let mut extra = doc;
+        extra["surprise"] = serde_json::json!(true);
+        assert!(validate(&extra, &schema, &schema).is_err());
+    }
+
+    #[test]
+    fn every_json_output_field_matches_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(OUTPUT_SCHEMA).expect("schema is valid JSON");
+        let tool_call = ToolCall {
+            name: "lookup".to_string(),
+            arguments: serde_json::json!({ "q": "x" }),
+        };
+        let full = |responses: JsonResponses| JsonOutput {
+            output_version: OUTPUT_VERSION,
+            prompt: Some("Hi".to_string()),
+            responses,
+            response_hash: Some(BTreeMap::from([(
+                "ChatGPT".to_string(),
+                response_hash("Hello"),
+            )])),
+            tool_call: Some(BTreeMap::from([("ChatGPT".to_string(), tool_call.clone())])),
+            tokens_per_second: Some(BTreeMap::from([("ChatGPT".to_string(), 214.3)])),
+            tone: Some(BTreeMap::from([(
+                "ChatGPT".to_string(),
+                tone::classify("This is definitely great."),
+            )])),
+            consensus: Some(compare::consensus(&[
+                ("ChatGPT".to_string(), "Paris is the capital.".to_string()),
+                ("Claude".to_string(), "Paris is the capital!".to_string()),
+            ])),
+            summary: Some(serde_json::json!({ "verdict": "agree" })),
+            diff: Some(vec![ResponseDiff {
+                from: "ChatGPT".to_string(),
+                to: "Claude".to_string(),
+                chunks: diff::diff_words("the cat", "the dog"),
+            }]),
+            metadata: Some(RunMetadata {
+                timestamp: "2026-01-01T00:00:00Z".to_string(),
+                models: BTreeMap::from([("gpt".to_string(), "gpt-4o".to_string())]),
+                temperature: Some(0.5),
+                retry_strategy: "exponential".to_string(),
+                usage: BTreeMap::from([(
+                    "ChatGPT".to_string(),
+                    ModelUsage {
+                        latency_ms: Some(420),
+                        tokens: Some(90),
+                    },
+                )]),
+            }),
+            warnings: vec!["slow provider".to_string()],
+        };
+
+        let documents = [
+            full(JsonResponses::ByModel(BTreeMap::from([(
+                "ChatGPT".to_string(),
+                "Hello".to_string(),
+            )]))),
+            full(JsonResponses::Ordered(vec![OrderedResponse {
+                model: "ChatGPT".to_string(),
+                response: "Hello".to_string(),
+                response_hash: Some(response_hash("Hello")),
+                tool_call: Some(tool_call.clone()),
+            }])),
+        ];
+        for document in documents {
+            let doc = serde_json::to_value(&document).unwrap();
+            validate(&doc, &schema, &schema).unwrap();
+            // Every section the schema describes is exercised
+            for key in schema["properties"].as_object().unwrap().keys() {
+                assert!(doc.get(key).is_some(), "{} is not covered", key);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Pager output for ChatDelta CLI (`--paginate`)
+//!
+//! Long results on a terminal can be sent through `$PAGER` (default `less -FRX`, which
+//! exits at once when everything fits on one screen). If the pager can't be started the
+//! result goes straight to stdout instead.
+
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// Pager used when `PAGER` is unset or empty
+pub const DEFAULT_PAGER: &str = "less -FRX";
+
+/// The pager command line: `PAGER`, or the default
+pub fn pager_command(pager_var: Option<String>) -> String {
+    pager_var
+        .filter(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// A running pager; writes go to its stdin, and dropping it waits for the pager to exit
+pub struct Pager {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Pager {
+    /// Start `command` (a program and its arguments, split on whitespace)
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pager command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        Ok(Pager { child, stdin })
+    }
+}
+
+impl Pager {
+    /// A closed pipe means the user quit the pager before reading everything; the rest of
+    /// the output is discarded rather than reported as an error
+    fn pipe_result<T>(&mut self, result: io::Result<T>, discarded: T) -> io::Result<T> {
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.stdin = None;
+                Ok(discarded)
+            }
+            other => other,
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match &mut self.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => return Ok(buf.len()),
+        };
+        self.pipe_result(result, buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match &mut self.stdin {
+            Some(stdin) => stdin.flush(),
+            None => return Ok(()),
+        };
+        self.pipe_result(result, ())
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // Closing stdin lets the pager see the end of the output
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+/// Writer for the result: the pager when it starts, otherwise stdout. The flag tells
+/// whether the output is paged.
+pub fn writer_or_stdout(command: &str) -> (Box<dyn Write>, bool) {
+    match Pager::spawn(command) {
+        Ok(pager) => (Box::new(pager), true),
+        Err(_) => (Box::new(io::stdout()), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pager_command_prefers_the_environment() {
+        assert_eq!(pager_command(Some("more".to_string())), "more");
+        assert_eq!(pager_command(Some("  ".to_string())), DEFAULT_PAGER);
+        assert_eq!(pager_command(None), DEFAULT_PAGER);
+    }
+
+    #[test]
+    fn quitting_the_pager_early_is_not_an_error() {
+        // `true` exits without reading, like a pager the user quits at once
+        let mut pager = Pager::spawn("true").unwrap();
+        pager.child.wait().unwrap();
+        let page = "line of output\n".repeat(100_000);
+        assert!(pager.write_all(page.as_bytes()).is_ok());
+        assert!(pager.write_all(b"more").is_ok());
+        assert!(pager.flush().is_ok());
+    }
+
+    #[test]
+    fn missing_pager_falls_back_to_stdout() {
+        assert!(Pager::spawn("chatdelta-no-such-pager --flag").is_err());
+        assert!(Pager::spawn("").is_err());
+        let (mut out, paged) = writer_or_stdout("chatdelta-no-such-pager");
+        assert!(!paged);
+        assert!(out.flush().is_ok());
+    }
+}
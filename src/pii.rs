@@ -0,0 +1,199 @@
+//! Sensitive-data scan for ChatDelta CLI prompts (`--pii-scan`, `--pii-block`)
+//!
+//! Before a prompt is sent to external APIs it can be checked for likely personal data:
+//! email addresses, phone numbers, credit-card numbers and US Social Security numbers.
+//! Detection is pattern based and errs on the side of reporting; card numbers must also
+//! pass the Luhn checksum. A match is reported once, under the first category that claims it.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Kind of personal data a match looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PiiCategory {
+    Email,
+    Ssn,
+    CreditCard,
+    Phone,
+}
+
+impl PiiCategory {
+    pub fn label(self) -> &'static str {
+        match self {
+            PiiCategory::Email => "email address",
+            PiiCategory::Ssn => "SSN",
+            PiiCategory::CreditCard => "credit card number",
+            PiiCategory::Phone => "phone number",
+        }
+    }
+}
+
+/// One match: its category and byte range in the scanned text
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiiMatch {
+    pub category: PiiCategory,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Detectors in priority order; a later one never reports text an earlier one matched
+fn detectors() -> &'static [(PiiCategory, Regex)] {
+    static DETECTORS: OnceLock<Vec<(PiiCategory, Regex)>> = OnceLock::new();
+    DETECTORS.get_or_init(|| {
+        [
+            (
+                PiiCategory::Email,
+                r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            ),
+            (PiiCategory::Ssn, r"\b\d{3}-\d{2}-\d{4}\b"),
+            (PiiCategory::CreditCard, r"\b(?:\d[ -]?){12,18}\d\b"),
+            (
+                PiiCategory::Phone,
+                r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\)\s?|\b\d{3}[ .-]?)\d{3}[ .-]?\d{4}\b",
+            ),
+        ]
+        .into_iter()
+        .map(|(category, pattern)| (category, Regex::new(pattern).expect("valid PII pattern")))
+        .collect()
+    })
+}
+
+/// Whether a digit string passes the Luhn checksum used by card numbers
+fn luhn_valid(digits: &str) -> bool {
+    let digits: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    digits.len() >= 13 && sum.is_multiple_of(10)
+}
+
+/// Every likely PII match in `text`, ordered by position
+pub fn scan(text: &str) -> Vec<PiiMatch> {
+    let mut found: Vec<PiiMatch> = Vec::new();
+    for (category, pattern) in detectors() {
+        for m in pattern.find_iter(text) {
+            if *category == PiiCategory::CreditCard && !luhn_valid(m.as_str()) {
+                continue;
+            }
+            if found.iter().any(|f| m.start() < f.end && f.start < m.end()) {
+                continue;
+            }
+            found.push(PiiMatch {
+                category: *category,
+                start: m.start(),
+                end: m.end(),
+            });
+        }
+    }
+    found.sort_by_key(|m| m.start);
+    found
+}
+
+/// Categories found with their counts, e.g. "email address (2), phone number (1)"
+pub fn describe(matches: &[PiiMatch]) -> String {
+    let mut categories: Vec<PiiCategory> = matches.iter().map(|m| m.category).collect();
+    categories.sort();
+    categories.dedup();
+    categories
+        .iter()
+        .map(|category| {
+            let count = matches.iter().filter(|m| m.category == *category).count();
+            format!("{} ({})", category.label(), count)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Outcome of the scan for one prompt: None when it may be sent, or the error that blocks it.
+/// A warning listing the categories is printed unless `quiet`.
+pub fn check(prompt: &str, block: bool, allow: bool, quiet: bool) -> Option<String> {
+    let matches = scan(prompt);
+    if matches.is_empty() {
+        return None;
+    }
+    let found = describe(&matches);
+    if block && !allow {
+        return Some(format!(
+            "Prompt appears to contain PII: {}. Not sent because of --pii-block; pass --allow-pii to send it anyway",
+            found
+        ));
+    }
+    if !quiet {
+        eprintln!("Warning: prompt appears to contain PII: {}", found);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories(text: &str) -> Vec<PiiCategory> {
+        scan(text).into_iter().map(|m| m.category).collect()
+    }
+
+    #[test]
+    fn detects_each_category_with_offsets() {
+        let text = "Mail jane.doe@example.com";
+        assert_eq!(
+            scan(text),
+            vec![PiiMatch {
+                category: PiiCategory::Email,
+                start: 5,
+                end: text.len()
+            }]
+        );
+        assert_eq!(
+            categories("SSN 123-45-6789 on file"),
+            vec![PiiCategory::Ssn]
+        );
+        assert_eq!(
+            categories("Card 4111 1111 1111 1111 exp 12/29"),
+            vec![PiiCategory::CreditCard]
+        );
+        assert_eq!(
+            categories("Card 4111-1111-1111-1111"),
+            vec![PiiCategory::CreditCard]
+        );
+        assert_eq!(categories("Call (555) 123-4567"), vec![PiiCategory::Phone]);
+        assert_eq!(
+            categories("Call +1 555.123.4567 today"),
+            vec![PiiCategory::Phone]
+        );
+
+        let mixed = "Reach bob@corp.io or 555-123-4567; SSN 987-65-4321";
+        assert_eq!(
+            categories(mixed),
+            vec![PiiCategory::Email, PiiCategory::Phone, PiiCategory::Ssn]
+        );
+        assert_eq!(
+            describe(&scan(&format!("{} and ann@corp.io", mixed))),
+            "email address (2), SSN (1), phone number (1)"
+        );
+    }
+
+    #[test]
+    fn ordinary_text_and_numbers_are_not_flagged() {
+        assert!(scan("Explain the borrow checker in Rust 1.75").is_empty());
+        // 16 digits that fail the Luhn check are not a card number
+        assert!(categories("Order 1234 5678 9012 3456").is_empty());
+        assert!(categories("version 2024-01-15").is_empty());
+    }
+
+    #[test]
+    fn block_stops_the_prompt_unless_allowed() {
+        let prompt = "My SSN is 123-45-6789";
+        let error = check(prompt, true, false, true).unwrap();
+        assert!(error.contains("SSN (1)") && error.contains("--allow-pii"));
+        assert_eq!(check(prompt, true, true, true), None);
+        assert_eq!(check(prompt, false, false, true), None);
+        assert_eq!(check("Nothing sensitive here", true, false, true), None);
+    }
+}
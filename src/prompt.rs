@@ -0,0 +1,581 @@
+//! Prompt resolution helpers for ChatDelta CLI
+//!
+//! Handles structured prompt files, `{{name}}` template substitution, comment stripping
+//! and `git diff` review prompts.
+
+use base64::Engine;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A structured prompt file loaded with `--input-format yaml|json`
+#[derive(Debug, Default, Deserialize)]
+pub struct PromptFile {
+    /// Optional system prompt (ignored when --system-prompt is given)
+    pub system: Option<String>,
+    /// The user prompt, which may contain `{{name}}` placeholders
+    pub user: String,
+    /// Template variables applied to the prompt
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+}
+
+/// Parse prompt file content according to `--input-format`
+pub fn parse_prompt_file(content: &str, format: &str) -> Result<PromptFile, String> {
+    match format {
+        "yaml" => serde_yaml::from_str(content)
+            .map_err(|e| format!("Failed to parse YAML prompt file: {}", e)),
+        "json" => serde_json::from_str(content)
+            .map_err(|e| format!("Failed to parse JSON prompt file: {}", e)),
+        _ => Ok(PromptFile {
+            user: content.to_string(),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Parse `--var name=value` arguments into a variable map
+pub fn parse_vars(raw: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut vars = HashMap::new();
+    for entry in raw {
+        match entry.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                vars.insert(name.trim().to_string(), value.to_string());
+            }
+            _ => return Err(format!("Invalid --var '{}'. Expected NAME=VALUE", entry)),
+        }
+    }
+    Ok(vars)
+}
+
+/// Replace every `{{name}}` placeholder with its value; unknown placeholders are left as-is
+pub fn render_template(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut rendered = template.to_string();
+    for (name, value) in vars {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    rendered
+}
+
+/// Read a piped prompt from `input`. When stdin is a terminal nothing was piped, so this
+/// errors rather than blocking on input that isn't coming.
+pub fn read_piped(mut input: impl Read, is_terminal: bool) -> Result<String, String> {
+    if is_terminal {
+        return Err("No piped input detected; stdin is a terminal. Pipe a prompt in or pass it as an argument".to_string());
+    }
+    let mut buffer = String::new();
+    input
+        .read_to_string(&mut buffer)
+        .map_err(|e| format!("Failed to read from stdin: {}", e))?;
+    Ok(buffer)
+}
+
+/// File extensions a named template may have, in lookup order
+const TEMPLATE_EXTENSIONS: [&str; 2] = ["txt", "md"];
+
+/// Names of the templates in `dir` (`.txt` and `.md` files, without the extension), sorted
+pub fn list_templates(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read template directory {}: {}", dir.display(), e))?;
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| TEMPLATE_EXTENSIONS.iter().any(|known| ext == *known))
+        })
+        .filter_map(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Path of the template `name` in `dir`: `<name>.txt`, else `<name>.md`. Names are plain
+/// file stems, so a name can't reach outside `dir`.
+pub fn find_template(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!(
+            "Invalid template name '{}': use a file name from {} without a path or extension",
+            name,
+            dir.display()
+        ));
+    }
+    TEMPLATE_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            let available = list_templates(dir).unwrap_or_default();
+            format!(
+                "Template '{}' not found in {} (looked for {0}.txt and {0}.md); available: {}",
+                name,
+                dir.display(),
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )
+        })
+}
+
+/// Template variable piped stdin fills when the prompt has a `{{stdin}}` placeholder
+pub const STDIN_VAR: &str = "stdin";
+
+/// The template variable piped stdin should fill: `--stdin-into`, or `stdin` when the
+/// prompt contains `{{stdin}}`. None means stdin isn't a template value.
+pub fn stdin_target(prompt: &str, stdin_into: Option<&str>) -> Option<String> {
+    match stdin_into {
+        Some(name) => Some(name.to_string()),
+        None => prompt
+            .contains(&format!("{{{{{}}}}}", STDIN_VAR))
+            .then(|| STDIN_VAR.to_string()),
+    }
+}
+
+/// Piped input as a template value; None when stdin is a terminal and nothing was piped
+pub fn read_piped_value(
+    input: impl Read,
+    is_terminal: bool,
+    no_trim: bool,
+) -> Result<Option<String>, String> {
+    if is_terminal {
+        return Ok(None);
+    }
+    read_piped(input, false).map(|buffer| Some(finish(&buffer, no_trim)))
+}
+
+/// Combine a prompt file with piped input (--merge-stdin-with-file). Piped text goes after
+/// the file content, or before it when `position` is `prepend`, joined by `separator`
+/// (`\n` and `\t` escapes are expanded). Nothing piped leaves the file content as is.
+pub fn merge_piped(
+    file_prompt: &str,
+    input: impl Read,
+    is_terminal: bool,
+    position: &str,
+    separator: &str,
+    no_trim: bool,
+) -> Result<String, String> {
+    let Some(piped) = read_piped_value(input, is_terminal, no_trim)? else {
+        return Ok(file_prompt.to_string());
+    };
+    let separator = separator.replace("\\n", "\n").replace("\\t", "\t");
+    Ok(match position {
+        "prepend" => format!("{}{}{}", piped, separator, file_prompt),
+        _ => format!("{}{}{}", file_prompt, separator, piped),
+    })
+}
+
+/// Final form of a loaded prompt: trimmed, unless --no-trim asks for the raw text
+pub fn finish(text: &str, no_trim: bool) -> String {
+    if no_trim {
+        text.to_string()
+    } else {
+        text.trim().to_string()
+    }
+}
+
+/// Decode a --prompt-base64 prompt. Whitespace in the encoded text (line wrapping, the
+/// trailing newline of `base64`) is ignored; the decoded text is then trimmed unless
+/// --no-trim is set.
+pub fn decode_base64(encoded: &str, no_trim: bool) -> Result<String, String> {
+    let compact: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|e| format!("Invalid --prompt-base64 prompt: {}", e))?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| "Invalid --prompt-base64 prompt: not UTF-8 text".to_string())?;
+    if text.contains('\0') {
+        return Err("Prompt contains invalid null characters".to_string());
+    }
+    Ok(finish(&text, no_trim))
+}
+
+/// Drop lines whose first non-blank text is `prefix`. A line starting with `\` followed by
+/// the prefix is kept, minus the backslash, so literal lines like `#include` survive.
+pub fn strip_comments(text: &str, prefix: &str) -> String {
+    let escaped = format!("\\{}", prefix);
+    text.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with(&escaped) {
+                let indent = &line[..line.len() - trimmed.len()];
+                Some(format!("{}{}", indent, &trimmed[1..]))
+            } else if trimmed.starts_with(prefix) {
+                None
+            } else {
+                Some(line.to_string())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + if text.ends_with('\n') { "\n" } else { "" }
+}
+
+/// Instruction placed before a `--prompt-from-git-diff` diff unless --prompt-prefix is given
+pub const GIT_DIFF_INSTRUCTION: &str =
+    "Review the following code changes. Point out bugs, risky changes, and anything that is unclear.";
+
+/// Captured result of an external command
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// `git diff [reference]` with pager, color and external diff tools turned off
+pub fn git_diff_command(reference: Option<&str>) -> Command {
+    let mut command = Command::new("git");
+    command.args(["--no-pager", "diff", "--no-color", "--no-ext-diff"]);
+    if let Some(reference) = reference.filter(|r| !r.is_empty()) {
+        command.arg(reference);
+    }
+    command
+}
+
+/// Run a command to completion, capturing its output
+pub fn run_command(command: &mut Command) -> io::Result<CommandOutput> {
+    let output = command.output()?;
+    Ok(CommandOutput {
+        success: output.status.success(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+/// Build a review prompt from `git diff [reference]`: the instruction (or `prefix`), then
+/// the diff in a fenced code block. `run` executes the command, so tests can stub git.
+pub fn git_diff_prompt(
+    reference: Option<&str>,
+    prefix: Option<&str>,
+    run: impl FnOnce(&mut Command) -> io::Result<CommandOutput>,
+) -> Result<String, String> {
+    let output = run(&mut git_diff_command(reference)).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => {
+            "--prompt-from-git-diff needs git, which isn't installed or on PATH".to_string()
+        }
+        _ => format!("Failed to run git diff: {}", e),
+    })?;
+    if !output.success {
+        let stderr = output.stderr.trim();
+        if stderr.contains("not a git repository") {
+            return Err("--prompt-from-git-diff must be run inside a git repository".to_string());
+        }
+        return Err(format!("git diff failed: {}", stderr));
+    }
+    if output.stdout.trim().is_empty() {
+        return Err(match reference.filter(|r| !r.is_empty()) {
+            Some(reference) => format!("git diff {} shows no changes", reference),
+            None => "git diff shows no changes".to_string(),
+        });
+    }
+    let diff = output.stdout.trim_end();
+    let fence = code_fence(diff);
+    Ok(format!(
+        "{}\n\n{}diff\n{}\n{}",
+        prefix.unwrap_or(GIT_DIFF_INSTRUCTION),
+        fence,
+        diff,
+        fence
+    ))
+}
+
+/// A backtick fence longer than any backtick run in `text` (at least three), so the
+/// text can't close the code block early
+fn code_fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_prompts_decode_to_utf8_text() {
+        // "  héllo \"world\" $HOME\n" encoded, wrapped across two lines
+        let encoded = "ICBow6lsbG8gIndv\ncmxkIiAkSE9NRQo=\n";
+        assert_eq!(
+            decode_base64(encoded, false).unwrap(),
+            "h\u{e9}llo \"world\" $HOME"
+        );
+        assert_eq!(
+            decode_base64(encoded, true).unwrap(),
+            "  h\u{e9}llo \"world\" $HOME\n"
+        );
+
+        assert!(decode_base64("not base64!", false)
+            .unwrap_err()
+            .contains("Invalid --prompt-base64"));
+        assert!(decode_base64("/w==", false)
+            .unwrap_err()
+            .contains("not UTF-8"));
+    }
+
+    #[test]
+    fn parse_yaml_prompt_file() {
+        let yaml = "system: You are a translator.\nuser: Translate '{{text}}' into {{lang}}.\nvars:\n  text: hello\n  lang: French\n";
+        let file = parse_prompt_file(yaml, "yaml").unwrap();
+        assert_eq!(file.system.as_deref(), Some("You are a translator."));
+        assert_eq!(file.vars.len(), 2);
+
+        let rendered = render_template(&file.user, &file.vars);
+        assert_eq!(rendered, "Translate 'hello' into French.");
+    }
+
+    #[test]
+    fn parse_json_prompt_file_without_vars() {
+        let json = r#"{"user": "What is Rust?"}"#;
+        let file = parse_prompt_file(json, "json").unwrap();
+        assert!(file.system.is_none());
+        assert!(file.vars.is_empty());
+        assert_eq!(file.user, "What is Rust?");
+    }
+
+    #[test]
+    fn text_format_is_raw_content() {
+        let file = parse_prompt_file("user: not parsed", "text").unwrap();
+        assert_eq!(file.user, "user: not parsed");
+    }
+
+    #[test]
+    fn cli_vars_parse_and_reject_malformed() {
+        let vars = parse_vars(&["lang=German".to_string(), "eq=a=b".to_string()]).unwrap();
+        assert_eq!(vars["lang"], "German");
+        assert_eq!(vars["eq"], "a=b");
+        assert!(parse_vars(&["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let rendered = render_template(
+            "Hi {{name}}, {{other}}",
+            &parse_vars(&["name=Ann".to_string()]).unwrap(),
+        );
+        assert_eq!(rendered, "Hi Ann, {{other}}");
+    }
+
+    #[test]
+    fn piped_data_fills_the_stdin_placeholder() {
+        let template = "Summarize this log:\n{{stdin}}";
+        let name = stdin_target(template, None).expect("{{stdin}} marks a stdin template");
+        let value = read_piped_value("error: disk full\n".as_bytes(), false, false)
+            .unwrap()
+            .unwrap();
+        let vars = HashMap::from([(name, value)]);
+        assert_eq!(
+            render_template(template, &vars),
+            "Summarize this log:\nerror: disk full"
+        );
+
+        // --stdin-into picks another placeholder; without either, stdin isn't a template value
+        assert_eq!(
+            stdin_target("Review {{code}}", Some("code")).as_deref(),
+            Some("code")
+        );
+        assert_eq!(stdin_target("Plain prompt", None), None);
+        // Nothing piped is not an error
+        assert_eq!(read_piped_value(io::empty(), true, false).unwrap(), None);
+    }
+
+    #[test]
+    fn named_templates_resolve_and_take_vars() {
+        let dir =
+            std::env::temp_dir().join(format!("chatdelta-templates-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("review.md"),
+            "Review this {{lang}} code for {{focus}}.",
+        )
+        .unwrap();
+        fs::write(dir.join("explain.txt"), "Explain {{topic}}.").unwrap();
+        fs::write(dir.join("notes.json"), "{}").unwrap();
+
+        assert_eq!(list_templates(&dir).unwrap(), vec!["explain", "review"]);
+        let path = find_template(&dir, "review").unwrap();
+        let vars = parse_vars(&["lang=Rust".to_string(), "focus=safety".to_string()]).unwrap();
+        let prompt = render_template(&fs::read_to_string(path).unwrap(), &vars);
+        assert_eq!(prompt, "Review this Rust code for safety.");
+
+        let missing = find_template(&dir, "summarize").unwrap_err();
+        assert!(missing.contains("Template 'summarize' not found"));
+        assert!(missing.contains("available: explain, review"));
+        for name in ["../review", "sub/review", "..\\review", ".."] {
+            assert!(
+                find_template(&dir, name)
+                    .unwrap_err()
+                    .starts_with("Invalid template name"),
+                "{}",
+                name
+            );
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn merges_piped_input_with_the_prompt_file() {
+        let file = "Review this log:";
+        let piped = "  error: disk full\n".as_bytes();
+        assert_eq!(
+            merge_piped(file, piped, false, "append", "\\n\\n", false).unwrap(),
+            "Review this log:\n\nerror: disk full"
+        );
+        assert_eq!(
+            merge_piped(file, piped, false, "prepend", "\n---\n", false).unwrap(),
+            "error: disk full\n---\nReview this log:"
+        );
+        // --no-trim keeps the piped text verbatim
+        assert_eq!(
+            merge_piped(file, piped, false, "append", " ", true).unwrap(),
+            "Review this log:   error: disk full\n"
+        );
+        // Nothing piped: the file alone is the prompt
+        assert_eq!(
+            merge_piped(file, io::empty(), true, "append", "\n\n", false).unwrap(),
+            file
+        );
+    }
+
+    #[test]
+    fn strip_comments_removes_comment_lines_only() {
+        let text = "# notes for myself\nExplain closures.\n  # indented note\nUse C# examples, not #hashtags.";
+        assert_eq!(
+            strip_comments(text, "#"),
+            "Explain closures.\nUse C# examples, not #hashtags."
+        );
+    }
+
+    #[test]
+    fn strip_comments_keeps_escaped_lines() {
+        let text =
+            "// reviewer: tighten wording\nFix this C code:\n```c\n\\#include <stdio.h>\n```";
+        assert_eq!(
+            strip_comments(text, "//"),
+            "Fix this C code:\n```c\n\\#include <stdio.h>\n```"
+        );
+        assert_eq!(
+            strip_comments("\\#include <stdio.h>\n# drop me", "#"),
+            "#include <stdio.h>"
+        );
+    }
+
+    #[test]
+    fn terminal_stdin_errors_instead_of_blocking() {
+        /// Input that fails the test if it is ever read
+        struct Unreadable;
+        impl Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                panic!("a terminal stdin must not be read");
+            }
+        }
+
+        let err = read_piped(Unreadable, true).unwrap_err();
+        assert!(err.starts_with("No piped input detected; stdin is a terminal."));
+        assert_eq!(
+            read_piped("piped prompt\n".as_bytes(), false).unwrap(),
+            "piped prompt\n"
+        );
+    }
+
+    /// Runner that records the command and answers with canned output
+    fn stub_git(
+        seen: &mut Vec<String>,
+        output: CommandOutput,
+    ) -> impl FnOnce(&mut Command) -> io::Result<CommandOutput> + '_ {
+        move |command| {
+            seen.push(command.get_program().to_string_lossy().into_owned());
+            seen.extend(command.get_args().map(|a| a.to_string_lossy().into_owned()));
+            Ok(output)
+        }
+    }
+
+    #[test]
+    fn git_diff_prompt_wraps_the_diff() {
+        let mut seen = Vec::new();
+        let diff = "diff --git a/x.rs b/x.rs\n+fn added() {}\n";
+        let output = CommandOutput {
+            success: true,
+            stdout: diff.to_string(),
+            ..Default::default()
+        };
+        let prompt = git_diff_prompt(Some("main"), None, stub_git(&mut seen, output)).unwrap();
+
+        assert_eq!(
+            seen,
+            [
+                "git",
+                "--no-pager",
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                "main"
+            ]
+        );
+        assert_eq!(
+            prompt,
+            format!(
+                "{}\n\n```diff\ndiff --git a/x.rs b/x.rs\n+fn added() {{}}\n```",
+                GIT_DIFF_INSTRUCTION
+            )
+        );
+        assert_eq!(git_diff_command(Some("")).get_args().count(), 4);
+
+        let output = CommandOutput {
+            success: true,
+            stdout: diff.to_string(),
+            ..Default::default()
+        };
+        let custom = git_diff_prompt(
+            None,
+            Some("Check for SQL injection."),
+            stub_git(&mut Vec::new(), output),
+        );
+        assert!(custom
+            .unwrap()
+            .starts_with("Check for SQL injection.\n\n```diff\n"));
+
+        // A diff of Markdown with its own fences gets a longer one
+        let output = CommandOutput {
+            success: true,
+            stdout: "+```rust\n+let x = 1;\n+```\n+Inline ````code````\n".to_string(),
+            ..Default::default()
+        };
+        let prompt = git_diff_prompt(None, None, stub_git(&mut Vec::new(), output)).unwrap();
+        assert!(prompt.contains("\n\n`````diff\n+```rust"), "{}", prompt);
+        assert!(prompt.ends_with("````\n`````"), "{}", prompt);
+    }
+
+    #[test]
+    fn git_diff_prompt_errors() {
+        let not_a_repo = CommandOutput {
+            success: false,
+            stderr: "fatal: not a git repository (or any of the parent directories): .git\n"
+                .to_string(),
+            ..Default::default()
+        };
+        let err = git_diff_prompt(None, None, stub_git(&mut Vec::new(), not_a_repo)).unwrap_err();
+        assert!(err.contains("inside a git repository"), "{}", err);
+
+        let missing = |_: &mut Command| Err(io::Error::from(io::ErrorKind::NotFound));
+        assert!(git_diff_prompt(None, None, missing)
+            .unwrap_err()
+            .contains("isn't installed"));
+
+        let clean = CommandOutput {
+            success: true,
+            ..Default::default()
+        };
+        let err =
+            git_diff_prompt(Some("HEAD~1"), None, stub_git(&mut Vec::new(), clean)).unwrap_err();
+        assert_eq!(err, "git diff HEAD~1 shows no changes");
+    }
+}
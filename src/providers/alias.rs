@@ -0,0 +1,86 @@
+//! Clients for `--model-map-file` aliases
+//!
+//! A library client reports its provider's display name; the wrapper reports the alias
+//! instead, so two aliases for one provider stay apart in results, logs and output.
+
+use async_trait::async_trait;
+use chatdelta::{AiClient, AiResponse, ClientError, Conversation, StreamChunk};
+use futures::stream::BoxStream;
+use tokio::sync::mpsc;
+
+/// A client that answers to an alias name and otherwise defers to `inner`
+pub struct AliasedClient {
+    name: String,
+    inner: Box<dyn AiClient>,
+}
+
+impl AliasedClient {
+    pub fn new(name: &str, inner: Box<dyn AiClient>) -> Self {
+        AliasedClient {
+            name: name.to_string(),
+            inner,
+        }
+    }
+}
+
+#[async_trait]
+impl AiClient for AliasedClient {
+    async fn send_prompt(&self, prompt: &str) -> Result<String, ClientError> {
+        self.inner.send_prompt(prompt).await
+    }
+
+    async fn send_prompt_with_metadata(&self, prompt: &str) -> Result<AiResponse, ClientError> {
+        self.inner.send_prompt_with_metadata(prompt).await
+    }
+
+    async fn send_conversation(&self, conversation: &Conversation) -> Result<String, ClientError> {
+        self.inner.send_conversation(conversation).await
+    }
+
+    async fn send_conversation_with_metadata(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<AiResponse, ClientError> {
+        self.inner
+            .send_conversation_with_metadata(conversation)
+            .await
+    }
+
+    async fn stream_prompt(
+        &self,
+        prompt: &str,
+    ) -> Result<BoxStream<'_, Result<StreamChunk, ClientError>>, ClientError> {
+        self.inner.stream_prompt(prompt).await
+    }
+
+    async fn stream_conversation(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<BoxStream<'_, Result<StreamChunk, ClientError>>, ClientError> {
+        self.inner.stream_conversation(conversation).await
+    }
+
+    async fn send_prompt_streaming(
+        &self,
+        prompt: &str,
+        tx: mpsc::UnboundedSender<StreamChunk>,
+    ) -> Result<(), ClientError> {
+        self.inner.send_prompt_streaming(prompt, tx).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_conversations(&self) -> bool {
+        self.inner.supports_conversations()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
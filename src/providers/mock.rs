@@ -0,0 +1,110 @@
+//! Mock `AiClient` for exercising the full pipeline without network access
+//!
+//! Enabled with `--mock` (requires the `mock` feature). Each selected AI answers with a
+//! templated response after `--mock-delay` milliseconds; AIs listed in `--mock-fail`
+//! return an error instead.
+
+use crate::cli::Args;
+use async_trait::async_trait;
+use chatdelta::{AiClient, ClientError, NetworkError};
+use std::time::Duration;
+
+/// Longest slice of the prompt echoed back in a mock response
+const ECHO_CHARS: usize = 80;
+
+/// Offline stand-in for a real provider client
+pub struct MockClient {
+    name: String,
+    model: String,
+    delay: Duration,
+    fail: bool,
+}
+
+impl MockClient {
+    pub fn new(name: &str, model: &str, delay: Duration, fail: bool) -> Self {
+        MockClient {
+            name: name.to_string(),
+            model: model.to_string(),
+            delay,
+            fail,
+        }
+    }
+}
+
+#[async_trait]
+impl AiClient for MockClient {
+    async fn send_prompt(&self, prompt: &str) -> Result<String, ClientError> {
+        tokio::time::sleep(self.delay).await;
+
+        if self.fail {
+            return Err(ClientError::Network(NetworkError::ConnectionFailed(
+                format!("mock failure for {}", self.name),
+            )));
+        }
+
+        let first_line = prompt.lines().next().unwrap_or_default();
+        let echo: String = first_line.chars().take(ECHO_CHARS).collect();
+        Ok(format!("[mock {}] Response to: {}", self.model, echo))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// One mock client per AI selected by --only/--exclude, then one per selected model alias
+pub fn clients(args: &Args) -> Vec<Box<dyn AiClient>> {
+    let delay = Duration::from_millis(args.mock_delay);
+    [
+        ("gpt", "ChatGPT", &args.gpt_model),
+        ("gemini", "Gemini", &args.gemini_model),
+        ("claude", "Claude", &args.claude_model),
+    ]
+    .into_iter()
+    .filter(|(ai, _, _)| args.should_use_ai(ai))
+    .chain(
+        args.selected_aliases()
+            .into_iter()
+            .map(|(name, alias)| (name, name, &alias.model)),
+    )
+    .map(|(ai, name, model)| {
+        let fail = args.mock_fail.iter().any(|f| f == ai);
+        Box::new(MockClient::new(name, model, delay, fail)) as Box<dyn AiClient>
+    })
+    .collect()
+}
+
+/// Mock summarizers for --summary-models; like the default summarizer they never fail
+pub fn summary_clients_for(args: &Args, ais: &[String]) -> Vec<Box<dyn AiClient>> {
+    let delay = Duration::from_millis(args.mock_delay);
+    ais.iter()
+        .map(|ai| {
+            let (name, model) = match ai.as_str() {
+                "gpt" => ("ChatGPT", &args.gpt_model),
+                "gemini" => ("Gemini", &args.gemini_model),
+                _ => ("Claude", &args.claude_model),
+            };
+            Box::new(MockClient::new(name, model, delay, false)) as Box<dyn AiClient>
+        })
+        .collect()
+}
+
+/// Mock summarizer (--summary-model's AI, or Gemini); it never fails so summary output can
+/// always be exercised
+pub fn summary_client(args: &Args) -> Box<dyn AiClient> {
+    if let Some(ai) = &args.summary_model {
+        if let Some(client) = summary_clients_for(args, std::slice::from_ref(ai)).pop() {
+            return client;
+        }
+    }
+    Box::new(MockClient::new(
+        "Gemini",
+        &args.gemini_model,
+        Duration::from_millis(args.mock_delay),
+        false,
+    ))
+}
@@ -0,0 +1,160 @@
+//! Provider helpers for ChatDelta CLI
+//!
+//! The `mock` provider stands in for the real APIs in tests and demos. It is compiled only
+//! for tests or with the `mock` feature, so production builds never contain it.
+
+use crate::cli::Args;
+use chatdelta::{AiClient, ClientConfig, ClientError};
+
+pub mod alias;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+/// chatdelta's `create_client`, traced under a `create_client` span (--log-level debug)
+pub fn create_client(
+    provider: &str,
+    key: &str,
+    model: &str,
+    config: ClientConfig,
+) -> Result<Box<dyn AiClient>, ClientError> {
+    let _span = tracing::debug_span!("create_client", provider, model).entered();
+    let result = chatdelta::create_client(provider, key, model, config);
+    if let Err(e) = &result {
+        tracing::warn!(error = %e, "client creation failed");
+    }
+    result
+}
+
+/// The AI providers the CLI can query, and what each needs to create a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Gpt,
+    Gemini,
+    Claude,
+}
+
+impl Provider {
+    /// Provider for an AI name (gpt, gemini, claude)
+    pub fn for_ai(ai_name: &str) -> Self {
+        match ai_name {
+            "gpt" => Provider::Gpt,
+            "gemini" => Provider::Gemini,
+            _ => Provider::Claude,
+        }
+    }
+
+    /// Provider id chatdelta's `create_client` expects
+    pub fn client_id(self) -> &'static str {
+        match self {
+            Provider::Gpt => "openai",
+            Provider::Gemini => "gemini",
+            Provider::Claude => "claude",
+        }
+    }
+
+    /// Display name the clients report
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Provider::Gpt => "ChatGPT",
+            Provider::Gemini => "Gemini",
+            Provider::Claude => "Claude",
+        }
+    }
+
+    /// Environment variables holding the API key, in lookup order
+    pub fn env_vars(self) -> &'static [&'static str] {
+        match self {
+            Provider::Gpt => &["OPENAI_API_KEY", "CHATGPT_API_KEY"],
+            Provider::Gemini => &["GEMINI_API_KEY"],
+            Provider::Claude => &["ANTHROPIC_API_KEY", "CLAUDE_API_KEY"],
+        }
+    }
+
+    /// The primary API key environment variable
+    pub fn env_var(self) -> &'static str {
+        self.env_vars()[0]
+    }
+}
+
+/// API endpoint an AI (gpt, gemini, claude) uses when no base URL override is set
+pub fn default_base_url(ai_name: &str) -> &'static str {
+    match ai_name {
+        "gpt" => "https://api.openai.com/v1",
+        "gemini" => "https://generativelanguage.googleapis.com/v1beta",
+        _ => "https://api.anthropic.com/v1",
+    }
+}
+
+/// Mock clients for every selected AI when `--mock` is set, otherwise none
+pub fn mock_clients(args: &Args) -> Vec<Box<dyn AiClient>> {
+    #[cfg(any(test, feature = "mock"))]
+    if args.mock {
+        return mock::clients(args);
+    }
+    let _ = args;
+    Vec::new()
+}
+
+/// Mock summarizers for `--summary-models` when `--mock` is set
+pub fn mock_summary_clients(args: &Args) -> Vec<Box<dyn AiClient>> {
+    #[cfg(any(test, feature = "mock"))]
+    if args.mock {
+        return mock::summary_clients_for(args, &args.summary_models);
+    }
+    let _ = args;
+    Vec::new()
+}
+
+/// Mock clients for the AIs left out of the run when `--mock` is set
+pub fn mock_unselected_clients(args: &Args) -> Vec<Box<dyn AiClient>> {
+    #[cfg(any(test, feature = "mock"))]
+    if args.mock {
+        let unselected: Vec<String> = crate::cli::AI_NAMES
+            .iter()
+            .filter(|ai| !args.should_use_ai(ai))
+            .map(|ai| ai.to_string())
+            .collect();
+        return mock::summary_clients_for(args, &unselected);
+    }
+    let _ = args;
+    Vec::new()
+}
+
+/// Mock summarizer when `--mock` is set
+pub fn mock_summary_client(args: &Args) -> Option<Box<dyn AiClient>> {
+    #[cfg(any(test, feature = "mock"))]
+    if args.mock {
+        return Some(mock::summary_client(args));
+    }
+    let _ = args;
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::AI_NAMES;
+
+    #[test]
+    fn each_ai_maps_to_its_library_provider_id() {
+        let ids: Vec<&str> = AI_NAMES
+            .iter()
+            .map(|ai| Provider::for_ai(ai).client_id())
+            .collect();
+        assert_eq!(ids, vec!["openai", "gemini", "claude"]);
+    }
+
+    #[test]
+    fn providers_know_their_key_variables() {
+        assert_eq!(
+            Provider::Gpt.env_vars(),
+            &["OPENAI_API_KEY", "CHATGPT_API_KEY"]
+        );
+        assert_eq!(Provider::Gemini.env_var(), "GEMINI_API_KEY");
+        assert_eq!(
+            Provider::Claude.env_vars(),
+            &["ANTHROPIC_API_KEY", "CLAUDE_API_KEY"]
+        );
+        assert_eq!(Provider::for_ai("claude").display_name(), "Claude");
+    }
+}
@@ -0,0 +1,1203 @@
+//! Parallel model queries for ChatDelta CLI
+//!
+//! Each client runs as its own task so that responses which completed before a
+//! `--max-runtime` deadline are kept while the remaining requests are cancelled.
+//! When a `--retry-budget` is set, retries happen here rather than in the library so
+//! every model draws from the same shared budget; verbose runs with `--retries` retry here
+//! too, so each retry can be reported as it happens, and with `--timeout-escalation` so each
+//! retry can wait longer than the attempt before it. A model whose request times out or hits
+//! a server error is retried once against its configured fallback model. With `--race`
+//! only the first successful answer is kept and the other requests are cancelled.
+
+use crate::chunk::ChunkPlans;
+use crate::cli::{Args, AI_NAMES};
+use crate::diagnostics;
+use chatdelta::{AiClient, AiResponse, ClientError, NetworkError, ResponseMetadata};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::future::{self, Future};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{error::Elapsed, Instant};
+
+/// Called with each model's result as soon as it completes
+pub type OnArrival<'a> = &'a mut (dyn FnMut(&str, &Result<AiResponse, ClientError>) + Send);
+
+/// Outcome of querying all clients
+pub struct QueryResults {
+    /// Per-model results in client order
+    pub results: Vec<(String, Result<AiResponse, ClientError>)>,
+    /// Models whose requests were cancelled by the deadline
+    pub unfinished: Vec<String>,
+    /// Primary requests that failed and were retried against a fallback model
+    pub fallbacks: Vec<FallbackAttempt>,
+}
+
+/// A failed primary request that was handed to the fallback model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackAttempt {
+    /// Display name of the AI (ChatGPT, Gemini, Claude)
+    pub name: String,
+    pub primary_model: String,
+    pub primary_error: String,
+    pub fallback_model: String,
+}
+
+/// Total number of retries all models may share in one run
+#[derive(Debug)]
+pub struct RetryBudget {
+    remaining: AtomicU32,
+}
+
+impl RetryBudget {
+    pub fn new(total: u32) -> Self {
+        RetryBudget {
+            remaining: AtomicU32::new(total),
+        }
+    }
+
+    /// Claim one retry, returning false once the budget is exhausted
+    pub fn try_take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+}
+
+/// Same delays the library strategies are configured with in client_config
+fn backoff_for(strategy: &str) -> (Backoff, Duration) {
+    match strategy {
+        "linear" => (Backoff::Linear, Duration::from_secs(1)),
+        "fixed" => (Backoff::Fixed, Duration::from_secs(2)),
+        _ => (Backoff::Exponential, Duration::from_secs(1)),
+    }
+}
+
+/// Delay growth between CLI-side retries, mirroring --retry-strategy
+#[derive(Debug, Clone, Copy)]
+pub enum Backoff {
+    Exponential,
+    Linear,
+    Fixed,
+}
+
+/// Retries each model has needed so far, for the verbose per-model status
+#[derive(Debug, Default)]
+pub struct RetryTracker {
+    retries: Mutex<BTreeMap<String, u32>>,
+    /// Print each retry to stderr as it happens
+    announce: bool,
+}
+
+impl RetryTracker {
+    pub fn new(announce: bool) -> Self {
+        RetryTracker {
+            announce,
+            ..Default::default()
+        }
+    }
+
+    fn record(&self, name: &str, retry: u32, max_retries: u32, error: &ClientError) {
+        self.retries.lock().unwrap().insert(name.to_string(), retry);
+        if self.announce {
+            eprintln!(
+                "  \u{21bb} {}: {} (retry {}/{})",
+                name, error, retry, max_retries
+            );
+        }
+    }
+
+    /// Retries the named model has used
+    pub fn retries(&self, name: &str) -> u32 {
+        self.retries
+            .lock()
+            .unwrap()
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// One status line per model: whether it answered and how many retries it took
+    pub fn render(&self, results: &[(String, Result<AiResponse, ClientError>)]) -> String {
+        let width = results
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+        let mut out = String::from("Model status:\n");
+        for (name, result) in results {
+            let mark = if result.is_ok() {
+                "\u{2713}"
+            } else {
+                "\u{2717}"
+            };
+            let retries = match self.retries(name) {
+                0 => String::new(),
+                1 => " after 1 retry".to_string(),
+                n => format!(" after {} retries", n),
+            };
+            out.push_str(&format!(
+                "  {:<width$}  {}{}\n",
+                name,
+                mark,
+                retries,
+                width = width
+            ));
+        }
+        out
+    }
+}
+
+/// Retries performed by the CLI, each drawn from a shared budget
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Per-model retry limit (--retries)
+    pub max_retries: u32,
+    pub backoff: Backoff,
+    pub base_delay: Duration,
+    pub budget: Arc<RetryBudget>,
+    /// Backoff for models with their own --<ai>-retry-strategy, by client name
+    pub overrides: HashMap<String, (Backoff, Duration)>,
+    /// Per-model retry counts for verbose output
+    pub tracker: Option<Arc<RetryTracker>>,
+    /// Per-attempt timeouts for --timeout-escalation
+    pub timeouts: Option<TimeoutEscalation>,
+}
+
+/// Timeouts that double with each attempt, up to a cap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeoutEscalation {
+    pub base: Duration,
+    pub cap: Duration,
+}
+
+impl TimeoutEscalation {
+    /// Timeout for the given attempt (0 is the first request, 1 the first retry)
+    pub fn for_attempt(&self, attempt: u32) -> Duration {
+        self.base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.cap)
+    }
+}
+
+impl RetryPolicy {
+    /// Policy for --retry-budget, --timeout-escalation, or for verbose runs with --retries so
+    /// each retry can be shown; None when the library handles retries itself
+    pub fn from_args(args: &Args) -> Option<Self> {
+        let verbose = args.verbose && !args.quiet;
+        let total = match args.retry_budget {
+            Some(total) => total,
+            None if (verbose || args.timeout_escalation) && args.retries > 0 => u32::MAX,
+            None => return None,
+        };
+        let (backoff, base_delay) = backoff_for(&args.retry_strategy);
+        let overrides = AI_NAMES
+            .iter()
+            .filter(|ai| args.retry_strategy_for(ai) != args.retry_strategy)
+            .map(|ai| {
+                (
+                    Args::display_name(ai).to_string(),
+                    backoff_for(args.retry_strategy_for(ai)),
+                )
+            })
+            .collect();
+        Some(RetryPolicy {
+            max_retries: args.retries,
+            backoff,
+            base_delay,
+            budget: Arc::new(RetryBudget::new(total)),
+            overrides,
+            tracker: verbose.then(|| Arc::new(RetryTracker::new(true))),
+            timeouts: args.timeout_escalation.then(|| TimeoutEscalation {
+                base: args.request_timeout(),
+                cap: args.timeout_cap(),
+            }),
+        })
+    }
+
+    /// This policy with the named model's own backoff, if it has one
+    pub fn for_model(&self, name: &str) -> Self {
+        let mut policy = self.clone();
+        if let Some(&(backoff, base_delay)) = self.overrides.get(name) {
+            policy.backoff = backoff;
+            policy.base_delay = base_delay;
+        }
+        policy
+    }
+
+    /// Delay before the given retry (1-based)
+    fn delay(&self, retry: u32) -> Duration {
+        match self.backoff {
+            Backoff::Exponential => self.base_delay * 2u32.saturating_pow(retry - 1),
+            Backoff::Linear => self.base_delay * retry,
+            Backoff::Fixed => self.base_delay,
+        }
+    }
+}
+
+/// Await `future`, giving up once `deadline` passes (no deadline waits indefinitely)
+pub async fn with_deadline<F: Future>(
+    deadline: Option<Instant>,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await,
+        None => Ok(future.await),
+    }
+}
+
+/// Throwaway prompt for --warmup; kept tiny so warming costs next to nothing
+pub const WARMUP_PROMPT: &str = "Reply with OK.";
+
+/// Send every client a throwaway request concurrently to set up its connection, stopping
+/// at `deadline`. Responses and errors are discarded. The requests borrow the clients, so
+/// a warm-up that panics costs nothing but the returned warning.
+pub async fn warm_up(clients: &[Box<dyn AiClient>], deadline: Option<Instant>) -> Vec<String> {
+    type WarmUp<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+    let mut pending: Vec<(&str, WarmUp<'_>)> = clients
+        .iter()
+        .map(|client| {
+            let request: WarmUp<'_> = Box::pin(async move {
+                let _ = client.send_prompt(WARMUP_PROMPT).await;
+            });
+            (client.name(), request)
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let all_done = future::poll_fn(|cx| {
+        pending.retain_mut(|(name, request)| {
+            match panic::catch_unwind(AssertUnwindSafe(|| request.as_mut().poll(cx))) {
+                Ok(poll) => poll.is_pending(),
+                Err(_) => {
+                    warnings.push(format!(
+                        "{} panicked during warm-up; it is still queried",
+                        name
+                    ));
+                    false
+                }
+            }
+        });
+        if pending.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    });
+    let _ = with_deadline(deadline, all_done).await;
+    warnings
+}
+
+/// Send `prompt` to one client, retrying while the policy and shared budget allow
+async fn query_with_retries(
+    client: &dyn AiClient,
+    prompt: &str,
+    retry: Option<&RetryPolicy>,
+) -> Result<AiResponse, ClientError> {
+    let mut retries = 0;
+    loop {
+        tracing::debug!(
+            client = client.name(),
+            model = client.model(),
+            attempt = retries + 1,
+            "sending request"
+        );
+        let result = match retry.and_then(|p| p.timeouts) {
+            Some(timeouts) => {
+                let timeout = timeouts.for_attempt(retries);
+                tracing::debug!(
+                    client = client.name(),
+                    timeout_secs = timeout.as_secs(),
+                    "escalated timeout"
+                );
+                tokio::time::timeout(timeout, client.send_prompt_with_metadata(prompt))
+                    .await
+                    .unwrap_or(Err(ClientError::Network(NetworkError::Timeout)))
+            }
+            None => client.send_prompt_with_metadata(prompt).await,
+        };
+        // Only transient failures are retried (and drawn from the budget); a bad key or
+        // request fails the same way every time
+        let retryable = result
+            .as_ref()
+            .is_err_and(|e| diagnostics::classify(e).is_retryable());
+        let retry_again =
+            retryable && retry.is_some_and(|p| retries < p.max_retries && p.budget.try_take());
+        if !retry_again {
+            match &result {
+                Ok(response) => {
+                    tracing::debug!(
+                        client = client.name(),
+                        latency_ms = response.metadata.latency_ms,
+                        "request succeeded"
+                    )
+                }
+                Err(error) => tracing::warn!(client = client.name(), %error, "request failed"),
+            }
+            return result;
+        }
+        retries += 1;
+        if let Some(policy) = retry {
+            if let Err(error) = &result {
+                let delay_ms = policy.delay(retries).as_millis() as u64;
+                tracing::info!(client = client.name(), retry = retries, delay_ms, %error, "retrying request");
+                if let Some(tracker) = &policy.tracker {
+                    tracker.record(client.name(), retries, policy.max_retries, error);
+                }
+            }
+            tokio::time::sleep(policy.delay(retries)).await;
+        }
+    }
+}
+
+/// Sum of two optional counts, None only when neither is known
+fn add_counts<T: std::ops::Add<Output = T> + Default>(
+    total: Option<T>,
+    more: Option<T>,
+) -> Option<T> {
+    match (total, more) {
+        (None, None) => None,
+        (total, more) => Some(total.unwrap_or_default() + more.unwrap_or_default()),
+    }
+}
+
+/// Send `prompt` to one client, or its part prompts in turn when `chunking` split the
+/// prompt for the client's model. Chunk answers are joined in order.
+async fn query_prompt(
+    client: &dyn AiClient,
+    prompt: &str,
+    retry: Option<&RetryPolicy>,
+    chunking: Option<&ChunkPlans>,
+) -> Result<AiResponse, ClientError> {
+    let Some(parts) = chunking.and_then(|plans| plans.parts(client.model())) else {
+        return query_with_retries(client, prompt, retry).await;
+    };
+
+    let mut answers = Vec::with_capacity(parts.len());
+    let mut metadata = ResponseMetadata::default();
+    for part in parts {
+        let response = query_with_retries(client, part, retry).await?;
+        metadata.prompt_tokens =
+            add_counts(metadata.prompt_tokens, response.metadata.prompt_tokens);
+        metadata.completion_tokens = add_counts(
+            metadata.completion_tokens,
+            response.metadata.completion_tokens,
+        );
+        metadata.total_tokens = add_counts(metadata.total_tokens, response.metadata.total_tokens);
+        metadata.latency_ms = add_counts(metadata.latency_ms, response.metadata.latency_ms);
+        metadata.model_used = response.metadata.model_used;
+        metadata.finish_reason = response.metadata.finish_reason;
+        answers.push(response.content);
+    }
+    Ok(AiResponse {
+        content: answers.join("\n\n"),
+        metadata,
+    })
+}
+
+/// Send `prompt` to every client in parallel, collecting responses with metadata.
+/// `fallbacks` maps a client's name to the client tried once when its request fails
+/// with a retryable error. With `chunking`, oversized prompts are sent in parts.
+/// Each result is also passed to `on_arrival` as it completes, before the others finish.
+/// A request that panics is reported as a failure for its model.
+pub async fn query_all(
+    clients: Vec<Box<dyn AiClient>>,
+    mut fallbacks: HashMap<String, Box<dyn AiClient>>,
+    prompt: &str,
+    deadline: Option<Instant>,
+    retry: Option<&RetryPolicy>,
+    chunking: Option<Arc<ChunkPlans>>,
+    mut on_arrival: Option<OnArrival<'_>>,
+) -> QueryResults {
+    let names: Vec<String> = clients.iter().map(|c| c.name().to_string()).collect();
+
+    let mut tasks = JoinSet::new();
+    let mut task_indexes = HashMap::new();
+    for (index, client) in clients.into_iter().enumerate() {
+        let prompt = prompt.to_string();
+        let retry = retry.map(|policy| policy.for_model(client.name()));
+        let fallback = fallbacks.remove(client.name());
+        let chunking = chunking.clone();
+        let task = tasks.spawn(async move {
+            let result = query_prompt(
+                client.as_ref(),
+                &prompt,
+                retry.as_ref(),
+                chunking.as_deref(),
+            )
+            .await;
+            let (Err(error), Some(fallback)) = (&result, fallback) else {
+                return (index, result, None);
+            };
+            if !diagnostics::classify(error).is_retryable() {
+                return (index, result, None);
+            }
+            let attempt = FallbackAttempt {
+                name: client.name().to_string(),
+                primary_model: client.model().to_string(),
+                primary_error: error.to_string(),
+                fallback_model: fallback.model().to_string(),
+            };
+            let result = query_prompt(fallback.as_ref(), &prompt, None, chunking.as_deref()).await;
+            (index, result, Some(attempt))
+        });
+        task_indexes.insert(task.id(), index);
+    }
+
+    let mut finished = Vec::with_capacity(names.len());
+    let timed_out = with_deadline(deadline, async {
+        while let Some(joined) = tasks.join_next_with_id().await {
+            let result = match joined {
+                Ok((_, result)) => result,
+                Err(error) => {
+                    let failure = ClientError::Network(NetworkError::ConnectionFailed(format!(
+                        "request panicked: {}",
+                        error
+                    )));
+                    (task_indexes[&error.id()], Err(failure), None)
+                }
+            };
+            if let Some(on_arrival) = on_arrival.as_mut() {
+                on_arrival(&names[result.0], &result.1);
+            }
+            finished.push(result);
+        }
+    })
+    .await
+    .is_err();
+    tasks.abort_all();
+
+    finished.sort_by_key(|(index, _, _)| *index);
+    let unfinished = if timed_out {
+        names
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !finished.iter().any(|(done, _, _)| done == index))
+            .map(|(_, name)| name.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut fallback_attempts = Vec::new();
+    let mut results = Vec::with_capacity(finished.len());
+    for (index, result, attempt) in finished {
+        fallback_attempts.extend(attempt);
+        results.push((names[index].clone(), result));
+    }
+
+    QueryResults {
+        results,
+        unfinished,
+        fallbacks: fallback_attempts,
+    }
+}
+
+/// Outcome of racing the clients with --race
+pub struct RaceResults {
+    /// The winning response and the failures that came in before it, in client order.
+    /// `unfinished` lists the models cancelled by the deadline when nobody won in time.
+    pub query: QueryResults,
+    /// The model that answered first, if any succeeded
+    pub winner: Option<String>,
+    /// Models whose requests were cancelled because another model won
+    pub cancelled: Vec<String>,
+}
+
+/// Send `prompt` to every client in parallel and keep only the first successful answer;
+/// the remaining requests are cancelled as soon as it arrives. Failures don't end the race.
+pub async fn race(
+    clients: Vec<Box<dyn AiClient>>,
+    prompt: &str,
+    deadline: Option<Instant>,
+    retry: Option<&RetryPolicy>,
+    chunking: Option<Arc<ChunkPlans>>,
+) -> RaceResults {
+    let names: Vec<String> = clients.iter().map(|c| c.name().to_string()).collect();
+
+    let mut tasks = JoinSet::new();
+    for (index, client) in clients.into_iter().enumerate() {
+        let prompt = prompt.to_string();
+        let retry = retry.map(|policy| policy.for_model(client.name()));
+        let chunking = chunking.clone();
+        tasks.spawn(async move {
+            (
+                index,
+                query_prompt(
+                    client.as_ref(),
+                    &prompt,
+                    retry.as_ref(),
+                    chunking.as_deref(),
+                )
+                .await,
+            )
+        });
+    }
+
+    let mut finished = Vec::with_capacity(names.len());
+    let mut winner = None;
+    let timed_out = with_deadline(deadline, async {
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((index, result)) = joined else {
+                continue;
+            };
+            let won = result.is_ok();
+            finished.push((index, result));
+            if won {
+                winner = Some(index);
+                break;
+            }
+        }
+    })
+    .await
+    .is_err();
+    tasks.abort_all();
+
+    finished.sort_by_key(|(index, _)| *index);
+    let pending: Vec<String> = names
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !finished.iter().any(|(done, _)| done == index))
+        .map(|(_, name)| name.clone())
+        .collect();
+    let (unfinished, cancelled) = match timed_out {
+        true => (pending, Vec::new()),
+        false => (Vec::new(), pending),
+    };
+
+    RaceResults {
+        query: QueryResults {
+            results: finished
+                .into_iter()
+                .map(|(index, result)| (names[index].clone(), result))
+                .collect(),
+            unfinished,
+            fallbacks: Vec::new(),
+        },
+        winner: winner.map(|index| names[index].clone()),
+        cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chatdelta::NetworkError;
+
+    /// Client that answers after a fixed delay
+    struct SlowClient {
+        name: String,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl AiClient for SlowClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            tokio::time::sleep(self.delay).await;
+            Ok(format!("{} answered", self.name))
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn model(&self) -> &str {
+            "slow-model"
+        }
+    }
+
+    fn slow(name: &str, millis: u64) -> Box<dyn AiClient> {
+        Box::new(SlowClient {
+            name: name.to_string(),
+            delay: Duration::from_millis(millis),
+        })
+    }
+
+    #[tokio::test]
+    async fn deadline_keeps_finished_and_cancels_slow_clients() {
+        let clients = vec![slow("Slow", 5_000), slow("Fast", 10)];
+        let started = Instant::now();
+        let deadline = Some(started + Duration::from_millis(200));
+
+        let outcome = query_all(clients, HashMap::new(), "test", deadline, None, None, None).await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(outcome.results.len(), 1);
+        assert_eq!(outcome.results[0].0, "Fast");
+        assert_eq!(outcome.unfinished, vec!["Slow".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn results_keep_client_order_without_deadline() {
+        let clients = vec![slow("First", 50), slow("Second", 1)];
+
+        let outcome = query_all(clients, HashMap::new(), "test", None, None, None, None).await;
+
+        let names: Vec<&str> = outcome.results.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["First", "Second"]);
+        assert!(outcome.unfinished.is_empty());
+        assert_eq!(
+            outcome.results[0].1.as_ref().unwrap().content,
+            "First answered"
+        );
+    }
+
+    #[tokio::test]
+    async fn each_result_is_reported_as_it_arrives() {
+        let clients = vec![slow("Slow", 5_000), slow("Fast", 10)];
+        let deadline = Some(Instant::now() + Duration::from_millis(200));
+        let mut arrived = Vec::new();
+        let mut on_arrival = |name: &str, result: &Result<AiResponse, ClientError>| {
+            arrived.push((name.to_string(), result.as_ref().unwrap().content.clone()));
+        };
+
+        query_all(
+            clients,
+            HashMap::new(),
+            "test",
+            deadline,
+            None,
+            None,
+            Some(&mut on_arrival),
+        )
+        .await;
+
+        // The fast answer is reported even though the slow request never finished
+        assert_eq!(arrived, [("Fast".to_string(), "Fast answered".to_string())]);
+    }
+
+    /// Client that answers after a delay, counting the requests that ran to completion
+    struct CountingClient {
+        name: String,
+        delay: Duration,
+        completed: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl AiClient for CountingClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            tokio::time::sleep(self.delay).await;
+            self.completed.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("{} answered", self.name))
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn model(&self) -> &str {
+            "counting-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn race_returns_the_fastest_answer_and_cancels_the_rest() {
+        let completed = Arc::new(AtomicU32::new(0));
+        let client = |name: &str, millis: u64| {
+            Box::new(CountingClient {
+                name: name.to_string(),
+                delay: Duration::from_millis(millis),
+                completed: Arc::clone(&completed),
+            }) as Box<dyn AiClient>
+        };
+        let failing = Box::new(FailingClient {
+            name: "Broken".to_string(),
+            attempts: Arc::new(AtomicU32::new(0)),
+        }) as Box<dyn AiClient>;
+        let clients = vec![
+            client("Slow", 300),
+            failing,
+            client("Fast", 20),
+            client("Slower", 400),
+        ];
+
+        let started = Instant::now();
+        let raced = race(clients, "test", None, None, None).await;
+        assert!(started.elapsed() < Duration::from_millis(250));
+
+        assert_eq!(raced.winner.as_deref(), Some("Fast"));
+        assert_eq!(
+            raced.cancelled,
+            vec!["Slow".to_string(), "Slower".to_string()]
+        );
+        // The failure that came in first is kept; it didn't end the race
+        let names: Vec<&str> = raced
+            .query
+            .results
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(names, vec!["Broken", "Fast"]);
+        assert_eq!(
+            raced.query.results[1].1.as_ref().unwrap().content,
+            "Fast answered"
+        );
+        assert!(raced.query.unfinished.is_empty());
+
+        // The cancelled requests never finish
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn race_deadline_without_winner_leaves_models_unfinished() {
+        let clients = vec![slow("Slow", 5_000), slow("Slower", 6_000)];
+        let deadline = Some(Instant::now() + Duration::from_millis(100));
+
+        let raced = race(clients, "test", deadline, None, None).await;
+        assert!(raced.winner.is_none());
+        assert!(raced.cancelled.is_empty());
+        assert_eq!(
+            raced.query.unfinished,
+            vec!["Slow".to_string(), "Slower".to_string()]
+        );
+    }
+
+    /// Client that always fails, counting every attempt
+    struct FailingClient {
+        name: String,
+        attempts: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl AiClient for FailingClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ClientError::Network(NetworkError::ConnectionFailed(
+                "503 Service Unavailable".to_string(),
+            )))
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn model(&self) -> &str {
+            "failing-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_budget_caps_total_retries_across_models() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let clients: Vec<Box<dyn AiClient>> = ["A", "B", "C"]
+            .iter()
+            .map(|name| {
+                Box::new(FailingClient {
+                    name: name.to_string(),
+                    attempts: Arc::clone(&attempts),
+                }) as Box<dyn AiClient>
+            })
+            .collect();
+        let policy = RetryPolicy {
+            max_retries: 5,
+            backoff: Backoff::Fixed,
+            base_delay: Duration::ZERO,
+            budget: Arc::new(RetryBudget::new(2)),
+            overrides: HashMap::new(),
+            tracker: None,
+            timeouts: None,
+        };
+
+        let outcome = query_all(
+            clients,
+            HashMap::new(),
+            "test",
+            None,
+            Some(&policy),
+            None,
+            None,
+        )
+        .await;
+
+        // Three first attempts plus exactly two retries drawn from the budget
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+        assert_eq!(policy.budget.remaining(), 0);
+        assert!(outcome.results.iter().all(|(_, r)| r.is_err()));
+    }
+
+    /// Client whose key is always rejected, counting every attempt
+    struct UnauthorizedClient {
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl AiClient for UnauthorizedClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ClientError::Network(NetworkError::ConnectionFailed(
+                "401 Unauthorized".to_string(),
+            )))
+        }
+
+        fn name(&self) -> &str {
+            "Rejected"
+        }
+
+        fn model(&self) -> &str {
+            "rejected-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn auth_errors_are_not_retried() {
+        let client = UnauthorizedClient {
+            attempts: AtomicU32::new(0),
+        };
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff: Backoff::Fixed,
+            base_delay: Duration::ZERO,
+            budget: Arc::new(RetryBudget::new(5)),
+            overrides: HashMap::new(),
+            tracker: None,
+            timeouts: None,
+        };
+
+        assert!(query_with_retries(&client, "test", Some(&policy))
+            .await
+            .is_err());
+        assert_eq!(client.attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            policy.budget.remaining(),
+            5,
+            "no budget is spent on a rejected key"
+        );
+    }
+
+    /// Client that fails its first `failures` attempts, then answers
+    struct FlakyClient {
+        name: String,
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl AiClient for FlakyClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ClientError::Network(NetworkError::ConnectionFailed(
+                    "502 Bad Gateway".to_string(),
+                )));
+            }
+            Ok(format!("{} answered", self.name))
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn model(&self) -> &str {
+            "flaky-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn verbose_status_reports_retries_per_model() {
+        let flaky = |name: &str, failures| {
+            Box::new(FlakyClient {
+                name: name.to_string(),
+                failures,
+                attempts: AtomicU32::new(0),
+            }) as Box<dyn AiClient>
+        };
+        let tracker = Arc::new(RetryTracker::new(false));
+        let policy = RetryPolicy {
+            max_retries: 2,
+            backoff: Backoff::Fixed,
+            base_delay: Duration::ZERO,
+            budget: Arc::new(RetryBudget::new(u32::MAX)),
+            overrides: HashMap::new(),
+            tracker: Some(Arc::clone(&tracker)),
+            timeouts: None,
+        };
+        let clients = vec![flaky("Steady", 0), flaky("Flaky", 2), flaky("Broken", 5)];
+
+        let outcome = query_all(
+            clients,
+            HashMap::new(),
+            "test",
+            None,
+            Some(&policy),
+            None,
+            None,
+        )
+        .await;
+
+        assert_eq!(tracker.retries("Steady"), 0);
+        assert_eq!(tracker.retries("Flaky"), 2);
+        assert!(outcome.results[1].1.is_ok(), "third attempt succeeds");
+        assert_eq!(
+            tracker.render(&outcome.results),
+            "Model status:\n  Steady  \u{2713}\n  Flaky   \u{2713} after 2 retries\n  Broken  \u{2717} after 2 retries\n"
+        );
+    }
+
+    /// Client that answers every prompt, recording what it was sent
+    struct RecordingClient {
+        name: String,
+        prompts: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl AiClient for RecordingClient {
+        async fn send_prompt(&self, prompt: &str) -> Result<String, ClientError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(format!("{}: {}", self.name, prompt))
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn model(&self) -> &str {
+            "recording-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn warmup_requests_are_sent_but_not_reported() {
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let clients: Vec<Box<dyn AiClient>> = ["A", "B"]
+            .iter()
+            .map(|name| {
+                Box::new(RecordingClient {
+                    name: name.to_string(),
+                    prompts: Arc::clone(&prompts),
+                }) as Box<dyn AiClient>
+            })
+            .collect();
+
+        assert!(warm_up(&clients, None).await.is_empty());
+        let outcome = query_all(clients, HashMap::new(), "real", None, None, None, None).await;
+
+        let sent = prompts.lock().unwrap().clone();
+        assert_eq!(sent.iter().filter(|p| *p == WARMUP_PROMPT).count(), 2);
+        assert_eq!(sent.iter().filter(|p| *p == "real").count(), 2);
+        let answers: Vec<&str> = outcome
+            .results
+            .iter()
+            .map(|(_, r)| r.as_ref().unwrap().content.as_str())
+            .collect();
+        assert_eq!(answers, ["A: real", "B: real"]);
+    }
+
+    /// Client whose requests panic
+    struct PanickingClient;
+
+    #[async_trait]
+    impl AiClient for PanickingClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            panic!("client bug")
+        }
+
+        fn name(&self) -> &str {
+            "Broken"
+        }
+
+        fn model(&self) -> &str {
+            "broken-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn panicked_requests_are_reported_as_failures() {
+        let clients: Vec<Box<dyn AiClient>> = vec![Box::new(PanickingClient), slow("Fast", 10)];
+
+        let outcome = query_all(clients, HashMap::new(), "test", None, None, None, None).await;
+
+        let names: Vec<&str> = outcome.results.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["Broken", "Fast"]);
+        let error = outcome.results[0].1.as_ref().unwrap_err().to_string();
+        assert!(error.contains("request panicked"), "{}", error);
+        assert!(outcome.results[1].1.is_ok());
+        assert!(outcome.unfinished.is_empty());
+    }
+
+    #[tokio::test]
+    async fn warmup_keeps_panicking_clients_and_stops_at_the_deadline() {
+        let clients: Vec<Box<dyn AiClient>> = vec![Box::new(PanickingClient), slow("Slow", 5_000)];
+        let started = Instant::now();
+
+        let warnings = warm_up(&clients, Some(started + Duration::from_millis(100))).await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(
+            warnings,
+            ["Broken panicked during warm-up; it is still queried"]
+        );
+        assert_eq!(clients.len(), 2);
+    }
+
+    /// Client that times out on every request
+    struct TimeoutClient;
+
+    #[async_trait]
+    impl AiClient for TimeoutClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            Err(ClientError::Network(NetworkError::Timeout))
+        }
+
+        fn name(&self) -> &str {
+            "Primary"
+        }
+
+        fn model(&self) -> &str {
+            "primary-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn timing_out_primary_falls_back_to_secondary() {
+        let clients: Vec<Box<dyn AiClient>> = vec![Box::new(TimeoutClient)];
+        let mut fallbacks = HashMap::new();
+        fallbacks.insert("Primary".to_string(), slow("Primary", 1));
+
+        let outcome = query_all(clients, fallbacks, "test", None, None, None, None).await;
+
+        assert_eq!(outcome.results.len(), 1);
+        assert_eq!(outcome.results[0].0, "Primary");
+        assert_eq!(
+            outcome.results[0].1.as_ref().unwrap().content,
+            "Primary answered"
+        );
+        assert_eq!(outcome.fallbacks.len(), 1);
+        assert_eq!(outcome.fallbacks[0].primary_model, "primary-model");
+        assert_eq!(outcome.fallbacks[0].fallback_model, "slow-model");
+    }
+
+    #[tokio::test]
+    async fn non_retryable_errors_skip_the_fallback() {
+        let clients: Vec<Box<dyn AiClient>> = vec![Box::new(UnauthorizedClient {
+            attempts: AtomicU32::new(0),
+        })];
+        let mut fallbacks = HashMap::new();
+        fallbacks.insert("Rejected".to_string(), slow("Rejected", 1));
+
+        let outcome = query_all(clients, fallbacks, "test", None, None, None, None).await;
+
+        assert!(outcome.results[0].1.is_err());
+        assert!(outcome.fallbacks.is_empty());
+    }
+
+    #[test]
+    fn backoff_delays_follow_strategy() {
+        let policy = |backoff| RetryPolicy {
+            max_retries: 3,
+            backoff,
+            base_delay: Duration::from_secs(1),
+            budget: Arc::new(RetryBudget::new(3)),
+            overrides: HashMap::new(),
+            tracker: None,
+            timeouts: None,
+        };
+        assert_eq!(
+            policy(Backoff::Exponential).delay(3),
+            Duration::from_secs(4)
+        );
+        assert_eq!(policy(Backoff::Linear).delay(3), Duration::from_secs(3));
+        assert_eq!(policy(Backoff::Fixed).delay(3), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn per_model_strategy_overrides_budget_backoff() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--retry-budget",
+            "4",
+            "--retry-strategy",
+            "linear",
+            "--claude-retry-strategy",
+            "fixed",
+            "Hi",
+        ])
+        .unwrap();
+        let policy = RetryPolicy::from_args(&args).unwrap();
+        assert_eq!(policy.for_model("Claude").delay(3), Duration::from_secs(2));
+        assert_eq!(policy.for_model("ChatGPT").delay(3), Duration::from_secs(3));
+        // Overrides share the run's budget
+        assert!(Arc::ptr_eq(
+            &policy.for_model("Claude").budget,
+            &policy.budget
+        ));
+    }
+
+    #[test]
+    fn escalated_timeouts_double_up_to_the_cap() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--retries",
+            "3",
+            "--timeout-escalation",
+            "--max-timeout",
+            "120",
+            "Hi",
+        ])
+        .unwrap();
+        let timeouts = RetryPolicy::from_args(&args).unwrap().timeouts.unwrap();
+        let secs: Vec<u64> = (0..4)
+            .map(|attempt| timeouts.for_attempt(attempt).as_secs())
+            .collect();
+        assert_eq!(secs, [30, 60, 120, 120]);
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--retries",
+            "1",
+            "--timeout-escalation",
+            "--timeout",
+            "10",
+            "Hi",
+        ])
+        .unwrap();
+        assert_eq!(
+            RetryPolicy::from_args(&args).unwrap().timeouts.unwrap().cap,
+            Duration::from_secs(40)
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_model_succeeds_once_the_timeout_has_grown() {
+        let client = slow("Slow", 150);
+        let mut policy = RetryPolicy {
+            max_retries: 1,
+            backoff: Backoff::Fixed,
+            base_delay: Duration::ZERO,
+            budget: Arc::new(RetryBudget::new(u32::MAX)),
+            overrides: HashMap::new(),
+            tracker: None,
+            timeouts: Some(TimeoutEscalation {
+                base: Duration::from_millis(50),
+                cap: Duration::from_millis(400),
+            }),
+        };
+
+        // 50ms and 100ms attempts both time out
+        let result = query_with_retries(client.as_ref(), "test", Some(&policy)).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::Network(NetworkError::Timeout))
+        ));
+
+        // A third attempt gets 200ms and finishes
+        policy.max_retries = 2;
+        let response = query_with_retries(client.as_ref(), "test", Some(&policy))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Slow answered");
+    }
+}
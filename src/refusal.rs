@@ -0,0 +1,219 @@
+//! Refusal handling for ChatDelta CLI (`--retry-on-refusal`)
+//!
+//! A reply that opens like a safety refusal ("I'm sorry, but I can't help with that") is
+//! treated as a refusal, and the prompt is re-sent once to a model left out of the run, so
+//! no model is asked the same prompt twice. Only the start of a reply is checked, so an answer that mentions a
+//! refusal further down is not mistaken for one. `--refusal-pattern` adds patterns.
+
+use chatdelta::{AiClient, AiResponse, ClientError};
+use regex::{Regex, RegexBuilder};
+use tokio::task::JoinSet;
+
+/// Built-in refusal phrases, matched case-insensitively
+const DEFAULT_PATTERNS: [&str; 4] = [
+    r"^\W*(i'?m|i am) (so )?(sorry|afraid),? but i (can'?t|cannot|won'?t|am unable|'?m unable|am not able)",
+    r"\bi (can'?t|cannot|won'?t|am unable to|'?m unable to|am not able to) (help|assist|comply|provide|fulfill|do) (you )?(with )?(that|this|the|your)\b",
+    r"\bas an ai( language model)?,? i (can'?t|cannot|am not able to|won'?t)\b",
+    r"\bi (must|have to) (respectfully )?(decline|refuse)\b",
+];
+
+/// How much of a reply is checked for refusal phrases
+const CHECKED_CHARS: usize = 300;
+
+/// Refusal patterns: the built-in phrases plus any given with --refusal-pattern
+#[derive(Debug)]
+pub struct RefusalDetector {
+    patterns: Vec<Regex>,
+}
+
+impl RefusalDetector {
+    pub fn new(extra: &[String]) -> Result<Self, String> {
+        let patterns = DEFAULT_PATTERNS
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("Invalid refusal pattern '{}': {}", pattern, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RefusalDetector { patterns })
+    }
+
+    /// Whether the opening of `reply` matches a refusal pattern
+    pub fn is_refusal(&self, reply: &str) -> bool {
+        let opening: String = reply.trim_start().chars().take(CHECKED_CHARS).collect();
+        let opening = opening.replace('\u{2019}', "'");
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.is_match(&opening))
+    }
+}
+
+/// A refused prompt and the model it was re-sent to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefusalRetry {
+    pub refused: String,
+    pub retried_with: String,
+    /// Model id of the client it was re-sent to
+    pub model: String,
+    /// Name the retry's result is listed under
+    pub label: String,
+}
+
+/// Re-send `prompt` for every refused reply in `results` to one of `spares`, clients for
+/// models that weren't queried this run. Each spare takes at most one refusal, in order;
+/// refusals left over once the spares run out are not retried. Each retry's result is
+/// appended to `results` under its label.
+pub async fn retry_refusals(
+    results: &mut Vec<(String, Result<AiResponse, ClientError>)>,
+    spares: Vec<Box<dyn AiClient>>,
+    prompt: &str,
+    detector: &RefusalDetector,
+) -> Vec<RefusalRetry> {
+    let refusers: Vec<String> = results
+        .iter()
+        .filter(|(_, result)| {
+            result
+                .as_ref()
+                .is_ok_and(|r| detector.is_refusal(&r.content))
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let mut retries = Vec::new();
+    let mut tasks = JoinSet::new();
+    for (refused, client) in refusers.iter().zip(spares) {
+        retries.push(RefusalRetry {
+            refused: refused.clone(),
+            retried_with: client.name().to_string(),
+            model: client.model().to_string(),
+            label: format!("{} (retry of {})", client.name(), refused),
+        });
+        let (index, prompt) = (retries.len() - 1, prompt.to_string());
+        tasks.spawn(async move { (index, client.send_prompt_with_metadata(&prompt).await) });
+    }
+
+    let mut finished = Vec::with_capacity(retries.len());
+    while let Some(joined) = tasks.join_next().await {
+        finished.extend(joined.ok());
+    }
+    finished.sort_by_key(|(index, _)| *index);
+    for (index, result) in finished {
+        results.push((retries[index].label.clone(), result));
+    }
+    retries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chatdelta::ResponseMetadata;
+
+    const REFUSALS: [&str; 5] = [
+        "I'm sorry, but I can't help with that request.",
+        "I’m afraid I cannot assist with this.",
+        "As an AI language model, I cannot provide instructions for that.",
+        "I must respectfully decline to answer.",
+        "Sorry — I can't help with that.",
+    ];
+
+    #[test]
+    fn recognizes_common_refusals() {
+        let detector = RefusalDetector::new(&[]).unwrap();
+        for reply in REFUSALS {
+            assert!(detector.is_refusal(reply), "{}", reply);
+        }
+        assert!(!detector.is_refusal("Paris is the capital of France."));
+        // A refusal quoted deep inside a long answer is not the model refusing
+        let answer = format!(
+            "{} Some models reply \"I can't help with that\".",
+            "Here is the plan. ".repeat(20)
+        );
+        assert!(!detector.is_refusal(&answer));
+    }
+
+    #[test]
+    fn extra_patterns_extend_the_defaults() {
+        let detector = RefusalDetector::new(&["^not permitted".to_string()]).unwrap();
+        assert!(detector.is_refusal("Not permitted under policy 4.2"));
+        assert!(detector.is_refusal(REFUSALS[0]));
+        assert!(RefusalDetector::new(&["(unclosed".to_string()]).is_err());
+    }
+
+    /// Client answering every prompt with a fixed reply
+    struct FixedClient {
+        name: &'static str,
+        reply: &'static str,
+    }
+
+    #[async_trait]
+    impl AiClient for FixedClient {
+        async fn send_prompt(&self, _prompt: &str) -> Result<String, ClientError> {
+            Ok(self.reply.to_string())
+        }
+
+        async fn send_prompt_with_metadata(&self, prompt: &str) -> Result<AiResponse, ClientError> {
+            Ok(AiResponse {
+                content: self.send_prompt(prompt).await?,
+                metadata: ResponseMetadata::default(),
+            })
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn model(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    fn reply(content: &str) -> Result<AiResponse, ClientError> {
+        Ok(AiResponse {
+            content: content.to_string(),
+            metadata: ResponseMetadata::default(),
+        })
+    }
+
+    fn spare(name: &'static str, reply: &'static str) -> Box<dyn AiClient> {
+        Box::new(FixedClient { name, reply })
+    }
+
+    #[tokio::test]
+    async fn refused_prompt_goes_to_a_model_not_queried() {
+        let mut results = vec![
+            ("ChatGPT".to_string(), reply(REFUSALS[0])),
+            ("Gemini".to_string(), reply(REFUSALS[1])),
+            ("Claude".to_string(), reply("Here is the answer.")),
+        ];
+        let detector = RefusalDetector::new(&[]).unwrap();
+
+        // One spare for two refusals: the second refusal stays as it is
+        let spares = vec![spare("Local", "A spare answer.")];
+        let retries = retry_refusals(&mut results, spares, "prompt", &detector).await;
+        let targets: Vec<(&str, &str)> = retries
+            .iter()
+            .map(|r| (r.refused.as_str(), r.retried_with.as_str()))
+            .collect();
+        assert_eq!(targets, vec![("ChatGPT", "Local")]);
+        assert_eq!(retries[0].model, "fixed");
+        let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["ChatGPT", "Gemini", "Claude", "Local (retry of ChatGPT)"]
+        );
+        assert_eq!(results[3].1.as_ref().unwrap().content, "A spare answer.");
+
+        // Nothing refused: no retries
+        let mut results = vec![("Claude".to_string(), reply("Fine."))];
+        let spares = vec![spare("Local", "A spare answer.")];
+        assert!(retry_refusals(&mut results, spares, "prompt", &detector)
+            .await
+            .is_empty());
+        assert_eq!(results.len(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+//! Role presets for ChatDelta CLI (`--role`)
+//!
+//! A role is a named framing: a system prompt plus an instruction placed before the
+//! prompt, so common setups don't have to be retyped. A few are built in; a TOML
+//! `--role-file` adds more, or replaces a built-in of the same name:
+//!
+//! ```toml
+//! critic = { system = "You are a blunt critic.", prefix = "Critique the following:" }
+//! ```
+
+use crate::cli::Args;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A role's system prompt and optional instruction prefix
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Role {
+    pub system: String,
+    pub prefix: Option<String>,
+}
+
+/// Roles by name
+pub type Roles = BTreeMap<String, Role>;
+
+/// Built-in roles: name, system prompt, prefix
+const BUILTIN: [(&str, &str, &str); 3] = [
+    (
+        "reviewer",
+        "You are a senior software engineer doing a careful code review. Point out bugs, \
+         security issues and unclear code, and suggest concrete fixes.",
+        "Review the following:",
+    ),
+    (
+        "summarizer",
+        "You write concise, faithful summaries. Keep the key facts and conclusions and leave \
+         out nothing essential; do not add opinions.",
+        "Summarize the following:",
+    ),
+    (
+        "translator",
+        "You are a professional translator. Preserve meaning, tone and formatting, and reply \
+         with the translation only.",
+        "Translate the following into English, unless another language is requested:",
+    ),
+];
+
+/// The built-in roles, plus those in `role_file` (which win on a name clash)
+pub fn load(role_file: Option<&Path>) -> Result<Roles, String> {
+    let mut roles: Roles = BUILTIN
+        .iter()
+        .map(|(name, system, prefix)| {
+            let role = Role {
+                system: system.to_string(),
+                prefix: Some(prefix.to_string()),
+            };
+            (name.to_string(), role)
+        })
+        .collect();
+    if let Some(path) = role_file {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read role file {}: {}", path.display(), e))?;
+        let custom: Roles = toml::from_str(&text)
+            .map_err(|e| format!("Invalid role file {}: {}", path.display(), e))?;
+        roles.extend(custom);
+    }
+    Ok(roles)
+}
+
+/// Apply --role: its system prompt and prefix fill in --system-prompt and
+/// --prompt-prefix where those weren't given
+pub fn apply(args: &mut Args) -> Result<(), String> {
+    let Some(name) = &args.role else {
+        return Ok(());
+    };
+    let roles = load(args.role_file.as_deref())?;
+    let role = roles.get(name).ok_or_else(|| {
+        format!(
+            "Unknown role '{}'. Available roles: {}",
+            name,
+            roles.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })?;
+    if args.system_prompt.is_none() {
+        args.system_prompt = Some(role.system.clone());
+    }
+    if args.prompt_prefix.is_none() {
+        args.prompt_prefix = role.prefix.clone();
+    }
+    Ok(())
+}
+
+/// One line per role for --list-roles: name and system prompt
+pub fn render_list(roles: &Roles) -> String {
+    let width = roles.keys().map(String::len).max().unwrap_or(0);
+    roles
+        .iter()
+        .map(|(name, role)| format!("{:<width$}  {}\n", name, role.system, width = width))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn role_file_adds_and_overrides_roles() {
+        let dir = std::env::temp_dir().join(format!("chatdelta-roles-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("roles.toml");
+        fs::write(
+            &file,
+            "critic = { system = \"You are a blunt critic.\" }\n\
+             reviewer = { system = \"Review like a pedant.\", prefix = \"Nitpick:\" }\n",
+        )
+        .unwrap();
+
+        let roles = load(Some(&file)).unwrap();
+        assert_eq!(roles["critic"].prefix, None);
+        assert_eq!(roles["reviewer"].prefix.as_deref(), Some("Nitpick:"));
+        assert!(roles.contains_key("translator"));
+        assert!(render_list(&roles)
+            .lines()
+            .any(|line| line.starts_with("critic      You are a blunt critic.")));
+
+        fs::write(&file, "broken = { system = \"x\", tone = \"y\" }\n").unwrap();
+        assert!(load(Some(&file)).unwrap_err().contains("Invalid role file"));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
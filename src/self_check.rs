@@ -0,0 +1,204 @@
+//! Offline self-check for ChatDelta CLI (`--test --no-network`)
+//!
+//! Validates the local setup — arguments, API keys, model names, prompt file and log
+//! directory — without making any HTTP requests.
+
+use crate::cli::Args;
+use crate::keys;
+use crate::logging;
+use crate::models;
+use crate::prompt;
+use std::env;
+use std::fs;
+
+/// One line of the self-check report
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub label: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(label: impl Into<String>, passed: bool, detail: impl Into<String>) -> Self {
+        Check {
+            label: label.into(),
+            passed,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run every local check. `has_key` reports whether an AI (gpt, gemini, claude) has a key.
+pub fn local_checks(args: &Args, has_key: impl Fn(&str) -> bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    checks.push(match args.validate() {
+        Ok(()) => Check::new("Arguments", true, "valid"),
+        Err(e) => Check::new("Arguments", false, e),
+    });
+
+    for (ai, label, model) in [
+        ("gpt", "ChatGPT", &args.gpt_model),
+        ("gemini", "Gemini", &args.gemini_model),
+        ("claude", "Claude", &args.claude_model),
+    ] {
+        if !args.should_use_ai(ai) {
+            continue;
+        }
+
+        checks.push(if has_key(ai) {
+            Check::new(format!("{} API key", label), true, "configured")
+        } else {
+            Check::new(format!("{} API key", label), false, "not set")
+        });
+
+        checks.push(check_model_name(ai, label, model));
+    }
+
+    if let Some(path) = &args.prompt_file {
+        let label = "Prompt file";
+        checks.push(match fs::read_to_string(path) {
+            Ok(content) => match prompt::parse_prompt_file(&content, &args.input_format) {
+                Ok(_) => Check::new(
+                    label,
+                    true,
+                    format!("{} parses as {}", path.display(), args.input_format),
+                ),
+                Err(e) => Check::new(label, false, format!("{}: {}", path.display(), e)),
+            },
+            Err(e) => Check::new(label, false, format!("{}: {}", path.display(), e)),
+        });
+    }
+
+    if args.log_metrics || args.log_errors || args.log_dir.is_some() {
+        checks.push(check_log_dir(args));
+    }
+
+    checks
+}
+
+/// A model name must be non-blank and free of whitespace; unlisted names are allowed
+fn check_model_name(ai: &str, label: &str, model: &str) -> Check {
+    let label = format!("{} model", label);
+    if model.trim().is_empty() || model.chars().any(char::is_whitespace) {
+        return Check::new(label, false, format!("invalid model name '{}'", model));
+    }
+
+    if models::lookup(model).is_some_and(|m| m.provider == ai) {
+        Check::new(label, true, model)
+    } else {
+        Check::new(label, true, format!("{} (not in --list-models)", model))
+    }
+}
+
+/// The structured log directory must exist (or be creatable) and accept new files
+fn check_log_dir(args: &Args) -> Check {
+    let dir = logging::log_dir(args);
+    let probe = dir.join(".chatdelta-write-check");
+    let result = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => Check::new(
+            "Log directory",
+            true,
+            format!("{} is writable", dir.display()),
+        ),
+        Err(e) => Check::new("Log directory", false, format!("{}: {}", dir.display(), e)),
+    }
+}
+
+/// Print the checklist and fail if any local check failed
+pub fn run(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let checks = local_checks(args, |ai| match ai {
+        "gpt" => keys::openai_keys(args).next_key().is_some(),
+        "claude" => keys::anthropic_keys(args).next_key().is_some(),
+        _ => env::var("GEMINI_API_KEY").is_ok_and(|k| !k.trim().is_empty()),
+    });
+
+    println!("Running offline self-check (no network requests)...");
+    for check in &checks {
+        let mark = if check.passed { "\u{2713}" } else { "\u{2717}" };
+        println!("{} {}: {}", mark, check.label, check.detail);
+    }
+
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    if failed == 0 {
+        println!("\n\u{2713} All local checks passed");
+        Ok(())
+    } else {
+        Err(format!(
+            "{} local check{} failed",
+            failed,
+            if failed == 1 { "" } else { "s" }
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn failed_labels(checks: &[Check]) -> Vec<&str> {
+        checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reports_missing_keys() {
+        let args =
+            Args::try_parse_from(["chatdelta", "--test", "--no-network", "--exclude", "gemini"])
+                .unwrap();
+
+        let checks = local_checks(&args, |ai| ai == "gpt");
+
+        assert_eq!(failed_labels(&checks), vec!["Claude API key"]);
+        assert!(checks.iter().all(|c| !c.label.starts_with("Gemini")));
+    }
+
+    #[test]
+    fn reports_unwritable_log_dir() {
+        // A directory can't be created underneath a regular file
+        let file =
+            std::env::temp_dir().join(format!("chatdelta-not-a-dir-{}", uuid::Uuid::new_v4()));
+        fs::write(&file, b"").unwrap();
+        let log_dir = file.join("logs");
+
+        let args = Args::try_parse_from([
+            "chatdelta",
+            "--test",
+            "--no-network",
+            "--log-dir",
+            log_dir.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        let checks = local_checks(&args, |_| true);
+        fs::remove_file(&file).ok();
+
+        assert_eq!(failed_labels(&checks), vec!["Log directory"]);
+    }
+
+    #[test]
+    fn flags_malformed_model_names() {
+        assert!(check_model_name("gpt", "ChatGPT", "gpt-4o").passed);
+        assert_eq!(
+            check_model_name("gpt", "ChatGPT", "gpt-4o").detail,
+            "gpt-4o"
+        );
+        // Listed, but for another provider
+        assert!(check_model_name("gpt", "ChatGPT", "claude-sonnet-4-6")
+            .detail
+            .contains("not in --list-models"));
+        assert!(check_model_name("gpt", "ChatGPT", "my-custom-model").passed);
+        assert!(!check_model_name("gpt", "ChatGPT", "gpt 4o").passed);
+        assert!(!check_model_name("claude", "Claude", "").passed);
+    }
+}
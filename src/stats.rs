@@ -0,0 +1,144 @@
+//! Personal usage statistics for ChatDelta CLI (`--stats-file`)
+//!
+//! Each run appends one JSON line to a local file: when it ran, how long it took, which
+//! models were asked, how each did, and an estimated cost. Prompts and responses are never
+//! written, so the file can be kept and analyzed long after the interaction logs are
+//! pruned. Nothing is sent anywhere.
+
+use crate::cost;
+use crate::status::RunStatus;
+use chatdelta::{AiResponse, ClientError};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// How one model did in a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelOutcome {
+    /// Display name (ChatGPT, Gemini, Claude, or a model alias)
+    pub name: String,
+    pub model: String,
+    pub success: bool,
+    pub latency_ms: Option<u64>,
+    pub tokens: Option<u32>,
+    /// Estimated from reported token counts, or from text length when a provider reports
+    /// none; None for models without known pricing
+    pub cost_usd: Option<f64>,
+}
+
+impl ModelOutcome {
+    /// Outcome of sending `prompt` to `model`; only counts are kept, never the text
+    pub fn new(
+        name: &str,
+        model: &str,
+        prompt: &str,
+        result: &Result<AiResponse, ClientError>,
+    ) -> Self {
+        let Ok(response) = result else {
+            return ModelOutcome {
+                name: name.to_string(),
+                model: model.to_string(),
+                success: false,
+                latency_ms: None,
+                tokens: None,
+                cost_usd: None,
+            };
+        };
+        let metadata = &response.metadata;
+        let input = metadata
+            .prompt_tokens
+            .unwrap_or_else(|| cost::estimate_tokens(prompt));
+        let output = metadata
+            .completion_tokens
+            .unwrap_or_else(|| cost::estimate_tokens(&response.content));
+        ModelOutcome {
+            name: name.to_string(),
+            model: model.to_string(),
+            success: true,
+            latency_ms: metadata.latency_ms,
+            tokens: metadata.total_tokens,
+            cost_usd: cost::price_for(cost::PRICING, model).map(|p| {
+                (input as f64 * p.input_per_mtok + output as f64 * p.output_per_mtok) / 1_000_000.0
+            }),
+        }
+    }
+}
+
+/// One line of the stats file
+#[derive(Debug, Serialize)]
+pub struct StatsRecord<'a> {
+    pub timestamp: String,
+    pub success: bool,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub responses: usize,
+    pub failures: usize,
+    pub estimated_cost_usd: f64,
+    pub models: &'a [ModelOutcome],
+}
+
+impl<'a> StatsRecord<'a> {
+    /// The record for a finished run
+    pub fn new(status: &'a RunStatus, elapsed: Duration) -> Self {
+        StatsRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            success: status.success,
+            exit_code: status.exit_code,
+            duration_ms: elapsed.as_millis() as u64,
+            responses: status.models.iter().filter(|m| m.success).count(),
+            failures: status.models.iter().filter(|m| !m.success).count(),
+            estimated_cost_usd: status.models.iter().filter_map(|m| m.cost_usd).sum(),
+            models: &status.models,
+        }
+    }
+}
+
+/// Append `record` to the stats file, creating it and its directory if needed
+pub fn append(path: &Path, record: &StatsRecord) -> Result<(), String> {
+    let write = || -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    };
+    write().map_err(|e| format!("Failed to write stats file '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chatdelta::ResponseMetadata;
+
+    #[test]
+    fn outcome_keeps_counts_and_prices_known_models() {
+        let response = AiResponse {
+            content: "A confidential answer".to_string(),
+            metadata: ResponseMetadata {
+                prompt_tokens: Some(1_000),
+                completion_tokens: Some(2_000),
+                total_tokens: Some(3_000),
+                latency_ms: Some(850),
+                ..Default::default()
+            },
+        };
+        let outcome = ModelOutcome::new("ChatGPT", "gpt-4o", "secret prompt", &Ok(response));
+        assert!(outcome.success);
+        assert_eq!(outcome.latency_ms, Some(850));
+        assert_eq!(outcome.tokens, Some(3_000));
+        // 1k input at $2.50/Mtok plus 2k output at $10/Mtok
+        assert!((outcome.cost_usd.unwrap() - 0.0225).abs() < 1e-9);
+
+        let unpriced = AiResponse {
+            content: "ok".to_string(),
+            metadata: ResponseMetadata::default(),
+        };
+        assert_eq!(
+            ModelOutcome::new("Local", "llama-3", "p", &Ok(unpriced)).cost_usd,
+            None
+        );
+    }
+}
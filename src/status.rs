@@ -0,0 +1,50 @@
+//! Machine-readable run status for --split-output
+//!
+//! The formatted result goes to stdout and diagnostics to stderr; with `--split-output` a
+//! small JSON status document (exit reason and counts) is also written to `--status-file`,
+//! so scripts can check the outcome without parsing either stream.
+
+use crate::error::CliError;
+use crate::stats::ModelOutcome;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Outcome and counts for one run
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RunStatus {
+    pub success: bool,
+    pub exit_code: i32,
+    /// `success`, or the error message the run exited with
+    pub exit_reason: String,
+    pub models_queried: usize,
+    pub responses: usize,
+    pub failures: usize,
+    pub warnings: usize,
+    /// Per-model outcomes for --stats-file; not part of the status document
+    #[serde(skip)]
+    pub models: Vec<ModelOutcome>,
+}
+
+impl RunStatus {
+    /// Fill in the outcome once the run has finished
+    pub fn finish(&mut self, result: &Result<(), CliError>, warnings: usize) {
+        self.success = result.is_ok();
+        self.exit_code = result.as_ref().err().map_or(0, CliError::exit_code);
+        self.exit_reason = match result {
+            Ok(()) => "success".to_string(),
+            Err(e) => e.to_string(),
+        };
+        self.warnings = warnings;
+    }
+}
+
+/// Write the status document as pretty-printed JSON
+pub fn write_status_file(
+    path: &Path,
+    status: &RunStatus,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, serde_json::to_string_pretty(status)?)
+        .map_err(|e| format!("Failed to write status file '{}': {}", path.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,508 @@
+//! Summary generation for ChatDelta CLI
+//!
+//! Large response sets are summarized hierarchically: responses are split into groups of
+//! `--summary-chunk-size`, each group is summarized, and the group summaries are then
+//! summarized in turn until a single summary remains. With `--summary-models` several
+//! models summarize the same responses in parallel. A `--summary-format` of markdown or
+//! json adds a formatting instruction to the final summary prompt, and
+//! `--summary-include-prompt` puts the original prompt in it. `--eager-summary` drafts
+//! a summary from the first two responses while the other models are still answering and
+//! then refines the draft with the responses that arrived since.
+
+use chatdelta::{generate_summary, AiClient, ClientError};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// Summary duration assumed when nothing has been measured yet
+pub const DEFAULT_SUMMARY_ESTIMATE: Duration = Duration::from_secs(30);
+
+/// Expected summary time: half again the slowest observed response (summary prompts are
+/// larger than the original prompt), or a conservative default without measurements
+pub fn estimate_summary_duration(slowest_response: Option<Duration>) -> Duration {
+    slowest_response
+        .filter(|d| !d.is_zero())
+        .map_or(DEFAULT_SUMMARY_ESTIMATE, |d| d * 3 / 2)
+}
+
+/// Whether to skip the summary because it likely wouldn't finish in the remaining time
+pub fn should_skip_summary(remaining: Duration, estimate: Duration) -> bool {
+    estimate > remaining
+}
+
+/// Default order in which AIs are tried as the summarizer
+const SUMMARY_PREFERENCE: [&str; 3] = ["gemini", "claude", "gpt"];
+
+/// Every AI in the order to try as the summarizer: those that answered this run first, then
+/// those that were queried, then the rest, each in the default Gemini, Claude, ChatGPT order
+pub fn summary_ai_order(answered: &[&str], queried: &[&str]) -> Vec<&'static str> {
+    let mut order: Vec<&'static str> = Vec::with_capacity(SUMMARY_PREFERENCE.len());
+    for group in [answered, queried, &SUMMARY_PREFERENCE[..]] {
+        for ai in SUMMARY_PREFERENCE {
+            if group.contains(&ai) && !order.contains(&ai) {
+                order.push(ai);
+            }
+        }
+    }
+    order
+}
+
+/// Instruction for a --summary-format markdown summary
+const MARKDOWN_INSTRUCTION: &str =
+    "Format the summary as Markdown: a `### Agreement` heading and a \
+     `### Differences` heading, each followed by a short bullet list.";
+
+/// Instruction for a --summary-format json summary
+const JSON_INSTRUCTION: &str = "Respond only with a JSON object of the form \
+     {\"agreement\": [\"...\"], \"differences\": [\"...\"], \"conclusion\": \"...\"} and no other text.";
+
+/// Formatting instruction for a summary format (text, markdown, json); None for plain text
+pub fn format_instruction(format: &str) -> Option<&'static str> {
+    match format {
+        "markdown" => Some(MARKDOWN_INSTRUCTION),
+        "json" => Some(JSON_INSTRUCTION),
+        _ => None,
+    }
+}
+
+/// Summary prompt with the original prompt before the responses and a formatting
+/// instruction after them. chatdelta's `generate_summary` builds its own prompt with room
+/// for neither, so summaries that need one are requested here.
+pub fn summary_prompt(
+    responses: &[(String, String)],
+    original: Option<&str>,
+    instruction: Option<&str>,
+) -> String {
+    let mut prompt = String::from(
+        "Below are responses from different AI models to the same prompt. Summarize where \
+         they agree and where they differ.\n\n",
+    );
+    if let Some(original) = original {
+        prompt.push_str(&format!("Original prompt:\n{}\n\n", original));
+    }
+    for (name, response) in responses {
+        prompt.push_str(&format!("{}:\n{}\n\n", name, response));
+    }
+    match instruction {
+        Some(instruction) => prompt.push_str(instruction),
+        None => prompt.truncate(prompt.trim_end().len()),
+    }
+    prompt
+}
+
+/// One summary call in the requested format, given the original prompt if there is one
+async fn summarize(
+    client: &dyn AiClient,
+    responses: &[(String, String)],
+    format: &str,
+    original: Option<&str>,
+) -> Result<String, ClientError> {
+    match (format_instruction(format), original) {
+        (None, None) => generate_summary(client, responses).await,
+        (instruction, original) => {
+            client
+                .send_prompt(&summary_prompt(responses, original, instruction))
+                .await
+        }
+    }
+}
+
+/// Split responses into consecutive groups of at most `chunk_size`
+pub fn chunk_responses(
+    responses: &[(String, String)],
+    chunk_size: usize,
+) -> Vec<Vec<(String, String)>> {
+    responses
+        .chunks(chunk_size.max(1))
+        .map(|group| group.to_vec())
+        .collect()
+}
+
+/// Summarize `responses`, in a single call when they fit in one chunk and otherwise by
+/// summarizing each chunk and then the chunk summaries. `chunk_size` must be at least 2.
+/// Only the final summary is asked for `format` and shown the `original` prompt; group
+/// summaries stay plain text.
+pub async fn hierarchical_summary(
+    responses: &[(String, String)],
+    client: &dyn AiClient,
+    chunk_size: usize,
+    format: &str,
+    original: Option<&str>,
+) -> Result<String, ClientError> {
+    tracing::debug!(
+        summarizer = client.name(),
+        responses = responses.len(),
+        chunk_size,
+        format,
+        "generating summary"
+    );
+    let mut level = responses.to_vec();
+    while level.len() > chunk_size {
+        let mut next = Vec::new();
+        for (index, group) in chunk_responses(&level, chunk_size).iter().enumerate() {
+            let summary = generate_summary(client, group).await?;
+            next.push((format!("Group {} summary", index + 1), summary));
+        }
+        level = next;
+    }
+    summarize(client, &level, format, original).await
+}
+
+/// Result of an `--eager-summary` run
+#[derive(Debug)]
+pub struct StagedSummary {
+    pub summary: String,
+    /// Models whose responses the draft covered
+    pub drafted_from: Vec<String>,
+    /// Models whose responses arrived after the draft started and were folded in
+    pub refined_with: Vec<String>,
+}
+
+/// Summarize responses as they arrive: once two are in, a draft is generated while the rest
+/// keep arriving; when `arrivals` closes, the draft and any later responses are summarized
+/// together, so the final summary covers every response. Returns None when fewer than two
+/// responses arrive.
+pub async fn eager_summary(
+    client: &dyn AiClient,
+    mut arrivals: mpsc::UnboundedReceiver<(String, String)>,
+    chunk_size: usize,
+    format: &str,
+    original: Option<&str>,
+) -> Option<Result<StagedSummary, ClientError>> {
+    let mut first = Vec::with_capacity(2);
+    while first.len() < 2 {
+        first.push(arrivals.recv().await?);
+    }
+
+    let drafted_from: Vec<String> = first.iter().map(|(name, _)| name.clone()).collect();
+    let mut later = Vec::new();
+    let mut open = true;
+    let draft = hierarchical_summary(&first, client, chunk_size, format, original);
+    tokio::pin!(draft);
+    let draft = loop {
+        tokio::select! {
+            draft = &mut draft => break draft,
+            arrived = arrivals.recv(), if open => match arrived {
+                Some(response) => later.push(response),
+                None => open = false,
+            },
+        }
+    };
+    let draft = match draft {
+        Ok(draft) => draft,
+        Err(e) => return Some(Err(e)),
+    };
+    while let Some(response) = arrivals.recv().await {
+        later.push(response);
+    }
+
+    let refined_with: Vec<String> = later.iter().map(|(name, _)| name.clone()).collect();
+    let summary = if later.is_empty() {
+        draft
+    } else {
+        let mut combined = vec![(format!("Summary of {}", drafted_from.join(" and ")), draft)];
+        combined.extend(later);
+        match hierarchical_summary(&combined, client, chunk_size, format, original).await {
+            Ok(summary) => summary,
+            Err(e) => return Some(Err(e)),
+        }
+    };
+    Some(Ok(StagedSummary {
+        summary,
+        drafted_from,
+        refined_with,
+    }))
+}
+
+/// Summarize `responses` with every client in parallel, returning each result labeled
+/// with the summarizing client's name, in client order
+pub async fn parallel_summaries(
+    responses: &[(String, String)],
+    clients: Vec<Box<dyn AiClient>>,
+    chunk_size: usize,
+    format: &'static str,
+    original: Option<&str>,
+) -> Vec<(String, Result<String, ClientError>)> {
+    let responses = Arc::new(responses.to_vec());
+    let original: Option<Arc<str>> = original.map(Arc::from);
+    let mut tasks = JoinSet::new();
+    for (index, client) in clients.into_iter().enumerate() {
+        let responses = Arc::clone(&responses);
+        let original = original.clone();
+        tasks.spawn(async move {
+            let result = hierarchical_summary(
+                &responses,
+                client.as_ref(),
+                chunk_size,
+                format,
+                original.as_deref(),
+            )
+            .await;
+            (index, client.name().to_string(), result)
+        });
+    }
+
+    let mut finished = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(summary) = joined {
+            finished.push(summary);
+        }
+    }
+    finished.sort_by_key(|(index, _, _)| *index);
+    finished
+        .into_iter()
+        .map(|(_, name, result)| (name, result))
+        .collect()
+}
+
+/// Combine summaries from several models into one labeled block
+pub fn label_summaries(summaries: &[(String, String)]) -> String {
+    summaries
+        .iter()
+        .map(|(name, summary)| format!("Summary by {}:\n{}", name, summary))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Client that records each prompt and answers with a numbered summary
+    #[derive(Default)]
+    struct RecordingClient {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AiClient for RecordingClient {
+        async fn send_prompt(&self, prompt: &str) -> Result<String, ClientError> {
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(prompt.to_string());
+            Ok(format!("summary #{}", prompts.len()))
+        }
+
+        fn name(&self) -> &str {
+            "Recorder"
+        }
+
+        fn model(&self) -> &str {
+            "recorder"
+        }
+    }
+
+    fn responses(count: usize) -> Vec<(String, String)> {
+        (1..=count)
+            .map(|i| (format!("Model {}", i), format!("answer {}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn skips_summary_when_time_is_short() {
+        let estimate = estimate_summary_duration(Some(Duration::from_secs(4)));
+        assert_eq!(estimate, Duration::from_secs(6));
+        assert!(should_skip_summary(Duration::from_secs(5), estimate));
+        assert!(!should_skip_summary(Duration::from_secs(10), estimate));
+
+        // Without measurements the conservative default applies
+        assert_eq!(estimate_summary_duration(None), DEFAULT_SUMMARY_ESTIMATE);
+        assert!(should_skip_summary(
+            Duration::from_secs(20),
+            estimate_summary_duration(None)
+        ));
+    }
+
+    #[test]
+    fn summary_prefers_an_ai_that_was_queried() {
+        // Only Claude queried: Claude summarizes even though Gemini is otherwise first
+        assert_eq!(
+            summary_ai_order(&["claude"], &["claude"]),
+            vec!["claude", "gemini", "gpt"]
+        );
+        // An AI that answered beats one that was queried but failed
+        assert_eq!(
+            summary_ai_order(&["gpt"], &["claude", "gpt"]),
+            vec!["gpt", "claude", "gemini"]
+        );
+        // Nothing answered: queried AIs still come first
+        assert_eq!(
+            summary_ai_order(&[], &["gpt", "claude"]),
+            vec!["claude", "gpt", "gemini"]
+        );
+        assert_eq!(summary_ai_order(&[], &[]), vec!["gemini", "claude", "gpt"]);
+    }
+
+    #[tokio::test]
+    async fn eager_summary_drafts_from_two_then_refines() {
+        let client = RecordingClient::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut arrivals = responses(3).into_iter();
+        tx.send(arrivals.next().unwrap()).unwrap();
+        tx.send(arrivals.next().unwrap()).unwrap();
+        // The third model is slow: it answers after the draft has started
+        let straggler = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tx.send(arrivals.next().unwrap()).unwrap();
+        });
+
+        let staged = eager_summary(&client, rx, 5, "text", None)
+            .await
+            .unwrap()
+            .unwrap();
+        straggler.await.unwrap();
+        assert_eq!(staged.drafted_from, vec!["Model 1", "Model 2"]);
+        assert_eq!(staged.refined_with, vec!["Model 3"]);
+        assert_eq!(staged.summary, "summary #2");
+
+        let prompts = client.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("answer 1") && prompts[0].contains("answer 2"));
+        assert!(!prompts[0].contains("answer 3"));
+        assert!(prompts[1].contains("summary #1") && prompts[1].contains("answer 3"));
+        assert!(!prompts[1].contains("answer 1"));
+    }
+
+    #[tokio::test]
+    async fn eager_summary_needs_two_responses() {
+        let client = RecordingClient::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(("Model 1".to_string(), "answer 1".to_string()))
+            .unwrap();
+        drop(tx);
+        assert!(eager_summary(&client, rx, 5, "text", None).await.is_none());
+
+        // Exactly two: the draft is the final summary
+        let (tx, rx) = mpsc::unbounded_channel();
+        for response in responses(2) {
+            tx.send(response).unwrap();
+        }
+        drop(tx);
+        let staged = eager_summary(&client, rx, 5, "text", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(staged.refined_with.is_empty());
+        assert_eq!(client.prompts.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn chunks_keep_order_and_remainder() {
+        let groups = chunk_responses(&responses(10), 4);
+        let sizes: Vec<usize> = groups.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+        assert_eq!(groups[2][1].1, "answer 10");
+    }
+
+    #[tokio::test]
+    async fn final_summary_is_built_from_group_summaries() {
+        let client = RecordingClient::default();
+
+        let summary = hierarchical_summary(&responses(10), &client, 4, "text", None)
+            .await
+            .unwrap();
+
+        // Three group summaries, then one summary of those
+        let prompts = client.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 4);
+        assert_eq!(summary, "summary #4");
+        let last = &prompts[3];
+        assert!(last.contains("summary #1") && last.contains("summary #3"));
+        assert!(!last.contains("answer 1"));
+    }
+
+    #[tokio::test]
+    async fn each_summary_model_produces_a_labeled_summary() {
+        use crate::providers::mock::MockClient;
+
+        let clients: Vec<Box<dyn AiClient>> = vec![
+            Box::new(MockClient::new("ChatGPT", "gpt-4o", Duration::ZERO, false)),
+            Box::new(MockClient::new(
+                "Claude",
+                "claude-sonnet-4-6",
+                Duration::ZERO,
+                false,
+            )),
+        ];
+
+        let summaries = parallel_summaries(&responses(3), clients, 8, "text", None).await;
+
+        let names: Vec<&str> = summaries.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["ChatGPT", "Claude"]);
+        let succeeded: Vec<(String, String)> = summaries
+            .into_iter()
+            .map(|(name, result)| (name, result.unwrap()))
+            .collect();
+        assert!(succeeded[1].1.starts_with("[mock claude-sonnet-4-6]"));
+
+        let labeled = label_summaries(&succeeded);
+        assert!(labeled.starts_with("Summary by ChatGPT:\n[mock gpt-4o]"));
+        assert!(labeled.contains("\n\nSummary by Claude:\n[mock claude-sonnet-4-6]"));
+    }
+
+    #[tokio::test]
+    async fn small_sets_use_a_single_call() {
+        let client = RecordingClient::default();
+
+        hierarchical_summary(&responses(3), &client, 8, "text", None)
+            .await
+            .unwrap();
+
+        let prompts = client.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(!prompts[0].contains("### Agreement") && !prompts[0].contains("JSON object"));
+    }
+
+    #[tokio::test]
+    async fn original_prompt_reaches_only_the_final_summary() {
+        let client = RecordingClient::default();
+
+        hierarchical_summary(
+            &responses(3),
+            &client,
+            8,
+            "text",
+            Some("Is Rust memory safe?"),
+        )
+        .await
+        .unwrap();
+        hierarchical_summary(
+            &responses(10),
+            &client,
+            4,
+            "json",
+            Some("Is Rust memory safe?"),
+        )
+        .await
+        .unwrap();
+
+        let prompts = client.prompts.lock().unwrap();
+        assert!(prompts[0].contains("Original prompt:\nIs Rust memory safe?\n\nModel 1:\nanswer 1"));
+        assert!(prompts[0].ends_with("answer 3"));
+        assert!(prompts[1..4]
+            .iter()
+            .all(|p| !p.contains("Is Rust memory safe?")));
+        assert!(
+            prompts[4].contains("Original prompt:\nIs Rust memory safe?")
+                && prompts[4].ends_with(JSON_INSTRUCTION)
+        );
+    }
+
+    #[tokio::test]
+    async fn summary_format_instructs_only_the_final_summary() {
+        let client = RecordingClient::default();
+
+        hierarchical_summary(&responses(10), &client, 4, "markdown", None)
+            .await
+            .unwrap();
+
+        let prompts = client.prompts.lock().unwrap();
+        assert!(prompts[..3].iter().all(|p| !p.contains("### Agreement")));
+        assert!(prompts[3].contains("Group 1 summary:\nsummary #1"));
+        assert!(prompts[3].ends_with(MARKDOWN_INSTRUCTION));
+        assert_eq!(format_instruction("json"), Some(JSON_INSTRUCTION));
+        assert_eq!(format_instruction("text"), None);
+    }
+}
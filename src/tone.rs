@@ -0,0 +1,248 @@
+//! Tone annotation for ChatDelta CLI (`--annotate-tone`)
+//!
+//! A local, word-list heuristic for comparing how models frame an answer: hedging words
+//! ("might", "perhaps") against assertive ones ("clearly", "definitely") give the tone, and
+//! positive against negative words give the sentiment. A word right after a negation
+//! ("not helpful") counts for the opposite sentiment. No API is called.
+
+use serde::Serialize;
+
+const HEDGES: [&str; 20] = [
+    "might",
+    "may",
+    "perhaps",
+    "possibly",
+    "maybe",
+    "likely",
+    "unlikely",
+    "probably",
+    "unclear",
+    "uncertain",
+    "seems",
+    "seem",
+    "appears",
+    "could",
+    "suggests",
+    "approximately",
+    "generally",
+    "typically",
+    "depends",
+    "arguably",
+];
+
+const ASSERTIONS: [&str; 16] = [
+    "definitely",
+    "certainly",
+    "clearly",
+    "always",
+    "undoubtedly",
+    "absolutely",
+    "must",
+    "guaranteed",
+    "obviously",
+    "proven",
+    "certain",
+    "sure",
+    "exactly",
+    "undeniably",
+    "unquestionably",
+    "indeed",
+];
+
+const POSITIVE: [&str; 24] = [
+    "good",
+    "great",
+    "excellent",
+    "best",
+    "better",
+    "benefit",
+    "benefits",
+    "beneficial",
+    "helpful",
+    "useful",
+    "effective",
+    "success",
+    "successful",
+    "positive",
+    "improve",
+    "improves",
+    "improved",
+    "easy",
+    "safe",
+    "reliable",
+    "love",
+    "wonderful",
+    "advantage",
+    "recommended",
+];
+
+const NEGATIVE: [&str; 24] = [
+    "bad",
+    "worse",
+    "worst",
+    "poor",
+    "risk",
+    "risks",
+    "risky",
+    "harmful",
+    "dangerous",
+    "problem",
+    "problems",
+    "fail",
+    "fails",
+    "failure",
+    "negative",
+    "difficult",
+    "unsafe",
+    "unreliable",
+    "hate",
+    "terrible",
+    "awful",
+    "disadvantage",
+    "wrong",
+    "avoid",
+];
+
+const NEGATIONS: [&str; 12] = [
+    "not", "no", "never", "don't", "doesn't", "didn't", "isn't", "aren't", "wasn't", "won't",
+    "can't", "cannot",
+];
+
+/// Sentiment scores below this (in absolute value) are neutral
+const NEUTRAL_BAND: f64 = 0.25;
+
+/// How sure of itself a response sounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    Cautious,
+    Neutral,
+    Confident,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sentiment {
+    Positive,
+    Neutral,
+    Negative,
+}
+
+/// The annotation for one response
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToneScore {
+    pub tone: Tone,
+    pub sentiment: Sentiment,
+    /// Positive minus negative words over all sentiment words plus one, from -1 to 1
+    pub score: f64,
+}
+
+impl ToneScore {
+    /// Short label for text output, e.g. `tone: cautious, positive`
+    pub fn label(&self) -> String {
+        format!("tone: {}, {}", self.tone.as_str(), self.sentiment.as_str())
+    }
+}
+
+impl Tone {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Tone::Cautious => "cautious",
+            Tone::Neutral => "neutral",
+            Tone::Confident => "confident",
+        }
+    }
+}
+
+impl Sentiment {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Sentiment::Positive => "positive",
+            Sentiment::Neutral => "neutral",
+            Sentiment::Negative => "negative",
+        }
+    }
+}
+
+/// Score the tone and sentiment of `text`
+pub fn classify(text: &str) -> ToneScore {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '\u{2019}')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase().replace('\u{2019}', "'"))
+        .collect();
+
+    let (mut hedges, mut assertions, mut positive, mut negative) = (0i32, 0i32, 0i32, 0i32);
+    for (index, word) in words.iter().enumerate() {
+        let word = word.as_str();
+        hedges += HEDGES.contains(&word) as i32;
+        assertions += ASSERTIONS.contains(&word) as i32;
+        let negated = index > 0 && NEGATIONS.contains(&words[index - 1].as_str());
+        match (POSITIVE.contains(&word), NEGATIVE.contains(&word), negated) {
+            (true, _, false) | (_, true, true) => positive += 1,
+            (true, _, true) | (_, true, false) => negative += 1,
+            _ => {}
+        }
+    }
+
+    let tone = match hedges - assertions {
+        d if d > 0 => Tone::Cautious,
+        d if d < 0 => Tone::Confident,
+        _ => Tone::Neutral,
+    };
+    let score = f64::from(positive - negative) / f64::from(positive + negative + 1);
+    let sentiment = match score {
+        s if s >= NEUTRAL_BAND => Sentiment::Positive,
+        s if s <= -NEUTRAL_BAND => Sentiment::Negative,
+        _ => Sentiment::Neutral,
+    };
+    ToneScore {
+        tone,
+        sentiment,
+        score: (score * 100.0).round() / 100.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_clear_sentiment() {
+        let positive =
+            classify("This is a great, reliable approach with real benefits. Highly recommended.");
+        assert_eq!(positive.sentiment, Sentiment::Positive);
+        assert!(positive.score > 0.5, "{:?}", positive);
+
+        let negative =
+            classify("That plan is risky and dangerous; it will fail and cause problems.");
+        assert_eq!(negative.sentiment, Sentiment::Negative);
+        assert!(negative.score < -0.5, "{:?}", negative);
+
+        let neutral = classify("The capital of France is Paris. It lies on the Seine.");
+        assert_eq!(neutral.sentiment, Sentiment::Neutral);
+        assert_eq!(neutral.score, 0.0);
+        assert_eq!(neutral.tone, Tone::Neutral);
+    }
+
+    #[test]
+    fn negation_flips_sentiment() {
+        assert_eq!(
+            classify("This is not helpful.").sentiment,
+            Sentiment::Negative
+        );
+        assert_eq!(
+            classify("It isn\u{2019}t dangerous.").sentiment,
+            Sentiment::Positive
+        );
+    }
+
+    #[test]
+    fn hedging_and_assertive_wording_set_the_tone() {
+        let cautious = classify("It might work, but it probably depends on your setup.");
+        assert_eq!(cautious.tone, Tone::Cautious);
+        let confident = classify("This is definitely the right choice; it clearly works.");
+        assert_eq!(confident.tone, Tone::Confident);
+        assert_eq!(confident.label(), "tone: confident, neutral");
+    }
+}
@@ -0,0 +1,221 @@
+//! Structured output via function/tool schemas for ChatDelta CLI (`--tool-schema`)
+//!
+//! Loads a JSON function schema, asks models to answer with arguments for it, and parses
+//! the tool call out of each response.
+
+use crate::json_util;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// A function definition in the OpenAI / Anthropic style
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema for the arguments; Anthropic calls this `input_schema`
+    #[serde(default = "empty_object_schema", alias = "input_schema")]
+    pub parameters: Value,
+}
+
+fn empty_object_schema() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+/// Arguments a model supplied for the schema's function
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+impl ToolSchema {
+    /// Parse a schema, either bare or wrapped as `{"type": "function", "function": {...}}`
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let mut value: Value =
+            serde_json::from_str(json).map_err(|e| format!("invalid JSON: {}", e))?;
+        if let Some(function) = value.get_mut("function").filter(|f| f.is_object()) {
+            value = function.take();
+        }
+        let schema: ToolSchema =
+            serde_json::from_value(value).map_err(|e| format!("not a function schema: {}", e))?;
+        if schema.name.trim().is_empty() {
+            return Err("function name cannot be empty".to_string());
+        }
+        if !schema.parameters.is_object() {
+            return Err("'parameters' must be a JSON Schema object".to_string());
+        }
+        Ok(schema)
+    }
+
+    /// Read and parse a schema file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tool schema '{}': {}", path.display(), e))?;
+        Self::parse(&content)
+            .map_err(|e| format!("Invalid tool schema '{}': {}", path.display(), e))
+    }
+
+    /// Prompt with instructions to answer only with the function's arguments as JSON.
+    /// Used for providers without native tool calling.
+    pub fn instruct(&self, prompt: &str) -> String {
+        format!("{}\n\n{}", prompt, self.instructions())
+    }
+
+    /// The instructions `instruct` appends to a prompt
+    pub fn instructions(&self) -> String {
+        let description = self
+            .description
+            .as_deref()
+            .map(|d| format!(" ({})", d))
+            .unwrap_or_default();
+        let parameters = serde_json::to_string_pretty(&self.parameters).unwrap_or_default();
+        format!(
+            "Respond only with a JSON object of arguments for the function `{}`{}, \
+             matching this JSON Schema, and no other text:\n{}",
+            self.name, description, parameters
+        )
+    }
+
+    /// Fields the schema marks as required
+    fn required(&self) -> impl Iterator<Item = &str> {
+        self.parameters
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    }
+}
+
+/// Parse the tool call from a response. Accepts an OpenAI `tool_calls` message, a
+/// `{"name", "arguments"}` or Anthropic `tool_use` block, or a bare arguments object;
+/// the JSON may be fenced or surrounded by prose (see `json_util::extract_json`).
+pub fn parse_tool_call(response: &str, schema: &ToolSchema) -> Result<ToolCall, String> {
+    let value = json_util::parse_json(response)?;
+
+    let call = value
+        .get("tool_calls")
+        .and_then(|calls| calls.get(0))
+        .and_then(|call| call.get("function"))
+        .unwrap_or(&value);
+
+    let (name, arguments) = match (
+        call.get("name").and_then(Value::as_str),
+        named_arguments(call),
+    ) {
+        (Some(name), Some(arguments)) => (name.to_string(), arguments),
+        _ => (schema.name.clone(), call.clone()),
+    };
+    // OpenAI sends arguments as a JSON-encoded string
+    let arguments = match arguments {
+        Value::String(encoded) => serde_json::from_str(&encoded)
+            .map_err(|e| format!("tool call arguments are not valid JSON: {}", e))?,
+        other => other,
+    };
+
+    if name != schema.name {
+        return Err(format!("called '{}' instead of '{}'", name, schema.name));
+    }
+    if !arguments.is_object() {
+        return Err("tool call arguments are not a JSON object".to_string());
+    }
+    if let Some(missing) = schema
+        .required()
+        .find(|field| arguments.get(field).is_none())
+    {
+        return Err(format!("missing required argument '{}'", missing));
+    }
+
+    Ok(ToolCall { name, arguments })
+}
+
+/// The `arguments` (OpenAI) or `input` (Anthropic) of a named call
+fn named_arguments(call: &Value) -> Option<Value> {
+    call.get("arguments").or_else(|| call.get("input")).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather_schema() -> ToolSchema {
+        ToolSchema::parse(
+            r#"{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather for a city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "city": {"type": "string"},
+                            "unit": {"type": "string", "enum": ["c", "f"]}
+                        },
+                        "required": ["city"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_openai_tool_call_response() {
+        let response = r#"{"tool_calls": [{"id": "call_1", "type": "function",
+            "function": {"name": "get_weather", "arguments": "{\"city\": \"Oslo\", \"unit\": \"c\"}"}}]}"#;
+        let call = parse_tool_call(response, &weather_schema()).unwrap();
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(
+            call.arguments,
+            serde_json::json!({"city": "Oslo", "unit": "c"})
+        );
+    }
+
+    #[test]
+    fn parses_tool_use_block_and_bare_fenced_arguments() {
+        let schema = weather_schema();
+        let tool_use = r#"{"type": "tool_use", "name": "get_weather", "input": {"city": "Lima"}}"#;
+        assert_eq!(
+            parse_tool_call(tool_use, &schema).unwrap().arguments["city"],
+            "Lima"
+        );
+
+        let bare = "```json\n{\"city\": \"Kyoto\"}\n```";
+        let call = parse_tool_call(bare, &schema).unwrap();
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(call.arguments["city"], "Kyoto");
+    }
+
+    #[test]
+    fn rejects_free_text_wrong_function_and_missing_fields() {
+        let schema = weather_schema();
+        assert!(parse_tool_call("It is sunny in Oslo.", &schema)
+            .unwrap_err()
+            .contains("not valid JSON"));
+        assert!(
+            parse_tool_call(r#"{"name": "get_time", "arguments": {}}"#, &schema)
+                .unwrap_err()
+                .contains("instead of")
+        );
+        assert!(parse_tool_call(r#"{"unit": "f"}"#, &schema)
+            .unwrap_err()
+            .contains("'city'"));
+    }
+
+    #[test]
+    fn schema_parsing_and_instructions() {
+        let schema =
+            ToolSchema::parse(r#"{"name": "lookup", "input_schema": {"type": "object"}}"#).unwrap();
+        assert_eq!(schema.parameters["type"], "object");
+        assert!(ToolSchema::parse(r#"{"description": "no name"}"#).is_err());
+        assert!(ToolSchema::parse(r#"{"name": "f", "parameters": []}"#).is_err());
+
+        let prompt = weather_schema().instruct("Weather in Oslo?");
+        assert!(prompt.starts_with("Weather in Oslo?\n\n"));
+        assert!(prompt.contains("`get_weather` (Current weather for a city)"));
+        assert!(prompt.contains("\"required\""));
+    }
+}
@@ -0,0 +1,121 @@
+//! Internal tracing for ChatDelta CLI (`--log-level`)
+//!
+//! Diagnostics about the tool itself (argument resolution, client creation, each request,
+//! retries, the summary) for debugging the CLI, as opposed to the interaction logs written
+//! by logging.rs. Events go to stderr so stdout carries only the result. At `debug` and
+//! `trace` the opening and closing of spans such as `create_client` are printed too.
+
+use std::io::IsTerminal;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::fmt::{format::FmtSpan, MakeWriter};
+
+/// Accepted --log-level values, least to most verbose
+pub const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+/// Parse a --log-level value
+pub fn parse_level(name: &str) -> Result<Level, String> {
+    LOG_LEVELS
+        .contains(&name)
+        .then(|| name.parse().ok())
+        .flatten()
+        .ok_or_else(|| format!("Log level must be one of: {}", LOG_LEVELS.join(", ")))
+}
+
+/// Subscriber writing events at `level` and above to `writer`
+pub fn subscriber<W>(level: Level, writer: W, ansi: bool) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let span_events = if level >= Level::DEBUG {
+        FmtSpan::NEW | FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(span_events)
+        .with_target(false)
+        .with_ansi(ansi)
+        .with_writer(writer)
+        .finish()
+}
+
+/// Install the stderr subscriber for the rest of the process
+pub fn init(level: &str) -> Result<(), String> {
+    let level = parse_level(level)?;
+    let ansi = std::io::stderr().is_terminal();
+    tracing::subscriber::set_global_default(subscriber(level, std::io::stderr, ansi))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers;
+    use chatdelta::ClientConfig;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// Writer collecting formatted events in memory
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn traced(level: Level) -> String {
+        let capture = Capture::default();
+        tracing::subscriber::with_default(subscriber(level, capture.clone(), false), || {
+            let _ = providers::create_client(
+                "openai",
+                "sk-test",
+                "gpt-4o",
+                ClientConfig::builder().build(),
+            );
+        });
+        let output = capture.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn debug_level_emits_client_creation_spans() {
+        let output = traced(Level::DEBUG);
+        assert!(
+            output.contains("create_client{provider=\"openai\" model=\"gpt-4o\"}"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains(": new") && output.contains(": close"),
+            "{}",
+            output
+        );
+        assert!(!output.contains("sk-test"));
+
+        assert!(!traced(Level::INFO).contains(": new"));
+    }
+
+    #[test]
+    fn parses_known_levels_only() {
+        assert_eq!(parse_level("debug"), Ok(Level::DEBUG));
+        assert_eq!(parse_level("error"), Ok(Level::ERROR));
+        assert!(parse_level("verbose").is_err());
+        assert!(parse_level("1").is_err());
+    }
+}
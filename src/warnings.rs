@@ -0,0 +1,107 @@
+//! Non-fatal warning collection for ChatDelta CLI
+//!
+//! Warnings raised during a run (missing keys, client failures, truncated responses,
+//! fallbacks) are gathered and reported together in one block at the end instead of being
+//! scattered through the progress output. `--warnings-inline` prints each one immediately.
+
+use crate::cli::Args;
+
+/// Collected warnings for one run
+#[derive(Debug)]
+pub struct Warnings {
+    messages: Vec<String>,
+    inline: bool,
+    quiet: bool,
+    reported: bool,
+}
+
+impl Warnings {
+    pub fn new(inline: bool, quiet: bool) -> Self {
+        Warnings {
+            messages: Vec::new(),
+            inline,
+            quiet,
+            reported: false,
+        }
+    }
+
+    pub fn from_args(args: &Args) -> Self {
+        Self::new(args.warnings_inline, args.quiet)
+    }
+
+    /// Record a warning, printing it right away with --warnings-inline
+    pub fn push(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if self.inline && !self.quiet {
+            eprintln!("Warning: {}", message);
+        }
+        self.messages.push(message);
+    }
+
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Note that the warnings were already shown (e.g. in JSON output), so no block is printed
+    pub fn mark_reported(&mut self) {
+        self.reported = true;
+    }
+
+    /// The consolidated block, or None when there is nothing to report
+    pub fn render(&self) -> Option<String> {
+        if self.messages.is_empty() {
+            return None;
+        }
+        let count = self.messages.len();
+        let mut block = format!(
+            "\u{26a0}\u{fe0f}  {} warning{}:\n",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        for message in &self.messages {
+            block.push_str(&format!("  \u{2022} {}\n", message));
+        }
+        Some(block)
+    }
+}
+
+/// The block is printed when the run ends, including early returns on error
+impl Drop for Warnings {
+    fn drop(&mut self) {
+        if self.inline || self.quiet || self.reported {
+            return;
+        }
+        if let Some(block) = self.render() {
+            eprint!("\n{}", block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_and_renders_multiple_warnings() {
+        let mut warnings = Warnings::new(false, true);
+        warnings.push("GEMINI_API_KEY not set, skipping Gemini");
+        warnings.push("ChatGPT response was cut off at the token limit");
+        warnings.push(String::from("Summary generation failed: timeout"));
+
+        assert_eq!(warnings.messages().len(), 3);
+        assert_eq!(
+            warnings.render().unwrap(),
+            "\u{26a0}\u{fe0f}  3 warnings:\n  \u{2022} GEMINI_API_KEY not set, skipping Gemini\n  \u{2022} ChatGPT response was cut off at the token limit\n  \u{2022} Summary generation failed: timeout\n"
+        );
+    }
+
+    #[test]
+    fn nothing_rendered_without_warnings() {
+        let warnings = Warnings::new(false, true);
+        assert!(warnings.render().is_none());
+
+        let mut one = Warnings::new(false, true);
+        one.push("only one");
+        assert!(one.render().unwrap().contains("1 warning:"));
+    }
+}